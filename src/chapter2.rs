@@ -1,8 +1,14 @@
 pub mod parser {
     pub mod infix2suffix;
+    pub mod astparser;
     mod _defparser;          // dead_code
 }
 pub mod lexer;
 pub mod token;
 pub mod mtype;
-pub mod symbol;
\ No newline at end of file
+pub mod symbol;
+pub mod diagnostic;
+pub mod ast;
+pub mod visit;
+pub mod parsetree;
+pub mod pretty;
@@ -0,0 +1,336 @@
+use std::fmt;
+
+use super::mtype::Type;
+use super::token::Span;
+
+/// 抽象構文木の節を識別する番号
+/// 型検査や名前解決の結果を節に結びつける表のキーとして使う
+pub type NodeId = usize;
+
+/// 単項演算子
+///
+/// # members
+/// - Neg => 符号反転(-)
+/// - Not => 論理否定(!)
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnOp {
+    Neg,
+    Not
+}
+
+/// 二項演算子
+///
+/// # members
+/// - Add, Sub, Mul, Div => 算術演算子
+/// - Lt, Le, Gt, Ge, Eq, Ne => 比較演算子
+/// - And, Or => 論理演算子
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BinOp {
+    Add, Sub, Mul, Div,
+    Lt, Le, Gt, Ge, Eq, Ne,
+    And, Or
+}
+
+impl BinOp {
+    /// 演算子の優先順位を返す(大きいほど強く結合する)
+    ///
+    /// # returns
+    /// - usize
+    pub fn precedence(&self) -> usize {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Eq | BinOp::Ne => 3,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 4,
+            BinOp::Add | BinOp::Sub => 5,
+            BinOp::Mul | BinOp::Div => 6
+        }
+    }
+
+    /// 比較演算子か
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_relational(&self) -> bool {
+        matches!(self, BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::Eq | BinOp::Ne)
+    }
+
+    /// 字句から演算子を求める
+    ///
+    /// # params
+    /// - lexeme: &str => 字句
+    ///
+    /// # returns
+    /// - Option<BinOp>
+    pub fn from_lexeme(lexeme: &str) -> Option<BinOp> {
+        match lexeme {
+            "+" => Some(BinOp::Add), "-" => Some(BinOp::Sub),
+            "*" => Some(BinOp::Mul), "/" => Some(BinOp::Div),
+            "<" => Some(BinOp::Lt), "<=" => Some(BinOp::Le),
+            ">" => Some(BinOp::Gt), ">=" => Some(BinOp::Ge),
+            "==" => Some(BinOp::Eq), "!=" => Some(BinOp::Ne),
+            "&&" => Some(BinOp::And), "||" => Some(BinOp::Or),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for UnOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnOp::Neg => write!(f, "-"),
+            UnOp::Not => write!(f, "!")
+        }
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            BinOp::Add => "+", BinOp::Sub => "-", BinOp::Mul => "*", BinOp::Div => "/",
+            BinOp::Lt => "<", BinOp::Le => "<=", BinOp::Gt => ">", BinOp::Ge => ">=",
+            BinOp::Eq => "==", BinOp::Ne => "!=",
+            BinOp::And => "&&", BinOp::Or => "||"
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// 式
+///
+/// # members
+/// - id: NodeId => 節番号
+/// - kind: ExprKind => 式の種類
+/// - span: Span => 位置
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Expr {
+    pub id: NodeId,
+    pub kind: ExprKind,
+    pub span: Span
+}
+
+/// 式の種類
+///
+/// # members
+/// - Int(i32) => 整数定数
+/// - Float(f32) => 小数定数
+/// - Bool(bool) => 真偽値定数
+/// - Var(String) => 変数
+/// - Index(Box<Expr>, Box<Expr>) => 配列参照 a[i]
+/// - Unary(UnOp, Box<Expr>) => 単項演算
+/// - Binary(BinOp, Box<Expr>, Box<Expr>) => 二項演算
+/// - Widen(Type, Box<Expr>) => 型検査で挿入した拡大変換
+/// - Call(String, Vec<Expr>) => 関数呼出し f(args)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub enum ExprKind {
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    Var(String),
+    Index(Box<Expr>, Box<Expr>),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Widen(Type, Box<Expr>),
+    Call(String, Vec<Expr>)
+}
+
+/// 宣言 `型 名前;`
+///
+/// # members
+/// - id: NodeId => 節番号
+/// - ty: Type => 宣言された型
+/// - name: String => 名前
+/// - span: Span => 位置
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Decl {
+    pub id: NodeId,
+    pub ty: Type,
+    pub name: String,
+    pub span: Span
+}
+
+/// 文
+///
+/// # members
+/// - id: NodeId => 節番号
+/// - kind: StmtKind => 文の種類
+/// - span: Span => 位置
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Stmt {
+    pub id: NodeId,
+    pub kind: StmtKind,
+    pub span: Span
+}
+
+/// 文の種類
+///
+/// # members
+/// - Decl(Decl) => 宣言
+/// - Expr(Expr) => 式文
+/// - Assign(Expr, Expr) => 代入 loc = expr; (左辺は変数か配列参照)
+/// - If(Expr, Box<Stmt>, Option<Box<Stmt>>) => if (expr) stmt [else stmt]
+/// - While(Expr, Box<Stmt>) => while (expr) stmt
+/// - DoWhile(Box<Stmt>, Expr) => do stmt while (expr);
+/// - Switch(Expr, Vec<Case>) => switch (expr) { case num: stmts ... default: stmts }
+/// - Return(Option<Expr>) => return [expr];
+/// - Break => break;
+/// - Continue => continue;
+/// - Block(Block) => ブロック
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub enum StmtKind {
+    Decl(Decl),
+    Expr(Expr),
+    Assign(Expr, Expr),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    DoWhile(Box<Stmt>, Expr),
+    Switch(Expr, Vec<Case>),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    Block(Block)
+}
+
+/// switch文の節 `case num: stmts` または `default: stmts`
+/// 節の終わりにbreakがなければ、次の節へそのまま進む
+///
+/// # members
+/// - id: NodeId => 節番号
+/// - value: Option<i32> => caseの値(defaultならNone)
+/// - stmts: Vec<Stmt> => 文の並び
+/// - span: Span => ラベル(`case num:`, `default:`)の位置
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Case {
+    pub id: NodeId,
+    pub value: Option<i32>,
+    pub stmts: Vec<Stmt>,
+    pub span: Span
+}
+
+/// ブロック `{ stmts }`
+///
+/// # members
+/// - id: NodeId => 節番号
+/// - stmts: Vec<Stmt> => 文の並び
+/// - span: Span => 位置
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Block {
+    pub id: NodeId,
+    pub stmts: Vec<Stmt>,
+    pub span: Span
+}
+
+/// 関数 `fn 名前(型 仮引数, ...) -> 型 block`
+/// 本体のないもの(`fn 名前(...) -> 型;`)は前方宣言で、相互再帰する関数を先に呼び出せるようにする
+/// 戻り値の型を省略するとvoid
+///
+/// # members
+/// - id: NodeId => 節番号
+/// - name: String => 名前
+/// - params: Vec<Decl> => 仮引数
+/// - ret: Type => 戻り値の型
+/// - body: Option<Block> => 本体(前方宣言ならNone)
+/// - span: Span => 位置
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Func {
+    pub id: NodeId,
+    pub name: String,
+    pub params: Vec<Decl>,
+    pub ret: Type,
+    pub body: Option<Block>,
+    pub span: Span
+}
+
+impl Func {
+    /// 関数の型 fn(仮引数の型, ...) -> 戻り値の型 を返す
+    ///
+    /// # returns
+    /// - Type
+    pub fn ty(&self) -> Type {
+        Type::new_function(self.params.iter().map(|p| p.ty.clone()).collect(), self.ret.clone())
+    }
+}
+
+/// プログラム(関数の並び)
+///
+/// # members
+/// - id: NodeId => 節番号
+/// - funcs: Vec<Func> => 関数
+/// - span: Span => 位置
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Program {
+    pub id: NodeId,
+    pub funcs: Vec<Func>,
+    pub span: Span
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BinOp;
+
+    #[test]
+    fn binop_test() {
+        for lexeme in &["+", "-", "*", "/", "<", "<=", ">", ">=", "==", "!=", "&&", "||"] {
+            assert_eq!(*lexeme, BinOp::from_lexeme(lexeme).unwrap().to_string());
+        }
+        assert!(BinOp::Mul.precedence() > BinOp::Add.precedence());
+        assert!(BinOp::And.precedence() > BinOp::Or.precedence());
+        assert_eq!(None, BinOp::from_lexeme("="));
+        assert!(BinOp::Le.is_relational() && !BinOp::And.is_relational());
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+
+use super::token::Span;
+
+/// 診断の重大度
+///
+/// # members
+/// - Error => エラー
+/// - Warning => 警告
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Level {
+    Error,
+    Warning
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Level::Error => write!(f, "error"),
+            Level::Warning => write!(f, "warning")
+        }
+    }
+}
+
+/// 位置情報付きの診断メッセージ
+///
+/// # members
+/// - level: Level => 重大度
+/// - message: String => メッセージ
+/// - span: Span => 対象の位置
+/// - notes: Vec<(Span, String)> => 関連する位置と補足
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<(Span, String)>
+}
+
+impl Diagnostic {
+    /// エラーを生成して返す
+    ///
+    /// # params
+    /// - span: Span => 対象の位置
+    /// - message: String => メッセージ
+    ///
+    /// # returns
+    /// - Diagnostic
+    pub fn error(span: Span, message: String) -> Diagnostic {
+        Diagnostic { level: Level::Error, message, span, notes: vec![] }
+    }
+
+    /// 警告を生成して返す
+    ///
+    /// # params
+    /// - span: Span => 対象の位置
+    /// - message: String => メッセージ
+    ///
+    /// # returns
+    /// - Diagnostic
+    pub fn warning(span: Span, message: String) -> Diagnostic {
+        Diagnostic { level: Level::Warning, message, span, notes: vec![] }
+    }
+
+    /// 補足を追加して返す
+    ///
+    /// # params
+    /// - span: Span => 関連する位置
+    /// - note: String => 補足
+    ///
+    /// # returns
+    /// - Diagnostic
+    pub fn with_note(mut self, span: Span, note: String) -> Diagnostic {
+        self.notes.push((span, note));
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} at {}", self.level, self.message, self.span)?;
+        for (span, note) in &self.notes {
+            write!(f, "\n  note: {} at {}", note, span)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+    use super::super::token::Span;
+
+    #[test]
+    fn diagnostic_display_test() {
+        let diag = Diagnostic::error(Span::new(3, 5, 20, 21), "undefined symbol x".to_string())
+            .with_note(Span::new(1, 1, 0, 1), "declared here".to_string());
+        assert_eq!("error: undefined symbol x at 3:5\n  note: declared here at 1:1", diag.to_string());
+    }
+}
@@ -1,12 +1,15 @@
 use std::collections::HashMap;
 
-use super::token::{Tag, Token};
+use super::diagnostic::Diagnostic;
+use super::token::{Span, Tag, Token};
 
 pub struct Lexer {
     pub line: usize,
     pub nowon: usize,
+    line_head: usize,
     program: Vec<char>,
-    match_table: HashMap<String, Token>
+    match_table: HashMap<String, Token>,
+    diagnostics: Vec<Diagnostic>
 }
 
 impl Lexer {
@@ -21,8 +24,10 @@ impl Lexer {
         let mut lexer = Lexer {
             line: 1,
             nowon: 0,
+            line_head: 0,
             program: (program+"@").chars().collect::<Vec<char>>(),
-            match_table: HashMap::new()
+            match_table: HashMap::new(),
+            diagnostics: vec![]
         };
         lexer.reserve(Token::new_word(Tag::Primary, "true"));
         lexer.reserve(Token::new_word(Tag::Primary, "false"));
@@ -35,6 +40,23 @@ impl Lexer {
         lexer.reserve(Token::new_word(Tag::Symbol, "{"));
         lexer.reserve(Token::new_word(Tag::Symbol, "}"));
         lexer.reserve(Token::new_word(Tag::Symbol, ";"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "("));
+        lexer.reserve(Token::new_word(Tag::Symbol, ")"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "+"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "-"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "*"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "/"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "="));
+        lexer.reserve(Token::new_word(Tag::Symbol, "!"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "&&"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "||"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "&"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "|"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "["));
+        lexer.reserve(Token::new_word(Tag::Symbol, "]"));
+        lexer.reserve(Token::new_word(Tag::Symbol, ","));
+        lexer.reserve(Token::new_word(Tag::Symbol, ":"));
+        lexer.reserve(Token::new_word(Tag::Symbol, "->"));
         lexer
     }
 
@@ -52,15 +74,25 @@ impl Lexer {
         match target[0] {
             // 数字
             '0'..='9' => {
-                let num = Self::consume_num(self);
-                if num - (num as i32) as f32 > 0.0 {
+                // 小数点を含むものは小数("2.0"も小数として扱う)
+                let start = self.nowon;
+                let num_str = Self::consume_num(self);
+                let num = num_str.parse::<f32>().unwrap();
+                if num_str.contains('.') {
                     Some(Token::new_numf32(num))
                 } else {
-                    Some(Token::new_numi32(num as i32))
+                    // i32に収まらない整数は誤りとして記録し、字句解析は続ける
+                    let value = num_str.parse::<i32>().unwrap_or_else(|_| {
+                        let span = Span::new(self.line, start - self.line_head + 1, start, self.nowon);
+                        self.diagnostics.push(Diagnostic::error(span, format!("integer literal `{}` out of range for i32", num_str)));
+                        i32::MAX
+                    });
+                    Some(Token::new_numi32(value))
                 }
             },
             // 語 or 記号
-            'a'..='z' | 'A'..='Z' | '_' | '!' | ';'..='>' | '{' | '}' => {
+            'a'..='z' | 'A'..='Z' | '_' | '!' | ';'..='>' | '{' | '}' |
+            '(' | ')' | '+' | '-' | '*' | '/' | '&' | '|' | '[' | ']' | ',' | ':' => {
                 let word: String;
                 if let Some(w) = Self::consume_mark(self) {
                     word = w;
@@ -83,10 +115,34 @@ impl Lexer {
         }
     }
 
+    /// 1字句だけ解析を行い、解析結果<Token>とその位置を返す
+    ///
+    /// # returns
+    /// - Option<(Token, Span)>
+    pub fn scan_spanned(&mut self) -> Option<(Token, Span)> {
+        if self.program.len() <= self.nowon {
+            return None
+        }
+        Self::skip_space(self);
+        let (line, start) = (self.line, self.nowon);
+        let col = start - self.line_head + 1;
+        let token = Self::scan(self)?;
+        Some((token, Span::new(line, col, start, self.nowon)))
+    }
+
+    /// 字句解析中に見つけた誤りを取り出して返す
+    ///
+    /// # returns
+    /// - Vec<Diagnostic>
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
     /// 予約語など、既知の語を管理対象として追加する
     ///
     /// # params
     /// - token: Token => 追加するToken
+    #[allow(clippy::single_match)]
     pub fn reserve(&mut self, token: Token) {
         match token.clone() {
             Token::Word { tag: _, lexeme } => {
@@ -102,17 +158,17 @@ impl Lexer {
         for c in &self.program[nowon..] {
             match c {
                 ' ' | '\t' => self.nowon += 1,
-                '\n' => { self.line += 1; self.nowon += 1 },
+                '\n' => { self.line += 1; self.nowon += 1; self.line_head = self.nowon },
                 _ => break
             }
         }
     }
 
-    /// 解析中の場所から連続する数字列を読み取って、その値を返す
+    /// 解析中の場所から連続する数字列を読み取って、その文字列を返す
     ///
     /// # returns
-    /// - f32
-    fn consume_num(&mut self) -> f32 {
+    /// - String
+    fn consume_num(&mut self) -> String {
         let mut num_str = String::new();
         for c in &self.program[self.nowon..] {
             match c {
@@ -121,7 +177,7 @@ impl Lexer {
             }
         }
         self.nowon += num_str.chars().count();
-        num_str.parse::<f32>().unwrap()
+        num_str
     }
 
     /// 解析中の場所から連続する文字列を読み取って、その値を返す
@@ -144,6 +200,7 @@ impl Lexer {
     ///
     /// # returns
     /// Option<String>
+    #[allow(clippy::partialeq_to_none, clippy::needless_return)]
     fn consume_mark(&mut self) -> Option<String> {
         let mut word = None;
         let c = self.program[self.nowon];
@@ -155,6 +212,9 @@ impl Lexer {
             '=' if c == '<' => word = Some("<=".to_string()),
             '=' if c == '=' => word = Some("==".to_string()),
             '=' if c == '!' => word = Some("!=".to_string()),
+            '&' if c == '&' => word = Some("&&".to_string()),
+            '|' if c == '|' => word = Some("||".to_string()),
+            '>' if c == '-' => word = Some("->".to_string()),
             _ => {}
         }
         if word != None { self.nowon += 2; return word; }
@@ -166,6 +226,20 @@ impl Lexer {
             '{' => word = Some("{".to_string()),
             '}' => word = Some("}".to_string()),
             ';' => word = Some(";".to_string()),
+            '(' => word = Some("(".to_string()),
+            ')' => word = Some(")".to_string()),
+            '+' => word = Some("+".to_string()),
+            '-' => word = Some("-".to_string()),
+            '*' => word = Some("*".to_string()),
+            '/' => word = Some("/".to_string()),
+            '=' => word = Some("=".to_string()),
+            '!' => word = Some("!".to_string()),
+            '[' => word = Some("[".to_string()),
+            ']' => word = Some("]".to_string()),
+            ',' => word = Some(",".to_string()),
+            ':' => word = Some(":".to_string()),
+            '&' => word = Some("&".to_string()),
+            '|' => word = Some("|".to_string()),
             _ => {}
         }
         if word != None { self.nowon += 1; return word; }
@@ -176,9 +250,10 @@ impl Lexer {
 #[cfg(test)]
 mod tests {
     use super::Lexer;
-    use super::super::token::Token;
+    use super::super::token::{Span, Token};
 
     #[test]
+    #[allow(clippy::while_let_loop)]
     fn lexer_simple_test() {
         let program =
         "\
@@ -188,6 +263,8 @@ mod tests {
     1.23456789 0.00123456;
     < > <= >= != == true false;
     10>=20 30<=40 1<2 3>0 abc!=def;
+    (1+2)*3-4/5;
+    a[i][j] = !x && y || z, w;
 }
         ".to_string();
 
@@ -204,4 +281,38 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lexer_span_test() {
+        let mut lexer = Lexer::new("x = 10;\n  y >= 2".to_string());
+        let spans: Vec<Span> = std::iter::from_fn(|| lexer.scan_spanned()).map(|(_, span)| span).collect();
+        assert_eq!(Span::new(1, 5, 4, 6), spans[2]);
+        assert_eq!(Span::new(2, 3, 10, 11), spans[4]);
+        assert_eq!(Span::new(2, 5, 12, 14), spans[5]);
+    }
+
+    #[test]
+    fn lexer_num_test() {
+        let mut lexer = Lexer::new("2 2.0 2.5 16777217".to_string());
+        assert_eq!(Some(Token::new_numi32(2)), lexer.scan());
+        assert_eq!(Some(Token::new_numf32(2.0)), lexer.scan());
+        assert_eq!(Some(Token::new_numf32(2.5)), lexer.scan());
+        assert_eq!(Some(Token::new_numi32(16777217)), lexer.scan());
+
+        // i32に収まらない整数は丸めずに誤りにする
+        let mut lexer = Lexer::new("x = 3000000000;".to_string());
+        while lexer.scan().is_some() {}
+        let diags = lexer.take_diagnostics();
+        assert_eq!(1, diags.len());
+        assert_eq!("error: integer literal `3000000000` out of range for i32 at 1:5", diags[0].to_string());
+        assert!(lexer.take_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn lexer_single_mark_test() {
+        // 1文字の & と | もそれぞれ1つの字句になる
+        let mut lexer = Lexer::new("a & b | c && d".to_string());
+        let lexemes: Vec<String> = std::iter::from_fn(|| lexer.scan()).map(|t| t.to_string()).collect();
+        assert_eq!(vec!["a", "&", "b", "|", "c", "&&", "d"], lexemes);
+    }
+}
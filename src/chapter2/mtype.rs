@@ -1,20 +1,57 @@
 use std::fmt;
 
-/// 型を管理する列挙体
+/// ポインタの大きさ
+pub const POINTER_SIZE: usize = 8;
+
+/// 型を管理する列挙体(型式)
+/// 導出したPartialEqは名前等価(レコードは名前も比べる)、構造等価はstructural_eqで調べる
 ///
 /// # members
 /// - I32 { tag: Tag, size: usize } => 整数型(32)
 /// - F32 { tag: Tag, size: usize } => 小数型(32)
 /// - Char { tag: Tag, size: usize } => 文字型(8)
+/// - Bool(usize) => 真偽値型(8)
+/// - Void => 値を持たない型
+/// - Array(usize, Box<Type>) => 要素数Nの配列型 [N]T
+/// - Record(Option<String>, Vec<Field>) => レコード型(名前と、オフセット付きのフィールド)
+/// - Pointer(Box<Type>) => ポインタ型 *T
+/// - Function(Vec<Type>, Box<Type>) => 関数型 fn(T1, .., Tn) -> T
+/// - Var(usize) => 型推論で用いる型変数
 ///
 /// # derive
 /// - PartialEq
 /// - Clone
-#[derive(PartialEq, Clone)]
+/// - Debug
+#[derive(PartialEq, Clone, Debug)]
 pub enum Type {
     I32 (usize),
     F32 (usize),
     Char (usize),
+    Bool (usize),
+    Void,
+    Array (usize, Box<Type>),
+    Record (Option<String>, Vec<Field>),
+    Pointer (Box<Type>),
+    Function (Vec<Type>, Box<Type>),
+    Var (usize),
+}
+
+/// レコードのフィールド
+///
+/// # members
+/// - name: String => フィールド名
+/// - ty: Type => 型
+/// - offset: usize => レコードの先頭からの相対アドレス
+///
+/// # derive
+/// - PartialEq
+/// - Clone
+/// - Debug
+#[derive(PartialEq, Clone, Debug)]
+pub struct Field {
+    pub name: String,
+    pub ty: Type,
+    pub offset: usize
 }
 
 impl Type {
@@ -41,6 +78,259 @@ impl Type {
     pub fn new_char() -> Type {
         Type::Char(1)
     }
+
+    /// Type::Boolを生成して返す
+    ///
+    /// # returns
+    /// - Type
+    pub fn new_bool() -> Type {
+        Type::Bool(1)
+    }
+
+    /// 配列型 [n]elem を生成して返す
+    ///
+    /// # params
+    /// - n: usize => 要素数
+    /// - elem: Type => 要素の型
+    ///
+    /// # returns
+    /// - Type
+    pub fn new_array(n: usize, elem: Type) -> Type {
+        Type::Array(n, Box::new(elem))
+    }
+
+    /// ポインタ型 *to を生成して返す
+    ///
+    /// # params
+    /// - to: Type => 指す先の型
+    ///
+    /// # returns
+    /// - Type
+    pub fn new_pointer(to: Type) -> Type {
+        Type::Pointer(Box::new(to))
+    }
+
+    /// 関数型を生成して返す
+    ///
+    /// # params
+    /// - params: Vec<Type> => 引数の型
+    /// - ret: Type => 戻り値の型
+    ///
+    /// # returns
+    /// - Type
+    pub fn new_function(params: Vec<Type>, ret: Type) -> Type {
+        Type::Function(params, Box::new(ret))
+    }
+
+    /// レコード型を生成して返す
+    /// 各フィールドはその整列境界に合わせて配置し、全体の幅はレコードの整列境界の倍数に切り上げる
+    ///
+    /// # params
+    /// - name: Option<&str> => レコードの名前(無名ならNone)
+    /// - fields: Vec<(&str, Type)> => フィールド名と型
+    ///
+    /// # returns
+    /// - Type
+    pub fn new_record(name: Option<&str>, fields: Vec<(&str, Type)>) -> Type {
+        let mut offset = 0;
+        let fields = fields.into_iter().map(|(name, ty)| {
+            offset = align_up(offset, ty.align());
+            let field = Field { name: name.to_string(), offset, ty };
+            offset += field.ty.width();
+            field
+        }).collect();
+        Type::Record(name.map(|n| n.to_string()), fields)
+    }
+
+    /// 型の幅(バイト数)を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn width(&self) -> usize {
+        match self {
+            Type::I32(size) | Type::F32(size) | Type::Char(size) | Type::Bool(size) => *size,
+            Type::Void | Type::Function(_, _) | Type::Var(_) => 0,
+            Type::Array(n, elem) => n*elem.width(),
+            Type::Record(_, fields) => {
+                let end = fields.iter().map(|f| f.offset+f.ty.width()).max().unwrap_or(0);
+                align_up(end, self.align())
+            },
+            Type::Pointer(_) => POINTER_SIZE
+        }
+    }
+
+    /// 型の整列境界を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn align(&self) -> usize {
+        match self {
+            Type::I32(size) | Type::F32(size) | Type::Char(size) | Type::Bool(size) => *size,
+            Type::Void | Type::Function(_, _) | Type::Var(_) => 1,
+            Type::Array(_, elem) => elem.align(),
+            Type::Record(_, fields) => fields.iter().map(|f| f.ty.align()).max().unwrap_or(1),
+            Type::Pointer(_) => POINTER_SIZE
+        }
+    }
+
+    /// レコードのフィールドを名前で探す
+    ///
+    /// # params
+    /// - name: &str => フィールド名
+    ///
+    /// # returns
+    /// - Option<&Field>
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        match self {
+            Type::Record(_, fields) => fields.iter().find(|f| f.name == name),
+            _ => None
+        }
+    }
+
+    /// 配列の要素の型を返す
+    ///
+    /// # returns
+    /// - Option<&Type>
+    pub fn elem(&self) -> Option<&Type> {
+        match self {
+            Type::Array(_, elem) => Some(elem),
+            _ => None
+        }
+    }
+
+    /// 構造等価かどうかを返す(レコードの名前とフィールド名は比べない)
+    ///
+    /// # params
+    /// - other: &Type => 比べる型
+    ///
+    /// # returns
+    /// - bool
+    pub fn structural_eq(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Array(n, a), Type::Array(m, b)) => n == m && a.structural_eq(b),
+            (Type::Record(_, a), Type::Record(_, b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.ty.structural_eq(&y.ty))
+            },
+            (Type::Pointer(a), Type::Pointer(b)) => a.structural_eq(b),
+            (Type::Function(ps, r), Type::Function(qs, s)) => {
+                ps.len() == qs.len() && ps.iter().zip(qs).all(|(p, q)| p.structural_eq(q)) && r.structural_eq(s)
+            },
+            _ => self == other
+        }
+    }
+
+    /// 名前等価かどうかを返す
+    /// 名前のついたレコードは名前が同じときだけ、無名のものは構造が同じときに等価とする
+    ///
+    /// # params
+    /// - other: &Type => 比べる型
+    ///
+    /// # returns
+    /// - bool
+    pub fn name_eq(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Record(Some(a), _), Type::Record(Some(b), _)) => a == b,
+            (Type::Record(Some(_), _), Type::Record(None, _)) | (Type::Record(None, _), Type::Record(Some(_), _)) => false,
+            (Type::Array(n, a), Type::Array(m, b)) => n == m && a.name_eq(b),
+            (Type::Pointer(a), Type::Pointer(b)) => a.name_eq(b),
+            (Type::Function(ps, r), Type::Function(qs, s)) => {
+                ps.len() == qs.len() && ps.iter().zip(qs).all(|(p, q)| p.name_eq(q)) && r.name_eq(s)
+            },
+            _ => self.structural_eq(other)
+        }
+    }
+
+    /// 数値型かどうかを返す
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_numeric(&self) -> bool {
+        self.rank().is_some()
+    }
+
+    /// 整数として扱える型(i32, char)かどうかを返す
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_integral(&self) -> bool {
+        matches!(self, Type::I32(_) | Type::Char(_))
+    }
+
+    /// 拡大変換で other に変換できるかを返す(char -> i32 -> f32)
+    ///
+    /// # params
+    /// - other: &Type => 変換先の型
+    ///
+    /// # returns
+    /// - bool
+    pub fn widens_to(&self, other: &Type) -> bool {
+        match (self.rank(), other.rank()) {
+            (Some(a), Some(b)) => a <= b,
+            _ => self == other
+        }
+    }
+
+    /// 拡大変換の階層で2つの型のうち大きい方を返す(6.5.2節のmax)
+    ///
+    /// # params
+    /// - other: &Type => もう一方の型
+    ///
+    /// # returns
+    /// - Option<Type>
+    pub fn max(&self, other: &Type) -> Option<Type> {
+        if self.widens_to(other) {
+            Some(other.clone())
+        } else if other.widens_to(self) {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+
+    /// 型に現れる型変数を現れた順に返す
+    ///
+    /// # returns
+    /// - Vec<usize>
+    pub fn vars(&self) -> Vec<usize> {
+        let mut vars = vec![];
+        self.collect_vars(&mut vars);
+        vars
+    }
+
+    fn collect_vars(&self, vars: &mut Vec<usize>) {
+        match self {
+            Type::Var(n) if !vars.contains(n) => vars.push(*n),
+            Type::Array(_, t) | Type::Pointer(t) => t.collect_vars(vars),
+            Type::Record(_, fields) => fields.iter().for_each(|f| f.ty.collect_vars(vars)),
+            Type::Function(params, ret) => {
+                params.iter().for_each(|p| p.collect_vars(vars));
+                ret.collect_vars(vars);
+            },
+            _ => {}
+        }
+    }
+
+    /// 拡大変換の階層での順位
+    fn rank(&self) -> Option<usize> {
+        match self {
+            Type::Char(_) => Some(0),
+            Type::I32(_) => Some(1),
+            Type::F32(_) => Some(2),
+            _ => None
+        }
+    }
+}
+
+/// offsetをalignの倍数に切り上げる
+///
+/// # params
+/// - offset: usize => 相対アドレス
+/// - align: usize => 境界
+///
+/// # returns
+/// - usize
+pub fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align.max(1))*align.max(1)
 }
 
 /// Displayトレイト
@@ -49,7 +339,26 @@ impl fmt::Display for Type {
         match self {
             Type::I32 (_) => write!(f, "i32"),
             Type::F32 (_) => write!(f, "f32"),
-            Type::Char (_) => write!(f, "char")
+            Type::Char (_) => write!(f, "char"),
+            Type::Bool (_) => write!(f, "bool"),
+            Type::Void => write!(f, "void"),
+            Type::Array (n, elem) => write!(f, "[{}]{}", n, elem),
+            Type::Record (Some(name), _) => write!(f, "record {}", name),
+            Type::Record (None, fields) => {
+                write!(f, "record {{")?;
+                for field in fields {
+                    write!(f, " {} {};", field.ty, field.name)?;
+                }
+                write!(f, " }}")
+            },
+            Type::Pointer (to) => write!(f, "*{}", to),
+            Type::Function (params, ret) => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) -> {}", params.join(", "), ret)
+            },
+            // 'a..'z、それ以降は't26のように番号で表す
+            Type::Var (n) if *n < 26 => write!(f, "'{}", (b'a'+*n as u8) as char),
+            Type::Var (n) => write!(f, "'t{}", n)
         }
     }
 }
@@ -64,4 +373,52 @@ mod tests {
         let _ = Type::new_f32();
         let _ = Type::new_char();
     }
+
+    #[test]
+    fn type_widen_test() {
+        assert!(Type::new_char().widens_to(&Type::new_i32()));
+        assert!(Type::new_i32().widens_to(&Type::new_f32()));
+        assert!(!Type::new_f32().widens_to(&Type::new_i32()));
+        assert_eq!(Some(Type::new_f32()), Type::new_char().max(&Type::new_f32()));
+        assert_eq!(Some(Type::new_i32()), Type::new_i32().max(&Type::new_i32()));
+        assert!(Type::new_char().is_integral() && !Type::new_f32().is_integral());
+        assert!(!Type::new_bool().is_numeric());
+        assert!(!Type::new_bool().widens_to(&Type::new_i32()));
+    }
+
+    #[test]
+    fn type_width_test() {
+        // i32[10][20] a; => [10][20]i32
+        let a = Type::new_array(10, Type::new_array(20, Type::new_i32()));
+        assert_eq!(800, a.width());
+        assert_eq!(80, a.elem().unwrap().width());
+        assert_eq!("[10][20]i32", a.to_string());
+
+        let r = Type::new_record(None, vec![("c", Type::new_char()), ("x", Type::new_f32()), ("d", Type::new_char())]);
+        assert_eq!(4, r.field("x").unwrap().offset);
+        assert_eq!(8, r.field("d").unwrap().offset);
+        assert_eq!((12, 4), (r.width(), r.align()));
+        assert_eq!("record { char c; f32 x; char d; }", r.to_string());
+
+        let p = Type::new_pointer(Type::new_char());
+        assert_eq!(8, p.width());
+        let f = Type::new_function(vec![Type::new_i32(), p], Type::Void);
+        assert_eq!("fn(i32, *char) -> void", f.to_string());
+        assert_eq!(0, f.width());
+    }
+
+    #[test]
+    fn type_equivalence_test() {
+        let point = Type::new_record(Some("point"), vec![("x", Type::new_i32()), ("y", Type::new_i32())]);
+        let pair = Type::new_record(Some("pair"), vec![("a", Type::new_i32()), ("b", Type::new_i32())]);
+        let anon = Type::new_record(None, vec![("x", Type::new_i32()), ("y", Type::new_i32())]);
+        assert!(point.structural_eq(&pair) && point.structural_eq(&anon));
+        assert!(!point.name_eq(&pair) && !point.name_eq(&anon));
+        assert!(point.name_eq(&point.clone()));
+
+        let pp = Type::new_pointer(point.clone());
+        assert!(pp.structural_eq(&Type::new_pointer(pair.clone())));
+        assert!(!pp.name_eq(&Type::new_pointer(pair)));
+        assert!(!Type::new_array(3, Type::new_i32()).structural_eq(&Type::new_array(4, Type::new_i32())));
+    }
 }
\ No newline at end of file
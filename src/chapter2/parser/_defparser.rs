@@ -1,6 +1,6 @@
 use super::super::lexer::Lexer;
 use super::super::mtype::Type;
-use super::super::token::{Token, Tag};
+use super::super::token::{Span, Token, Tag};
 use super::super::symbol::{Symbol, SymbolTable};
 
 /// 定義と使用からなるプログラムをパースする(未完成/失敗作)
@@ -29,6 +29,7 @@ impl DefParser {
     }
 
     /// blocks: ブロックの集合
+    #[allow(non_fmt_panics)]
     fn blocks(&mut self) {
         loop {
             match Self::block(self) {
@@ -43,16 +44,17 @@ impl DefParser {
     ///
     /// # returns
     /// Result<(), String>
+    #[allow(clippy::needless_return)]
     fn block(&mut self) -> Result<(), String> {
         let mut ret_result = Ok(());
         for cnt in 0..=1 {
             let block_s = Self::expect(self, Tag::Symbol)?;
             if let Token::Word { tag: _, lexeme } = block_s {
                 if cnt == 0 && lexeme == "{" {
-                    self.table = SymbolTable::new_with_table(self.table.clone());
+                    self.table.enter(Span::new(self.lexer.line, 0, self.lexer.nowon, self.lexer.nowon));
                     ret_result = Self::stmts(self);
                 } else if cnt == 1 && lexeme == "}" {
-                    self.table = self.table.clone().release().unwrap();
+                    self.table.exit(Span::new(self.lexer.line, 0, self.lexer.nowon, self.lexer.nowon));
                 } else {
                     return Err("block undefined/unclosed!!".to_string());
                 }
@@ -80,7 +82,7 @@ impl DefParser {
             match tag {
                 Tag::Type => {
                     let id_t = Self::expect(self, Tag::Id)?;
-                    Self::decl(self, token, id_t);
+                    Self::decl(self, token, id_t)?;
                 },
                 Tag::Id => Self::factor(self, token),
                 _ => {}
@@ -95,7 +97,10 @@ impl DefParser {
     /// # params
     /// - ty_t: Token => Tag::TypeであるToken
     /// - id_t: Token => Tag::IdであるToken
-    fn decl(&mut self, ty_t: Token, id_t: Token) {
+    ///
+    /// # returns
+    /// Result<(), String> => 同じスコープで再宣言した場合はErr
+    fn decl(&mut self, ty_t: Token, id_t: Token) -> Result<(), String> {
         if let Token::Word { tag: _, lexeme } = ty_t {
             let ty = match &lexeme[..] {
                 "i32" => Type::new_i32(),
@@ -104,9 +109,10 @@ impl DefParser {
                 _ => Type::new_i32()    // ここに来ることは絶対無いけど...
             };
             if let Token::Word { tag: _, lexeme } = id_t {
-                self.table.add(Symbol::new(lexeme, ty));
+                self.table.add(Symbol::new(lexeme, ty)).map_err(|diag| diag.to_string())?;
             }
         }
+        Ok(())
     }
 
     /// factor: 構文の最小単位
@@ -131,6 +137,7 @@ impl DefParser {
     ///
     /// # return
     /// Result<Token, ()>
+    #[allow(clippy::useless_format)]
     fn expect(&mut self, etag: Tag) -> Result<Token, String> {
         if let Some(token) = self.lexer.scan() {
             match token {
@@ -170,4 +177,14 @@ mod tests {
         let mut parser = DefParser::new(lexer, table);
         parser.blocks();
     }
+
+    #[test]
+    fn defparser_redeclaration_test() {
+        let mut lexer = Lexer::new("{ i32 a f32 a }".to_string());
+        lexer.reserve(Token::new_word(Tag::Type, "i32"));
+        lexer.reserve(Token::new_word(Tag::Type, "f32"));
+        let mut parser = DefParser::new(lexer, SymbolTable::new());
+        let err = parser.block().unwrap_err();
+        assert!(err.contains("redeclaration of `a` in the same scope"));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,804 @@
+use super::super::ast::{BinOp, Block, Case, Decl, Expr, ExprKind, Func, NodeId, Program, Stmt, StmtKind, UnOp};
+use super::super::diagnostic::Diagnostic;
+use super::super::lexer::Lexer;
+use super::super::mtype::Type;
+use super::super::symbol::{Symbol, SymbolKind, SymbolTable};
+use super::super::token::{Span, Tag, Token};
+
+/// 付録A・2.8節の言語を構文解析して抽象構文木を組み立てる
+/// ぶら下がりelseは最も近いifに結びつける
+///
+/// # members
+/// - lexer: Lexer => 字句解析器
+/// - table: SymbolTable => 記号表(現在のブロックのスコープにいる)
+/// - lookahead: Option<(Token, Span)> => 先読みしたトークン
+/// - last: Span => 直前に読んだトークンの位置
+/// - next_id: NodeId => 次に割り当てる節番号
+/// - loops: usize => 解析中のループの入れ子の深さ(break, continueの検査に使う)
+/// - switches: usize => 解析中のswitchの入れ子の深さ(breakの検査に使う)
+/// - in_function: bool => 関数の本体を解析中か(returnの検査に使う)
+/// - diagnostics: Vec<Diagnostic> => 構文解析を止めない診断(再宣言や範囲外の整数など)
+pub struct AstParser {
+    lexer: Lexer,
+    table: SymbolTable,
+    lookahead: Option<(Token, Span)>,
+    last: Span,
+    next_id: NodeId,
+    loops: usize,
+    switches: usize,
+    in_function: bool,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl AstParser {
+    /// AstParserを生成して返す
+    ///
+    /// # params
+    /// - program: &str => 構文解析対象文字列
+    ///
+    /// # returns
+    /// - AstParser
+    pub fn new(program: &str) -> AstParser {
+        let mut lexer = Lexer::new(program.to_string());
+        lexer.reserve(Token::new_word(Tag::Type, "i32"));
+        lexer.reserve(Token::new_word(Tag::Type, "f32"));
+        lexer.reserve(Token::new_word(Tag::Type, "char"));
+        lexer.reserve(Token::new_word(Tag::Type, "bool"));
+        for keyword in &["if", "else", "while", "do", "break", "continue", "switch", "case", "default", "fn", "return"] {
+            lexer.reserve(Token::new_word(Tag::Keyword, keyword));
+        }
+        let lookahead = lexer.scan_spanned();
+        let diagnostics = lexer.take_diagnostics();
+        AstParser {
+            lexer,
+            table: SymbolTable::new(),
+            lookahead,
+            last: Span::default(),
+            next_id: 0,
+            loops: 0,
+            switches: 0,
+            in_function: false,
+            diagnostics
+        }
+    }
+
+    /// 構文解析で作った記号表を返す
+    /// 抜けたブロックのスコープも残っているので、位置からスコープや見える名前を引ける
+    ///
+    /// # returns
+    /// - &SymbolTable
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.table
+    }
+
+    /// 構文解析中に見つけた、解析を止めない診断を返す
+    ///
+    /// # returns
+    /// - &[Diagnostic]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// 入力全体を関数の並びとして構文解析する
+    /// 関数の名前は本体より先に記号表に入れるので、再帰呼出しができる
+    /// 相互再帰は前方宣言 fn f(i32 n) -> bool; で書く
+    ///
+    /// # returns
+    /// - Result<Program, Diagnostic>
+    pub fn parse_program(&mut self) -> Result<Program, Diagnostic> {
+        let start = self.span();
+        let mut funcs = vec![];
+        while self.lookahead.is_some() {
+            funcs.push(self.func()?);
+        }
+        let diagnostics = self.table.scope(0).undefined_diagnostics();
+        self.diagnostics.extend(diagnostics);
+        Ok(Program { id: self.id(), funcs, span: start.to(self.last) })
+    }
+
+    /// 入力全体を1つのブロックとして構文解析する
+    ///
+    /// # returns
+    /// - Result<Block, Diagnostic>
+    pub fn parse_block(&mut self) -> Result<Block, Diagnostic> {
+        let block = self.block()?;
+        self.expect_eof()?;
+        Ok(block)
+    }
+
+    /// 入力全体を1つの式として構文解析する
+    ///
+    /// # returns
+    /// - Result<Expr, Diagnostic>
+    pub fn parse_expr(&mut self) -> Result<Expr, Diagnostic> {
+        let expr = self.expr()?;
+        self.expect_eof()?;
+        Ok(expr)
+    }
+
+    /// func -> fn Id ( params ) [-> Type] ( block | ; )
+    /// params -> [param (, param)*]
+    /// 仮引数は関数のスコープに、本体の宣言はその内側のブロックのスコープに入る
+    fn func(&mut self) -> Result<Func, Diagnostic> {
+        let start = self.expect("fn")?;
+        let name = self.ident()?;
+        let open = self.expect("(")?;
+        let mut params = vec![];
+        while !self.check(")") {
+            if !params.is_empty() {
+                self.expect(",")?;
+            }
+            let (ty, name, span) = self.typed_name()?;
+            params.push(Decl { id: self.id(), ty, name, span });
+        }
+        self.expect(")")?;
+        let ret = if self.check("->") {
+            self.bump();
+            match &self.lookahead {
+                Some((Token::Word { tag: Tag::Type, .. }, _)) => self.base_type(),
+                _ => return Err(self.error("type"))
+            }
+        } else {
+            Type::Void
+        };
+        let header = start.to(self.last);
+        let sig = Type::new_function(params.iter().map(|p| p.ty.clone()).collect(), ret.clone());
+        let forward = self.check(";");
+        let mut symbol = Symbol::new(name.clone(), sig).with_kind(SymbolKind::Func).with_span(header);
+        if forward {
+            symbol = symbol.forward();
+        }
+        if let Err(diag) = self.table.insert(symbol) {
+            self.diagnostics.push(diag);
+        }
+        if forward {
+            let end = self.expect(";")?;
+            return Ok(Func { id: self.id(), name, params, ret, body: None, span: start.to(end) });
+        }
+
+        self.table.enter_function(open);
+        for param in &params {
+            let symbol = Symbol::new(param.name.clone(), param.ty.clone()).with_kind(SymbolKind::Param).with_span(param.span);
+            if let Err(diag) = self.table.insert(symbol) {
+                self.diagnostics.push(diag);
+            }
+        }
+        self.in_function = true;
+        let body = self.block();
+        self.in_function = false;
+        self.table.exit(self.last);
+        let body = body?;
+        let span = start.to(body.span);
+        Ok(Func { id: self.id(), name, params, ret, body: Some(body), span })
+    }
+
+    /// block -> { stmt* }
+    /// ブロックごとに記号表のスコープに入る(誤りがあってもスコープは閉じる)
+    fn block(&mut self) -> Result<Block, Diagnostic> {
+        let start = self.expect("{")?;
+        self.table.enter(start);
+        let body = self.stmts().and_then(|stmts| self.expect("}").map(|end| (stmts, end)));
+        self.table.exit(self.last);
+        let (stmts, end) = body?;
+        Ok(Block { id: self.id(), stmts, span: start.to(end) })
+    }
+
+    fn stmts(&mut self) -> Result<Vec<Stmt>, Diagnostic> {
+        let mut stmts = vec![];
+        while !self.check("}") {
+            if self.lookahead.is_none() {
+                return Err(self.error("`}`"));
+            }
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    /// stmt -> Type Id ;
+    ///       | if ( expr ) stmt [else stmt]
+    ///       | while ( expr ) stmt
+    ///       | do stmt while ( expr ) ;
+    ///       | switch ( expr ) { cases }
+    ///       | break ;
+    ///       | continue ;
+    ///       | return [expr] ;
+    ///       | block
+    ///       | loc = expr ;
+    ///       | expr ;
+    fn stmt(&mut self) -> Result<Stmt, Diagnostic> {
+        let start = self.span();
+        let kind = match &self.lookahead {
+            Some((Token::Word { tag: Tag::Type, .. }, _)) => StmtKind::Decl(self.decl()?),
+            Some((Token::Word { lexeme, .. }, _)) if lexeme == "{" => StmtKind::Block(self.block()?),
+            Some((Token::Word { tag: Tag::Keyword, lexeme }, _)) => match &lexeme[..] {
+                "if" => {
+                    self.bump();
+                    let cond = self.cond()?;
+                    let then = Box::new(self.stmt()?);
+                    // elseは最も近いifに結びつく
+                    let els = if self.check("else") {
+                        self.bump();
+                        Some(Box::new(self.stmt()?))
+                    } else {
+                        None
+                    };
+                    StmtKind::If(cond, then, els)
+                },
+                "while" => {
+                    self.bump();
+                    let cond = self.cond()?;
+                    let body = Box::new(self.loop_body()?);
+                    StmtKind::While(cond, body)
+                },
+                "do" => {
+                    self.bump();
+                    let body = Box::new(self.loop_body()?);
+                    self.expect("while")?;
+                    let cond = self.cond()?;
+                    self.expect(";")?;
+                    StmtKind::DoWhile(body, cond)
+                },
+                "switch" => {
+                    self.bump();
+                    let expr = self.cond()?;
+                    self.expect("{")?;
+                    self.switches += 1;
+                    let cases = self.cases();
+                    self.switches -= 1;
+                    let cases = cases?;
+                    self.expect("}")?;
+                    StmtKind::Switch(expr, cases)
+                },
+                "break" => {
+                    self.bump();
+                    if self.loops == 0 && self.switches == 0 {
+                        return Err(Diagnostic::error(self.last, "`break` outside of a loop".to_string()));
+                    }
+                    self.expect(";")?;
+                    StmtKind::Break
+                },
+                "continue" => {
+                    self.bump();
+                    if self.loops == 0 {
+                        return Err(Diagnostic::error(self.last, "`continue` outside of a loop".to_string()));
+                    }
+                    self.expect(";")?;
+                    StmtKind::Continue
+                },
+                "return" => {
+                    self.bump();
+                    if !self.in_function {
+                        return Err(Diagnostic::error(self.last, "`return` outside of a function".to_string()));
+                    }
+                    let value = if self.check(";") { None } else { Some(self.expr()?) };
+                    self.expect(";")?;
+                    StmtKind::Return(value)
+                },
+                _ => return Err(self.error("statement"))
+            },
+            _ => {
+                let expr = self.expr()?;
+                if self.check("=") {
+                    if !matches!(expr.kind, ExprKind::Var(_) | ExprKind::Index(_, _)) {
+                        return Err(Diagnostic::error(expr.span, "invalid left-hand side of assignment".to_string()));
+                    }
+                    self.bump();
+                    let value = self.expr()?;
+                    self.expect(";")?;
+                    StmtKind::Assign(expr, value)
+                } else {
+                    self.expect(";")?;
+                    StmtKind::Expr(expr)
+                }
+            }
+        };
+        Ok(Stmt { id: self.id(), kind, span: start.to(self.last) })
+    }
+
+    /// ( expr )
+    fn cond(&mut self) -> Result<Expr, Diagnostic> {
+        self.expect("(")?;
+        let cond = self.expr()?;
+        self.expect(")")?;
+        Ok(cond)
+    }
+
+    /// cases -> ( case label : stmts | default : stmts )*
+    /// label -> num | - num
+    fn cases(&mut self) -> Result<Vec<Case>, Diagnostic> {
+        let mut cases = vec![];
+        while !self.check("}") {
+            let start = self.span();
+            let value = if self.check("case") {
+                self.bump();
+                let negative = self.check("-");
+                if negative {
+                    self.bump();
+                }
+                match self.lookahead.clone() {
+                    Some((Token::NumI32 { num }, _)) => { self.bump(); Some(if negative { -num } else { num }) },
+                    _ => return Err(self.error("integer constant"))
+                }
+            } else if self.check("default") {
+                self.bump();
+                None
+            } else {
+                return Err(self.error("`case` or `default`"));
+            };
+            let end = self.expect(":")?;
+            let mut stmts = vec![];
+            while !self.check("case") && !self.check("default") && !self.check("}") {
+                if self.lookahead.is_none() {
+                    return Err(self.error("`}`"));
+                }
+                stmts.push(self.stmt()?);
+            }
+            cases.push(Case { id: self.id(), value, stmts, span: start.to(end) });
+        }
+        Ok(cases)
+    }
+
+    /// ループ本体の文を、breakとcontinueを許して構文解析する
+    fn loop_body(&mut self) -> Result<Stmt, Diagnostic> {
+        self.loops += 1;
+        let body = self.stmt();
+        self.loops -= 1;
+        body
+    }
+
+    /// decl -> Type ( [ num ] )* Id ;
+    /// Type[2][3] は [2][3]Type すなわち array(2, array(3, Type)) になる(6.3.2節)
+    fn decl(&mut self) -> Result<Decl, Diagnostic> {
+        let (ty, name, span) = self.typed_name()?;
+        let end = self.expect(";")?;
+        let span = span.to(end);
+        if let Err(diag) = self.table.insert(Symbol::new(name.clone(), ty.clone()).with_span(span)) {
+            self.diagnostics.push(diag);
+        }
+        Ok(Decl { id: self.id(), ty, name, span })
+    }
+
+    /// Type ( [ num ] )* Id を読み、型と名前、その位置を返す(宣言と仮引数に共通)
+    fn typed_name(&mut self) -> Result<(Type, String, Span), Diagnostic> {
+        let start = self.span();
+        if !matches!(&self.lookahead, Some((Token::Word { tag: Tag::Type, .. }, _))) {
+            return Err(self.error("type"));
+        }
+        let mut ty = self.base_type();
+        let mut dims = vec![];
+        while self.check("[") {
+            self.bump();
+            match self.lookahead {
+                Some((Token::NumI32 { num }, _)) if num > 0 => { self.bump(); dims.push(num as usize) },
+                _ => return Err(self.error("array size"))
+            }
+            self.expect("]")?;
+        }
+        for n in dims.into_iter().rev() {
+            ty = Type::new_array(n, ty);
+        }
+        let name = self.ident()?;
+        Ok((ty, name, start.to(self.last)))
+    }
+
+    /// 型名を読んで型を返す(先読みが型名であることは呼び出し側が確かめる)
+    fn base_type(&mut self) -> Type {
+        let (ty, _) = self.bump().unwrap();
+        match ty.to_string().as_str() {
+            "f32" => Type::new_f32(),
+            "char" => Type::new_char(),
+            "bool" => Type::new_bool(),
+            _ => Type::new_i32()
+        }
+    }
+
+    /// 名前を読む
+    fn ident(&mut self) -> Result<String, Diagnostic> {
+        match self.lookahead.clone() {
+            Some((Token::Word { tag: Tag::Id, lexeme }, _)) => { self.bump(); Ok(lexeme) },
+            _ => Err(self.error("identifier"))
+        }
+    }
+
+    /// 優先順位に従って二項演算子を結合する
+    fn expr(&mut self) -> Result<Expr, Diagnostic> {
+        self.binary(1)
+    }
+
+    fn binary(&mut self, min_prec: usize) -> Result<Expr, Diagnostic> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match &self.lookahead {
+                Some((Token::Word { lexeme, .. }, _)) => BinOp::from_lexeme(lexeme),
+                _ => None
+            };
+            match op {
+                Some(op) if op.precedence() >= min_prec => {
+                    self.bump();
+                    let rhs = self.binary(op.precedence()+1)?;
+                    let span = lhs.span.to(rhs.span);
+                    lhs = Expr { id: self.id(), kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), span };
+                },
+                _ => return Ok(lhs)
+            }
+        }
+    }
+
+    /// unary -> - unary | ! unary | postfix
+    fn unary(&mut self) -> Result<Expr, Diagnostic> {
+        let op = if self.check("-") {
+            Some(UnOp::Neg)
+        } else if self.check("!") {
+            Some(UnOp::Not)
+        } else {
+            None
+        };
+        match op {
+            Some(op) => {
+                let (_, start) = self.bump().unwrap();
+                let operand = self.unary()?;
+                let span = start.to(operand.span);
+                Ok(Expr { id: self.id(), kind: ExprKind::Unary(op, Box::new(operand)), span })
+            },
+            None => self.postfix()
+        }
+    }
+
+    /// postfix -> primary ( [ expr ] )*
+    fn postfix(&mut self) -> Result<Expr, Diagnostic> {
+        let mut expr = self.primary()?;
+        while self.check("[") {
+            self.bump();
+            let index = self.expr()?;
+            let end = self.expect("]")?;
+            let span = expr.span.to(end);
+            expr = Expr { id: self.id(), kind: ExprKind::Index(Box::new(expr), Box::new(index)), span };
+        }
+        Ok(expr)
+    }
+
+    /// primary -> num | true | false | id | id ( args ) | ( expr )
+    /// args -> [expr (, expr)*]
+    /// 名前が宣言されているかは名前解決(chapter6::resolve)で調べる
+    fn primary(&mut self) -> Result<Expr, Diagnostic> {
+        let kind = match self.lookahead.clone() {
+            Some((Token::NumI32 { num }, _)) => ExprKind::Int(num),
+            Some((Token::NumF32 { num }, _)) => ExprKind::Float(num),
+            Some((Token::Word { tag: Tag::Primary, lexeme }, _)) => ExprKind::Bool(lexeme == "true"),
+            Some((Token::Word { tag: Tag::Id, lexeme }, span)) => {
+                self.bump();
+                if !self.check("(") {
+                    return Ok(Expr { id: self.id(), kind: ExprKind::Var(lexeme), span });
+                }
+                self.bump();
+                let mut args = vec![];
+                while !self.check(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.expr()?);
+                }
+                let end = self.expect(")")?;
+                return Ok(Expr { id: self.id(), kind: ExprKind::Call(lexeme, args), span: span.to(end) });
+            },
+            Some((Token::Word { lexeme, .. }, _)) if lexeme == "(" => {
+                let start = self.expect("(")?;
+                let mut expr = self.expr()?;
+                let end = self.expect(")")?;
+                expr.span = start.to(end);
+                return Ok(expr);
+            },
+            _ => return Err(self.error("expression"))
+        };
+        let (_, span) = self.bump().unwrap();
+        Ok(Expr { id: self.id(), kind, span })
+    }
+
+    /// 新しい節番号を返す
+    fn id(&mut self) -> NodeId {
+        self.next_id += 1;
+        self.next_id-1
+    }
+
+    /// 先読みを1つ進め、読んだトークンを返す
+    fn bump(&mut self) -> Option<(Token, Span)> {
+        let current = self.lookahead.take();
+        if let Some((_, span)) = &current {
+            self.last = *span;
+        }
+        self.lookahead = self.lexer.scan_spanned();
+        let diagnostics = self.lexer.take_diagnostics();
+        self.diagnostics.extend(diagnostics);
+        current
+    }
+
+    /// 先読みの位置を返す(入力終端なら直前のトークンの直後)
+    fn span(&self) -> Span {
+        match &self.lookahead {
+            Some((_, span)) => *span,
+            None => Span::new(self.last.line, self.last.col+self.last.end-self.last.start, self.last.end, self.last.end)
+        }
+    }
+
+    /// 先読みが指定した字句の語かを返す
+    fn check(&self, lexeme: &str) -> bool {
+        matches!(&self.lookahead, Some((Token::Word { lexeme: l, .. }, _)) if l == lexeme)
+    }
+
+    /// 指定した字句の語を読み、その位置を返す
+    fn expect(&mut self, lexeme: &str) -> Result<Span, Diagnostic> {
+        if self.check(lexeme) {
+            Ok(self.bump().unwrap().1)
+        } else {
+            Err(self.error(&format!("`{}`", lexeme)))
+        }
+    }
+
+    /// 入力が終わっていることを確かめる
+    fn expect_eof(&mut self) -> Result<(), Diagnostic> {
+        match self.lookahead {
+            None => Ok(()),
+            Some(_) => Err(self.error("end of input"))
+        }
+    }
+
+    /// 先読みの位置に構文エラーを作る
+    fn error(&self, expected: &str) -> Diagnostic {
+        let found = match &self.lookahead {
+            Some((token, _)) => format!("`{}`", token),
+            None => "end of input".to_string()
+        };
+        Diagnostic::error(self.span(), format!("syntax error: expected {}, found {}", expected, found))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AstParser;
+    use super::super::super::ast::{BinOp, ExprKind, StmtKind, UnOp};
+    use super::super::super::mtype::Type;
+    use super::super::super::symbol::SymbolKind;
+    use super::super::super::token::Span;
+
+    #[test]
+    fn astparser_expr_test() {
+        let expr = AstParser::new("a + b * -c[1] < 2 && !d || x == 1.5").parse_expr().unwrap();
+        assert_eq!(Span::new(1, 1, 0, 35), expr.span);
+        match expr.kind {
+            ExprKind::Binary(BinOp::Or, lhs, rhs) => {
+                assert!(matches!(lhs.kind, ExprKind::Binary(BinOp::And, _, _)));
+                match rhs.kind {
+                    ExprKind::Binary(BinOp::Eq, x, f) => {
+                        assert_eq!(ExprKind::Var("x".to_string()), x.kind);
+                        assert_eq!(ExprKind::Float(1.5), f.kind);
+                    },
+                    _ => panic!("test failed at [astparser_expr_test]")
+                }
+            },
+            _ => panic!("test failed at [astparser_expr_test]")
+        }
+
+        let expr = AstParser::new("(1 - 2) - -3").parse_expr().unwrap();
+        match expr.kind {
+            ExprKind::Binary(BinOp::Sub, lhs, rhs) => {
+                assert_eq!(Span::new(1, 1, 0, 7), lhs.span);
+                assert!(matches!(rhs.kind, ExprKind::Unary(UnOp::Neg, _)));
+            },
+            _ => panic!("test failed at [astparser_expr_test]")
+        }
+    }
+
+    #[test]
+    fn astparser_block_test() {
+        let block = AstParser::new("{ i32 x; f32 y;\n  { char c; x; } y; }").parse_block().unwrap();
+        assert_eq!(4, block.stmts.len());
+        match &block.stmts[1].kind {
+            StmtKind::Decl(decl) => {
+                assert_eq!(Type::new_f32(), decl.ty);
+                assert_eq!("y", decl.name);
+            },
+            _ => panic!("test failed at [astparser_block_test]")
+        }
+        match &block.stmts[2].kind {
+            StmtKind::Block(inner) => {
+                assert_eq!(2, inner.stmts.len());
+                assert_eq!(Span::new(2, 3, 18, 32), inner.span);
+            },
+            _ => panic!("test failed at [astparser_block_test]")
+        }
+
+        // 節番号はすべて異なる
+        let mut ids = vec![block.id];
+        ids.extend(block.stmts.iter().map(|s| s.id));
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(5, ids.len());
+    }
+
+    #[test]
+    fn astparser_error_test() {
+        let err = AstParser::new("{ i32 x; x + ; }").parse_block().unwrap_err();
+        assert_eq!("syntax error: expected expression, found `;`", err.message);
+        assert_eq!(Span::new(1, 14, 13, 14), err.span);
+        let err = AstParser::new("{ x; ").parse_block().unwrap_err();
+        assert_eq!("syntax error: expected `}`, found end of input", err.message);
+        assert!(AstParser::new("1 + 2 3").parse_expr().is_err());
+        let err = AstParser::new("{ break; }").parse_block().unwrap_err();
+        assert_eq!("`break` outside of a loop", err.message);
+        let err = AstParser::new("{ if (true) continue; }").parse_block().unwrap_err();
+        assert_eq!("`continue` outside of a loop", err.message);
+        let err = AstParser::new("{ switch (1) { default: continue; } }").parse_block().unwrap_err();
+        assert_eq!("`continue` outside of a loop", err.message);
+        let err = AstParser::new("{ i32 x; switch (x) { case x: break; } }").parse_block().unwrap_err();
+        assert_eq!("syntax error: expected integer constant, found `x`", err.message);
+        let err = AstParser::new("{ i32 x; switch (x) { x = 1; } }").parse_block().unwrap_err();
+        assert_eq!("syntax error: expected `case` or `default`, found `x`", err.message);
+        let err = AstParser::new("{ i32 x; x + 1 = 2; }").parse_block().unwrap_err();
+        assert_eq!("invalid left-hand side of assignment", err.message);
+        assert_eq!(Span::new(1, 10, 9, 14), err.span);
+        let err = AstParser::new("{ do x = 1; while (x) }").parse_block().unwrap_err();
+        assert_eq!("syntax error: expected `;`, found `}`", err.message);
+    }
+
+    #[test]
+    fn astparser_stmt_test() {
+        let mut parser = AstParser::new("\
+{
+    i32 i; f32[] a;
+    while (true) {
+        do i = i + 1; while (a[i] < 1.5);
+        if (i >= 10) break;
+    }
+}");
+        let err = parser.parse_block().unwrap_err();
+        assert_eq!("syntax error: expected array size, found `]`", err.message);
+        assert_eq!(Span::new(2, 16, 17, 18), err.span);
+
+        let mut parser = AstParser::new("\
+{
+    i32 i; f32 a;
+    while (true) {
+        do i = i + 1; while (a[i] < 1.5);
+        if (i >= 10) break;
+    }
+    { i32 j; j = i; }
+    j = k;
+}");
+        let block = parser.parse_block().unwrap();
+        match &block.stmts[2].kind {
+            StmtKind::While(cond, body) => {
+                assert_eq!(ExprKind::Bool(true), cond.kind);
+                match &body.kind {
+                    StmtKind::Block(inner) => {
+                        assert!(matches!(inner.stmts[0].kind, StmtKind::DoWhile(_, _)));
+                        assert!(matches!(&inner.stmts[1].kind, StmtKind::If(_, then, None) if then.kind == StmtKind::Break));
+                        assert_eq!(Span::new(5, 9, 89, 108), inner.stmts[1].span);
+                    },
+                    _ => panic!("test failed at [astparser_stmt_test]")
+                }
+            },
+            _ => panic!("test failed at [astparser_stmt_test]")
+        }
+
+        // 未宣言の名前は構文解析では報告せず、名前解決に任せる
+        assert!(parser.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn astparser_array_decl_test() {
+        let block = AstParser::new("{ i32[10][20] a; bool b; a[1][2] = 3; }").parse_block().unwrap();
+        match &block.stmts[0].kind {
+            StmtKind::Decl(decl) => {
+                assert_eq!(Type::new_array(10, Type::new_array(20, Type::new_i32())), decl.ty);
+                assert_eq!(800, decl.ty.width());
+            },
+            _ => panic!("test failed at [astparser_array_decl_test]")
+        }
+        match &block.stmts[1].kind {
+            StmtKind::Decl(decl) => assert_eq!(Type::new_bool(), decl.ty),
+            _ => panic!("test failed at [astparser_array_decl_test]")
+        }
+    }
+
+    #[test]
+    fn astparser_dangling_else_test() {
+        let block = AstParser::new("{ if (a) if (b) x = 1; else x = 2; }").parse_block().unwrap();
+        match &block.stmts[0].kind {
+            StmtKind::If(_, inner, None) => match &inner.kind {
+                StmtKind::If(_, _, Some(els)) => assert!(matches!(els.kind, StmtKind::Assign(_, _))),
+                _ => panic!("test failed at [astparser_dangling_else_test]")
+            },
+            _ => panic!("test failed at [astparser_dangling_else_test]")
+        }
+    }
+
+    #[test]
+    fn astparser_scope_test() {
+        let src = "{ i32 x; { f32 y; y; } { char x; x; } x; }";
+        let mut parser = AstParser::new(src);
+        parser.parse_block().unwrap();
+        let table = parser.symbols();
+        // 最も外側のスコープと、3つのブロックのスコープが残っている
+        assert_eq!(4, table.scopes().len());
+        let names = |pos: usize| -> Vec<String> {
+            table.visible_at(pos).iter().map(|s| format!("{}: {}", s.lexeme, s.ty)).collect()
+        };
+        assert_eq!(vec!["y: f32", "x: i32"], names(src.find("y;").unwrap()));
+        assert_eq!(vec!["x: char"], names(src.find("x; }").unwrap()));
+        assert_eq!(vec!["x: i32"], names(src.rfind("x;").unwrap()));
+        assert_eq!(Span::new(1, 10, 9, 22), table.scope(table.scope_at(12)).span);
+    }
+
+    #[test]
+    fn astparser_redeclaration_test() {
+        let mut parser = AstParser::new("{ i32 x; f32 x; { f32 x; } }");
+        parser.parse_block().unwrap();
+        let diag = &parser.diagnostics()[0];
+        assert_eq!("redeclaration of `x` in the same scope", diag.message);
+        assert_eq!((Span::new(1, 10, 9, 15), Span::new(1, 3, 2, 8)), (diag.span, diag.notes[0].0));
+        assert_eq!(1, parser.diagnostics().len());
+    }
+
+    #[test]
+    fn astparser_int_range_test() {
+        let mut parser = AstParser::new("{ i32 x; x = 2147483647 + 2147483648; }");
+        parser.parse_block().unwrap();
+        let messages: Vec<String> = parser.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(vec!["error: integer literal `2147483648` out of range for i32 at 1:27"], messages);
+    }
+
+    #[test]
+    fn astparser_program_test() {
+        let src = "fn fact(i32 n) -> i32 { if (n <= 1) return 1; return n * fact(n - 1); }\nfn main() { i32 x; x = fact(5); }";
+        let mut parser = AstParser::new(src);
+        let program = parser.parse_program().unwrap();
+        assert!(parser.diagnostics().is_empty());
+        assert_eq!(2, program.funcs.len());
+        let fact = &program.funcs[0];
+        assert_eq!(("fact", Type::new_i32()), (&fact.name[..], fact.ret.clone()));
+        assert_eq!("fn(i32) -> i32", fact.ty().to_string());
+        assert_eq!(Span::new(1, 1, 0, 71), fact.span);
+        assert_eq!(Type::Void, program.funcs[1].ret);
+        match &program.funcs[1].body.as_ref().unwrap().stmts[1].kind {
+            StmtKind::Assign(_, value) => assert!(matches!(&value.kind, ExprKind::Call(f, args) if f == "fact" && args.len() == 1)),
+            _ => panic!("test failed at [astparser_program_test]")
+        }
+
+        // 仮引数と局所変数は関数ごとに相対アドレス0から割り付ける
+        let table = parser.symbols();
+        let n = table.lookup_from(table.scope_at(src.find("n <=").unwrap()), "n").unwrap().1;
+        assert_eq!((SymbolKind::Param, 0), (n.kind, n.offset));
+        let x = table.lookup_from(table.scope_at(src.find("x =").unwrap()), "x").unwrap().1;
+        assert_eq!(0, x.offset);
+
+        // 相互再帰は前方宣言で書く
+        let src = "fn even(i32 n) -> bool; fn odd(i32 n) -> bool { return !even(n); } fn even(i32 n) -> bool { return odd(n); }";
+        let mut parser = AstParser::new(src);
+        parser.parse_program().unwrap();
+        assert!(parser.diagnostics().is_empty());
+        let mut parser = AstParser::new("fn odd(i32 n) -> bool { return !even(n); } fn even(i32 n) -> bool { return odd(n); } fn f();");
+        parser.parse_program().unwrap();
+        let messages: Vec<&str> = parser.diagnostics().iter().map(|d| &d.message[..]).collect();
+        assert_eq!(vec!["function `f` is declared but never defined"], messages);
+        let mut parser = AstParser::new("fn f(i32 a, f32 a) {} fn f() {}");
+        parser.parse_program().unwrap();
+        let messages: Vec<&str> = parser.diagnostics().iter().map(|d| &d.message[..]).collect();
+        assert_eq!(vec!["redeclaration of `a` in the same scope", "redeclaration of `f` in the same scope"], messages);
+    }
+
+    #[test]
+    fn astparser_program_error_test() {
+        let err = AstParser::new("{ return 1; }").parse_block().unwrap_err();
+        assert_eq!("`return` outside of a function", err.message);
+        let err = AstParser::new("fn f(i32 a b) {}").parse_program().unwrap_err();
+        assert_eq!("syntax error: expected `,`, found `b`", err.message);
+        let err = AstParser::new("fn f(a) {}").parse_program().unwrap_err();
+        assert_eq!("syntax error: expected type, found `a`", err.message);
+        let err = AstParser::new("fn f() -> g {}").parse_program().unwrap_err();
+        assert_eq!("syntax error: expected type, found `g`", err.message);
+        let err = AstParser::new("fn f() { f(1,); }").parse_program().unwrap_err();
+        assert_eq!("syntax error: expected expression, found `)`", err.message);
+        let err = AstParser::new("i32 x;").parse_program().unwrap_err();
+        assert_eq!("syntax error: expected `fn`, found `i32`", err.message);
+    }
+}
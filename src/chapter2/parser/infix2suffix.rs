@@ -36,6 +36,7 @@ impl Infix2Suffix {
     }
 
     /// 現在読んでいる文字が数字ならその値を出力する
+    #[allow(clippy::manual_range_contains)]
     fn term(&mut self) {
         let lookahead = Self::get_lookahead(self);
         if '0' <= lookahead && lookahead <= '9' {
@@ -47,6 +48,7 @@ impl Infix2Suffix {
     }
 
     /// 現在読んでいる文字との比較を行ってその結果を返す
+    #[allow(clippy::manual_unwrap_or)]
     fn expect(&mut self, c: char) -> bool {
         let lookahead = match self.expr.chars().nth(self.lookidx as usize) {
             Some(c)  => c,
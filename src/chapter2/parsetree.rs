@@ -0,0 +1,233 @@
+use std::fmt;
+
+use super::token::{Span, Token};
+
+/// 具象構文木(構文解析木)
+/// 内部節は非終端記号、葉はトークンに対応する
+///
+/// # members
+/// - label: String => 節の名前(非終端記号名、葉ならトークンの文字列)
+/// - token: Option<Token> => 葉のトークン(内部節ならNone)
+/// - span: Span => 節が導出するトークン列の位置
+/// - children: Vec<ParseTree> => 子
+/// - attrs: Vec<(String, String)> => 注釈として付けた属性の名前と値(注釈付き構文解析木)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseTree {
+    pub label: String,
+    pub token: Option<Token>,
+    pub span: Span,
+    pub children: Vec<ParseTree>,
+    pub attrs: Vec<(String, String)>
+}
+
+impl ParseTree {
+    /// トークンの葉を生成して返す
+    ///
+    /// # params
+    /// - token: Token => トークン
+    /// - span: Span => トークンの位置
+    ///
+    /// # returns
+    /// - ParseTree
+    pub fn leaf(token: Token, span: Span) -> ParseTree {
+        ParseTree { label: token.to_string(), token: Some(token), span, children: vec![], attrs: vec![] }
+    }
+
+    /// 内部節を生成して返す
+    /// 位置は子の位置を連結したもの(子がなければ空の位置)
+    ///
+    /// # params
+    /// - label: &str => 非終端記号名
+    /// - children: Vec<ParseTree> => 子
+    ///
+    /// # returns
+    /// - ParseTree
+    pub fn node(label: &str, children: Vec<ParseTree>) -> ParseTree {
+        let mut spans = children.iter().filter(|c| c.token.is_some() || !c.children.is_empty()).map(|c| c.span);
+        let span = match (spans.next(), spans.next_back()) {
+            (Some(first), Some(last)) => first.to(last),
+            (Some(first), None) => first,
+            _ => Span::default()
+        };
+        ParseTree { label: label.to_string(), token: None, span, children, attrs: vec![] }
+    }
+
+    /// 葉かどうかを返す
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_leaf(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// 葉のトークンを左から順に返す(木の yield)
+    ///
+    /// # returns
+    /// - Vec<&Token>
+    pub fn leaves(&self) -> Vec<&Token> {
+        let mut leaves = vec![];
+        self.collect_leaves(&mut leaves);
+        leaves
+    }
+
+    fn collect_leaves<'a>(&'a self, leaves: &mut Vec<&'a Token>) {
+        match &self.token {
+            Some(token) => leaves.push(token),
+            None => self.children.iter().for_each(|c| c.collect_leaves(leaves))
+        }
+    }
+
+    /// 木の yield を空白区切りの文字列にして返す
+    ///
+    /// # returns
+    /// - String
+    pub fn text(&self) -> String {
+        self.leaves().iter().map(|t| t.to_string()).collect::<Vec<String>>().join(" ")
+    }
+
+    /// 属性を設定する(同名の属性があれば上書きする)
+    ///
+    /// # params
+    /// - name: &str => 属性名
+    /// - value: String => 属性値
+    pub fn set_attr(&mut self, name: &str, value: String) {
+        match self.attrs.iter_mut().find(|(n, _)| n == name) {
+            Some(attr) => attr.1 = value,
+            None => self.attrs.push((name.to_string(), value))
+        }
+    }
+
+    /// 属性の値を返す
+    ///
+    /// # params
+    /// - name: &str => 属性名
+    ///
+    /// # returns
+    /// - Option<&str>
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(n, _)| n == name).map(|(_, v)| &v[..])
+    }
+
+    /// 子から親へ向かって(後行順に)合成属性を計算して注釈を付ける
+    /// evalには子の注釈が付いた後の節が渡され、Noneを返した節には注釈を付けない
+    ///
+    /// # params
+    /// - name: &str => 属性名
+    /// - eval: F => 節から属性値を求める関数
+    pub fn annotate<F>(&mut self, name: &str, eval: &mut F)
+    where
+        F: FnMut(&ParseTree) -> Option<String>
+    {
+        for child in &mut self.children {
+            child.annotate(name, eval);
+        }
+        if let Some(value) = eval(self) {
+            self.set_attr(name, value);
+        }
+    }
+
+    /// Graphvizのdot形式で出力する
+    /// 注釈が付いていれば、節の名前の下に"名前 = 値"の形で表示する
+    ///
+    /// # returns
+    /// - String
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph parse_tree {\n    node [shape=plaintext];\n");
+        let mut next = 0;
+        self.write_dot(&mut out, &mut next);
+        out.push_str("}\n");
+        out
+    }
+
+    fn write_dot(&self, out: &mut String, next: &mut usize) -> usize {
+        let id = *next;
+        *next += 1;
+        let mut label = escape(&self.label);
+        for (name, value) in &self.attrs {
+            label.push_str(&format!("\\n{} = {}", escape(name), escape(value)));
+        }
+        let shape = if self.is_leaf() { ", shape=box" } else { "" };
+        out.push_str(&format!("    n{} [label=\"{}\"{}];\n", id, label, shape));
+        for child in &self.children {
+            let child_id = child.write_dot(out, next);
+            out.push_str(&format!("    n{} -> n{};\n", id, child_id));
+        }
+        id
+    }
+}
+
+/// dot形式の文字列リテラル中で特別な意味を持つ文字をエスケープする
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl fmt::Display for ParseTree {
+    /// 括弧付きの形 [E [E 1] + [T 2]] で表示する
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_leaf() {
+            return write!(f, "{}", self.label);
+        }
+        write!(f, "[{}", self.label)?;
+        for child in &self.children {
+            write!(f, " {}", child)?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseTree;
+    use super::super::token::{Span, Tag, Token};
+
+    #[test]
+    fn parsetree_simple_test() {
+        // E -> E + T ; T -> 2 ; E' -> ε
+        let one = ParseTree::leaf(Token::NumI32 { num: 1 }, Span::new(1, 1, 0, 1));
+        let plus = ParseTree::leaf(Token::new_word(Tag::Symbol, "+"), Span::new(1, 3, 2, 3));
+        let two = ParseTree::leaf(Token::NumI32 { num: 2 }, Span::new(1, 5, 4, 5));
+        let tree = ParseTree::node("E", vec![
+            ParseTree::node("E", vec![one]),
+            plus,
+            ParseTree::node("T", vec![two]),
+            ParseTree::node("E'", vec![])
+        ]);
+        assert_eq!("[E [E 1] + [T 2] [E']]", tree.to_string());
+        assert_eq!("1 + 2", tree.text());
+        assert_eq!(Span::new(1, 1, 0, 5), tree.span);
+        assert!(!tree.is_leaf() && tree.children[1].is_leaf());
+        assert_eq!(3, tree.leaves().len());
+    }
+
+    #[test]
+    fn parsetree_dot_test() {
+        // T -> T * F | F ; F -> digit (例5.1の注釈付き構文解析木)
+        let digit = |num: i32, col: usize| ParseTree::node("F", vec![ParseTree::leaf(Token::NumI32 { num }, Span::new(1, col, col-1, col))]);
+        let star = ParseTree::leaf(Token::new_word(Tag::Symbol, "*"), Span::new(1, 3, 2, 3));
+        let mut tree = ParseTree::node("T", vec![ParseTree::node("T", vec![digit(3, 1)]), star, digit(5, 5)]);
+        tree.annotate("val", &mut |node| match (&node.token, node.children.len()) {
+            (Some(Token::NumI32 { num }), _) => Some(num.to_string()),
+            (None, 1) => node.children[0].attr("val").map(|v| v.to_string()),
+            (None, 3) => {
+                let lhs: i32 = node.children[0].attr("val")?.parse().ok()?;
+                let rhs: i32 = node.children[2].attr("val")?.parse().ok()?;
+                Some((lhs*rhs).to_string())
+            },
+            _ => None
+        });
+        assert_eq!(Some("15"), tree.attr("val"));
+        assert_eq!(None, tree.children[1].attr("val"));
+
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph parse_tree {\n"));
+        assert!(dot.contains("    n0 [label=\"T\\nval = 15\"];\n"));
+        assert!(dot.contains("    n4 [label=\"*\", shape=box];\n"));
+        assert!(dot.contains("    n0 -> n4;\n"));
+        assert_eq!(6, dot.matches(" -> ").count());
+    }
+}
@@ -0,0 +1,458 @@
+use super::ast::{Block, Decl, Expr, ExprKind, Func, Program, Stmt, StmtKind};
+use super::mtype::Type;
+
+/// 一段の字下げ幅
+const INDENT: usize = 4;
+
+/// 単項演算子と配列参照の優先順位(どの二項演算子よりも強い)
+const UNARY_PRECEDENCE: usize = 7;
+
+/// 式を必要最小限の括弧をつけたソースに戻す
+/// 二項演算子はすべて左結合として扱う
+/// 拡大変換はソース上は暗黙なので、変換される式だけを書く
+///
+/// # params
+/// - expr: &Expr => 式
+///
+/// # returns
+/// - String
+pub fn pretty_expr(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Int(num) => num.to_string(),
+        ExprKind::Float(num) => float_to_string(*num),
+        ExprKind::Bool(b) => b.to_string(),
+        ExprKind::Var(name) => name.clone(),
+        ExprKind::Index(array, index) => format!("{}[{}]", operand(array, UNARY_PRECEDENCE), pretty_expr(index)),
+        ExprKind::Unary(op, e) => format!("{}{}", op, operand(e, UNARY_PRECEDENCE)),
+        ExprKind::Binary(op, lhs, rhs) => {
+            let prec = op.precedence();
+            format!("{} {} {}", operand(lhs, prec), op, operand(rhs, prec+1))
+        },
+        ExprKind::Widen(_, e) => pretty_expr(e),
+        ExprKind::Call(name, args) => {
+            let args: Vec<String> = args.iter().map(pretty_expr).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+    }
+}
+
+/// ブロックを字下げしたソースに戻す
+///
+/// # params
+/// - block: &Block => ブロック
+///
+/// # returns
+/// - String
+pub fn pretty_block(block: &Block) -> String {
+    let mut out = String::new();
+    write_block(&mut out, block, 0);
+    out
+}
+
+/// プログラムを字下げしたソースに戻す(関数の間は1行空ける)
+///
+/// # params
+/// - program: &Program => プログラム
+///
+/// # returns
+/// - String
+pub fn pretty_program(program: &Program) -> String {
+    let funcs: Vec<String> = program.funcs.iter().map(|func| {
+        let mut out = String::new();
+        write_func(&mut out, func);
+        out
+    }).collect();
+    funcs.join("\n\n")
+}
+
+/// 式をS式にする (+ 1 (* x 2))
+///
+/// # params
+/// - expr: &Expr => 式
+///
+/// # returns
+/// - String
+pub fn sexpr_expr(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::Var(_) => pretty_expr(expr),
+        ExprKind::Index(array, index) => format!("([] {} {})", sexpr_expr(array), sexpr_expr(index)),
+        ExprKind::Unary(op, e) => format!("({} {})", op, sexpr_expr(e)),
+        ExprKind::Binary(op, lhs, rhs) => format!("({} {} {})", op, sexpr_expr(lhs), sexpr_expr(rhs)),
+        ExprKind::Widen(ty, e) => format!("(widen {} {})", ty, sexpr_expr(e)),
+        ExprKind::Call(name, args) => {
+            let args: String = args.iter().map(|a| format!(" {}", sexpr_expr(a))).collect();
+            format!("(call {}{})", name, args)
+        }
+    }
+}
+
+/// プログラムをS式にする (program (fn f ((decl i32 a)) i32 (block ...)))
+///
+/// # params
+/// - program: &Program => プログラム
+///
+/// # returns
+/// - String
+pub fn sexpr_program(program: &Program) -> String {
+    let mut out = "(program".to_string();
+    for func in &program.funcs {
+        let params: Vec<String> = func.params.iter().map(|p| format!("(decl {} {})", p.ty, p.name)).collect();
+        out.push_str(&format!(" (fn {} ({}) {}", func.name, params.join(" "), func.ret));
+        if let Some(body) = &func.body {
+            out.push(' ');
+            out.push_str(&sexpr_block(body));
+        }
+        out.push(')');
+    }
+    out.push(')');
+    out
+}
+
+/// ブロックをS式にする (block (decl i32 x) (+ x 1))
+///
+/// # params
+/// - block: &Block => ブロック
+///
+/// # returns
+/// - String
+pub fn sexpr_block(block: &Block) -> String {
+    let mut out = "(block".to_string();
+    for stmt in &block.stmts {
+        out.push(' ');
+        out.push_str(&sexpr_stmt(stmt));
+    }
+    out.push(')');
+    out
+}
+
+/// 文をS式にする
+///
+/// # params
+/// - stmt: &Stmt => 文
+///
+/// # returns
+/// - String
+pub fn sexpr_stmt(stmt: &Stmt) -> String {
+    match &stmt.kind {
+        StmtKind::Decl(decl) => format!("(decl {} {})", decl.ty, decl.name),
+        StmtKind::Expr(expr) => sexpr_expr(expr),
+        StmtKind::Assign(loc, expr) => format!("(= {} {})", sexpr_expr(loc), sexpr_expr(expr)),
+        StmtKind::If(cond, then, None) => format!("(if {} {})", sexpr_expr(cond), sexpr_stmt(then)),
+        StmtKind::If(cond, then, Some(els)) => format!("(if {} {} {})", sexpr_expr(cond), sexpr_stmt(then), sexpr_stmt(els)),
+        StmtKind::While(cond, body) => format!("(while {} {})", sexpr_expr(cond), sexpr_stmt(body)),
+        StmtKind::DoWhile(body, cond) => format!("(do {} {})", sexpr_stmt(body), sexpr_expr(cond)),
+        StmtKind::Switch(expr, cases) => {
+            let cases: Vec<String> = cases.iter().map(|case| {
+                let label = match case.value {
+                    Some(value) => format!("case {}", value),
+                    None => "default".to_string()
+                };
+                let stmts: Vec<String> = case.stmts.iter().map(sexpr_stmt).collect();
+                format!("({}{})", label, stmts.iter().map(|s| format!(" {}", s)).collect::<String>())
+            }).collect();
+            format!("(switch {} {})", sexpr_expr(expr), cases.join(" "))
+        },
+        StmtKind::Return(None) => "(return)".to_string(),
+        StmtKind::Return(Some(expr)) => format!("(return {})", sexpr_expr(expr)),
+        StmtKind::Break => "(break)".to_string(),
+        StmtKind::Continue => "(continue)".to_string(),
+        StmtKind::Block(block) => sexpr_block(block)
+    }
+}
+
+/// 優先順位がmin_prec未満の式を括弧で囲む
+fn operand(expr: &Expr, min_prec: usize) -> String {
+    let prec = match &expr.kind {
+        ExprKind::Widen(_, e) => return operand(e, min_prec),
+        ExprKind::Binary(op, _, _) => op.precedence(),
+        ExprKind::Unary(_, _) | ExprKind::Index(_, _) => UNARY_PRECEDENCE,
+        // 負の定数は単項演算として読み直されるため、単項演算と同じに扱う
+        ExprKind::Int(num) if *num < 0 => UNARY_PRECEDENCE,
+        ExprKind::Float(num) if num.is_sign_negative() => UNARY_PRECEDENCE,
+        _ => UNARY_PRECEDENCE+1
+    };
+    if prec < min_prec {
+        format!("({})", pretty_expr(expr))
+    } else {
+        pretty_expr(expr)
+    }
+}
+
+/// 小数を、整数と区別できるよう必ず小数点をつけて文字列にする
+fn float_to_string(num: f32) -> String {
+    let s = num.to_string();
+    if s.contains('.') || !num.is_finite() {
+        s
+    } else {
+        format!("{}.0", s)
+    }
+}
+
+/// 関数を書き出す(戻り値の型がvoidなら省く)
+fn write_func(out: &mut String, func: &Func) {
+    let params: Vec<String> = func.params.iter().map(pretty_decl).collect();
+    out.push_str(&format!("fn {}({})", func.name, params.join(", ")));
+    if func.ret != Type::Void {
+        out.push_str(&format!(" -> {}", func.ret));
+    }
+    match &func.body {
+        Some(body) => {
+            out.push(' ');
+            write_block(out, body, 0);
+        },
+        None => out.push(';')
+    }
+}
+
+/// 宣言を `型 名前` の形にする([2][3]i32 は i32[2][3] と書く)
+fn pretty_decl(decl: &Decl) -> String {
+    let mut base = &decl.ty;
+    let mut dims = String::new();
+    while let Type::Array(n, elem) = base {
+        dims.push_str(&format!("[{}]", n));
+        base = elem;
+    }
+    format!("{}{} {}", base, dims, decl.name)
+}
+
+fn write_block(out: &mut String, block: &Block, depth: usize) {
+    out.push_str("{\n");
+    for stmt in &block.stmts {
+        indent(out, depth+1);
+        write_stmt(out, stmt, depth+1);
+        out.push('\n');
+    }
+    indent(out, depth);
+    out.push('}');
+}
+
+/// 文を書き出す(行頭の字下げは呼び出し側で済ませておく)
+fn write_stmt(out: &mut String, stmt: &Stmt, depth: usize) {
+    match &stmt.kind {
+        StmtKind::Decl(decl) => out.push_str(&format!("{};", pretty_decl(decl))),
+        StmtKind::Expr(expr) => out.push_str(&format!("{};", pretty_expr(expr))),
+        StmtKind::Assign(loc, expr) => out.push_str(&format!("{} = {};", pretty_expr(loc), pretty_expr(expr))),
+        StmtKind::If(cond, then, els) => {
+            out.push_str(&format!("if ({})", pretty_expr(cond)));
+            match els {
+                None => write_body(out, then, depth),
+                Some(els) => {
+                    // then節がelseのないifで終わると、elseがそちらに結びついてしまうためブロックで囲む
+                    if open_if(then) {
+                        out.push_str(" {\n");
+                        indent(out, depth+1);
+                        write_stmt(out, then, depth+1);
+                        out.push('\n');
+                        indent(out, depth);
+                        out.push('}');
+                    } else {
+                        write_body(out, then, depth);
+                    }
+                    if out.ends_with('}') {
+                        out.push(' ');
+                    } else {
+                        out.push('\n');
+                        indent(out, depth);
+                    }
+                    out.push_str("else");
+                    if let StmtKind::If(_, _, _) = els.kind {
+                        out.push(' ');
+                        write_stmt(out, els, depth);
+                    } else {
+                        write_body(out, els, depth);
+                    }
+                }
+            }
+        },
+        StmtKind::While(cond, body) => {
+            out.push_str(&format!("while ({})", pretty_expr(cond)));
+            write_body(out, body, depth);
+        },
+        StmtKind::DoWhile(body, cond) => {
+            out.push_str("do");
+            write_body(out, body, depth);
+            if out.ends_with('}') {
+                out.push(' ');
+            } else {
+                out.push('\n');
+                indent(out, depth);
+            }
+            out.push_str(&format!("while ({});", pretty_expr(cond)));
+        },
+        // caseのラベルはswitchと同じ深さに、文は一段下げて書く
+        StmtKind::Switch(expr, cases) => {
+            out.push_str(&format!("switch ({}) {{\n", pretty_expr(expr)));
+            for case in cases {
+                indent(out, depth);
+                match case.value {
+                    Some(value) => out.push_str(&format!("case {}:\n", value)),
+                    None => out.push_str("default:\n")
+                }
+                for stmt in &case.stmts {
+                    indent(out, depth+1);
+                    write_stmt(out, stmt, depth+1);
+                    out.push('\n');
+                }
+            }
+            indent(out, depth);
+            out.push('}');
+        },
+        StmtKind::Return(None) => out.push_str("return;"),
+        StmtKind::Return(Some(expr)) => out.push_str(&format!("return {};", pretty_expr(expr))),
+        StmtKind::Break => out.push_str("break;"),
+        StmtKind::Continue => out.push_str("continue;"),
+        StmtKind::Block(inner) => write_block(out, inner, depth)
+    }
+}
+
+/// if, while, doの本体を書き出す
+/// ブロックなら同じ行に、それ以外は次の行に一段下げて書く
+fn write_body(out: &mut String, body: &Stmt, depth: usize) {
+    match &body.kind {
+        StmtKind::Block(block) => {
+            out.push(' ');
+            write_block(out, block, depth);
+        },
+        _ => {
+            out.push('\n');
+            indent(out, depth+1);
+            write_stmt(out, body, depth+1);
+        }
+    }
+}
+
+/// 文がelseのないifで終わるか
+fn open_if(stmt: &Stmt) -> bool {
+    match &stmt.kind {
+        StmtKind::If(_, _, None) => true,
+        StmtKind::If(_, _, Some(els)) => open_if(els),
+        StmtKind::While(_, body) => open_if(body),
+        _ => false
+    }
+}
+
+fn indent(out: &mut String, depth: usize) {
+    out.push_str(&" ".repeat(depth*INDENT));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pretty_block, pretty_expr, pretty_program, sexpr_block, sexpr_expr, sexpr_program};
+    use super::super::parser::astparser::AstParser;
+
+    #[test]
+    fn pretty_expr_test() {
+        let cases = [
+            ("((1 + 2)) * (x)", "(1 + 2) * x"),
+            ("a - (b - c)", "a - (b - c)"),
+            ("(a - b) - c", "a - b - c"),
+            ("-(a[i + 1]) * !(b && c)", "-a[i + 1] * !(b && c)"),
+            ("a || b && c == d < e + f * g", "a || b && c == d < e + f * g"),
+            ("2.0 / 0.5", "2.0 / 0.5")
+        ];
+        for (src, expected) in cases.iter() {
+            let expr = AstParser::new(src).parse_expr().unwrap();
+            let pretty = pretty_expr(&expr);
+            assert_eq!(*expected, pretty);
+            // 整形結果を構文解析し直しても同じ木になる
+            let reparsed = AstParser::new(&pretty).parse_expr().unwrap();
+            assert_eq!(sexpr_expr(&expr), sexpr_expr(&reparsed));
+        }
+    }
+
+    #[test]
+    fn pretty_block_test() {
+        let block = AstParser::new("{ i32[2][3] x; f32 y; { char c; x+1; } y*(x-1); }").parse_block().unwrap();
+        let pretty = pretty_block(&block);
+        assert_eq!("{\n    i32[2][3] x;\n    f32 y;\n    {\n        char c;\n        x + 1;\n    }\n    y * (x - 1);\n}", pretty);
+        let reparsed = AstParser::new(&pretty).parse_block().unwrap();
+        assert_eq!(pretty, pretty_block(&reparsed));
+    }
+
+    #[test]
+    fn sexpr_test() {
+        let block = AstParser::new("{ i32 x; -a[1] + x * 2; }").parse_block().unwrap();
+        assert_eq!("(block (decl i32 x) (+ (- ([] a 1)) (* x 2)))", sexpr_block(&block));
+    }
+
+    #[test]
+    fn pretty_stmt_test() {
+        let src = "{ i32 i; i32 a; while (i < 10) { if (a[i] > 0) break; else a[i] = 0; i = i + 1; } do i = i - 1; while (i > 0); }";
+        let block = AstParser::new(src).parse_block().unwrap();
+        let pretty = pretty_block(&block);
+        assert_eq!("\
+{
+    i32 i;
+    i32 a;
+    while (i < 10) {
+        if (a[i] > 0)
+            break;
+        else
+            a[i] = 0;
+        i = i + 1;
+    }
+    do
+        i = i - 1;
+    while (i > 0);
+}", pretty);
+        let reparsed = AstParser::new(&pretty).parse_block().unwrap();
+        assert_eq!(sexpr_block(&block), sexpr_block(&reparsed));
+
+        // ぶら下がりelseを外側のifに付けるには、内側をブロックで囲む必要がある
+        let block = AstParser::new("{ if (a) { if (b) x = 1; } else x = 2; }").parse_block().unwrap();
+        let pretty = pretty_block(&block);
+        let reparsed = AstParser::new(&pretty).parse_block().unwrap();
+        assert_eq!(sexpr_block(&block), sexpr_block(&reparsed));
+        assert_eq!("(block (if a (block (if b (= x 1))) (= x 2)))", sexpr_block(&reparsed));
+
+        let block = AstParser::new("{ i32 x; switch (x) { case -1: case 1: x = 0; break; default: } }").parse_block().unwrap();
+        let pretty = pretty_block(&block);
+        assert_eq!("\
+{
+    i32 x;
+    switch (x) {
+    case -1:
+    case 1:
+        x = 0;
+        break;
+    default:
+    }
+}", pretty);
+        let reparsed = AstParser::new(&pretty).parse_block().unwrap();
+        assert_eq!(sexpr_block(&block), sexpr_block(&reparsed));
+        assert_eq!("(block (decl i32 x) (switch x (case -1) (case 1 (= x 0) (break)) (default)))", sexpr_block(&reparsed));
+    }
+
+    #[test]
+    fn pretty_program_test() {
+        let src = "fn even(i32 n) -> bool; fn odd(i32 n) -> bool { if (n == 0) return false; return even(n - 1); } \
+fn even(i32 n) -> bool { if (n == 0) return true; return odd(n - 1); } fn main() { f32[2] a; odd(max(1, 2)); return; }";
+        let program = AstParser::new(src).parse_program().unwrap();
+        let pretty = pretty_program(&program);
+        assert_eq!("\
+fn even(i32 n) -> bool;
+
+fn odd(i32 n) -> bool {
+    if (n == 0)
+        return false;
+    return even(n - 1);
+}
+
+fn even(i32 n) -> bool {
+    if (n == 0)
+        return true;
+    return odd(n - 1);
+}
+
+fn main() {
+    f32[2] a;
+    odd(max(1, 2));
+    return;
+}", pretty);
+        assert_eq!("(program (fn even ((decl i32 n)) bool) (fn odd ((decl i32 n)) bool (block (if (== n 0) (return false)) (return (call even (- n 1))))) \
+(fn even ((decl i32 n)) bool (block (if (== n 0) (return true)) (return (call odd (- n 1))))) \
+(fn main () void (block (decl [2]f32 a) (call odd (call max 1 2)) (return))))", sexpr_program(&program));
+        let reparsed = AstParser::new(&pretty).parse_program().unwrap();
+        assert_eq!(sexpr_program(&program), sexpr_program(&reparsed));
+    }
+}
@@ -1,20 +1,150 @@
 use std::collections::HashMap;
+use std::fmt;
 
-use super::mtype::Type;
+use super::diagnostic::Diagnostic;
+use super::mtype::{align_up, Type};
+use super::token::Span;
+
+/// 記号の種類
+///
+/// # members
+/// - Var => 変数
+/// - Const => 定数
+/// - Param => 仮引数
+/// - Func => 関数
+/// - Type => 型名
+/// - Label => ラベル
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SymbolKind {
+    Var,
+    Const,
+    Param,
+    Func,
+    Type,
+    Label
+}
+
+impl SymbolKind {
+    /// 種類が属する名前空間を返す
+    ///
+    /// # returns
+    /// - Namespace
+    pub fn namespace(&self) -> Namespace {
+        match self {
+            SymbolKind::Type => Namespace::Type,
+            SymbolKind::Label => Namespace::Label,
+            _ => Namespace::Value
+        }
+    }
+
+    /// 活性レコードに領域を割り付ける種類か
+    ///
+    /// # returns
+    /// - bool
+    pub fn has_storage(&self) -> bool {
+        matches!(self, SymbolKind::Var | SymbolKind::Const | SymbolKind::Param)
+    }
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolKind::Var => write!(f, "variable"),
+            SymbolKind::Const => write!(f, "constant"),
+            SymbolKind::Param => write!(f, "parameter"),
+            SymbolKind::Func => write!(f, "function"),
+            SymbolKind::Type => write!(f, "type"),
+            SymbolKind::Label => write!(f, "label")
+        }
+    }
+}
+
+/// 名前空間
+/// 同じスコープでも、型名と値(変数、定数、仮引数、関数)とラベルは別々に宣言できる
+///
+/// # members
+/// - Type => 型名
+/// - Value => 値
+/// - Label => ラベル
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq, Eq, Hash
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Namespace {
+    Type,
+    Value,
+    Label
+}
+
+/// 外側の宣言を隠す宣言の扱い
+///
+/// # members
+/// - Allow => 何もしない
+/// - Warn => 警告する
+/// - Deny => 誤りとして宣言を拒む
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShadowPolicy {
+    Allow,
+    Warn,
+    Deny
+}
+
+/// 記号の使われ方
+///
+/// # members
+/// - Read => 値の読み出し
+/// - Write => 代入
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Access {
+    Read,
+    Write
+}
 
 /// 記号表で管理する1単位を表す
 ///
 /// # members
 /// - lexeme: String => 語
 /// - ty: Type => 型
+/// - offset: usize => 活性レコード内の相対アドレス
+/// - kind: SymbolKind => 種類
+/// - mutable: bool => 代入できるか
+/// - span: Span => 宣言の位置
+/// - depth: usize => 宣言されたスコープの深さ(最も外側が0)
+/// - uses: Vec<(Span, Access)> => 使われた位置
+/// - defined: bool => 定義済みか(前方宣言だけならfalse)
 ///
 /// # derive
 /// - PartialEq
 /// - Clone
-#[derive(PartialEq, Clone)]
+/// - Debug
+#[derive(PartialEq, Clone, Debug)]
 pub struct Symbol {
     pub lexeme: String,
-    pub ty: Type
+    pub ty: Type,
+    pub offset: usize,
+    pub kind: SymbolKind,
+    pub mutable: bool,
+    pub span: Span,
+    pub depth: usize,
+    pub uses: Vec<(Span, Access)>,
+    pub defined: bool
 }
 
 impl Symbol {
@@ -27,93 +157,600 @@ impl Symbol {
     /// # return
     /// - Symbol
     pub fn new(lexeme: String, ty: Type) -> Symbol {
-        Symbol { lexeme, ty }
+        Symbol { lexeme, ty, offset: 0, kind: SymbolKind::Var, mutable: true, span: Span::default(), depth: 0, uses: vec![], defined: true }
+    }
+
+    /// 種類を設定した記号を返す(定数と関数、型名、ラベルは代入できない)
+    ///
+    /// # params
+    /// - kind: SymbolKind => 種類
+    ///
+    /// # returns
+    /// - Symbol
+    pub fn with_kind(mut self, kind: SymbolKind) -> Symbol {
+        self.kind = kind;
+        self.mutable = matches!(kind, SymbolKind::Var | SymbolKind::Param);
+        self
+    }
+
+    /// 宣言の位置を設定した記号を返す
+    ///
+    /// # params
+    /// - span: Span => 宣言の位置
+    ///
+    /// # returns
+    /// - Symbol
+    pub fn with_span(mut self, span: Span) -> Symbol {
+        self.span = span;
+        self
+    }
+
+    /// 前方宣言(後で同じ型の定義が続く宣言)にした記号を返す
+    ///
+    /// # returns
+    /// - Symbol
+    pub fn forward(mut self) -> Symbol {
+        self.defined = false;
+        self
+    }
+
+    /// 値が読み出されたことがあるか
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_read(&self) -> bool {
+        self.uses.iter().any(|(_, access)| *access == Access::Read)
+    }
+
+    /// 代入されたことがあるか
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_written(&self) -> bool {
+        self.uses.iter().any(|(_, access)| *access == Access::Write)
+    }
+
+    /// 記号が占める領域の大きさを返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn width(&self) -> usize {
+        self.ty.width()
+    }
+}
+
+/// スコープの番号(SymbolTable内での添字)
+pub type ScopeId = usize;
+
+/// 1つのスコープ(ブロック)で宣言された記号の表
+/// 入れ子のスコープは親の変数の後ろから領域を割り付け、兄弟のスコープどうしは領域を共有する
+///
+/// # members
+/// - id: ScopeId => 番号
+/// - parent: Option<ScopeId> => 親のスコープ(最も外側ならNone)
+/// - children: Vec<ScopeId> => 子のスコープ(現れた順)
+/// - depth: usize => スコープの深さ(最も外側が0)
+/// - span: Span => スコープが覆う範囲
+/// - table: (名前空間, 名前), Symbolの照合表
+/// - base: usize => このスコープの領域の先頭の相対アドレス
+/// - offset: usize => 次に割り付ける相対アドレス(6.3節のoffset)
+/// - frame: usize => 内側のスコープも含めて使った領域の末尾
+/// - procedure: bool => 関数のスコープか(自分の活性レコードを持ち、親の領域を使わない)
+///
+/// # derive
+/// - Clone
+#[derive(Clone)]
+pub struct Scope {
+    pub id: ScopeId,
+    pub parent: Option<ScopeId>,
+    pub children: Vec<ScopeId>,
+    pub depth: usize,
+    pub span: Span,
+    table: HashMap<(Namespace, String), Symbol>,
+    base: usize,
+    offset: usize,
+    frame: usize,
+    procedure: bool
+}
+
+impl Scope {
+    fn new(id: ScopeId, parent: Option<ScopeId>, depth: usize, span: Span, offset: usize) -> Scope {
+        Scope { id, parent, children: vec![], depth, span, table: HashMap::new(), base: offset, offset, frame: offset, procedure: false }
+    }
+
+    /// このスコープで宣言された値の記号を返す
+    ///
+    /// # params
+    /// - target: &str => 要素名
+    ///
+    /// # returns
+    /// - Option<&Symbol>
+    pub fn get(&self, target: &str) -> Option<&Symbol> {
+        self.get_in(Namespace::Value, target)
+    }
+
+    /// このスコープで名前空間nsに宣言された記号を返す
+    ///
+    /// # params
+    /// - ns: Namespace => 名前空間
+    /// - target: &str => 要素名
+    ///
+    /// # returns
+    /// - Option<&Symbol>
+    pub fn get_in(&self, ns: Namespace, target: &str) -> Option<&Symbol> {
+        self.table.get(&(ns, target.to_string()))
+    }
+
+    /// 前方宣言されたまま定義されなかった記号を誤りとして返す
+    ///
+    /// # returns
+    /// - Vec<Diagnostic>
+    pub fn undefined_diagnostics(&self) -> Vec<Diagnostic> {
+        self.symbols().into_iter()
+            .filter(|s| !s.defined)
+            .map(|s| Diagnostic::error(s.span, format!("{} `{}` is declared but never defined", s.kind, s.lexeme)))
+            .collect()
+    }
+
+    /// このスコープで宣言された記号を宣言の位置の順に返す
+    ///
+    /// # returns
+    /// - Vec<&Symbol>
+    pub fn symbols(&self) -> Vec<&Symbol> {
+        let mut symbols: Vec<&Symbol> = self.table.values().collect();
+        symbols.sort_by(|a, b| (a.span.start, &a.lexeme).cmp(&(b.span.start, &b.lexeme)));
+        symbols
+    }
+
+    /// 次に割り付ける相対アドレスを返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// このスコープの領域の大きさ(内側のスコープを含む)を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn size(&self) -> usize {
+        self.frame_end()-self.base
+    }
+
+    /// このスコープで宣言された変数の使われ方を検査する
+    /// - 一度も使われない => unused variable `x`
+    /// - 代入されるだけで読まれない => variable `x` is assigned but never read
+    ///
+    /// # returns
+    /// - Vec<Diagnostic> => 宣言の位置の順に並べた警告
+    pub fn usage_diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        for symbol in self.symbols() {
+            if !matches!(symbol.kind, SymbolKind::Var | SymbolKind::Param | SymbolKind::Const) {
+                continue;
+            }
+            if symbol.uses.is_empty() {
+                diagnostics.push(Diagnostic::warning(symbol.span, format!("unused {} `{}`", symbol.kind, symbol.lexeme)));
+            } else if !symbol.is_read() {
+                let diag = Diagnostic::warning(symbol.span, format!("variable `{}` is assigned but never read", symbol.lexeme));
+                let (span, _) = symbol.uses[symbol.uses.len()-1];
+                diagnostics.push(diag.with_note(span, "last assigned here".to_string()));
+            }
+        }
+        diagnostics
+    }
+
+    /// このスコープで宣言された記号を相対アドレス順に並べた表を返す
+    ///
+    /// # returns
+    /// - String => "offset width name: type" を1行ずつ並べたもの
+    pub fn layout(&self) -> String {
+        let mut symbols: Vec<&Symbol> = self.table.values().collect();
+        symbols.sort_by(|a, b| (a.offset, &a.lexeme).cmp(&(b.offset, &b.lexeme)));
+        let mut out = String::new();
+        for symbol in symbols {
+            out.push_str(&format!("{:>4} {:>4} {}: {}\n", symbol.offset, symbol.width(), symbol.lexeme, symbol.ty));
+            if let Type::Record(_, fields) = &symbol.ty {
+                for field in fields {
+                    let name = format!("{}.{}", symbol.lexeme, field.name);
+                    out.push_str(&format!("{:>4} {:>4}   {}: {}\n", symbol.offset+field.offset, field.ty.width(), name, field.ty));
+                }
+            }
+        }
+        out
+    }
+
+    fn frame_end(&self) -> usize {
+        self.frame.max(self.offset)
     }
 }
 
 /// 記号表
+/// スコープの木を配列(アリーナ)に持ち、現在のスコープの番号だけを切り替える
+/// スコープに入る/出るのはO(1)で、抜けたスコープも消さずに残すので、構文解析の後でも位置からスコープを引ける
 ///
 /// # members
-/// - prev: Box<SymbolTable> => 上位に位置する記号表をもつ
-/// - table: String, Symbolの照合表
+/// - scopes: Vec<Scope> => すべてのスコープ(0番が最も外側)
+/// - current: ScopeId => 現在のスコープ
+/// - policy: ShadowPolicy => 外側の宣言を隠す宣言の扱い
+/// - warnings: Vec<Diagnostic> => 宣言で出た警告
 #[derive(Clone)]
 pub struct SymbolTable {
-    prev: Box<Option<SymbolTable>>,
-    table: HashMap<String, Symbol>
+    scopes: Vec<Scope>,
+    current: ScopeId,
+    policy: ShadowPolicy,
+    warnings: Vec<Diagnostic>
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SymbolTable {
-    /// rootに位置する記号表を生成して返す
+    /// 最も外側のスコープだけをもつ記号表を生成して返す
     ///
     /// # returns
-    /// - Box<Option<SymbolTable>>
+    /// - SymbolTable
     pub fn new() -> SymbolTable {
-        SymbolTable { prev: Box::new(None), table: HashMap::new( )}
+        SymbolTable { scopes: vec![Scope::new(0, None, 0, Span::default(), 0)], current: 0, policy: ShadowPolicy::Allow, warnings: vec![] }
     }
 
-    /// 親を持った記号表を生成する
+    /// 外側の宣言を隠す宣言の扱いを設定する(既定はAllow)
     ///
     /// # params
-    /// prev: Box<Option<SymbolTable>> => 親となる記号表
+    /// - policy: ShadowPolicy => 扱い
+    pub fn set_shadow_policy(&mut self, policy: ShadowPolicy) {
+        self.policy = policy;
+    }
+
+    /// 宣言で出た警告を取り出す
     ///
-    /// # return
-    /// - Box<Option<SymbolTable>>
-    pub fn new_with_table(prev: SymbolTable) -> SymbolTable {
-        SymbolTable { prev: Box::new(Some(prev)), table: HashMap::new() }
+    /// # returns
+    /// - Vec<Diagnostic>
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
     }
 
-    /// 保持している親に当たる記号表を返す
+    /// 現在のスコープの子となるスコープを作って入る
     ///
-    /// # return
-    /// Option<SymbolTable>
-    pub fn release(self) -> Option<SymbolTable> {
-        *self.prev
+    /// # params
+    /// - span: Span => スコープの開始位置(ブロック全体がわかっていればその範囲)
+    ///
+    /// # returns
+    /// - ScopeId => 入ったスコープ
+    pub fn enter(&mut self, span: Span) -> ScopeId {
+        let id = self.scopes.len();
+        let parent = &self.scopes[self.current];
+        let scope = Scope::new(id, Some(self.current), parent.depth+1, span, parent.offset);
+        self.scopes[self.current].children.push(id);
+        self.scopes.push(scope);
+        self.current = id;
+        id
+    }
+
+    /// 関数のスコープを作って入る
+    /// 仮引数と局所変数は、関数ごとの活性レコードの先頭(相対アドレス0)から割り付ける
+    ///
+    /// # params
+    /// - span: Span => スコープの開始位置
+    ///
+    /// # returns
+    /// - ScopeId => 入ったスコープ
+    pub fn enter_function(&mut self, span: Span) -> ScopeId {
+        let id = self.enter(span);
+        let scope = &mut self.scopes[id];
+        scope.procedure = true;
+        scope.base = 0;
+        scope.offset = 0;
+        scope.frame = 0;
+        id
+    }
+
+    /// 現在のスコープを出て親のスコープに戻る
+    /// 親の使用領域には、このスコープで使った領域が加わる(関数のスコープは加えない)
+    ///
+    /// # params
+    /// - end: Span => スコープの終了位置
+    pub fn exit(&mut self, end: Span) {
+        let scope = &mut self.scopes[self.current];
+        scope.span = scope.span.to(end);
+        let frame = if scope.procedure { 0 } else { scope.frame_end() };
+        if let Some(parent) = scope.parent {
+            self.scopes[parent].frame = self.scopes[parent].frame.max(frame);
+            self.current = parent;
+        }
+    }
+
+    /// 現在のスコープの番号を返す
+    ///
+    /// # returns
+    /// - ScopeId
+    pub fn current(&self) -> ScopeId {
+        self.current
+    }
+
+    /// 番号のスコープを返す
+    ///
+    /// # params
+    /// - id: ScopeId => スコープの番号
+    ///
+    /// # returns
+    /// - &Scope
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id]
+    }
+
+    /// すべてのスコープを作られた順に返す
+    ///
+    /// # returns
+    /// - &[Scope]
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    /// 文字位置を含む最も内側のスコープを返す
+    ///
+    /// # params
+    /// - pos: usize => 文字位置
+    ///
+    /// # returns
+    /// - ScopeId
+    pub fn scope_at(&self, pos: usize) -> ScopeId {
+        let mut id = 0;
+        while let Some(child) = self.scopes[id].children.iter().find(|c| {
+            let span = self.scopes[**c].span;
+            span.start <= pos && pos < span.end
+        }) {
+            id = *child;
+        }
+        id
     }
 
     /// 記号表に要素を追加する
+    /// 相対アドレスは割り付けないので、symbol.offsetがそのまま使われる
+    /// - 同じスコープの同じ名前空間に宣言済み => 誤り(前方宣言と同じ型の宣言なら置き換える)
+    /// - 外側の宣言を隠す => ShadowPolicyに従って許す、警告する、誤りにする
     ///
     /// # params
     /// - symbol: Symbol => 追加する記号要素
-    pub fn add(&mut self, symbol: Symbol) {
-        self.table.insert(symbol.lexeme.clone(), symbol);
+    ///
+    /// # returns
+    /// - Result<(), Diagnostic> => 誤りなら記号表は変わらない
+    pub fn add(&mut self, mut symbol: Symbol) -> Result<(), Diagnostic> {
+        let ns = symbol.kind.namespace();
+        let key = (ns, symbol.lexeme.clone());
+        if let Some(prev) = self.scopes[self.current].table.get(&key) {
+            let note = (prev.span, format!("previous declaration of `{}` here", prev.lexeme));
+            if prev.defined && symbol.defined || prev.kind != symbol.kind {
+                let message = format!("redeclaration of `{}` in the same scope", symbol.lexeme);
+                return Err(Diagnostic::error(symbol.span, message).with_note(note.0, note.1));
+            }
+            if !prev.ty.structural_eq(&symbol.ty) {
+                let message = format!("conflicting types for `{}`: `{}` and `{}`", symbol.lexeme, prev.ty, symbol.ty);
+                return Err(Diagnostic::error(symbol.span, message).with_note(note.0, note.1));
+            }
+            // 前方宣言への使用は、後に続く宣言や定義に引き継ぐ
+            symbol.uses = prev.uses.iter().cloned().chain(symbol.uses).collect();
+            symbol.defined = prev.defined || symbol.defined;
+            symbol.offset = prev.offset;
+        } else if let Some(outer) = self.scopes[self.current].parent.and_then(|p| self.find(p, ns, &symbol.lexeme)).map(|(_, s)| s) {
+            let message = format!("`{}` shadows outer declaration at line {}", symbol.lexeme, outer.span.line);
+            let note = (outer.span, "outer declaration here".to_string());
+            match self.policy {
+                ShadowPolicy::Allow => {},
+                ShadowPolicy::Warn => self.warnings.push(Diagnostic::warning(symbol.span, message).with_note(note.0, note.1)),
+                ShadowPolicy::Deny => return Err(Diagnostic::error(symbol.span, message).with_note(note.0, note.1))
+            }
+        }
+        self.scopes[self.current].table.insert(key, symbol);
+        Ok(())
     }
 
-    /// 記号表から要素を検索する
+    /// 宣言された名前に型の境界に合わせた相対アドレスを割り付けて記号表に追加する
+    /// (6.3.5節 T id ; { top.put(id.lexeme, T.type, offset); offset = offset + T.width; })
     ///
     /// # params
-    /// - target: String => 要素名
+    /// - lexeme: String => 語
+    /// - ty: Type => 型
     ///
     /// # returns
-    /// Option<Symbol>
-    pub fn search(&self, target: String) -> Option<Symbol>{
-        Self::search_table(&target, self)
+    /// - Result<Symbol, Diagnostic> => 割り付けた記号(addと同じ誤りがある)
+    pub fn declare(&mut self, lexeme: String, ty: Type) -> Result<Symbol, Diagnostic> {
+        self.insert(Symbol::new(lexeme, ty))
     }
 
-    /// searchから呼ばれて、再帰的に要素を検索する
+    /// 記号を現在のスコープの深さで記号表に追加する
+    /// 変数、定数、仮引数には declare と同じく相対アドレスを割り付ける
+    /// (前方宣言の後に続く宣言は、前方宣言と同じ記号として扱う)
     ///
     /// # params
-    /// target: &String => 要素名
-    /// symboltable: &SymbolTable => 検索対象の記号表
+    /// - symbol: Symbol => 追加する記号
     ///
     /// # returns
-    /// Option<Symbol>
-    fn search_table(target: &String, symboltable: &SymbolTable) -> Option<Symbol> {
-        match symboltable.table.get(target) {
-            Some(symbol) => Some(symbol.clone()),
-            None => {
-                match *(symboltable.prev) {
-                    Some(ref table) => Self::search_table(target, table),
-                    None => None
+    /// - Result<Symbol, Diagnostic> => 追加した記号(addと同じ誤りがある)
+    pub fn insert(&mut self, mut symbol: Symbol) -> Result<Symbol, Diagnostic> {
+        let scope = &self.scopes[self.current];
+        let key = (symbol.kind.namespace(), symbol.lexeme.clone());
+        let allocate = symbol.kind.has_storage() && !scope.table.contains_key(&key);
+        symbol.depth = scope.depth;
+        if allocate {
+            symbol.offset = align_up(scope.offset, symbol.ty.align());
+        }
+        self.add(symbol.clone())?;
+        if allocate {
+            let scope = &mut self.scopes[self.current];
+            scope.offset = symbol.offset+symbol.width();
+            scope.frame = scope.frame.max(scope.offset);
+        }
+        Ok(self.scopes[self.current].table[&key].clone())
+    }
+
+    /// 現在のスコープから外側に向かって名前を探し、見つかったスコープと記号を返す
+    ///
+    /// # params
+    /// - target: &str => 要素名
+    ///
+    /// # returns
+    /// - Option<(ScopeId, &Symbol)>
+    pub fn lookup(&self, target: &str) -> Option<(ScopeId, &Symbol)> {
+        self.lookup_from(self.current, target)
+    }
+
+    /// 指定したスコープから外側に向かって名前を探す
+    ///
+    /// # params
+    /// - scope: ScopeId => 探し始めるスコープ
+    /// - target: &str => 要素名
+    ///
+    /// # returns
+    /// - Option<(ScopeId, &Symbol)>
+    pub fn lookup_from(&self, scope: ScopeId, target: &str) -> Option<(ScopeId, &Symbol)> {
+        self.find(scope, Namespace::Value, target)
+    }
+
+    /// 現在のスコープから外側に向かって、名前空間nsの名前を探す
+    ///
+    /// # params
+    /// - ns: Namespace => 名前空間
+    /// - target: &str => 要素名
+    ///
+    /// # returns
+    /// - Option<(ScopeId, &Symbol)>
+    pub fn lookup_in(&self, ns: Namespace, target: &str) -> Option<(ScopeId, &Symbol)> {
+        self.find(self.current, ns, target)
+    }
+
+    fn find(&self, scope: ScopeId, ns: Namespace, target: &str) -> Option<(ScopeId, &Symbol)> {
+        let key = (ns, target.to_string());
+        let mut id = Some(scope);
+        while let Some(scope) = id {
+            if let Some(symbol) = self.scopes[scope].table.get(&key) {
+                return Some((scope, symbol));
+            }
+            id = self.scopes[scope].parent;
+        }
+        None
+    }
+
+    /// 文字位置から見える記号を返す(内側の宣言に隠されたものは除く)
+    ///
+    /// # params
+    /// - pos: usize => 文字位置
+    ///
+    /// # returns
+    /// - Vec<&Symbol> => 内側のスコープから順に並べた記号
+    pub fn visible_at(&self, pos: usize) -> Vec<&Symbol> {
+        let mut visible: Vec<&Symbol> = vec![];
+        let mut id = Some(self.scope_at(pos));
+        while let Some(scope) = id {
+            for symbol in self.scopes[scope].symbols() {
+                if !visible.iter().any(|s| s.lexeme == symbol.lexeme && s.kind.namespace() == symbol.kind.namespace()) {
+                    visible.push(symbol);
                 }
             }
+            id = self.scopes[scope].parent;
         }
+        visible
+    }
+
+    /// 外側のスコープで宣言されていて、現在のスコープに同じ名前を宣言すると隠れてしまう記号を返す
+    ///
+    /// # params
+    /// - target: String => 要素名
+    ///
+    /// # returns
+    /// - Option<Symbol>
+    pub fn shadowed(&self, target: String) -> Option<Symbol> {
+        let parent = self.scopes[self.current].parent?;
+        self.lookup_from(parent, &target).map(|(_, symbol)| symbol.clone())
+    }
+
+    /// 名前の使用を、その名前を宣言した記号に記録する
+    ///
+    /// # params
+    /// - target: &str => 要素名
+    /// - span: Span => 使われた位置
+    /// - access: Access => 使われ方
+    ///
+    /// # returns
+    /// - Option<Symbol> => 使われた記号(未宣言ならNone)
+    pub fn record_use(&mut self, target: &str, span: Span, access: Access) -> Option<Symbol> {
+        let (scope, _) = self.lookup(target)?;
+        let symbol = self.scopes[scope].table.get_mut(&(Namespace::Value, target.to_string()))?;
+        symbol.uses.push((span, access));
+        Some(symbol.clone())
+    }
+
+    /// 現在のスコープの深さを返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn depth(&self) -> usize {
+        self.scopes[self.current].depth
+    }
+
+    /// 現在のスコープで宣言された変数の使われ方を検査する(Scope::usage_diagnostics)
+    ///
+    /// # returns
+    /// - Vec<Diagnostic>
+    pub fn usage_diagnostics(&self) -> Vec<Diagnostic> {
+        self.scopes[self.current].usage_diagnostics()
+    }
+
+    /// 現在のスコープで次に割り付ける相対アドレスを返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn offset(&self) -> usize {
+        self.scopes[self.current].offset()
+    }
+
+    /// 現在のスコープの領域の大きさ(内側のスコープを含む)を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn size(&self) -> usize {
+        self.scopes[self.current].size()
+    }
+
+    /// 活性レコードの大きさを返す
+    /// 全体で必要な大きさを、宣言された型の最大の境界に切り上げたもの
+    ///
+    /// # returns
+    /// - usize
+    pub fn frame_size(&self) -> usize {
+        let align = self.scopes.iter().flat_map(|s| s.table.values()).map(|s| s.ty.align()).max().unwrap_or(1);
+        align_up(self.scopes[0].frame_end(), align)
+    }
+
+    /// 現在のスコープで宣言された記号を相対アドレス順に並べた表を返す(Scope::layout)
+    ///
+    /// # returns
+    /// - String
+    pub fn layout(&self) -> String {
+        self.scopes[self.current].layout()
+    }
+
+    /// 記号表から要素を検索する
+    ///
+    /// # params
+    /// - target: String => 要素名
+    ///
+    /// # returns
+    /// Option<Symbol>
+    pub fn search(&self, target: String) -> Option<Symbol> {
+        self.lookup(&target).map(|(_, symbol)| symbol.clone())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Symbol, SymbolTable};
+    use super::{Access, Namespace, ShadowPolicy, Symbol, SymbolKind, SymbolTable};
     use super::super::mtype::Type;
+    use super::super::token::Span;
 
     #[test]
     fn symbol_new_test() {
@@ -124,29 +761,210 @@ mod tests {
 
     #[test]
     fn symboltable_simple_test() {
-        let table_a = SymbolTable::new();
+        let mut table = SymbolTable::new();
 
-        let mut table_b = SymbolTable::new_with_table(table_a);
-        table_b.add(Symbol::new("a".to_string(), Type::new_i32()));
-        table_b.add(Symbol::new("b".to_string(), Type::new_f32()));
+        let b = table.enter(Span::default());
+        table.add(Symbol::new("a".to_string(), Type::new_i32())).unwrap();
+        table.add(Symbol::new("b".to_string(), Type::new_f32())).unwrap();
 
-        let mut table_c = SymbolTable::new_with_table(table_b);
-        table_c.add(Symbol::new("c".to_string(), Type::new_i32()));
-        table_c.add(Symbol::new("d".to_string(), Type::new_f32()));
+        let c = table.enter(Span::default());
+        table.add(Symbol::new("c".to_string(), Type::new_i32())).unwrap();
+        table.add(Symbol::new("d".to_string(), Type::new_f32())).unwrap();
 
-        validate_symbol(table_c.search("a".to_string()), "a", Type::new_i32());
-        validate_symbol(table_c.search("b".to_string()), "b", Type::new_f32());
-        validate_symbol(table_c.search("c".to_string()), "c", Type::new_i32());
-        validate_symbol(table_c.search("d".to_string()), "d", Type::new_f32());
+        validate_symbol(table.search("a".to_string()), "a", Type::new_i32());
+        validate_symbol(table.search("b".to_string()), "b", Type::new_f32());
+        validate_symbol(table.search("c".to_string()), "c", Type::new_i32());
+        validate_symbol(table.search("d".to_string()), "d", Type::new_f32());
+        assert_eq!(Some(b), table.lookup("a").map(|(scope, _)| scope));
+        assert_eq!(Some(c), table.lookup("d").map(|(scope, _)| scope));
 
-        let table_b = table_c.release().unwrap();
-        let _table_a = table_b.release().unwrap();
+        table.exit(Span::default());
+        assert_eq!(b, table.current());
+        assert!(table.search("c".to_string()).is_none());
+        table.exit(Span::default());
+        assert_eq!(0, table.current());
+        assert!(table.search("a".to_string()).is_none());
     }
 
     fn validate_symbol(symbol: Option<Symbol>, lexemec: &str, tyc: Type) {
         match symbol {
-            Some(Symbol { lexeme, ty }) if lexeme == lexemec && ty == tyc => {},
+            Some(Symbol { lexeme, ty, .. }) if lexeme == lexemec && ty == tyc => {},
             _ => panic!("test failed at [symboltable_simple_test] => {}", lexemec)
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn symboltable_layout_test() {
+        let mut outer = SymbolTable::new();
+        assert_eq!(0, outer.declare("c".to_string(), Type::new_char()).unwrap().offset);
+        assert_eq!(4, outer.declare("x".to_string(), Type::new_i32()).unwrap().offset);
+        assert_eq!(8, outer.declare("a".to_string(), Type::new_array(3, Type::new_f32())).unwrap().offset);
+
+        // 入れ子のブロックは親の後ろから、兄弟のブロックは同じ位置から割り付ける
+        outer.enter(Span::default());
+        assert_eq!(20, outer.declare("d".to_string(), Type::new_char()).unwrap().offset);
+        assert_eq!(24, outer.declare("p".to_string(), Type::new_pointer(Type::new_i32())).unwrap().offset);
+        assert_eq!(12, outer.size());
+        outer.exit(Span::default());
+        outer.enter(Span::default());
+        assert_eq!(20, outer.declare("y".to_string(), Type::new_i32()).unwrap().offset);
+        outer.exit(Span::default());
+
+        assert_eq!(20, outer.offset());
+        assert_eq!(32, outer.frame_size());
+        match outer.search("x".to_string()) {
+            Some(Symbol { offset: 4, .. }) => {},
+            _ => panic!("test failed at [symboltable_layout_test]")
+        }
+        assert_eq!("   0    1 c: char\n   4    4 x: i32\n   8   12 a: [3]f32\n", outer.layout());
+
+        let mut table = SymbolTable::new();
+        table.declare("b".to_string(), Type::new_bool()).unwrap();
+        table.declare("r".to_string(), Type::new_record(Some("pt"), vec![("tag", Type::new_char()), ("x", Type::new_f32())])).unwrap();
+        assert_eq!("   0    1 b: bool\n   4    8 r: record pt\n   4    1   r.tag: char\n   8    4   r.x: f32\n", table.layout());
+        assert_eq!(12, table.frame_size());
+    }
+
+    #[test]
+    fn symboltable_usage_test() {
+        let mut inner = SymbolTable::new();
+        let n = Symbol::new("n".to_string(), Type::new_i32()).with_kind(SymbolKind::Param).with_span(Span::new(1, 1, 0, 5));
+        inner.insert(n).unwrap();
+        inner.insert(Symbol::new("f".to_string(), Type::new_function(vec![], Type::Void)).with_kind(SymbolKind::Func)).unwrap();
+        inner.enter(Span::default());
+        let x = inner.insert(Symbol::new("x".to_string(), Type::new_i32()).with_span(Span::new(2, 1, 10, 16))).unwrap();
+        inner.insert(Symbol::new("y".to_string(), Type::new_i32()).with_span(Span::new(3, 1, 20, 26))).unwrap();
+        assert_eq!((1, 4, true), (x.depth, x.offset, x.mutable));
+        assert!(inner.shadowed("n".to_string()).is_some());
+        assert!(inner.shadowed("x".to_string()).is_none());
+
+        inner.record_use("x", Span::new(4, 1, 30, 31), Access::Write);
+        inner.record_use("n", Span::new(4, 5, 34, 35), Access::Read);
+        assert!(inner.record_use("z", Span::new(4, 9, 38, 39), Access::Read).is_none());
+        let messages: Vec<String> = inner.usage_diagnostics().into_iter().map(|d| d.message).collect();
+        assert_eq!(vec!["variable `x` is assigned but never read", "unused variable `y`"], messages);
+
+        inner.exit(Span::default());
+        let outer = inner;
+        match outer.search("f".to_string()) {
+            Some(Symbol { kind: SymbolKind::Func, mutable: false, offset: 0, .. }) => {},
+            _ => panic!("test failed at [symboltable_usage_test]")
+        }
+        assert!(outer.usage_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn symboltable_scope_tree_test() {
+        // { i32 x; { f32 y; } { char x; } }
+        //  0       9         19          31
+        let mut table = SymbolTable::new();
+        let outer = table.enter(Span::new(1, 1, 0, 1));
+        table.insert(Symbol::new("x".to_string(), Type::new_i32()).with_span(Span::new(1, 3, 2, 8))).unwrap();
+        let first = table.enter(Span::new(1, 10, 9, 10));
+        table.insert(Symbol::new("y".to_string(), Type::new_f32()).with_span(Span::new(1, 12, 11, 17))).unwrap();
+        table.exit(Span::new(1, 18, 17, 18));
+        let second = table.enter(Span::new(1, 20, 19, 20));
+        table.insert(Symbol::new("x".to_string(), Type::new_char()).with_span(Span::new(1, 22, 21, 28))).unwrap();
+        table.exit(Span::new(1, 30, 29, 30));
+        table.exit(Span::new(1, 32, 31, 32));
+
+        // 抜けたスコープも残っていて、位置から引ける
+        assert_eq!(4, table.scopes().len());
+        assert_eq!(vec![first, second], table.scope(outer).children);
+        assert_eq!(Span::new(1, 10, 9, 18), table.scope(first).span);
+        assert_eq!(first, table.scope_at(12));
+        assert_eq!(outer, table.scope_at(18));
+        assert_eq!(0, table.scope_at(40));
+        match table.lookup_from(table.scope_at(25), "x") {
+            Some((scope, Symbol { ty: Type::Char(_), .. })) if scope == second => {},
+            _ => panic!("test failed at [symboltable_scope_tree_test]")
+        }
+        let visible: Vec<String> = table.visible_at(25).iter().map(|s| format!("{}: {}", s.lexeme, s.ty)).collect();
+        assert_eq!(vec!["x: char"], visible);
+        let visible: Vec<String> = table.visible_at(12).iter().map(|s| format!("{}: {}", s.lexeme, s.ty)).collect();
+        assert_eq!(vec!["y: f32", "x: i32"], visible);
+    }
+
+    #[test]
+    fn symboltable_redeclaration_test() {
+        let mut table = SymbolTable::new();
+        let x = Symbol::new("x".to_string(), Type::new_i32()).with_span(Span::new(1, 3, 2, 8));
+        table.insert(x).unwrap();
+        let diag = table.insert(Symbol::new("x".to_string(), Type::new_f32()).with_span(Span::new(1, 10, 9, 15))).err().unwrap();
+        assert_eq!("redeclaration of `x` in the same scope", diag.message);
+        assert_eq!((Span::new(1, 10, 9, 15), Span::new(1, 3, 2, 8)), (diag.span, diag.notes[0].0));
+        match table.search("x".to_string()) {
+            Some(Symbol { ty: Type::I32(_), .. }) => {},
+            _ => panic!("test failed at [symboltable_redeclaration_test]")
+        }
+
+        // 型名、値、ラベルは別の名前空間
+        table.insert(Symbol::new("x".to_string(), Type::new_f32()).with_kind(SymbolKind::Type)).unwrap();
+        table.insert(Symbol::new("x".to_string(), Type::Void).with_kind(SymbolKind::Label)).unwrap();
+        assert_eq!(Some(SymbolKind::Type), table.lookup_in(Namespace::Type, "x").map(|(_, s)| s.kind));
+        assert_eq!(Some(SymbolKind::Var), table.lookup("x").map(|(_, s)| s.kind));
+        assert_eq!(4, table.offset());
+
+        // 外側の宣言を隠す宣言の扱い
+        table.enter(Span::default());
+        table.insert(Symbol::new("x".to_string(), Type::new_char())).unwrap();
+        table.set_shadow_policy(ShadowPolicy::Warn);
+        table.insert(Symbol::new("x".to_string(), Type::new_char()).with_kind(SymbolKind::Type)).unwrap();
+        let warnings = table.take_warnings();
+        assert_eq!("`x` shadows outer declaration at line 0", warnings[0].message);
+        table.set_shadow_policy(ShadowPolicy::Deny);
+        assert!(table.insert(Symbol::new("x".to_string(), Type::Void).with_kind(SymbolKind::Label)).is_err());
+        assert!(table.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn symboltable_forward_test() {
+        let mut table = SymbolTable::new();
+        let sig = Type::new_function(vec![Type::new_i32()], Type::new_i32());
+        let f = Symbol::new("f".to_string(), sig.clone()).with_kind(SymbolKind::Func);
+        table.insert(f.clone().forward().with_span(Span::new(1, 1, 0, 10))).unwrap();
+        table.insert(f.clone().forward()).unwrap();
+        table.record_use("f", Span::new(2, 1, 11, 12), Access::Read);
+        assert_eq!(1, table.scope(0).undefined_diagnostics().len());
+
+        let other = Symbol::new("f".to_string(), Type::new_function(vec![], Type::new_i32())).with_kind(SymbolKind::Func);
+        let diag = table.insert(other).err().unwrap();
+        assert_eq!("conflicting types for `f`: `fn(i32) -> i32` and `fn() -> i32`", diag.message);
+
+        // 定義は前方宣言への使用を引き継ぐ
+        let defined = table.insert(f.clone().with_span(Span::new(3, 1, 20, 30))).unwrap();
+        assert!(defined.defined);
+        assert_eq!(1, defined.uses.len());
+        assert!(table.scope(0).undefined_diagnostics().is_empty());
+        assert_eq!("redeclaration of `f` in the same scope", table.insert(f).err().unwrap().message);
+
+        let mut table = SymbolTable::new();
+        table.insert(Symbol::new("g".to_string(), sig).with_kind(SymbolKind::Func).forward()).unwrap();
+        let messages: Vec<String> = table.scope(0).undefined_diagnostics().into_iter().map(|d| d.message).collect();
+        assert_eq!(vec!["function `g` is declared but never defined"], messages);
+    }
+
+    #[test]
+    fn symboltable_function_frame_test() {
+        let mut table = SymbolTable::new();
+        table.declare("g".to_string(), Type::new_f32()).unwrap();
+        // 関数の仮引数と局所変数は相対アドレス0から割り付ける
+        let f = table.enter_function(Span::default());
+        let a = table.insert(Symbol::new("a".to_string(), Type::new_char()).with_kind(SymbolKind::Param)).unwrap();
+        assert_eq!(0, a.offset);
+        table.enter(Span::default());
+        let x = table.declare("x".to_string(), Type::new_i32()).unwrap();
+        assert_eq!(4, x.offset);
+        table.exit(Span::default());
+        assert_eq!(8, table.size());
+        table.exit(Span::default());
+        assert_eq!(8, table.scope(f).size());
+
+        // 関数の領域は外側のスコープに加わらない
+        assert_eq!(4, table.size());
+        let h = table.enter_function(Span::default());
+        assert_eq!(0, table.declare("y".to_string(), Type::new_f32()).unwrap().offset);
+        table.exit(Span::default());
+        assert_eq!((4, 4), (table.scope(h).size(), table.size()));
+    }
+}
@@ -7,16 +7,24 @@ use std::str::FromStr;
 /// # derive
 /// - Clone
 /// - PartialEQ
-#[derive(Clone, PartialEq)]
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
 pub enum Tag {
     Id,             // 語
     Type,           // 型
     Symbol,         // 記号
     Primary,        // 値
     Comparison,     // 比較演算子
+    Keyword,        // 予約語(if, whileなど)
     None,           // その他、特にタグづけする必要がないものに使う
 }
 
+impl Tag {
+    /// すべてのタグ
+    /// 構文解析器の生成でタグの一覧が要るときは、ここから引く(タグを足したらここにも足す)
+    pub const ALL: [Tag; 7] = [Tag::Id, Tag::Type, Tag::Symbol, Tag::Primary, Tag::Comparison, Tag::Keyword, Tag::None];
+}
+
 impl fmt::Display for Tag {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -25,6 +33,7 @@ impl fmt::Display for Tag {
             Tag::Symbol => write!(f, "Symbol"),
             Tag::Primary => write!(f, "Primary"),
             Tag::Comparison => write!(f, "Comparison"),
+            Tag::Keyword => write!(f, "Keyword"),
             Tag::None => write!(f, "None")
         }
     }
@@ -39,7 +48,9 @@ impl fmt::Display for Tag {
 ///
 /// # derive
 /// - Clone
-#[derive(Clone)]
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
 pub enum Token {
     NumI32 { num: i32 },
     NumF32 { num: f32 },
@@ -81,9 +92,73 @@ impl Token {
     }
 }
 
+/// ソースコード上の位置
+///
+/// # members
+/// - line: usize => 行番号(1始まり)
+/// - col: usize => 列番号(1始まり)
+/// - start: usize => 先頭の文字位置
+/// - end: usize => 末尾の次の文字位置
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+/// - Default
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    /// Span構造体を生成して返す
+    ///
+    /// # params
+    /// - line: usize => 行番号
+    /// - col: usize => 列番号
+    /// - start: usize => 先頭の文字位置
+    /// - end: usize => 末尾の次の文字位置
+    ///
+    /// # returns
+    /// - Span
+    pub fn new(line: usize, col: usize, start: usize, end: usize) -> Span {
+        Span { line, col, start, end }
+    }
+
+    /// selfからotherまでを覆うSpanを返す
+    ///
+    /// # params
+    /// - other: Span => 後ろ側の位置
+    ///
+    /// # returns
+    /// - Span
+    pub fn to(self, other: Span) -> Span {
+        Span { line: self.line, col: self.col, start: self.start, end: other.end.max(self.end) }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::NumI32 { num } => write!(f, "{}", num),
+            Token::NumF32 { num } => write!(f, "{}", num),
+            Token::Word { tag: _, lexeme } => write!(f, "{}", lexeme)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Token, Tag};
+    use super::{Span, Token, Tag};
 
     #[test]
     fn token_new_test() {
@@ -91,4 +166,11 @@ mod tests {
         let _ = Token::new_numf32(12.04);
         let _ = Token::new_word(Tag::Id, "abcdefghijklmn");
     }
+
+    #[test]
+    fn span_to_test() {
+        let span = Span::new(1, 3, 2, 5).to(Span::new(1, 9, 8, 10));
+        assert_eq!(Span::new(1, 3, 2, 10), span);
+        assert_eq!("1:3", span.to_string());
+    }
 }
\ No newline at end of file
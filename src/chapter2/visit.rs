@@ -0,0 +1,411 @@
+use super::ast::{Block, Case, Decl, Expr, ExprKind, Func, Program, Stmt, StmtKind};
+
+/// 抽象構文木を読み取り専用でたどる
+/// 既定の実装は子を順にたどるだけなので、必要なメソッドだけを上書きする
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_func(&mut self, func: &Func) {
+        walk_func(self, func);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_decl(&mut self, _decl: &Decl) {}
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+/// プログラムの各関数をたどる
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for func in &program.funcs {
+        visitor.visit_func(func);
+    }
+}
+
+/// 関数の仮引数と本体をたどる
+pub fn walk_func<V: Visitor + ?Sized>(visitor: &mut V, func: &Func) {
+    for param in &func.params {
+        visitor.visit_decl(param);
+    }
+    if let Some(body) = &func.body {
+        visitor.visit_block(body);
+    }
+}
+
+/// ブロックの各文をたどる
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    for stmt in &block.stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+/// 文の子をたどる
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Decl(decl) => visitor.visit_decl(decl),
+        StmtKind::Expr(expr) => visitor.visit_expr(expr),
+        StmtKind::Assign(loc, expr) => {
+            visitor.visit_expr(loc);
+            visitor.visit_expr(expr);
+        },
+        StmtKind::If(cond, then, els) => {
+            visitor.visit_expr(cond);
+            visitor.visit_stmt(then);
+            if let Some(els) = els {
+                visitor.visit_stmt(els);
+            }
+        },
+        StmtKind::While(cond, body) => {
+            visitor.visit_expr(cond);
+            visitor.visit_stmt(body);
+        },
+        StmtKind::DoWhile(body, cond) => {
+            visitor.visit_stmt(body);
+            visitor.visit_expr(cond);
+        },
+        StmtKind::Switch(expr, cases) => {
+            visitor.visit_expr(expr);
+            for stmt in cases.iter().flat_map(|case| &case.stmts) {
+                visitor.visit_stmt(stmt);
+            }
+        },
+        StmtKind::Return(expr) => {
+            if let Some(expr) = expr {
+                visitor.visit_expr(expr);
+            }
+        },
+        StmtKind::Break | StmtKind::Continue => {},
+        StmtKind::Block(block) => visitor.visit_block(block)
+    }
+}
+
+/// 式の子をたどる
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::Var(_) => {},
+        ExprKind::Index(array, index) => {
+            visitor.visit_expr(array);
+            visitor.visit_expr(index);
+        },
+        ExprKind::Unary(_, operand) | ExprKind::Widen(_, operand) => visitor.visit_expr(operand),
+        ExprKind::Binary(_, lhs, rhs) => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        },
+        ExprKind::Call(_, args) => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+    }
+}
+
+/// 抽象構文木をその場で書き換えながらたどる
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_func_mut(&mut self, func: &mut Func) {
+        walk_func_mut(self, func);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_decl_mut(&mut self, _decl: &mut Decl) {}
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+}
+
+/// プログラムの各関数をたどる
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for func in &mut program.funcs {
+        visitor.visit_func_mut(func);
+    }
+}
+
+/// 関数の仮引数と本体をたどる
+pub fn walk_func_mut<V: VisitorMut + ?Sized>(visitor: &mut V, func: &mut Func) {
+    for param in &mut func.params {
+        visitor.visit_decl_mut(param);
+    }
+    if let Some(body) = &mut func.body {
+        visitor.visit_block_mut(body);
+    }
+}
+
+/// ブロックの各文をたどる
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for stmt in &mut block.stmts {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+/// 文の子をたどる
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match &mut stmt.kind {
+        StmtKind::Decl(decl) => visitor.visit_decl_mut(decl),
+        StmtKind::Expr(expr) => visitor.visit_expr_mut(expr),
+        StmtKind::Assign(loc, expr) => {
+            visitor.visit_expr_mut(loc);
+            visitor.visit_expr_mut(expr);
+        },
+        StmtKind::If(cond, then, els) => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_stmt_mut(then);
+            if let Some(els) = els {
+                visitor.visit_stmt_mut(els);
+            }
+        },
+        StmtKind::While(cond, body) => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_stmt_mut(body);
+        },
+        StmtKind::DoWhile(body, cond) => {
+            visitor.visit_stmt_mut(body);
+            visitor.visit_expr_mut(cond);
+        },
+        StmtKind::Switch(expr, cases) => {
+            visitor.visit_expr_mut(expr);
+            for stmt in cases.iter_mut().flat_map(|case| &mut case.stmts) {
+                visitor.visit_stmt_mut(stmt);
+            }
+        },
+        StmtKind::Return(expr) => {
+            if let Some(expr) = expr {
+                visitor.visit_expr_mut(expr);
+            }
+        },
+        StmtKind::Break | StmtKind::Continue => {},
+        StmtKind::Block(block) => visitor.visit_block_mut(block)
+    }
+}
+
+/// 式の子をたどる
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::Var(_) => {},
+        ExprKind::Index(array, index) => {
+            visitor.visit_expr_mut(array);
+            visitor.visit_expr_mut(index);
+        },
+        ExprKind::Unary(_, operand) | ExprKind::Widen(_, operand) => visitor.visit_expr_mut(operand),
+        ExprKind::Binary(_, lhs, rhs) => {
+            visitor.visit_expr_mut(lhs);
+            visitor.visit_expr_mut(rhs);
+        },
+        ExprKind::Call(_, args) => {
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+    }
+}
+
+/// 抽象構文木を消費して新しい木を組み立てる
+/// 既定の実装は子を畳み込んで同じ形の節を作り直す
+pub trait Folder {
+    fn fold_program(&mut self, program: Program) -> Program {
+        fold_program(self, program)
+    }
+
+    fn fold_func(&mut self, func: Func) -> Func {
+        fold_func(self, func)
+    }
+
+    fn fold_block(&mut self, block: Block) -> Block {
+        fold_block(self, block)
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        fold_stmt(self, stmt)
+    }
+
+    fn fold_decl(&mut self, decl: Decl) -> Decl {
+        decl
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        fold_expr(self, expr)
+    }
+}
+
+/// プログラムの各関数を畳み込む
+pub fn fold_program<F: Folder + ?Sized>(folder: &mut F, program: Program) -> Program {
+    Program {
+        funcs: program.funcs.into_iter().map(|f| folder.fold_func(f)).collect(),
+        ..program
+    }
+}
+
+/// 関数の仮引数と本体を畳み込む
+pub fn fold_func<F: Folder + ?Sized>(folder: &mut F, func: Func) -> Func {
+    Func {
+        params: func.params.into_iter().map(|p| folder.fold_decl(p)).collect(),
+        body: func.body.map(|b| folder.fold_block(b)),
+        ..func
+    }
+}
+
+/// ブロックの各文を畳み込む
+pub fn fold_block<F: Folder + ?Sized>(folder: &mut F, block: Block) -> Block {
+    Block {
+        stmts: block.stmts.into_iter().map(|s| folder.fold_stmt(s)).collect(),
+        ..block
+    }
+}
+
+/// 文の子を畳み込む
+pub fn fold_stmt<F: Folder + ?Sized>(folder: &mut F, stmt: Stmt) -> Stmt {
+    let kind = match stmt.kind {
+        StmtKind::Decl(decl) => StmtKind::Decl(folder.fold_decl(decl)),
+        StmtKind::Expr(expr) => StmtKind::Expr(folder.fold_expr(expr)),
+        StmtKind::Assign(loc, expr) => StmtKind::Assign(folder.fold_expr(loc), folder.fold_expr(expr)),
+        StmtKind::If(cond, then, els) => StmtKind::If(folder.fold_expr(cond), Box::new(folder.fold_stmt(*then)),
+            els.map(|els| Box::new(folder.fold_stmt(*els)))),
+        StmtKind::While(cond, body) => StmtKind::While(folder.fold_expr(cond), Box::new(folder.fold_stmt(*body))),
+        StmtKind::DoWhile(body, cond) => StmtKind::DoWhile(Box::new(folder.fold_stmt(*body)), folder.fold_expr(cond)),
+        StmtKind::Switch(expr, cases) => StmtKind::Switch(folder.fold_expr(expr), cases.into_iter().map(|case| Case {
+            stmts: case.stmts.into_iter().map(|stmt| folder.fold_stmt(stmt)).collect(),
+            ..case
+        }).collect()),
+        StmtKind::Return(expr) => StmtKind::Return(expr.map(|e| folder.fold_expr(e))),
+        StmtKind::Break => StmtKind::Break,
+        StmtKind::Continue => StmtKind::Continue,
+        StmtKind::Block(block) => StmtKind::Block(folder.fold_block(block))
+    };
+    Stmt { kind, ..stmt }
+}
+
+/// 式の子を畳み込む
+pub fn fold_expr<F: Folder + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    let kind = match expr.kind {
+        ExprKind::Index(array, index) => ExprKind::Index(Box::new(folder.fold_expr(*array)), Box::new(folder.fold_expr(*index))),
+        ExprKind::Unary(op, operand) => ExprKind::Unary(op, Box::new(folder.fold_expr(*operand))),
+        ExprKind::Widen(ty, operand) => ExprKind::Widen(ty, Box::new(folder.fold_expr(*operand))),
+        ExprKind::Binary(op, lhs, rhs) => ExprKind::Binary(op, Box::new(folder.fold_expr(*lhs)), Box::new(folder.fold_expr(*rhs))),
+        ExprKind::Call(name, args) => ExprKind::Call(name, args.into_iter().map(|a| folder.fold_expr(a)).collect()),
+        kind => kind
+    };
+    Expr { kind, ..expr }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Folder, Visitor, VisitorMut, fold_expr, walk_expr};
+    use super::super::ast::{BinOp, Decl, Expr, ExprKind};
+    use super::super::parser::astparser::AstParser;
+
+    struct VarCounter {
+        vars: Vec<String>,
+        decls: usize
+    }
+
+    impl Visitor for VarCounter {
+        fn visit_decl(&mut self, _decl: &Decl) {
+            self.decls += 1;
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Var(name) = &expr.kind {
+                self.vars.push(name.clone());
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    struct Renamer;
+
+    impl VisitorMut for Renamer {
+        fn visit_decl_mut(&mut self, decl: &mut Decl) {
+            decl.name.push('_');
+        }
+
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            if let ExprKind::Var(name) = &mut expr.kind {
+                name.push('_');
+            }
+            super::walk_expr_mut(self, expr);
+        }
+    }
+
+    // 定数同士の加算を畳み込む
+    struct AddFolder;
+
+    impl Folder for AddFolder {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            let expr = fold_expr(self, expr);
+            match expr.kind {
+                ExprKind::Binary(BinOp::Add, lhs, rhs) => match (&lhs.kind, &rhs.kind) {
+                    (ExprKind::Int(a), ExprKind::Int(b)) => Expr { kind: ExprKind::Int(a+b), ..expr },
+                    _ => Expr { kind: ExprKind::Binary(BinOp::Add, lhs, rhs), ..expr }
+                },
+                kind => Expr { kind, ..expr }
+            }
+        }
+    }
+
+    #[test]
+    fn visitor_test() {
+        let block = AstParser::new("{ i32 x; i32 y; x + y * x; { f32 z; z; } }").parse_block().unwrap();
+        let mut counter = VarCounter { vars: vec![], decls: 0 };
+        counter.visit_block(&block);
+        assert_eq!(vec!["x", "y", "x", "z"], counter.vars);
+        assert_eq!(3, counter.decls);
+    }
+
+    #[test]
+    fn visitor_mut_test() {
+        let mut block = AstParser::new("{ a[i] + b; }").parse_block().unwrap();
+        Renamer.visit_block_mut(&mut block);
+        let mut counter = VarCounter { vars: vec![], decls: 0 };
+        counter.visit_block(&block);
+        assert_eq!(vec!["a_", "i_", "b_"], counter.vars);
+
+        // 関数の仮引数と本体もたどる
+        let mut program = AstParser::new("fn f(i32 n) -> i32 { i32 m; m = n; return m; } fn g() -> i32;").parse_program().unwrap();
+        Renamer.visit_program_mut(&mut program);
+        let mut counter = VarCounter { vars: vec![], decls: 0 };
+        counter.visit_program(&program);
+        assert_eq!(vec!["m_", "n_", "m_"], counter.vars);
+        assert_eq!(2, counter.decls);
+        assert_eq!("n_", program.funcs[0].params[0].name);
+    }
+
+    #[test]
+    fn folder_test() {
+        let expr = AstParser::new("1 + 2 + x * (3 + 4)").parse_expr().unwrap();
+        let span = expr.span;
+        let folded = AddFolder.fold_expr(expr);
+        assert_eq!(span, folded.span);
+        match folded.kind {
+            ExprKind::Binary(BinOp::Add, lhs, rhs) => {
+                assert_eq!(ExprKind::Int(3), lhs.kind);
+                match rhs.kind {
+                    ExprKind::Binary(BinOp::Mul, _, seven) => assert_eq!(ExprKind::Int(7), seven.kind),
+                    _ => panic!("test failed at [folder_test]")
+                }
+            },
+            _ => panic!("test failed at [folder_test]")
+        }
+    }
+}
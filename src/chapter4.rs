@@ -0,0 +1,6 @@
+pub mod grammar;
+pub mod lr;
+pub mod lalr;
+pub mod yacc;
+pub mod earley;
+pub mod calc;           // calc.yから生成
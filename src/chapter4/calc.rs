@@ -0,0 +1,105 @@
+// dragonbook::chapter4::yacc によって生成されたコード
+// 直接編集しないこと
+
+use crate::chapter2::diagnostic::Diagnostic;
+use crate::chapter2::lexer::Lexer;
+use crate::chapter2::token::{Span, Token};
+use crate::chapter4::lr::{Action, LrParser, LrTable};
+
+/// 意味値の型
+pub type Value = f32;
+
+/// 構文解析表を生成して返す
+pub fn table() -> LrTable {
+    LrTable::from_entries(17, &["$end", "error", "NUM", "'+'", "'-'", "'*'", "'/'", "UMINUS", "'('", "')'"], 3,
+        &[
+            (0, 2, Action::Shift(1)), (0, 4, Action::Shift(2)), (0, 8, Action::Shift(3)),
+            (1, 0, Action::Reduce(8)), (1, 3, Action::Reduce(8)), (1, 4, Action::Reduce(8)), (1, 5, Action::Reduce(8)), (1, 6, Action::Reduce(8)), (1, 9, Action::Reduce(8)),
+            (2, 2, Action::Shift(1)), (2, 4, Action::Shift(2)), (2, 8, Action::Shift(3)),
+            (3, 2, Action::Shift(1)), (3, 4, Action::Shift(2)), (3, 8, Action::Shift(3)),
+            (4, 0, Action::Accept),
+            (5, 0, Action::Reduce(1)), (5, 3, Action::Shift(8)), (5, 4, Action::Shift(9)), (5, 5, Action::Shift(10)), (5, 6, Action::Shift(11)),
+            (6, 0, Action::Reduce(7)), (6, 3, Action::Reduce(7)), (6, 4, Action::Reduce(7)), (6, 5, Action::Reduce(7)), (6, 6, Action::Reduce(7)), (6, 9, Action::Reduce(7)),
+            (7, 3, Action::Shift(8)), (7, 4, Action::Shift(9)), (7, 5, Action::Shift(10)), (7, 6, Action::Shift(11)), (7, 9, Action::Shift(12)),
+            (8, 2, Action::Shift(1)), (8, 4, Action::Shift(2)), (8, 8, Action::Shift(3)),
+            (9, 2, Action::Shift(1)), (9, 4, Action::Shift(2)), (9, 8, Action::Shift(3)),
+            (10, 2, Action::Shift(1)), (10, 4, Action::Shift(2)), (10, 8, Action::Shift(3)),
+            (11, 2, Action::Shift(1)), (11, 4, Action::Shift(2)), (11, 8, Action::Shift(3)),
+            (12, 0, Action::Reduce(6)), (12, 3, Action::Reduce(6)), (12, 4, Action::Reduce(6)), (12, 5, Action::Reduce(6)), (12, 6, Action::Reduce(6)), (12, 9, Action::Reduce(6)),
+            (13, 0, Action::Reduce(2)), (13, 3, Action::Reduce(2)), (13, 4, Action::Reduce(2)), (13, 5, Action::Shift(10)), (13, 6, Action::Shift(11)), (13, 9, Action::Reduce(2)),
+            (14, 0, Action::Reduce(3)), (14, 3, Action::Reduce(3)), (14, 4, Action::Reduce(3)), (14, 5, Action::Shift(10)), (14, 6, Action::Shift(11)), (14, 9, Action::Reduce(3)),
+            (15, 0, Action::Reduce(4)), (15, 3, Action::Reduce(4)), (15, 4, Action::Reduce(4)), (15, 5, Action::Reduce(4)), (15, 6, Action::Reduce(4)), (15, 9, Action::Reduce(4)),
+            (16, 0, Action::Reduce(5)), (16, 3, Action::Reduce(5)), (16, 4, Action::Reduce(5)), (16, 5, Action::Reduce(5)), (16, 6, Action::Reduce(5)), (16, 9, Action::Reduce(5)),
+        ],
+        &[(0, 1, 4), (0, 2, 5), (2, 2, 6), (3, 2, 7), (8, 2, 13), (9, 2, 14), (10, 2, 15), (11, 2, 16)],
+        &[(0, 1), (1, 1), (2, 3), (2, 3), (2, 3), (2, 3), (2, 3), (2, 2), (2, 1)])
+}
+
+/// トークンの意味値を返す
+fn lexval(token: &Token) -> Value {
+    match token {
+        Token::NumI32 { num } => *num as f32,
+        Token::NumF32 { num } => *num,
+        _ => 0.0
+    }
+}
+
+/// トークンを終端記号番号へ分類する
+pub fn classify(token: &Token) -> Option<usize> {
+    match token {
+        Token::Word { lexeme, .. } if lexeme == "+" => Some(3),
+        Token::Word { lexeme, .. } if lexeme == "-" => Some(4),
+        Token::Word { lexeme, .. } if lexeme == "*" => Some(5),
+        Token::Word { lexeme, .. } if lexeme == "/" => Some(6),
+        Token::Word { lexeme, .. } if lexeme == "(" => Some(8),
+        Token::Word { lexeme, .. } if lexeme == ")" => Some(9),
+        Token::NumI32 { .. } | Token::NumF32 { .. } => Some(2),
+        _ => None
+    }
+}
+
+/// 規則prodで還元したときの意味動作
+#[allow(clippy::clone_on_copy)]
+fn reduce(prod: usize, __v: Vec<Value>) -> Value {
+    match prod {
+        // line -> expr
+        1 => __v[0].clone(),
+        // expr -> expr '+' expr
+        2 => { __v[0].clone() + __v[2].clone() },
+        // expr -> expr '-' expr
+        3 => { __v[0].clone() - __v[2].clone() },
+        // expr -> expr '*' expr
+        4 => { __v[0].clone() * __v[2].clone() },
+        // expr -> expr '/' expr
+        5 => { __v[0].clone() / __v[2].clone() },
+        // expr -> '(' expr ')'
+        6 => { __v[1].clone() },
+        // expr -> '-' expr
+        7 => { -__v[1].clone() },
+        // expr -> NUM
+        8 => __v[0].clone(),
+        _ => unreachable!()
+    }
+}
+
+/// トークン列を構文解析し、開始記号の意味値を返す
+pub fn parse<I: IntoIterator<Item = (Token, Span)>>(tokens: I) -> Result<Value, Vec<Diagnostic>> {
+    let table = table();
+    LrParser::new(&table).parse(tokens, classify, |token, _| lexval(token), reduce)
+}
+
+/// プログラムを字句解析・構文解析し、開始記号の意味値を返す
+pub fn parse_str(program: &str) -> Result<Value, Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(program.to_string());
+    let result = parse(std::iter::from_fn(|| lexer.scan_spanned()));
+    // 字句解析の誤り(範囲外の整数など)も返す
+    let mut diagnostics = lexer.take_diagnostics();
+    match result {
+        Ok(value) if diagnostics.is_empty() => Ok(value),
+        Ok(_) => Err(diagnostics),
+        Err(errors) => {
+            diagnostics.extend(errors);
+            Err(diagnostics)
+        }
+    }
+}
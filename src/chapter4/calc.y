@@ -0,0 +1,25 @@
+/* 電卓(4.9節) */
+%token NUM
+%left '+' '-'
+%left '*' '/'
+%right UMINUS
+%value f32
+%lexval {
+    match token {
+        Token::NumI32 { num } => *num as f32,
+        Token::NumF32 { num } => *num,
+        _ => 0.0
+    }
+}
+%%
+line : expr
+     ;
+expr : expr '+' expr            { $1 + $3 }
+     | expr '-' expr            { $1 - $3 }
+     | expr '*' expr            { $1 * $3 }
+     | expr '/' expr            { $1 / $3 }
+     | '(' expr ')'             { $2 }
+     | '-' expr %prec UMINUS    { -$2 }
+     | NUM
+     ;
+%%
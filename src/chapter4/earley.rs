@@ -0,0 +1,563 @@
+use std::collections::{HashMap, HashSet};
+
+use super::super::chapter2::diagnostic::Diagnostic;
+use super::super::chapter2::parsetree::ParseTree;
+use super::super::chapter2::token::{Span, Token};
+use super::grammar::{Grammar, Sym};
+
+/// Earley項 [A -> α・β, origin] を(規則番号, ドットの位置, 開始位置)で表す
+pub type EarleyItem = (usize, usize, usize);
+
+/// Earley法のチャート
+///
+/// # members
+/// - sets: Vec<Vec<EarleyItem>> => 入力位置ごとの項集合
+/// - input_len: usize => 入力の終端記号数
+pub struct Chart {
+    pub sets: Vec<Vec<EarleyItem>>,
+    pub input_len: usize,
+    index: Vec<HashSet<EarleyItem>>
+}
+
+impl Chart {
+    fn new(input_len: usize) -> Chart {
+        Chart { sets: vec![], input_len, index: vec![] }
+    }
+
+    fn push_set(&mut self) {
+        self.sets.push(vec![]);
+        self.index.push(HashSet::new());
+    }
+
+    fn add(&mut self, pos: usize, item: EarleyItem) {
+        if self.index[pos].insert(item) {
+            self.sets[pos].push(item);
+        }
+    }
+
+    /// 項が指定位置の項集合に含まれるかを返す
+    ///
+    /// # params
+    /// - pos: usize => 入力位置
+    /// - item: EarleyItem => 項
+    ///
+    /// # returns
+    /// - bool
+    pub fn contains(&self, pos: usize, item: EarleyItem) -> bool {
+        self.index.get(pos).map(|set| set.contains(&item)).unwrap_or(false)
+    }
+
+    /// 入力全体が受理されたかを返す
+    ///
+    /// # returns
+    /// - bool
+    pub fn accepted(&self) -> bool {
+        self.contains(self.input_len, (0, 1, 0))
+    }
+
+    /// 全項集合の項の総数を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn size(&self) -> usize {
+        self.sets.iter().map(|s| s.len()).sum()
+    }
+}
+
+/// 共有圧縮構文森の節
+///
+/// # members
+/// - sym: Sym => 文法記号
+/// - start: usize => 導出するトークン列の先頭位置
+/// - end: usize => 導出するトークン列の末尾の次の位置
+/// - packed: Vec<Packed> => 導出の選択肢(2つ以上なら曖昧)
+pub struct ForestNode {
+    pub sym: Sym,
+    pub start: usize,
+    pub end: usize,
+    pub packed: Vec<Packed>
+}
+
+/// 構文森の導出の1選択肢
+///
+/// # members
+/// - prod: usize => 用いた規則
+/// - children: Vec<usize> => 右辺の各記号に対応する節
+pub struct Packed {
+    pub prod: usize,
+    pub children: Vec<usize>
+}
+
+/// 共有圧縮構文森(SPPF)
+///
+/// # members
+/// - nodes: Vec<ForestNode> => 節
+/// - root: usize => 根の節
+/// - tokens: Vec<String> => 入力トークンの文字列
+/// - spans: Vec<Span> => 入力トークンの位置
+pub struct Forest {
+    pub nodes: Vec<ForestNode>,
+    pub root: usize,
+    pub tokens: Vec<String>,
+    pub spans: Vec<Span>
+}
+
+impl Forest {
+    /// 節が導出するトークン列の位置を返す
+    ///
+    /// # params
+    /// - node: usize => 節
+    ///
+    /// # returns
+    /// - Span
+    pub fn span(&self, node: usize) -> Span {
+        let n = &self.nodes[node];
+        match (self.spans.get(n.start), n.end.checked_sub(1).and_then(|e| self.spans.get(e))) {
+            (Some(first), Some(last)) if n.start < n.end => first.to(*last),
+            (Some(first), _) => Span::new(first.line, first.col, first.start, first.start),
+            _ => self.spans.last().map(|s| Span::new(s.line, s.col, s.end, s.end)).unwrap_or_default()
+        }
+    }
+
+    /// 根から到達可能な節を深さ優先の順で返す
+    ///
+    /// # returns
+    /// - Vec<usize>
+    pub fn reachable(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut order = vec![];
+        let mut stack = vec![self.root];
+        while let Some(node) = stack.pop() {
+            if seen[node] {
+                continue;
+            }
+            seen[node] = true;
+            order.push(node);
+            for packed in self.nodes[node].packed.iter().rev() {
+                stack.extend(packed.children.iter().rev());
+            }
+        }
+        order
+    }
+
+    /// 構文森が表す構文木の数を返す
+    /// 循環する文法で無限個になる場合はNone
+    ///
+    /// # returns
+    /// - Option<usize>
+    pub fn count_trees(&self) -> Option<usize> {
+        let mut memo: HashMap<usize, Option<usize>> = HashMap::new();
+        self.count_node(self.root, &mut memo)
+    }
+
+    fn count_node(&self, node: usize, memo: &mut HashMap<usize, Option<usize>>) -> Option<usize> {
+        if let Some(count) = memo.get(&node) {
+            return *count;
+        }
+        memo.insert(node, None);
+        let mut total: usize = if self.nodes[node].packed.is_empty() { 1 } else { 0 };
+        for packed in &self.nodes[node].packed {
+            let mut product: usize = 1;
+            for child in &packed.children {
+                product = product.saturating_mul(self.count_node(*child, memo)?);
+            }
+            total = total.saturating_add(product);
+        }
+        memo.insert(node, Some(total));
+        Some(total)
+    }
+
+    /// 曖昧な節(選択肢が2つ以上ある節)をすべて診断として返す
+    ///
+    /// # params
+    /// - grammar: &Grammar => 構文解析に用いた文法
+    ///
+    /// # returns
+    /// - Vec<Diagnostic>
+    pub fn ambiguities(&self, grammar: &Grammar) -> Vec<Diagnostic> {
+        let mut nodes: Vec<usize> = self.reachable().into_iter()
+            .filter(|n| self.nodes[*n].packed.len() > 1)
+            .collect();
+        nodes.sort_by_key(|n| (self.nodes[*n].start, std::cmp::Reverse(self.nodes[*n].end)));
+        nodes.iter().map(|n| {
+            let node = &self.nodes[*n];
+            let span = self.span(*n);
+            let mut diag = Diagnostic::warning(span, format!("ambiguous {} `{}` has {} derivations",
+                grammar.name(node.sym), self.text(*n), node.packed.len()));
+            for packed in &node.packed {
+                let parts: Vec<String> = packed.children.iter().map(|c| format!("[{}]", self.text(*c))).collect();
+                diag = diag.with_note(span, format!("{} as {}", grammar.production_to_string(packed.prod), parts.join(" ")));
+            }
+            diag
+        }).collect()
+    }
+
+    /// 構文森から構文解析木を1つ取り出す
+    /// 曖昧な節では、循環しない最初の選択肢を選ぶ
+    ///
+    /// # params
+    /// - grammar: &Grammar => 構文解析に用いた文法
+    /// - tokens: &[(Token, Span)] => 構文解析したトークン列
+    ///
+    /// # returns
+    /// - ParseTree
+    pub fn tree(&self, grammar: &Grammar, tokens: &[(Token, Span)]) -> ParseTree {
+        let mut path = HashSet::new();
+        self.tree_node(grammar, tokens, self.root, &mut path)
+    }
+
+    fn tree_node(&self, grammar: &Grammar, tokens: &[(Token, Span)], node: usize, path: &mut HashSet<usize>) -> ParseTree {
+        let n = &self.nodes[node];
+        if let Sym::T(_) = n.sym {
+            let (token, span) = &tokens[n.start];
+            return ParseTree::leaf(token.clone(), *span);
+        }
+        path.insert(node);
+        let children = n.packed.iter()
+            .find(|p| p.children.iter().all(|c| !path.contains(c)))
+            .map(|p| p.children.iter().map(|c| self.tree_node(grammar, tokens, *c, path)).collect())
+            .unwrap_or_default();
+        path.remove(&node);
+        ParseTree::node(grammar.name(n.sym), children)
+    }
+
+    /// 節が導出するトークン列を文字列にして返す
+    ///
+    /// # params
+    /// - node: usize => 節
+    ///
+    /// # returns
+    /// - String
+    pub fn text(&self, node: usize) -> String {
+        let n = &self.nodes[node];
+        self.tokens[n.start..n.end].join(" ")
+    }
+}
+
+/// Earley法による一般の文脈自由文法の構文解析器
+/// 右再帰に対してはLeoの最適化で完了項の連鎖を省略できる
+///
+/// # members
+/// - grammar: &Grammar => 文法
+/// - leo: bool => Leoの最適化を行うか
+/// - nullable: Vec<bool> => 空列を導出可能な非終端記号
+/// - by_lhs: Vec<Vec<usize>> => 左辺ごとの規則番号
+pub struct Earley<'a> {
+    grammar: &'a Grammar,
+    leo: bool,
+    nullable: Vec<bool>,
+    by_lhs: Vec<Vec<usize>>
+}
+
+impl<'a> Earley<'a> {
+    /// Earleyを生成して返す
+    ///
+    /// # params
+    /// - grammar: &Grammar => 文法
+    ///
+    /// # returns
+    /// - Earley
+    pub fn new(grammar: &'a Grammar) -> Earley<'a> {
+        let mut by_lhs = vec![vec![]; grammar.nonterminals.len()];
+        for (idx, p) in grammar.productions.iter().enumerate() {
+            by_lhs[p.lhs].push(idx);
+        }
+        Earley { grammar, leo: false, nullable: grammar.nullable(), by_lhs }
+    }
+
+    /// Leoの最適化の有無を設定する
+    /// 最適化した場合のチャートは認識にのみ使え、構文森は作れない
+    /// そのためparseはこの設定によらず、最適化しないチャートから構文森を作る
+    ///
+    /// # params
+    /// - leo: bool => 最適化するか
+    ///
+    /// # returns
+    /// - Earley
+    pub fn with_leo(mut self, leo: bool) -> Earley<'a> {
+        self.leo = leo;
+        self
+    }
+
+    /// 終端記号列を認識し、チャートを返す
+    /// 途中で項集合が空になった場合はその位置までのチャートを返す
+    ///
+    /// # params
+    /// - input: &[usize] => 終端記号列
+    ///
+    /// # returns
+    /// - Chart
+    pub fn recognize(&self, input: &[usize]) -> Chart {
+        self.chart(input, self.leo)
+    }
+
+    /// Leoの最適化の有無を指定してチャートを作る
+    fn chart(&self, input: &[usize], leo: bool) -> Chart {
+        let mut chart = Chart::new(input.len());
+        let mut leo_memo: HashMap<(usize, usize), Option<(usize, usize)>> = HashMap::new();
+        chart.push_set();
+        chart.add(0, (0, 0, 0));
+        for pos in 0..=input.len() {
+            let mut idx = 0;
+            while idx < chart.sets[pos].len() {
+                let (prod, dot, origin) = chart.sets[pos][idx];
+                idx += 1;
+                let rhs = &self.grammar.productions[prod].rhs;
+                match rhs.get(dot) {
+                    // 予測
+                    Some(Sym::N(n)) => {
+                        for p in &self.by_lhs[*n] {
+                            chart.add(pos, (*p, 0, pos));
+                        }
+                        if self.nullable[*n] {
+                            chart.add(pos, (prod, dot+1, origin));
+                        }
+                    },
+                    // 走査
+                    Some(Sym::T(t)) => {
+                        if input.get(pos) == Some(t) {
+                            if chart.sets.len() == pos+1 {
+                                chart.push_set();
+                            }
+                            chart.add(pos+1, (prod, dot+1, origin));
+                        }
+                    },
+                    // 完了
+                    None => {
+                        let lhs = self.grammar.productions[prod].lhs;
+                        if leo && origin < pos {
+                            if let Some((top, top_origin)) = self.leo_top(&chart, &mut leo_memo, origin, lhs) {
+                                let len = self.grammar.productions[top].rhs.len();
+                                chart.add(pos, (top, len, top_origin));
+                                continue;
+                            }
+                        }
+                        let waiting: Vec<EarleyItem> = chart.sets[origin].iter()
+                            .filter(|(p, d, _)| self.grammar.productions[*p].rhs.get(*d) == Some(&Sym::N(lhs)))
+                            .cloned()
+                            .collect();
+                        for (p, d, o) in waiting {
+                            chart.add(pos, (p, d+1, o));
+                        }
+                    }
+                }
+            }
+            if chart.sets.len() == pos+1 && pos < input.len() {
+                break;
+            }
+        }
+        chart
+    }
+
+    /// 位置posで記号symを待つ項が決定的還元経路を成すとき、その最上位の完了項を返す
+    ///
+    /// # params
+    /// - chart: &Chart => 構築中のチャート
+    /// - memo: &mut HashMap<(usize, usize), Option<(usize, usize)>> => Leo項の記録
+    /// - pos: usize => 位置
+    /// - sym: usize => 非終端記号
+    ///
+    /// # returns
+    /// - Option<(usize, usize)> => (規則番号, 開始位置)
+    fn leo_top(&self, chart: &Chart, memo: &mut HashMap<(usize, usize), Option<(usize, usize)>>, pos: usize, sym: usize) -> Option<(usize, usize)> {
+        if let Some(top) = memo.get(&(pos, sym)) {
+            return *top;
+        }
+        let mut waiting = chart.sets[pos].iter()
+            .filter(|(p, d, _)| self.grammar.productions[*p].rhs.get(*d) == Some(&Sym::N(sym)));
+        let top = match (waiting.next(), waiting.next()) {
+            (Some((p, d, o)), None) if *d+1 == self.grammar.productions[*p].rhs.len() => {
+                let lhs = self.grammar.productions[*p].lhs;
+                let above = if *o < pos { self.leo_top(chart, memo, *o, lhs) } else { None };
+                Some(above.unwrap_or((*p, *o)))
+            },
+            _ => None
+        };
+        memo.insert((pos, sym), top);
+        top
+    }
+
+    /// トークン列を構文解析し、共有圧縮構文森を返す
+    /// 構文森を作るには省略のない完了項が要るので、Leoの最適化は行わない
+    ///
+    /// # params
+    /// - tokens: &[(Token, Span)] => トークンと位置の列
+    ///
+    /// # returns
+    /// - Result<Forest, Diagnostic>
+    pub fn parse(&self, tokens: &[(Token, Span)]) -> Result<Forest, Diagnostic> {
+        let mut input = vec![];
+        for (token, span) in tokens {
+            match self.grammar.classify(token) {
+                Some(t) => input.push(t),
+                None => return Err(Diagnostic::error(*span, format!("syntax error: unknown token `{}`", token)))
+            }
+        }
+        let chart = self.chart(&input, false);
+        if chart.sets.len() <= input.len() {
+            let (token, span) = &tokens[chart.sets.len()-1];
+            return Err(Diagnostic::error(*span, format!("syntax error: unexpected `{}`", token)));
+        }
+        if !chart.accepted() {
+            let span = tokens.last().map(|(_, s)| Span::new(s.line, s.col+s.end-s.start, s.end, s.end)).unwrap_or_default();
+            return Err(Diagnostic::error(span, "syntax error: unexpected end of input".to_string()));
+        }
+
+        let mut forest = Forest {
+            nodes: vec![],
+            root: 0,
+            tokens: tokens.iter().map(|(t, _)| t.to_string()).collect(),
+            spans: tokens.iter().map(|(_, s)| *s).collect()
+        };
+        let mut memo = HashMap::new();
+        let start = Sym::N(self.grammar.start.unwrap_or(0));
+        forest.root = self.build(&chart, &input, &mut forest, &mut memo, start, 0, input.len());
+        Ok(forest)
+    }
+
+    /// 記号symが input[start..end] を導出する節を構築する
+    #[allow(clippy::too_many_arguments)]
+    fn build(&self, chart: &Chart, input: &[usize], forest: &mut Forest, memo: &mut HashMap<(Sym, usize, usize), usize>,
+             sym: Sym, start: usize, end: usize) -> usize {
+        if let Some(node) = memo.get(&(sym, start, end)) {
+            return *node;
+        }
+        let node = forest.nodes.len();
+        forest.nodes.push(ForestNode { sym, start, end, packed: vec![] });
+        memo.insert((sym, start, end), node);
+        if let Sym::N(n) = sym {
+            let completed: Vec<usize> = chart.sets[end].iter()
+                .filter(|(p, d, o)| *o == start && self.grammar.productions[*p].lhs == n && *d == self.grammar.productions[*p].rhs.len())
+                .map(|(p, _, _)| *p)
+                .collect();
+            for prod in completed {
+                let len = self.grammar.productions[prod].rhs.len();
+                for split in self.splits(chart, input, prod, len, start, end) {
+                    let children = split.iter().map(|(s, i, j)| self.build(chart, input, forest, memo, *s, *i, *j)).collect();
+                    forest.nodes[node].packed.push(Packed { prod, children });
+                }
+            }
+        }
+        node
+    }
+
+    /// 規則prodの右辺の先頭dot個が input[start..end] を導出する分割をすべて返す
+    fn splits(&self, chart: &Chart, input: &[usize], prod: usize, dot: usize, start: usize, end: usize) -> Vec<Vec<(Sym, usize, usize)>> {
+        if dot == 0 {
+            return if start == end { vec![vec![]] } else { vec![] };
+        }
+        let sym = self.grammar.productions[prod].rhs[dot-1];
+        let mids: Vec<usize> = match sym {
+            Sym::T(t) if end > start && input[end-1] == t => vec![end-1],
+            Sym::T(_) => vec![],
+            Sym::N(n) => (start..=end).filter(|mid| {
+                chart.sets[end].iter().any(|(p, d, o)| *o == *mid && self.grammar.productions[*p].lhs == n
+                    && *d == self.grammar.productions[*p].rhs.len())
+            }).collect()
+        };
+        let mut result = vec![];
+        for mid in mids {
+            if !chart.contains(mid, (prod, dot-1, start)) {
+                continue;
+            }
+            for mut prefix in self.splits(chart, input, prod, dot-1, start, mid) {
+                prefix.push((sym, mid, end));
+                result.push(prefix);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Earley;
+    use super::super::grammar::{Grammar, Sym};
+    use super::super::yacc::Yacc;
+    use super::super::super::chapter2::lexer::Lexer;
+    use super::super::super::chapter2::token::{Span, Token};
+
+    fn tokens(program: &str) -> Vec<(Token, Span)> {
+        let mut lexer = Lexer::new(program.to_string());
+        std::iter::from_fn(|| lexer.scan_spanned()).collect()
+    }
+
+    #[test]
+    fn earley_ambiguous_expr_test() {
+        let yacc = Yacc::parse("%token NUM\n%%\nexpr : expr '-' expr | NUM ;\n").unwrap();
+        let earley = Earley::new(&yacc.grammar);
+        let forest = earley.parse(&tokens("1 - 2 - 3")).unwrap();
+        assert_eq!(Some(2), forest.count_trees());
+        let diags = forest.ambiguities(&yacc.grammar);
+        assert_eq!(1, diags.len());
+        assert_eq!("ambiguous expr `1 - 2 - 3` has 2 derivations", diags[0].message);
+        assert_eq!(Span::new(1, 1, 0, 9), diags[0].span);
+        let notes: Vec<&str> = diags[0].notes.iter().map(|(_, n)| &n[..]).collect();
+        assert!(notes.contains(&"expr -> expr '-' expr as [1 - 2] [-] [3]"));
+        assert!(notes.contains(&"expr -> expr '-' expr as [1] [-] [2 - 3]"));
+
+        // 4項ならカタラン数C3 = 5通り
+        let forest = earley.parse(&tokens("1 - 2 - 3 - 4")).unwrap();
+        assert_eq!(Some(5), forest.count_trees());
+    }
+
+    #[test]
+    fn earley_dangling_else_test() {
+        let yacc = Yacc::parse("\
+%%
+stmt : 'if' cond 'then' stmt
+     | 'if' cond 'then' stmt 'else' stmt
+     | 'x'
+     ;
+cond : 'b' ;
+").unwrap();
+        let earley = Earley::new(&yacc.grammar);
+        let forest = earley.parse(&tokens("if b then if b then x else x")).unwrap();
+        let diags = forest.ambiguities(&yacc.grammar);
+        assert_eq!(1, diags.len());
+        let notes: Vec<&str> = diags[0].notes.iter().map(|(_, n)| &n[..]).collect();
+        assert!(notes.contains(&"stmt -> 'if' cond 'then' stmt as [if] [b] [then] [if b then x else x]"));
+        assert!(notes.contains(&"stmt -> 'if' cond 'then' stmt 'else' stmt as [if] [b] [then] [if b then x] [else] [x]"));
+
+        let input = tokens("if b then x else x");
+        let forest = earley.parse(&input).unwrap();
+        assert!(forest.ambiguities(&yacc.grammar).is_empty());
+        assert_eq!("[stmt if [cond b] then [stmt x] else [stmt x]]", forest.tree(&yacc.grammar, &input).to_string());
+        assert_eq!(Some(1), forest.count_trees());
+    }
+
+    #[test]
+    fn earley_nullable_test() {
+        // S -> A A 'x' ; A -> ε | 'a'
+        let yacc = Yacc::parse("%%\ns : a a 'x' ;\na : | 'a' ;\n").unwrap();
+        let earley = Earley::new(&yacc.grammar);
+        assert_eq!(Some(1), earley.parse(&tokens("x")).unwrap().count_trees());
+        assert_eq!(Some(2), earley.parse(&tokens("a x")).unwrap().count_trees());
+        assert_eq!(Some(1), earley.parse(&tokens("a a x")).unwrap().count_trees());
+        let err = earley.parse(&tokens("a a a x")).err().unwrap();
+        assert_eq!("syntax error: unexpected `a`", err.message);
+        assert_eq!(Span::new(1, 5, 4, 5), err.span);
+        assert!(earley.parse(&tokens("a")).is_err());
+    }
+
+    #[test]
+    fn earley_leo_test() {
+        // A -> 'a' A | 'a' (右再帰)
+        let mut g = Grammar::new();
+        let a = g.nonterminal("A");
+        let t = g.terminal("'a'");
+        g.add_production(a, vec![Sym::T(t), Sym::N(a)], None, None);
+        g.add_production(a, vec![Sym::T(t)], None, None);
+        let input = vec![t; 60];
+        let plain = Earley::new(&g).recognize(&input);
+        let leo = Earley::new(&g).with_leo(true).recognize(&input);
+        assert!(plain.accepted() && leo.accepted());
+        assert!(leo.size() * 5 < plain.size());
+        assert!(!Earley::new(&g).with_leo(true).recognize(&[]).accepted());
+
+        // 最適化を有効にしても、構文森は省略のないチャートから作る
+        let yacc = Yacc::parse("%%\na : 'a' a | 'a' ;\n").unwrap();
+        let forest = Earley::new(&yacc.grammar).with_leo(true).parse(&tokens("a a a")).unwrap();
+        assert_eq!(Some(1), forest.count_trees());
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::super::chapter2::token::Token;
+
+/// 入力終端を表す終端記号`$end`
+pub const END: usize = 0;
+
+/// エラー回復に用いる終端記号`error`
+pub const ERROR: usize = 1;
+
+/// 結合性
+///
+/// # members
+/// - Left => 左結合(%left)
+/// - Right => 右結合(%right)
+/// - NonAssoc => 非結合(%nonassoc)
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Assoc {
+    Left,
+    Right,
+    NonAssoc
+}
+
+/// 文法記号
+///
+/// # members
+/// - T(usize) => 終端記号(Grammar::terminalsの添字)
+/// - N(usize) => 非終端記号(Grammar::nonterminalsの添字)
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq, Eq, Hash, PartialOrd, Ord
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum Sym {
+    T(usize),
+    N(usize)
+}
+
+/// 生成規則 lhs -> rhs
+///
+/// # members
+/// - lhs: usize => 左辺の非終端記号
+/// - rhs: Vec<Sym> => 右辺
+/// - prec: Option<usize> => %precで指定された終端記号
+/// - action: Option<String> => 意味動作(Rustのブロック)
+///
+/// # derive
+/// - Clone
+/// - Debug
+#[derive(Clone, Debug)]
+pub struct Production {
+    pub lhs: usize,
+    pub rhs: Vec<Sym>,
+    pub prec: Option<usize>,
+    pub action: Option<String>
+}
+
+/// 文脈自由文法
+/// 終端記号0は入力終端`$end`、1はエラー回復用の`error`、
+/// 非終端記号0は拡大文法の開始記号`$accept`に予約されている
+///
+/// # members
+/// - terminals: Vec<String> => 終端記号名
+/// - nonterminals: Vec<String> => 非終端記号名
+/// - productions: Vec<Production> => 生成規則(0番は $accept -> start)
+/// - precedence: Vec<Option<(usize, Assoc)>> => 終端記号ごとの優先順位と結合性
+/// - start: Option<usize> => 開始記号
+///
+/// # derive
+/// - Clone
+#[derive(Clone)]
+pub struct Grammar {
+    pub terminals: Vec<String>,
+    pub nonterminals: Vec<String>,
+    pub productions: Vec<Production>,
+    pub precedence: Vec<Option<(usize, Assoc)>>,
+    pub start: Option<usize>
+}
+
+impl Default for Grammar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Grammar {
+    /// `$end`, `error`, `$accept`のみを持つ空の文法を生成して返す
+    ///
+    /// # returns
+    /// - Grammar
+    pub fn new() -> Grammar {
+        Grammar {
+            terminals: vec!["$end".to_string(), "error".to_string()],
+            nonterminals: vec!["$accept".to_string()],
+            productions: vec![Production { lhs: 0, rhs: vec![], prec: None, action: None }],
+            precedence: vec![None, None],
+            start: None
+        }
+    }
+
+    /// 終端記号を登録してその番号を返す(登録済みなら既存の番号)
+    ///
+    /// # params
+    /// - name: &str => 記号名
+    ///
+    /// # returns
+    /// - usize
+    pub fn terminal(&mut self, name: &str) -> usize {
+        if let Some(idx) = self.find_terminal(name) {
+            return idx;
+        }
+        self.terminals.push(name.to_string());
+        self.precedence.push(None);
+        self.terminals.len()-1
+    }
+
+    /// 非終端記号を登録してその番号を返す(登録済みなら既存の番号)
+    ///
+    /// # params
+    /// - name: &str => 記号名
+    ///
+    /// # returns
+    /// - usize
+    pub fn nonterminal(&mut self, name: &str) -> usize {
+        if let Some(idx) = self.find_nonterminal(name) {
+            return idx;
+        }
+        self.nonterminals.push(name.to_string());
+        self.nonterminals.len()-1
+    }
+
+    /// 終端記号を名前から検索する
+    ///
+    /// # params
+    /// - name: &str => 記号名
+    ///
+    /// # returns
+    /// - Option<usize>
+    pub fn find_terminal(&self, name: &str) -> Option<usize> {
+        self.terminals.iter().position(|t| t == name)
+    }
+
+    /// 非終端記号を名前から検索する
+    ///
+    /// # params
+    /// - name: &str => 記号名
+    ///
+    /// # returns
+    /// - Option<usize>
+    pub fn find_nonterminal(&self, name: &str) -> Option<usize> {
+        self.nonterminals.iter().position(|n| n == name)
+    }
+
+    /// 終端記号に優先順位と結合性を設定する
+    /// levelが大きいほど強く結合する
+    ///
+    /// # params
+    /// - terminal: usize => 対象の終端記号
+    /// - level: usize => 優先順位
+    /// - assoc: Assoc => 結合性
+    pub fn set_precedence(&mut self, terminal: usize, level: usize, assoc: Assoc) {
+        self.precedence[terminal] = Some((level, assoc));
+    }
+
+    /// 生成規則を追加してその番号を返す
+    /// 最初に追加された規則の左辺は、開始記号が未設定なら開始記号になる
+    ///
+    /// # params
+    /// - lhs: usize => 左辺
+    /// - rhs: Vec<Sym> => 右辺
+    /// - prec: Option<usize> => %precで指定された終端記号
+    /// - action: Option<String> => 意味動作
+    ///
+    /// # returns
+    /// - usize
+    pub fn add_production(&mut self, lhs: usize, rhs: Vec<Sym>, prec: Option<usize>, action: Option<String>) -> usize {
+        if self.start.is_none() {
+            self.set_start(lhs);
+        }
+        self.productions.push(Production { lhs, rhs, prec, action });
+        self.productions.len()-1
+    }
+
+    /// 開始記号を設定し、拡大文法の規則 $accept -> start を更新する
+    ///
+    /// # params
+    /// - start: usize => 開始記号
+    pub fn set_start(&mut self, start: usize) {
+        self.start = Some(start);
+        self.productions[0].rhs = vec![Sym::N(start)];
+    }
+
+    /// 生成規則の優先順位を返す
+    /// %precがあればそれを、なければ右辺の最も右の終端記号の優先順位を用いる
+    ///
+    /// # params
+    /// - prod: usize => 生成規則番号
+    ///
+    /// # returns
+    /// - Option<(usize, Assoc)>
+    pub fn production_precedence(&self, prod: usize) -> Option<(usize, Assoc)> {
+        let production = &self.productions[prod];
+        if let Some(t) = production.prec {
+            return self.precedence[t];
+        }
+        production.rhs.iter().rev().find_map(|s| match s {
+            Sym::T(t) => Some(self.precedence[*t]),
+            _ => None
+        }).flatten()
+    }
+
+    /// トークンを終端記号番号へ分類する
+    /// - Token::Word => lexemeのリテラル('x')、なければTag名の大文字(ID, TYPE, ..)
+    /// - Token::NumI32, Token::NumF32 => NUMI32, NUMF32、なければNUM
+    ///
+    /// # params
+    /// - token: &Token => 分類するトークン
+    ///
+    /// # returns
+    /// - Option<usize>
+    pub fn classify(&self, token: &Token) -> Option<usize> {
+        let candidates = match token {
+            Token::Word { tag, lexeme } => vec![format!("'{}'", lexeme), tag.to_string().to_uppercase()],
+            Token::NumI32 { .. } => vec!["NUMI32".to_string(), "NUM".to_string()],
+            Token::NumF32 { .. } => vec!["NUMF32".to_string(), "NUM".to_string()]
+        };
+        candidates.iter().find_map(|c| self.find_terminal(c))
+    }
+
+    /// 記号名を返す
+    ///
+    /// # params
+    /// - sym: Sym => 文法記号
+    ///
+    /// # returns
+    /// - &str
+    pub fn name(&self, sym: Sym) -> &str {
+        match sym {
+            Sym::T(t) => &self.terminals[t],
+            Sym::N(n) => &self.nonterminals[n]
+        }
+    }
+
+    /// 空列を導出可能な非終端記号を求める
+    ///
+    /// # returns
+    /// - Vec<bool>
+    pub fn nullable(&self) -> Vec<bool> {
+        let mut nullable = vec![false; self.nonterminals.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for p in &self.productions {
+                if !nullable[p.lhs] && p.rhs.iter().all(|s| matches!(s, Sym::N(n) if nullable[*n])) {
+                    nullable[p.lhs] = true;
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+
+    /// 各非終端記号のFIRST集合を求める
+    ///
+    /// # returns
+    /// - Vec<BTreeSet<usize>>
+    pub fn first_sets(&self) -> Vec<BTreeSet<usize>> {
+        let nullable = self.nullable();
+        let mut first = vec![BTreeSet::new(); self.nonterminals.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for p in &self.productions {
+                for s in &p.rhs {
+                    let add: Vec<usize> = match s {
+                        Sym::T(t) => vec![*t],
+                        Sym::N(n) => first[*n].iter().cloned().collect()
+                    };
+                    for t in add {
+                        changed |= first[p.lhs].insert(t);
+                    }
+                    match s {
+                        Sym::N(n) if nullable[*n] => continue,
+                        _ => break
+                    }
+                }
+            }
+        }
+        first
+    }
+
+    /// 記号列 symbols の後ろに lookahead が続く列のFIRST集合を求める
+    ///
+    /// # params
+    /// - symbols: &[Sym] => 記号列
+    /// - lookahead: &BTreeSet<usize> => 後続する終端記号の集合
+    /// - first: &[BTreeSet<usize>] => first_setsの結果
+    /// - nullable: &[bool] => nullableの結果
+    ///
+    /// # returns
+    /// - BTreeSet<usize>
+    pub fn first_of(symbols: &[Sym], lookahead: &BTreeSet<usize>, first: &[BTreeSet<usize>], nullable: &[bool]) -> BTreeSet<usize> {
+        let mut result = BTreeSet::new();
+        for s in symbols {
+            match s {
+                Sym::T(t) => { result.insert(*t); return result; },
+                Sym::N(n) => {
+                    result.extend(first[*n].iter().cloned());
+                    if !nullable[*n] {
+                        return result;
+                    }
+                }
+            }
+        }
+        result.extend(lookahead.iter().cloned());
+        result
+    }
+
+    /// 生成規則を"lhs -> rhs"の形で返す
+    ///
+    /// # params
+    /// - prod: usize => 生成規則番号
+    ///
+    /// # returns
+    /// - String
+    pub fn production_to_string(&self, prod: usize) -> String {
+        let p = &self.productions[prod];
+        let rhs: Vec<&str> = p.rhs.iter().map(|s| self.name(*s)).collect();
+        if rhs.is_empty() {
+            format!("{} -> ε", self.nonterminals[p.lhs])
+        } else {
+            format!("{} -> {}", self.nonterminals[p.lhs], rhs.join(" "))
+        }
+    }
+}
+
+impl fmt::Display for Grammar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for idx in 0..self.productions.len() {
+            writeln!(f, "{:>4}  {}", idx, self.production_to_string(idx))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grammar, Sym};
+
+    #[test]
+    fn grammar_first_test() {
+        // E -> T E' ; E' -> + T E' | ε ; T -> id
+        let mut g = Grammar::new();
+        let e = g.nonterminal("E");
+        let ed = g.nonterminal("E'");
+        let t = g.nonterminal("T");
+        let plus = g.terminal("'+'");
+        let id = g.terminal("ID");
+        g.add_production(e, vec![Sym::N(t), Sym::N(ed)], None, None);
+        g.add_production(ed, vec![Sym::T(plus), Sym::N(t), Sym::N(ed)], None, None);
+        g.add_production(ed, vec![], None, None);
+        g.add_production(t, vec![Sym::T(id)], None, None);
+
+        let nullable = g.nullable();
+        assert!(nullable[ed] && !nullable[e]);
+        let first = g.first_sets();
+        assert!(first[e].contains(&id) && first[e].len() == 1);
+        assert!(first[ed].contains(&plus) && first[ed].len() == 1);
+        assert_eq!("E -> T E'", g.production_to_string(1));
+        assert_eq!("E' -> ε", g.production_to_string(3));
+    }
+}
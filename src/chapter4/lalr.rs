@@ -0,0 +1,504 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use super::grammar::{Assoc, Grammar, Sym};
+use super::lr::{Action, LrTable};
+
+/// 先読みの伝播を調べるための仮の終端記号(#)
+const PROPAGATE: usize = usize::MAX;
+
+/// LR(0)項 [A -> α・β] を(規則番号, ドットの位置)で表す
+pub type Item = (usize, usize);
+
+/// 衝突の種類
+///
+/// # members
+/// - ShiftReduce { shift: usize, reduce: usize } => シフト/還元衝突(シフト先状態, 還元規則)
+/// - ReduceReduce { chosen: usize, rejected: usize } => 還元/還元衝突(採用した規則, 捨てた規則)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub enum ConflictKind {
+    ShiftReduce { shift: usize, reduce: usize },
+    ReduceReduce { chosen: usize, rejected: usize }
+}
+
+/// 構文解析表の構築中に見つかった衝突
+///
+/// # members
+/// - state: usize => 状態
+/// - terminal: usize => 先読み記号
+/// - kind: ConflictKind => 衝突の種類
+/// - resolved: bool => 優先順位宣言によって解消されたか
+/// - chosen: Action => 採用した動作
+///
+/// # derive
+/// - Clone
+/// - Debug
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub state: usize,
+    pub terminal: usize,
+    pub kind: ConflictKind,
+    pub resolved: bool,
+    pub chosen: Action
+}
+
+/// LALR(1)オートマトンと構文解析表
+///
+/// # members
+/// - kernels: Vec<BTreeMap<Item, BTreeSet<usize>>> => 各状態の核項と先読み集合
+/// - transitions: Vec<BTreeMap<Sym, usize>> => 状態遷移
+/// - table: LrTable => 構文解析表
+/// - conflicts: Vec<Conflict> => 衝突の一覧
+pub struct Lalr {
+    pub kernels: Vec<BTreeMap<Item, BTreeSet<usize>>>,
+    pub transitions: Vec<BTreeMap<Sym, usize>>,
+    pub table: LrTable,
+    pub conflicts: Vec<Conflict>
+}
+
+impl Lalr {
+    /// 文法からLALR(1)構文解析表を構築する
+    /// 核項の先読みを自発的生成と伝播によって求める(4.7.5節)
+    ///
+    /// # params
+    /// - grammar: &Grammar => 文法
+    ///
+    /// # returns
+    /// - Lalr
+    pub fn build(grammar: &Grammar) -> Lalr {
+        let first = grammar.first_sets();
+        let nullable = grammar.nullable();
+        let (kernels, transitions) = Self::lr0(grammar);
+
+        // 自発的に生成される先読みと伝播経路を調べる
+        let mut lookaheads: Vec<BTreeMap<Item, BTreeSet<usize>>> = kernels.iter()
+            .map(|k| k.iter().map(|i| (*i, BTreeSet::new())).collect())
+            .collect();
+        let mut propagate: HashMap<(usize, Item), Vec<(usize, Item)>> = HashMap::new();
+        lookaheads[0].get_mut(&(0, 0)).unwrap().insert(0);
+        for (state, kernel) in kernels.iter().enumerate() {
+            for item in kernel {
+                let mut seed = BTreeMap::new();
+                seed.insert(*item, [PROPAGATE].iter().cloned().collect());
+                for ((prod, dot), las) in Self::closure(grammar, seed, &first, &nullable) {
+                    let rhs = &grammar.productions[prod].rhs;
+                    if dot >= rhs.len() {
+                        continue;
+                    }
+                    let target = transitions[state][&rhs[dot]];
+                    for la in las {
+                        if la == PROPAGATE {
+                            propagate.entry((state, *item)).or_default().push((target, (prod, dot+1)));
+                        } else {
+                            lookaheads[target].get_mut(&(prod, dot+1)).unwrap().insert(la);
+                        }
+                    }
+                }
+            }
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (from, tos) in &propagate {
+                let las = lookaheads[from.0][&from.1].clone();
+                for (state, item) in tos {
+                    let set = lookaheads[*state].get_mut(item).unwrap();
+                    let before = set.len();
+                    set.extend(las.iter().cloned());
+                    changed |= set.len() != before;
+                }
+            }
+        }
+
+        let mut lalr = Lalr {
+            kernels: lookaheads,
+            transitions,
+            table: LrTable {
+                terminals: grammar.terminals.clone(),
+                action: vec![],
+                goto: vec![],
+                productions: grammar.productions.iter().map(|p| (p.lhs, p.rhs.len())).collect()
+            },
+            conflicts: vec![]
+        };
+        for state in 0..lalr.kernels.len() {
+            Self::fill_row(&mut lalr, grammar, state, &first, &nullable);
+        }
+        lalr
+    }
+
+    /// 衝突のうち、優先順位宣言で解消されなかったものの数を(シフト/還元, 還元/還元)で返す
+    ///
+    /// # returns
+    /// - (usize, usize)
+    pub fn unresolved(&self) -> (usize, usize) {
+        let sr = self.conflicts.iter()
+            .filter(|c| !c.resolved && matches!(c.kind, ConflictKind::ShiftReduce { .. }))
+            .count();
+        let rr = self.conflicts.iter()
+            .filter(|c| !c.resolved && matches!(c.kind, ConflictKind::ReduceReduce { .. }))
+            .count();
+        (sr, rr)
+    }
+
+    /// yaccのy.outputに相当する報告を返す
+    ///
+    /// # params
+    /// - grammar: &Grammar => 構築に用いた文法
+    ///
+    /// # returns
+    /// - String
+    pub fn report(&self, grammar: &Grammar) -> String {
+        let mut out = String::new();
+        let mut per_state: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+        for c in self.conflicts.iter().filter(|c| !c.resolved) {
+            let entry = per_state.entry(c.state).or_insert((0, 0));
+            match c.kind {
+                ConflictKind::ShiftReduce { .. } => entry.0 += 1,
+                ConflictKind::ReduceReduce { .. } => entry.1 += 1
+            }
+        }
+        for (state, (sr, rr)) in &per_state {
+            let mut kinds = vec![];
+            if *sr > 0 { kinds.push(format!("{} shift/reduce", sr)); }
+            if *rr > 0 { kinds.push(format!("{} reduce/reduce", rr)); }
+            writeln!(out, "State {} conflicts: {}", state, kinds.join(", ")).unwrap();
+        }
+        if !per_state.is_empty() {
+            writeln!(out).unwrap();
+        }
+
+        writeln!(out, "Grammar\n").unwrap();
+        write!(out, "{}", grammar).unwrap();
+
+        for (state, kernel) in self.kernels.iter().enumerate() {
+            writeln!(out, "\nstate {}\n", state).unwrap();
+            for ((prod, dot), las) in kernel {
+                let p = &grammar.productions[*prod];
+                let mut rhs: Vec<&str> = p.rhs.iter().map(|s| grammar.name(*s)).collect();
+                rhs.insert(*dot, ".");
+                write!(out, "    {} -> {}", grammar.nonterminals[p.lhs], rhs.join(" ")).unwrap();
+                if *dot == p.rhs.len() {
+                    let las: Vec<&str> = las.iter().map(|t| &grammar.terminals[*t][..]).collect();
+                    write!(out, "  [{}]", las.join(", ")).unwrap();
+                }
+                writeln!(out).unwrap();
+            }
+            writeln!(out).unwrap();
+            for (terminal, action) in self.table.action[state].iter().enumerate() {
+                let name = &grammar.terminals[terminal];
+                match action {
+                    Action::Shift(next) => writeln!(out, "    {:<12} shift, and go to state {}", name, next).unwrap(),
+                    Action::Reduce(prod) => writeln!(out, "    {:<12} reduce using rule {} ({})", name, prod, grammar.production_to_string(*prod)).unwrap(),
+                    Action::Accept => writeln!(out, "    {:<12} accept", name).unwrap(),
+                    Action::Error => {}
+                }
+            }
+            for (nonterminal, goto) in self.table.goto[state].iter().enumerate() {
+                if let Some(next) = goto {
+                    writeln!(out, "    {:<12} go to state {}", grammar.nonterminals[nonterminal], next).unwrap();
+                }
+            }
+            for c in self.conflicts.iter().filter(|c| c.state == state) {
+                let name = &grammar.terminals[c.terminal];
+                match (&c.kind, c.resolved) {
+                    (ConflictKind::ShiftReduce { reduce, .. }, true) => {
+                        let how = match c.chosen {
+                            Action::Shift(_) => "shift",
+                            Action::Reduce(_) => "reduce",
+                            _ => "an error"
+                        };
+                        writeln!(out, "    Conflict between rule {} and token {} resolved as {}.", reduce, name, how).unwrap();
+                    },
+                    (ConflictKind::ShiftReduce { reduce: rejected, .. }, false) |
+                    (ConflictKind::ReduceReduce { rejected, .. }, _) => {
+                        writeln!(out, "    {:<12} [reduce using rule {} ({})]", name, rejected, grammar.production_to_string(*rejected)).unwrap();
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// LR(0)項集合の核と遷移を求める
+    ///
+    /// # params
+    /// - grammar: &Grammar => 文法
+    ///
+    /// # returns
+    /// - (Vec<BTreeSet<Item>>, Vec<BTreeMap<Sym, usize>>)
+    fn lr0(grammar: &Grammar) -> (Vec<BTreeSet<Item>>, Vec<BTreeMap<Sym, usize>>) {
+        let mut kernels: Vec<BTreeSet<Item>> = vec![[(0, 0)].iter().cloned().collect()];
+        let mut transitions: Vec<BTreeMap<Sym, usize>> = vec![];
+        let mut index: HashMap<BTreeSet<Item>, usize> = HashMap::new();
+        index.insert(kernels[0].clone(), 0);
+        let mut state = 0;
+        while state < kernels.len() {
+            let mut moves: BTreeMap<Sym, BTreeSet<Item>> = BTreeMap::new();
+            for (prod, dot) in Self::closure0(grammar, &kernels[state]) {
+                if let Some(sym) = grammar.productions[prod].rhs.get(dot) {
+                    moves.entry(*sym).or_default().insert((prod, dot+1));
+                }
+            }
+            let mut trans = BTreeMap::new();
+            for (sym, kernel) in moves {
+                let next = match index.get(&kernel) {
+                    Some(next) => *next,
+                    None => {
+                        kernels.push(kernel.clone());
+                        index.insert(kernel, kernels.len()-1);
+                        kernels.len()-1
+                    }
+                };
+                trans.insert(sym, next);
+            }
+            transitions.push(trans);
+            state += 1;
+        }
+        (kernels, transitions)
+    }
+
+    /// LR(0)項集合の閉包を求める
+    ///
+    /// # params
+    /// - grammar: &Grammar => 文法
+    /// - kernel: &BTreeSet<Item> => 核項
+    ///
+    /// # returns
+    /// - BTreeSet<Item>
+    fn closure0(grammar: &Grammar, kernel: &BTreeSet<Item>) -> BTreeSet<Item> {
+        let mut items = kernel.clone();
+        let mut work: Vec<Item> = kernel.iter().cloned().collect();
+        while let Some((prod, dot)) = work.pop() {
+            if let Some(Sym::N(n)) = grammar.productions[prod].rhs.get(dot) {
+                for (idx, p) in grammar.productions.iter().enumerate() {
+                    if p.lhs == *n && items.insert((idx, 0)) {
+                        work.push((idx, 0));
+                    }
+                }
+            }
+        }
+        items
+    }
+
+    /// LR(1)項集合の閉包を求める
+    ///
+    /// # params
+    /// - grammar: &Grammar => 文法
+    /// - items: BTreeMap<Item, BTreeSet<usize>> => 項と先読み集合
+    /// - first: &[BTreeSet<usize>] => FIRST集合
+    /// - nullable: &[bool] => 空列を導出可能か
+    ///
+    /// # returns
+    /// - BTreeMap<Item, BTreeSet<usize>>
+    fn closure(grammar: &Grammar, mut items: BTreeMap<Item, BTreeSet<usize>>,
+               first: &[BTreeSet<usize>], nullable: &[bool]) -> BTreeMap<Item, BTreeSet<usize>> {
+        let mut work: Vec<Item> = items.keys().cloned().collect();
+        while let Some((prod, dot)) = work.pop() {
+            let rhs = &grammar.productions[prod].rhs;
+            if let Some(Sym::N(n)) = rhs.get(dot) {
+                let las = Grammar::first_of(&rhs[dot+1..], &items[&(prod, dot)], first, nullable);
+                for (idx, p) in grammar.productions.iter().enumerate() {
+                    if p.lhs != *n {
+                        continue;
+                    }
+                    let set = items.entry((idx, 0)).or_default();
+                    let before = set.len();
+                    set.extend(las.iter().cloned());
+                    if set.len() != before || before == 0 {
+                        work.push((idx, 0));
+                    }
+                }
+            }
+        }
+        items
+    }
+
+    /// 1状態分の動作表と行き先表を埋める
+    ///
+    /// # params
+    /// - lalr: &mut Lalr => 構築中の表
+    /// - grammar: &Grammar => 文法
+    /// - state: usize => 状態
+    /// - first: &[BTreeSet<usize>] => FIRST集合
+    /// - nullable: &[bool] => 空列を導出可能か
+    fn fill_row(lalr: &mut Lalr, grammar: &Grammar, state: usize, first: &[BTreeSet<usize>], nullable: &[bool]) {
+        let mut shifts: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut reduces: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        let mut goto = vec![None; grammar.nonterminals.len()];
+        for (sym, next) in &lalr.transitions[state] {
+            match sym {
+                Sym::T(t) => { shifts.insert(*t, *next); },
+                Sym::N(n) => goto[*n] = Some(*next)
+            }
+        }
+        for ((prod, dot), las) in Self::closure(grammar, lalr.kernels[state].clone(), first, nullable) {
+            if dot == grammar.productions[prod].rhs.len() {
+                for la in las {
+                    reduces.entry(la).or_default().push(prod);
+                }
+            }
+        }
+
+        let mut row = vec![Action::Error; grammar.terminals.len()];
+        for (t, next) in &shifts {
+            row[*t] = Action::Shift(*next);
+        }
+        for (t, mut prods) in reduces {
+            prods.sort_unstable();
+            prods.dedup();
+            // 受理(拡大文法の規則0)は他の還元より優先するが、捨てた規則は衝突として報告する
+            let reduce = prods[0];
+            let action = if reduce == 0 { Action::Accept } else { Action::Reduce(reduce) };
+            for rejected in &prods[1..] {
+                lalr.conflicts.push(Conflict {
+                    state, terminal: t,
+                    kind: ConflictKind::ReduceReduce { chosen: reduce, rejected: *rejected },
+                    resolved: false,
+                    chosen: action
+                });
+            }
+            if reduce == 0 {
+                row[t] = action;
+                continue;
+            }
+            row[t] = match shifts.get(&t) {
+                None => Action::Reduce(reduce),
+                Some(next) => {
+                    let (chosen, resolved) = Self::resolve(grammar, t, *next, reduce);
+                    lalr.conflicts.push(Conflict {
+                        state, terminal: t,
+                        kind: ConflictKind::ShiftReduce { shift: *next, reduce },
+                        resolved,
+                        chosen
+                    });
+                    chosen
+                }
+            };
+        }
+        lalr.table.action.push(row);
+        lalr.table.goto.push(goto);
+    }
+
+    /// シフト/還元衝突を優先順位と結合性で解消する
+    /// 解消できなければyaccと同様にシフトを選ぶ
+    ///
+    /// # params
+    /// - grammar: &Grammar => 文法
+    /// - terminal: usize => 先読み記号
+    /// - shift: usize => シフト先状態
+    /// - reduce: usize => 還元規則
+    ///
+    /// # returns
+    /// - (Action, bool) => (採用した動作, 解消できたか)
+    fn resolve(grammar: &Grammar, terminal: usize, shift: usize, reduce: usize) -> (Action, bool) {
+        match (grammar.precedence[terminal], grammar.production_precedence(reduce)) {
+            (Some((tlevel, assoc)), Some((plevel, _))) => {
+                if plevel > tlevel {
+                    (Action::Reduce(reduce), true)
+                } else if plevel < tlevel {
+                    (Action::Shift(shift), true)
+                } else {
+                    match assoc {
+                        Assoc::Left => (Action::Reduce(reduce), true),
+                        Assoc::Right => (Action::Shift(shift), true),
+                        Assoc::NonAssoc => (Action::Error, true)
+                    }
+                }
+            },
+            _ => (Action::Shift(shift), false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConflictKind, Lalr};
+    use super::super::grammar::{Assoc, Grammar, Sym};
+    use super::super::lr::Action;
+
+    // E -> E + E | E * E | n
+    fn ambiguous(with_prec: bool) -> Grammar {
+        let mut g = Grammar::new();
+        let e = g.nonterminal("E");
+        let plus = g.terminal("'+'");
+        let times = g.terminal("'*'");
+        let n = g.terminal("n");
+        if with_prec {
+            g.set_precedence(plus, 1, Assoc::Left);
+            g.set_precedence(times, 2, Assoc::Left);
+        }
+        g.add_production(e, vec![Sym::N(e), Sym::T(plus), Sym::N(e)], None, None);
+        g.add_production(e, vec![Sym::N(e), Sym::T(times), Sym::N(e)], None, None);
+        g.add_production(e, vec![Sym::T(n)], None, None);
+        g
+    }
+
+    #[test]
+    fn lalr_conflict_test() {
+        let g = ambiguous(false);
+        let lalr = Lalr::build(&g);
+        assert_eq!((4, 0), lalr.unresolved());
+        assert!(lalr.report(&g).contains("shift/reduce"));
+    }
+
+    #[test]
+    fn lalr_accept_conflict_test() {
+        // S -> S | n では、S' -> S . と S -> S . が $end で衝突する
+        let mut g = Grammar::new();
+        let s = g.nonterminal("S");
+        let n = g.terminal("n");
+        g.add_production(s, vec![Sym::N(s)], None, None);
+        g.add_production(s, vec![Sym::T(n)], None, None);
+        let lalr = Lalr::build(&g);
+        assert_eq!((0, 1), lalr.unresolved());
+        let c = &lalr.conflicts[0];
+        assert_eq!(ConflictKind::ReduceReduce { chosen: 0, rejected: 1 }, c.kind);
+        assert_eq!((Action::Accept, Action::Accept), (c.chosen, lalr.table.action[c.state][c.terminal]));
+        assert!(lalr.report(&g).contains("1 reduce/reduce"));
+    }
+
+    #[test]
+    fn lalr_precedence_test() {
+        let g = ambiguous(true);
+        let lalr = Lalr::build(&g);
+        assert_eq!((0, 0), lalr.unresolved());
+        // E + E . * => shift, E * E . + => reduce
+        let plus = g.find_terminal("'+'").unwrap();
+        let times = g.find_terminal("'*'").unwrap();
+        for row in &lalr.table.action {
+            if let Action::Reduce(1) = row[0] {
+                assert!(matches!(row[times], Action::Shift(_)));
+                assert_eq!(Action::Reduce(1), row[plus]);
+            }
+            if let Action::Reduce(2) = row[0] {
+                assert_eq!(Action::Reduce(2), row[times]);
+            }
+        }
+    }
+
+    #[test]
+    fn lalr_lookahead_test() {
+        // 4.7.5節の例: S -> L = R | R ; L -> * R | id ; R -> L
+        let mut g = Grammar::new();
+        let s = g.nonterminal("S");
+        let l = g.nonterminal("L");
+        let r = g.nonterminal("R");
+        let eq = g.terminal("'='");
+        let star = g.terminal("'*'");
+        let id = g.terminal("id");
+        g.add_production(s, vec![Sym::N(l), Sym::T(eq), Sym::N(r)], None, None);
+        g.add_production(s, vec![Sym::N(r)], None, None);
+        g.add_production(l, vec![Sym::T(star), Sym::N(r)], None, None);
+        g.add_production(l, vec![Sym::T(id)], None, None);
+        g.add_production(r, vec![Sym::N(l)], None, None);
+        let lalr = Lalr::build(&g);
+        assert_eq!(10, lalr.kernels.len());
+        assert!(lalr.conflicts.is_empty());
+    }
+}
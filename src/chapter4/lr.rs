@@ -0,0 +1,434 @@
+use super::super::chapter2::diagnostic::Diagnostic;
+use super::super::chapter2::token::{Span, Tag, Token};
+use super::super::chapter2::parsetree::ParseTree;
+use super::grammar::{Grammar, END, ERROR};
+
+/// LR構文解析表の動作
+///
+/// # members
+/// - Shift(usize) => 指定状態へシフト
+/// - Reduce(usize) => 指定規則で還元
+/// - Accept => 受理
+/// - Error => エラー
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+    Error
+}
+
+/// LR構文解析表
+///
+/// # members
+/// - terminals: Vec<String> => 終端記号名(エラーメッセージ用)
+/// - action: Vec<Vec<Action>> => action[状態][終端記号]
+/// - goto: Vec<Vec<Option<usize>>> => goto[状態][非終端記号]
+/// - productions: Vec<(usize, usize)> => 各規則の(左辺, 右辺の長さ)
+///
+/// # derive
+/// - Clone
+#[derive(Clone)]
+pub struct LrTable {
+    pub terminals: Vec<String>,
+    pub action: Vec<Vec<Action>>,
+    pub goto: Vec<Vec<Option<usize>>>,
+    pub productions: Vec<(usize, usize)>
+}
+
+impl LrTable {
+    /// 疎な表現から構文解析表を生成して返す
+    /// yaccが生成するコードから呼ばれることを想定している
+    ///
+    /// # params
+    /// - states: usize => 状態数
+    /// - terminals: &[&str] => 終端記号名
+    /// - nonterminals: usize => 非終端記号数
+    /// - actions: &[(usize, usize, Action)] => (状態, 終端記号, 動作)
+    /// - gotos: &[(usize, usize, usize)] => (状態, 非終端記号, 遷移先)
+    /// - productions: &[(usize, usize)] => (左辺, 右辺の長さ)
+    ///
+    /// # returns
+    /// - LrTable
+    pub fn from_entries(states: usize, terminals: &[&str], nonterminals: usize,
+                        actions: &[(usize, usize, Action)], gotos: &[(usize, usize, usize)],
+                        productions: &[(usize, usize)]) -> LrTable {
+        let mut action = vec![vec![Action::Error; terminals.len()]; states];
+        let mut goto = vec![vec![None; nonterminals]; states];
+        for (s, t, a) in actions {
+            action[*s][*t] = *a;
+        }
+        for (s, n, g) in gotos {
+            goto[*s][*n] = Some(*g);
+        }
+        LrTable {
+            terminals: terminals.iter().map(|t| t.to_string()).collect(),
+            action,
+            goto,
+            productions: productions.to_vec()
+        }
+    }
+
+    /// 指定状態でエラーとならない終端記号の一覧を返す
+    ///
+    /// # params
+    /// - state: usize => 状態
+    ///
+    /// # returns
+    /// - Vec<usize>
+    pub fn expected(&self, state: usize) -> Vec<usize> {
+        (0..self.terminals.len()).filter(|t| self.action[state][*t] != Action::Error).collect()
+    }
+}
+
+/// 句レベル回復で試す修復
+///
+/// # members
+/// - Insert(usize) => 終端記号を挿入
+/// - Delete => 先読みを削除
+/// - Replace(usize) => 先読みを終端記号で置換
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Repair {
+    Insert(usize),
+    Delete,
+    Replace(usize)
+}
+
+/// 修復の妥当性を確かめるために読み進めるトークン数
+const REPAIR_WINDOW: usize = 3;
+
+/// 表駆動のLR構文解析器
+/// 構文エラーに出会うと、句レベル回復(最小の挿入・削除・置換)を試み、
+/// それが失敗した場合は`error`をシフトできる状態までスタックを巻き戻す(パニックモード)
+/// パニックモードの後、トークンをシフトする前に出会った誤りでは先読みを捨てる
+///
+/// # members
+/// - table: &LrTable => 構文解析表
+pub struct LrParser<'a> {
+    table: &'a LrTable
+}
+
+impl<'a> LrParser<'a> {
+    /// LrParserを生成して返す
+    ///
+    /// # params
+    /// - table: &LrTable => 構文解析表
+    ///
+    /// # returns
+    /// - LrParser
+    pub fn new(table: &'a LrTable) -> LrParser<'a> {
+        LrParser { table }
+    }
+
+    /// トークン列を構文解析し、開始記号の意味値を返す
+    /// `error`トークンの意味値は、lexemeが"error"のToken::Wordをshiftに渡して求める
+    ///
+    /// # params
+    /// - tokens: I => トークンと位置の列
+    /// - classify: C => トークンを終端記号番号へ分類する関数
+    /// - shift: S => シフトしたトークンとその位置から意味値を求める関数
+    /// - reduce: R => 規則番号と右辺の意味値から左辺の意味値を求める関数
+    ///
+    /// # returns
+    /// - Result<V, Vec<Diagnostic>>
+    pub fn parse<V, I, C, S, R>(&self, tokens: I, classify: C, mut shift: S, mut reduce: R) -> Result<V, Vec<Diagnostic>>
+    where
+        I: IntoIterator<Item = (Token, Span)>,
+        C: Fn(&Token) -> Option<usize>,
+        S: FnMut(&Token, Span) -> V,
+        R: FnMut(usize, Vec<V>) -> V
+    {
+        let mut input: Vec<(Token, Span)> = tokens.into_iter().collect();
+        let eof = input.last().map(|(_, span)| Span::new(span.line, span.col+span.end-span.start, span.end, span.end))
+            .unwrap_or_default();
+        let mut terminals: Vec<Option<usize>> = input.iter().map(|(t, _)| classify(t)).collect();
+        let mut pos = 0;
+        let mut states = vec![0];
+        let mut values: Vec<V> = vec![];
+        let mut diagnostics = vec![];
+        // パニックモードで`error`をシフトしてから、まだトークンをシフトしていないか
+        let mut recovering = false;
+        loop {
+            let span = input.get(pos).map(|(_, span)| *span).unwrap_or(eof);
+            let state = *states.last().unwrap();
+            let action = match terminals.get(pos) {
+                Some(Some(t)) => self.table.action[state][*t],
+                Some(None) => Action::Error,
+                None => self.table.action[state][END]
+            };
+            match action {
+                Action::Shift(next) => {
+                    values.push(shift(&input[pos].0, input[pos].1));
+                    states.push(next);
+                    pos += 1;
+                    recovering = false;
+                },
+                Action::Reduce(prod) => {
+                    let (lhs, len) = self.table.productions[prod];
+                    states.truncate(states.len()-len);
+                    let rhs = values.split_off(values.len()-len);
+                    values.push(reduce(prod, rhs));
+                    let top = *states.last().unwrap();
+                    match self.table.goto[top][lhs] {
+                        Some(next) => states.push(next),
+                        None => {
+                            diagnostics.push(Diagnostic::error(span, "broken goto table".to_string()));
+                            return Err(diagnostics);
+                        }
+                    }
+                },
+                Action::Accept => {
+                    return match values.pop() {
+                        Some(value) if diagnostics.is_empty() => Ok(value),
+                        _ => Err(diagnostics)
+                    };
+                },
+                // yaccと同様に、回復後にトークンを1つもシフトしないうちの誤りでは、報告せずに先読みを捨てる
+                Action::Error if recovering => {
+                    if pos == input.len() {
+                        return Err(diagnostics);
+                    }
+                    pos += 1;
+                },
+                Action::Error => {
+                    let found = match input.get(pos) {
+                        Some((token, _)) => format!("`{}`", token),
+                        None => "end of input".to_string()
+                    };
+                    let message = format!("syntax error: unexpected {}", found);
+
+                    // 句レベル回復
+                    if let Some(repair) = self.find_repair(&states, &terminals[pos..]) {
+                        let note = match repair {
+                            Repair::Insert(t) => format!("inserted missing {}", self.display(t)),
+                            Repair::Delete => format!("deleted unexpected {}", found),
+                            Repair::Replace(t) => format!("replaced {} with {}", found, self.display(t))
+                        };
+                        diagnostics.push(Diagnostic::error(span, format!("{}; {}", message, note)));
+                        match repair {
+                            Repair::Insert(t) => {
+                                input.insert(pos, (Self::synthesize(&self.table.terminals[t]).unwrap(), Span::new(span.line, span.col, span.start, span.start)));
+                                terminals.insert(pos, Some(t));
+                            },
+                            Repair::Delete => {
+                                input.remove(pos);
+                                terminals.remove(pos);
+                            },
+                            Repair::Replace(t) => {
+                                input[pos] = (Self::synthesize(&self.table.terminals[t]).unwrap(), span);
+                                terminals[pos] = Some(t);
+                            }
+                        }
+                        continue;
+                    }
+
+                    // パニックモード回復
+                    let depth = states.iter().rposition(|s| matches!(self.table.action[*s][ERROR], Action::Shift(_)));
+                    let expected: Vec<String> = self.table.expected(state).iter()
+                        .filter(|t| **t != ERROR)
+                        .map(|t| self.display(*t))
+                        .collect();
+                    diagnostics.push(Diagnostic::error(span, format!("{}, expected one of {}", message, expected.join(", "))));
+                    match depth {
+                        Some(depth) => {
+                            states.truncate(depth+1);
+                            values.truncate(depth);
+                            if let Action::Shift(next) = self.table.action[states[depth]][ERROR] {
+                                values.push(shift(&Token::new_word(Tag::Symbol, "error"), span));
+                                states.push(next);
+                                recovering = true;
+                            }
+                            // 回復後の状態で受理できるトークンまで読み飛ばす
+                            let state = *states.last().unwrap();
+                            while pos < input.len() && terminals[pos].map(|t| self.table.action[state][t]).unwrap_or(Action::Error) == Action::Error {
+                                pos += 1;
+                            }
+                            if pos == input.len() && self.table.action[state][END] == Action::Error {
+                                return Err(diagnostics);
+                            }
+                        },
+                        None if pos < input.len() => pos += 1,
+                        None => return Err(diagnostics)
+                    }
+                }
+            }
+        }
+    }
+
+    /// トークン列を構文解析し、構文解析木を返す
+    /// 内部節の名前には文法の非終端記号名を用いる
+    ///
+    /// # params
+    /// - grammar: &Grammar => 構文解析表の元になった文法
+    /// - tokens: I => トークンと位置の列
+    ///
+    /// # returns
+    /// - Result<ParseTree, Vec<Diagnostic>>
+    pub fn parse_tree<I>(&self, grammar: &Grammar, tokens: I) -> Result<ParseTree, Vec<Diagnostic>>
+    where
+        I: IntoIterator<Item = (Token, Span)>
+    {
+        self.parse(tokens, |t| grammar.classify(t),
+            |token, span| ParseTree::leaf(token.clone(), span),
+            |prod, children| ParseTree::node(&grammar.nonterminals[grammar.productions[prod].lhs], children))
+    }
+
+    /// 挿入・削除・置換の順に、後続のトークンを読み進められる修復を探す
+    ///
+    /// # params
+    /// - states: &[usize] => 状態スタック
+    /// - rest: &[Option<usize>] => 未読の終端記号列
+    ///
+    /// # returns
+    /// - Option<Repair>
+    fn find_repair(&self, states: &[usize], rest: &[Option<usize>]) -> Option<Repair> {
+        let window = |from: usize| -> Vec<Option<usize>> {
+            let mut seq: Vec<Option<usize>> = rest.iter().skip(from).take(REPAIR_WINDOW).cloned().collect();
+            if seq.len() < REPAIR_WINDOW {
+                seq.push(Some(END));
+            }
+            seq
+        };
+        let candidates: Vec<usize> = (0..self.table.terminals.len())
+            .filter(|t| Self::synthesize(&self.table.terminals[*t]).is_some())
+            .collect();
+        for t in &candidates {
+            let mut seq = vec![Some(*t)];
+            seq.extend(window(0));
+            if self.simulate(states, &seq) {
+                return Some(Repair::Insert(*t));
+            }
+        }
+        if !rest.is_empty() && self.simulate(states, &window(1)) {
+            return Some(Repair::Delete);
+        }
+        if !rest.is_empty() {
+            for t in &candidates {
+                let mut seq = vec![Some(*t)];
+                seq.extend(window(1));
+                if self.simulate(states, &seq) {
+                    return Some(Repair::Replace(*t));
+                }
+            }
+        }
+        None
+    }
+
+    /// 状態スタックのみを用いて終端記号列を読み、エラーなく読み切れるかを返す
+    ///
+    /// # params
+    /// - states: &[usize] => 状態スタック
+    /// - seq: &[Option<usize>] => 終端記号列
+    ///
+    /// # returns
+    /// - bool
+    fn simulate(&self, states: &[usize], seq: &[Option<usize>]) -> bool {
+        let mut states = states.to_vec();
+        for terminal in seq {
+            let terminal = match terminal {
+                Some(t) => *t,
+                None => return false
+            };
+            loop {
+                match self.table.action[*states.last().unwrap()][terminal] {
+                    Action::Shift(next) => { states.push(next); break; },
+                    Action::Reduce(prod) => {
+                        let (lhs, len) = self.table.productions[prod];
+                        states.truncate(states.len()-len);
+                        match self.table.goto[*states.last().unwrap()][lhs] {
+                            Some(next) => states.push(next),
+                            None => return false
+                        }
+                    },
+                    Action::Accept => return true,
+                    Action::Error => return false
+                }
+            }
+        }
+        true
+    }
+
+    /// 終端記号名を表示用の形にする('x' => `x`)
+    fn display(&self, terminal: usize) -> String {
+        let name = &self.table.terminals[terminal];
+        match Self::synthesize(name) {
+            Some(token) => format!("`{}`", token),
+            None => name.clone()
+        }
+    }
+
+    /// リテラルの終端記号('x')から挿入用のトークンを作る
+    fn synthesize(name: &str) -> Option<Token> {
+        if name.len() > 2 && name.starts_with('\'') && name.ends_with('\'') {
+            Some(Token::new_word(Tag::Symbol, &name[1..name.len()-1]))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, LrParser, LrTable};
+    use super::super::super::chapter2::token::{Span, Tag, Token};
+
+    // 0: $accept -> E   1: E -> E + n   2: E -> n
+    fn sample_table() -> LrTable {
+        LrTable::from_entries(5, &["$end", "error", "'+'", "n"], 1,
+            &[
+                (0, 3, Action::Shift(2)),
+                (1, 0, Action::Accept), (1, 2, Action::Shift(3)),
+                (2, 0, Action::Reduce(2)), (2, 2, Action::Reduce(2)),
+                (3, 3, Action::Shift(4)),
+                (4, 0, Action::Reduce(1)), (4, 2, Action::Reduce(1))
+            ],
+            &[(0, 0, 1)],
+            &[(0, 1), (0, 3), (0, 1)])
+    }
+
+    fn classify(token: &Token) -> Option<usize> {
+        match token {
+            Token::NumI32 { .. } => Some(3),
+            Token::Word { lexeme, .. } if lexeme == "+" => Some(2),
+            _ => None
+        }
+    }
+
+    fn spanned(tokens: Vec<Token>) -> Vec<(Token, Span)> {
+        tokens.into_iter().enumerate().map(|(i, t)| (t, Span::new(1, i+1, i, i+1))).collect()
+    }
+
+    #[test]
+    fn lrparser_simple_test() {
+        let table = sample_table();
+        let tokens = spanned(vec![Token::new_numi32(1), Token::new_word(Tag::Symbol, "+"), Token::new_numi32(2)]);
+        let result = LrParser::new(&table).parse(tokens, classify,
+            |t, _| match t { Token::NumI32 { num } => *num, _ => 0 },
+            |prod, v| match prod { 1 => v[0]+v[2], _ => v[0] });
+        assert_eq!(Ok(3), result);
+    }
+
+    #[test]
+    fn lrparser_error_test() {
+        let table = sample_table();
+        let tokens = spanned(vec![Token::new_numi32(1), Token::new_numi32(2)]);
+        let diags = LrParser::new(&table).parse(tokens, classify, |_, _| 0, |_, _| 0).unwrap_err();
+        assert_eq!(1, diags.len());
+        assert_eq!("syntax error: unexpected `2`; inserted missing `+`", diags[0].message);
+        assert_eq!(Span::new(1, 2, 1, 2), diags[0].span);
+
+        let tokens = spanned(vec![Token::new_numi32(1), Token::new_numi32(2), Token::new_numi32(3)]);
+        let diags = LrParser::new(&table).parse(tokens, classify, |_, _| 0, |_, _| 0).unwrap_err();
+        assert_eq!("syntax error: unexpected `2`; replaced `2` with `+`", diags[0].message);
+    }
+}
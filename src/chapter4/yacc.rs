@@ -0,0 +1,684 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::super::chapter2::token::{Tag, Token};
+use super::grammar::{Assoc, Grammar, Sym};
+use super::lalr::Lalr;
+use super::lr::Action;
+
+/// yacc風の文法記述を読み込み、構文解析器のRustコードを生成する
+///
+/// 記述は宣言部・規則部・追加コード部を`%%`で区切る
+/// - `%token A B` => 終端記号の宣言
+/// - `%left`, `%right`, `%nonassoc` => 優先順位と結合性(後に書いたものほど強い)
+/// - `%start s` => 開始記号
+/// - `%value T` => 意味値の型
+/// - `%lexval { .. }` => トークン`token: &Token`から意味値を求める式
+/// - `%{ .. %}` => 生成コードの先頭にそのまま出力するコード
+/// - `a : b 'c' { $1 } | .. ;` => 規則と意味動作(`$$`と`$n`が使える)
+/// - `a : error ';' ;` => 予約済みの終端記号`error`によるエラー回復規則
+///
+/// 終端記号とトークンの対応は次の通り
+/// - `'+'` => lexemeが`+`のToken::Word
+/// - `ID`, `TYPE`, `PRIMARY`, `COMPARISON`, `SYMBOL` => 対応するTagを持つToken::Word
+/// - `NUMI32`, `NUMF32`, `NUM` => Token::NumI32, Token::NumF32, その両方
+///
+/// # members
+/// - grammar: Grammar => 読み込んだ文法
+/// - value_type: String => 意味値の型
+/// - lexval: Option<String> => トークンの意味値を求めるブロック
+/// - prologue: String => 生成コードの先頭に出力するコード
+/// - epilogue: String => 生成コードの末尾に出力するコード
+pub struct Yacc {
+    pub grammar: Grammar,
+    pub value_type: String,
+    pub lexval: Option<String>,
+    pub prologue: String,
+    pub epilogue: String
+}
+
+/// 規則部から読み込んだ1つの選択肢
+struct RawAlt {
+    rhs: Vec<String>,
+    prec: Option<String>,
+    action: Option<String>,
+    line: usize
+}
+
+/// 文法記述を1文字ずつ読み進める
+struct Reader {
+    src: Vec<char>,
+    pos: usize,
+    line: usize
+}
+
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        self.src.get(self.pos).cloned()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.src.get(self.pos+i) == Some(&c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    /// 空白・改行・コメントを読み飛ばす
+    /// inlineがtrueなら改行の手前で止まる
+    fn skip(&mut self, inline: bool) {
+        while let Some(c) = self.peek() {
+            if c == '\n' && inline {
+                break;
+            } else if c.is_whitespace() {
+                self.bump();
+            } else if self.starts_with("/*") {
+                while self.peek().is_some() && !self.starts_with("*/") {
+                    self.bump();
+                }
+                self.bump();
+                self.bump();
+            } else if self.starts_with("//") {
+                while self.peek().is_some() && self.peek() != Some('\n') {
+                    self.bump();
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn ident(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' => { word.push(c); self.bump(); },
+                _ => break
+            }
+        }
+        word
+    }
+
+    /// 'x' または "xx" を読み、'xx'の形に正規化して返す
+    fn literal(&mut self) -> Result<String, String> {
+        let quote = self.bump().unwrap();
+        let mut body = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => break,
+                Some('\n') | None => return Err(format!("line {}: unterminated literal", self.line)),
+                Some(c) => body.push(c)
+            }
+        }
+        Ok(format!("'{}'", body))
+    }
+
+    /// 対応の取れた { .. } を読んで返す
+    fn block(&mut self) -> Result<String, String> {
+        let start = self.line;
+        let mut depth = 0;
+        let mut code = String::new();
+        let mut in_string = false;
+        while let Some(c) = self.bump() {
+            code.push(c);
+            match c {
+                '\\' if in_string => { if let Some(n) = self.bump() { code.push(n); } },
+                '"' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(code);
+                    }
+                },
+                _ => {}
+            }
+        }
+        Err(format!("line {}: unclosed action block", start))
+    }
+
+    /// 記号名(識別子またはリテラル)を読む
+    fn symbol(&mut self) -> Result<Option<String>, String> {
+        match self.peek() {
+            Some('\'') | Some('"') => Ok(Some(self.literal()?)),
+            Some(c) if c.is_alphabetic() || c == '_' => Ok(Some(self.ident())),
+            _ => Ok(None)
+        }
+    }
+}
+
+impl Yacc {
+    /// 文法記述を読み込んで返す
+    ///
+    /// # params
+    /// - src: &str => 文法記述
+    ///
+    /// # returns
+    /// - Result<Yacc, String>
+    pub fn parse(src: &str) -> Result<Yacc, String> {
+        let mut reader = Reader { src: src.chars().collect(), pos: 0, line: 1 };
+        let mut yacc = Yacc {
+            grammar: Grammar::new(),
+            value_type: "()".to_string(),
+            lexval: None,
+            prologue: String::new(),
+            epilogue: String::new()
+        };
+        let mut start = None;
+        yacc.declarations(&mut reader, &mut start)?;
+        let rules = Self::rules(&mut reader)?;
+        reader.skip(false);
+        if reader.starts_with("%%") {
+            reader.pos += 2;
+            yacc.epilogue = reader.src[reader.pos..].iter().collect::<String>().trim().to_string();
+        } else if reader.peek().is_some() {
+            return Err(format!("line {}: unexpected character", reader.line));
+        }
+        yacc.build_grammar(rules, start)?;
+        Ok(yacc)
+    }
+
+    /// LALR(1)構文解析表を構築する
+    ///
+    /// # returns
+    /// - Lalr
+    pub fn build(&self) -> Lalr {
+        Lalr::build(&self.grammar)
+    }
+
+    /// トークンを終端記号番号へ分類する
+    /// 生成コード中のclassifyと同じ規則(Grammar::classify)に従う
+    ///
+    /// # params
+    /// - token: &Token => 分類するトークン
+    ///
+    /// # returns
+    /// - Option<usize>
+    pub fn classify(&self, token: &Token) -> Option<usize> {
+        self.grammar.classify(token)
+    }
+
+    /// 構文解析器のRustコードを生成する
+    ///
+    /// # params
+    /// - lalr: &Lalr => buildで構築した構文解析表
+    /// - crate_path: &str => 生成コードから見たdragonbookクレートのパス
+    ///
+    /// # returns
+    /// - Result<String, String>
+    pub fn generate(&self, lalr: &Lalr, crate_path: &str) -> Result<String, String> {
+        let table = &lalr.table;
+        let classify = self.generate_classify();
+        let mut out = String::new();
+        writeln!(out, "// dragonbook::chapter4::yacc によって生成されたコード").unwrap();
+        writeln!(out, "// 直接編集しないこと\n").unwrap();
+        writeln!(out, "use {}::chapter2::diagnostic::Diagnostic;", crate_path).unwrap();
+        writeln!(out, "use {}::chapter2::lexer::Lexer;", crate_path).unwrap();
+        if classify.contains("Tag::") {
+            writeln!(out, "use {}::chapter2::token::{{Span, Tag, Token}};", crate_path).unwrap();
+        } else {
+            writeln!(out, "use {}::chapter2::token::{{Span, Token}};", crate_path).unwrap();
+        }
+        writeln!(out, "use {}::chapter4::lr::{{Action, LrParser, LrTable}};\n", crate_path).unwrap();
+        if !self.prologue.is_empty() {
+            writeln!(out, "{}\n", self.prologue).unwrap();
+        }
+        writeln!(out, "/// 意味値の型").unwrap();
+        writeln!(out, "pub type Value = {};\n", self.value_type).unwrap();
+
+        // 構文解析表
+        writeln!(out, "/// 構文解析表を生成して返す").unwrap();
+        writeln!(out, "pub fn table() -> LrTable {{").unwrap();
+        let names: Vec<String> = self.grammar.terminals.iter().map(|t| format!("{:?}", t)).collect();
+        writeln!(out, "    LrTable::from_entries({}, &[{}], {},", table.action.len(), names.join(", "), self.grammar.nonterminals.len()).unwrap();
+        writeln!(out, "        &[").unwrap();
+        for (state, row) in table.action.iter().enumerate() {
+            let entries: Vec<String> = row.iter().enumerate()
+                .filter(|(_, a)| **a != Action::Error)
+                .map(|(t, a)| format!("({}, {}, Action::{:?})", state, t, a))
+                .collect();
+            if !entries.is_empty() {
+                writeln!(out, "            {},", entries.join(", ")).unwrap();
+            }
+        }
+        writeln!(out, "        ],").unwrap();
+        let gotos: Vec<String> = table.goto.iter().enumerate()
+            .flat_map(|(s, row)| row.iter().enumerate().filter_map(move |(n, g)| g.map(|g| format!("({}, {}, {})", s, n, g))))
+            .collect();
+        writeln!(out, "        &[{}],", gotos.join(", ")).unwrap();
+        let prods: Vec<String> = table.productions.iter().map(|(l, n)| format!("({}, {})", l, n)).collect();
+        writeln!(out, "        &[{}])", prods.join(", ")).unwrap();
+        writeln!(out, "}}\n").unwrap();
+
+        // トークンの意味値と分類
+        writeln!(out, "/// トークンの意味値を返す").unwrap();
+        match &self.lexval {
+            Some(block) => writeln!(out, "fn lexval(token: &Token) -> Value {}\n", block).unwrap(),
+            None => writeln!(out, "fn lexval(_token: &Token) -> Value {{\n    Default::default()\n}}\n").unwrap()
+        }
+        writeln!(out, "/// トークンを終端記号番号へ分類する").unwrap();
+        writeln!(out, "pub fn classify(token: &Token) -> Option<usize> {{").unwrap();
+        writeln!(out, "    match token {{\n{}        _ => None\n    }}\n}}\n", classify).unwrap();
+
+        // 意味動作
+        writeln!(out, "/// 規則prodで還元したときの意味動作").unwrap();
+        writeln!(out, "#[allow(clippy::clone_on_copy)]").unwrap();
+        writeln!(out, "fn reduce(prod: usize, __v: Vec<Value>) -> Value {{").unwrap();
+        writeln!(out, "    match prod {{").unwrap();
+        for (idx, p) in self.grammar.productions.iter().enumerate().skip(1) {
+            writeln!(out, "        // {}", self.grammar.production_to_string(idx)).unwrap();
+            let body = match &p.action {
+                Some(action) => Self::translate_action(action, p.rhs.len())?,
+                None if p.rhs.is_empty() => "Default::default()".to_string(),
+                None => "__v[0].clone()".to_string()
+            };
+            writeln!(out, "        {} => {},", idx, body).unwrap();
+        }
+        writeln!(out, "        _ => unreachable!()\n    }}\n}}\n").unwrap();
+
+        writeln!(out, "/// トークン列を構文解析し、開始記号の意味値を返す").unwrap();
+        writeln!(out, "pub fn parse<I: IntoIterator<Item = (Token, Span)>>(tokens: I) -> Result<Value, Vec<Diagnostic>> {{").unwrap();
+        writeln!(out, "    let table = table();").unwrap();
+        writeln!(out, "    LrParser::new(&table).parse(tokens, classify, |token, _| lexval(token), reduce)\n}}\n").unwrap();
+        writeln!(out, "/// プログラムを字句解析・構文解析し、開始記号の意味値を返す").unwrap();
+        writeln!(out, "pub fn parse_str(program: &str) -> Result<Value, Vec<Diagnostic>> {{").unwrap();
+        writeln!(out, "    let mut lexer = Lexer::new(program.to_string());").unwrap();
+        writeln!(out, "    let result = parse(std::iter::from_fn(|| lexer.scan_spanned()));").unwrap();
+        writeln!(out, "    // 字句解析の誤り(範囲外の整数など)も返す").unwrap();
+        writeln!(out, "    let mut diagnostics = lexer.take_diagnostics();").unwrap();
+        writeln!(out, "    match result {{").unwrap();
+        writeln!(out, "        Ok(value) if diagnostics.is_empty() => Ok(value),").unwrap();
+        writeln!(out, "        Ok(_) => Err(diagnostics),").unwrap();
+        writeln!(out, "        Err(errors) => {{\n            diagnostics.extend(errors);\n            Err(diagnostics)\n        }}").unwrap();
+        writeln!(out, "    }}\n}}").unwrap();
+        if !self.epilogue.is_empty() {
+            writeln!(out, "\n{}", self.epilogue).unwrap();
+        }
+        Ok(out)
+    }
+
+    /// 宣言部を読む
+    fn declarations(&mut self, reader: &mut Reader, start: &mut Option<String>) -> Result<(), String> {
+        let mut level = 0;
+        loop {
+            reader.skip(false);
+            if reader.peek().is_none() {
+                return Err("missing %% after declarations".to_string());
+            }
+            if reader.starts_with("%%") {
+                reader.pos += 2;
+                return Ok(());
+            }
+            if reader.starts_with("%{") {
+                reader.pos += 2;
+                let mut code = String::new();
+                while !reader.starts_with("%}") {
+                    code.push(reader.bump().ok_or("unclosed %{")?);
+                }
+                reader.pos += 2;
+                self.prologue.push_str(code.trim());
+                continue;
+            }
+            let line = reader.line;
+            if reader.bump() != Some('%') {
+                return Err(format!("line {}: expected declaration", line));
+            }
+            let directive = reader.ident();
+            match &directive[..] {
+                "token" | "left" | "right" | "nonassoc" => {
+                    let assoc = match &directive[..] {
+                        "left" => Some(Assoc::Left),
+                        "right" => Some(Assoc::Right),
+                        "nonassoc" => Some(Assoc::NonAssoc),
+                        _ => None
+                    };
+                    level += assoc.is_some() as usize;
+                    loop {
+                        reader.skip(true);
+                        match reader.symbol()? {
+                            Some(name) => {
+                                let t = self.grammar.terminal(&name);
+                                if let Some(assoc) = assoc {
+                                    self.grammar.set_precedence(t, level, assoc);
+                                }
+                            },
+                            None => break
+                        }
+                    }
+                },
+                "start" => {
+                    reader.skip(true);
+                    *start = Some(reader.ident());
+                },
+                "value" => {
+                    let mut ty = String::new();
+                    while reader.peek().is_some() && reader.peek() != Some('\n') {
+                        ty.push(reader.bump().unwrap());
+                    }
+                    self.value_type = ty.trim().to_string();
+                },
+                "lexval" => {
+                    reader.skip(false);
+                    if reader.peek() != Some('{') {
+                        return Err(format!("line {}: %lexval requires a block", line));
+                    }
+                    self.lexval = Some(reader.block()?);
+                },
+                _ => return Err(format!("line {}: unknown directive %{}", line, directive))
+            }
+        }
+    }
+
+    /// 規則部を読む
+    fn rules(reader: &mut Reader) -> Result<Vec<(String, Vec<RawAlt>)>, String> {
+        let mut rules = vec![];
+        loop {
+            reader.skip(false);
+            if reader.peek().is_none() || reader.starts_with("%%") {
+                return Ok(rules);
+            }
+            let line = reader.line;
+            let lhs = match reader.symbol()? {
+                Some(name) if !name.starts_with('\'') => name,
+                _ => return Err(format!("line {}: expected rule name", line))
+            };
+            reader.skip(false);
+            if reader.bump() != Some(':') {
+                return Err(format!("line {}: expected `:` after {}", line, lhs));
+            }
+            let mut alts = vec![];
+            let mut alt = RawAlt { rhs: vec![], prec: None, action: None, line };
+            loop {
+                reader.skip(false);
+                let line = reader.line;
+                match reader.peek() {
+                    Some('|') | Some(';') => {
+                        let end = reader.bump() == Some(';');
+                        alts.push(alt);
+                        alt = RawAlt { rhs: vec![], prec: None, action: None, line: reader.line };
+                        if end {
+                            break;
+                        }
+                    },
+                    Some('{') => {
+                        if alt.action.is_some() {
+                            return Err(format!("line {}: only one action is allowed at the end of a rule", line));
+                        }
+                        alt.action = Some(reader.block()?);
+                    },
+                    Some('%') => {
+                        reader.bump();
+                        if reader.ident() != "prec" {
+                            return Err(format!("line {}: expected %prec", line));
+                        }
+                        reader.skip(true);
+                        alt.prec = Some(reader.symbol()?.ok_or(format!("line {}: %prec requires a symbol", line))?);
+                    },
+                    None => return Err(format!("line {}: missing `;` after rule {}", line, lhs)),
+                    _ => {
+                        if alt.action.is_some() {
+                            return Err(format!("line {}: only one action is allowed at the end of a rule", line));
+                        }
+                        match reader.symbol()? {
+                            Some(name) => alt.rhs.push(name),
+                            None => return Err(format!("line {}: unexpected character `{}`", line, reader.peek().unwrap()))
+                        }
+                    }
+                }
+            }
+            rules.push((lhs, alts));
+        }
+    }
+
+    /// 読み込んだ規則から文法を構築する
+    fn build_grammar(&mut self, rules: Vec<(String, Vec<RawAlt>)>, start: Option<String>) -> Result<(), String> {
+        if rules.is_empty() {
+            return Err("no rules in grammar".to_string());
+        }
+        for (lhs, alts) in &rules {
+            if self.grammar.find_terminal(lhs).is_some() {
+                return Err(format!("line {}: token {} appears on the left-hand side of a rule", alts[0].line, lhs));
+            }
+            self.grammar.nonterminal(lhs);
+        }
+        if let Some(start) = start {
+            let s = self.grammar.find_nonterminal(&start).ok_or(format!("start symbol {} has no rules", start))?;
+            self.grammar.set_start(s);
+        }
+        for (lhs, alts) in rules {
+            let l = self.grammar.find_nonterminal(&lhs).unwrap();
+            for alt in alts {
+                let mut rhs = vec![];
+                for name in &alt.rhs {
+                    let sym = if name.starts_with('\'') {
+                        Sym::T(self.grammar.terminal(name))
+                    } else if let Some(n) = self.grammar.find_nonterminal(name) {
+                        Sym::N(n)
+                    } else if let Some(t) = self.grammar.find_terminal(name) {
+                        Sym::T(t)
+                    } else {
+                        return Err(format!("line {}: symbol {} is used, but is not defined as a token and has no rules", alt.line, name));
+                    };
+                    rhs.push(sym);
+                }
+                let prec = match &alt.prec {
+                    Some(name) => Some(self.grammar.find_terminal(name)
+                        .ok_or(format!("line {}: %prec symbol {} is not a token", alt.line, name))?),
+                    None => None
+                };
+                if let Some(action) = &alt.action {
+                    Self::translate_action(action, rhs.len()).map_err(|e| format!("line {}: {}", alt.line, e))?;
+                }
+                self.grammar.add_production(l, rhs, prec, alt.action);
+            }
+        }
+        Ok(())
+    }
+
+    /// classifyの各腕を生成する
+    fn generate_classify(&self) -> String {
+        let mut literals = String::new();
+        let mut others = String::new();
+        let mut numbers: BTreeSet<(usize, &str, usize)> = BTreeSet::new();
+        for (idx, name) in self.grammar.terminals.iter().enumerate().skip(1) {
+            if name.starts_with('\'') && name.len() > 2 {
+                let lexeme = &name[1..name.len()-1];
+                writeln!(literals, "        Token::Word {{ lexeme, .. }} if lexeme == {:?} => Some({}),", lexeme, idx).unwrap();
+            } else if let Some(tag) = Tag::ALL.iter().find(|t| t.to_string().to_uppercase() == *name) {
+                writeln!(others, "        Token::Word {{ tag: Tag::{}, .. }} => Some({}),", tag, idx).unwrap();
+            } else {
+                match &name[..] {
+                    "NUMI32" => { numbers.insert((0, "Token::NumI32 { .. }", idx)); },
+                    "NUMF32" => { numbers.insert((1, "Token::NumF32 { .. }", idx)); },
+                    "NUM" => { numbers.insert((2, "Token::NumI32 { .. } | Token::NumF32 { .. }", idx)); },
+                    _ => {}
+                }
+            }
+        }
+        for (_, pattern, idx) in numbers {
+            writeln!(others, "        {} => Some({}),", pattern, idx).unwrap();
+        }
+        literals + &others
+    }
+
+    /// 意味動作中の$$と$nを値スタックへの参照に置き換える
+    ///
+    /// # params
+    /// - action: &str => 意味動作
+    /// - len: usize => 規則の右辺の長さ
+    ///
+    /// # returns
+    /// - Result<String, String>
+    fn translate_action(action: &str, len: usize) -> Result<String, String> {
+        let chars: Vec<char> = action.chars().collect();
+        let mut out = String::new();
+        let mut uses_lhs = false;
+        let mut in_string = false;
+        let mut idx = 0;
+        while idx < chars.len() {
+            let c = chars[idx];
+            idx += 1;
+            match c {
+                '\\' if in_string => {
+                    out.push(c);
+                    if idx < chars.len() { out.push(chars[idx]); idx += 1; }
+                },
+                '"' => { in_string = !in_string; out.push(c); },
+                '$' if !in_string => {
+                    if chars.get(idx) == Some(&'$') {
+                        idx += 1;
+                        uses_lhs = true;
+                        out.push_str("__lhs");
+                    } else {
+                        let digits: String = chars[idx..].iter().take_while(|c| c.is_ascii_digit()).collect();
+                        idx += digits.len();
+                        match digits.parse::<usize>() {
+                            Ok(n) if 1 <= n && n <= len => out.push_str(&format!("__v[{}].clone()", n-1)),
+                            Ok(n) => return Err(format!("${} out of range in action {}", n, action)),
+                            Err(_) => return Err(format!("invalid $ reference in action {}", action))
+                        }
+                    }
+                },
+                _ => out.push(c)
+            }
+        }
+        if uses_lhs {
+            Ok(format!("{{ let __lhs: Value; {} __lhs }}", out))
+        } else {
+            Ok(out)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Yacc;
+    use super::super::calc;
+    use super::super::lr::LrParser;
+    use super::super::super::chapter2::lexer::Lexer;
+    use super::super::super::chapter2::token::{Span, Tag, Token};
+
+    fn tokens(program: &str) -> Vec<(Token, Span)> {
+        let mut lexer = Lexer::new(program.to_string());
+        std::iter::from_fn(|| lexer.scan_spanned()).collect()
+    }
+
+    #[test]
+    fn yacc_generate_test() {
+        // calc.rsはcalc.yから生成したもの
+        let yacc = Yacc::parse(include_str!("calc.y")).unwrap();
+        let lalr = yacc.build();
+        assert_eq!((0, 0), lalr.unresolved());
+        assert_eq!(include_str!("calc.rs"), yacc.generate(&lalr, "crate").unwrap());
+    }
+
+    #[test]
+    fn yacc_calc_test() {
+        assert_eq!(Ok(5.0), calc::parse_str("1+2*3-4/2"));
+        assert_eq!(Ok(-10.0), calc::parse_str("-(2+3)*2"));
+        assert_eq!(Ok(-1.0), calc::parse_str("1-1-1"));
+        assert_eq!(Ok(3.5), calc::parse(tokens("1.5+2")));
+        let diags = calc::parse_str("1+3000000000").unwrap_err();
+        assert_eq!(vec!["integer literal `3000000000` out of range for i32".to_string()], diags.iter().map(|d| d.message.clone()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn yacc_parse_tree_test() {
+        let yacc = Yacc::parse(include_str!("calc.y")).unwrap();
+        let lalr = yacc.build();
+        let tree = LrParser::new(&lalr.table).parse_tree(&yacc.grammar, tokens("1 + 2 * -3")).unwrap();
+        assert_eq!("[line [expr [expr 1] + [expr [expr 2] * [expr - [expr 3]]]]]", tree.to_string());
+        assert_eq!("1 + 2 * - 3", tree.text());
+        assert_eq!(Span::new(1, 1, 0, 10), tree.span);
+    }
+
+    #[test]
+    fn yacc_repair_test() {
+        let diags = calc::parse_str("1+*2").unwrap_err();
+        assert_eq!("syntax error: unexpected `*`; deleted unexpected `*`", diags[0].message);
+        let diags = calc::parse_str("(1+2").unwrap_err();
+        assert_eq!("syntax error: unexpected end of input; inserted missing `)`", diags[0].message);
+        assert_eq!(Span::new(1, 5, 4, 4), diags[0].span);
+    }
+
+    #[test]
+    fn yacc_error_production_test() {
+        let yacc = Yacc::parse("\
+%token ID NUM
+%%
+stmts : stmts stmt | ;
+stmt  : ID '=' expr ';' | error ';' ;
+expr  : expr '+' NUM | NUM ;
+").unwrap();
+        let lalr = yacc.build();
+        let mut stmts = 0;
+        let diags = LrParser::new(&lalr.table).parse(tokens("a = 1 + ;\nb = 2 c = 3;"), |t| yacc.classify(t),
+            |_, _| (), |prod, _| if prod == 3 || prod == 4 { stmts += 1; }).unwrap_err();
+        assert_eq!(2, diags.len());
+        assert_eq!("syntax error: unexpected `;`, expected one of NUM", diags[0].message);
+        assert_eq!(Span::new(1, 9, 8, 9), diags[0].span);
+        assert_eq!("syntax error: unexpected `c`; inserted missing `;`", diags[1].message);
+        assert_eq!(2, diags[1].span.line);
+        assert_eq!(3, stmts);
+    }
+
+    #[test]
+    fn yacc_error_loop_test() {
+        // r -> error . は = でも還元できるが、還元の後の = でまた回復に入らずに読み捨てる
+        let yacc = Yacc::parse("%token ID\n%%\ns : l '=' r | r ;\nl : '*' r | ID ;\nr : l | error ;\n").unwrap();
+        let lalr = yacc.build();
+        assert_eq!((0, 0), lalr.unresolved());
+        let diags = LrParser::new(&lalr.table).parse(tokens("a = b = c"), |t| yacc.classify(t), |_, _| (), |_, _| ()).unwrap_err();
+        assert_eq!(1, diags.len());
+        assert_eq!("syntax error: unexpected `=`, expected one of $end", diags[0].message);
+        assert_eq!(Span::new(1, 7, 6, 7), diags[0].span);
+    }
+
+    #[test]
+    fn yacc_conflict_report_test() {
+        let yacc = Yacc::parse("%token NUM\n%%\ne : e '-' e | NUM ;\n").unwrap();
+        let lalr = yacc.build();
+        assert_eq!((1, 0), lalr.unresolved());
+        let report = lalr.report(&yacc.grammar);
+        assert!(report.starts_with("State 4 conflicts: 1 shift/reduce"));
+
+        // 表自体は解消済み(シフト優先)なので、右結合として解析される
+        let table = &lalr.table;
+        let result = LrParser::new(table).parse(tokens("1-1-1"), |t| yacc.classify(t),
+            |t, _| match t { Token::NumI32 { num } => *num, _ => 0 },
+            |prod, v| if prod == 1 { v[0]-v[2] } else { v[0] });
+        assert_eq!(Ok(1), result);
+    }
+
+    #[test]
+    fn yacc_classify_tag_test() {
+        // 生成したclassifyは、Grammar::classifyと同じくすべてのタグを分類する
+        let yacc = Yacc::parse("%token KEYWORD ID\n%%\ns : KEYWORD ID ;\n").unwrap();
+        let code = yacc.generate(&yacc.build(), "crate").unwrap();
+        for tag in &[Tag::Keyword, Tag::Id] {
+            let terminal = yacc.classify(&Token::new_word(tag.clone(), "x")).unwrap();
+            assert!(code.contains(&format!("Token::Word {{ tag: Tag::{}, .. }} => Some({}),", tag, terminal)));
+        }
+    }
+
+    fn spec_error(src: &str) -> String {
+        match Yacc::parse(src) {
+            Ok(_) => panic!("test failed at [yacc_spec_error_test] => {}", src),
+            Err(msg) => msg
+        }
+    }
+
+    #[test]
+    fn yacc_spec_error_test() {
+        assert!(spec_error("%token NUM\n%%\ne : e '+' f ;\n").contains("symbol f is used"));
+        assert!(spec_error("%token NUM\n%%\ne : NUM { $2 } ;\n").contains("out of range"));
+        assert!(spec_error("%token NUM\n%%\ne : NUM\n").contains("missing `;`"));
+        assert!(spec_error("%foo\n%%\ne : ;\n").contains("unknown directive"));
+    }
+}
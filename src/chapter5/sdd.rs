@@ -0,0 +1,674 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::super::chapter2::diagnostic::Diagnostic;
+use super::super::chapter2::parsetree::ParseTree;
+use super::super::chapter2::token::Token;
+use super::super::chapter4::grammar::{Grammar, Sym};
+
+/// 終端記号が持つ合成属性の名前(字句解析器が与える値)
+pub const LEXVAL: &str = "lexval";
+
+/// 属性の種類
+///
+/// # members
+/// - Synthesized => 合成属性(子と自身の属性から決まる)
+/// - Inherited => 継承属性(親と兄弟と自身の属性から決まる)
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AttrKind {
+    Synthesized,
+    Inherited
+}
+
+/// 意味規則で属性を計算する関数
+pub type RuleFn<V> = Box<dyn Fn(&[V]) -> V>;
+
+/// 生成規則に付けた意味規則 X.a = f(Y1.b1, ..., Yk.bk)
+/// 位置0は規則の左辺、位置i(1以上)は右辺のi番目の記号を表す
+///
+/// # members
+/// - target: (usize, String) => 定義する属性の位置と名前
+/// - deps: Vec<(usize, String)> => 依存する属性の位置と名前
+/// - f: RuleFn<V> => 依存する属性の値から属性値を求める関数
+pub struct Rule<V> {
+    pub target: (usize, String),
+    pub deps: Vec<(usize, String)>,
+    f: RuleFn<V>
+}
+
+/// 評価に用いた方法
+///
+/// # members
+/// - SAttributed => 後行順の1回のなぞり
+/// - LAttributed => 左から右への深さ優先の1回のなぞり
+/// - Topological => 依存グラフのトポロジカル順
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Strategy {
+    SAttributed,
+    LAttributed,
+    Topological
+}
+
+/// 構文解析木の属性の依存グラフ
+/// 頂点は(節番号, 属性名)で、節番号は構文解析木の前順の番号
+///
+/// # members
+/// - vertices: Vec<(usize, String)> => 属性のインスタンス
+/// - deps: Vec<Vec<usize>> => 各頂点が依存する頂点
+pub struct DependencyGraph {
+    pub vertices: Vec<(usize, String)>,
+    pub deps: Vec<Vec<usize>>
+}
+
+impl DependencyGraph {
+    /// 依存される側が先に来るトポロジカル順を返す
+    /// 循環があれば循環をなす頂点の列を返す
+    ///
+    /// # returns
+    /// - Result<Vec<usize>, Vec<usize>>
+    pub fn topological_order(&self) -> Result<Vec<usize>, Vec<usize>> {
+        // 0: 未訪問, 1: 訪問中, 2: 訪問済み
+        let mut color = vec![0u8; self.vertices.len()];
+        let mut order = vec![];
+        for root in 0..self.vertices.len() {
+            if color[root] != 0 {
+                continue;
+            }
+            let mut stack = vec![(root, 0)];
+            color[root] = 1;
+            while let Some((v, next)) = stack.pop() {
+                match self.deps[v].get(next) {
+                    Some(&d) => {
+                        stack.push((v, next+1));
+                        match color[d] {
+                            0 => {
+                                color[d] = 1;
+                                stack.push((d, 0));
+                            },
+                            1 => {
+                                let from = stack.iter().position(|(u, _)| *u == d).unwrap();
+                                return Err(stack[from..].iter().map(|(u, _)| *u).collect());
+                            },
+                            _ => {}
+                        }
+                    },
+                    None => {
+                        color[v] = 2;
+                        order.push(v);
+                    }
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+/// 評価した属性値
+///
+/// # members
+/// - values: Vec<HashMap<String, V>> => 前順の節番号ごとの属性値
+/// - strategy: Strategy => 評価に用いた方法
+pub struct Attributes<V> {
+    pub values: Vec<HashMap<String, V>>,
+    pub strategy: Strategy
+}
+
+impl<V: Clone + fmt::Display> Attributes<V> {
+    /// 節の属性値を返す
+    ///
+    /// # params
+    /// - node: usize => 前順の節番号(根は0)
+    /// - attr: &str => 属性名
+    ///
+    /// # returns
+    /// - Option<&V>
+    pub fn get(&self, node: usize, attr: &str) -> Option<&V> {
+        self.values.get(node).and_then(|m| m.get(attr))
+    }
+
+    /// 構文解析木に属性値を注釈として書き込む(注釈付き構文解析木)
+    /// 終端記号のlexvalは葉の名前と同じなので書き込まない
+    ///
+    /// # params
+    /// - tree: &mut ParseTree => 評価した構文解析木
+    pub fn annotate(&self, tree: &mut ParseTree) {
+        let mut next = 0;
+        self.annotate_node(tree, &mut next);
+    }
+
+    fn annotate_node(&self, tree: &mut ParseTree, next: &mut usize) {
+        let node = *next;
+        *next += 1;
+        if !tree.is_leaf() {
+            let mut attrs: Vec<(&String, &V)> = self.values[node].iter().collect();
+            attrs.sort_by_key(|(name, _)| *name);
+            for (name, value) in attrs {
+                tree.set_attr(name, value.to_string());
+            }
+        }
+        for child in &mut tree.children {
+            self.annotate_node(child, next);
+        }
+    }
+}
+
+/// 構文解析木を前順に平らにしたもの
+struct Flat<'t> {
+    nodes: Vec<&'t ParseTree>,
+    children: Vec<Vec<usize>>,
+    prods: Vec<Option<usize>>
+}
+
+/// 構文主導定義(属性文法)
+/// 文法記号ごとに合成属性と継承属性を宣言し、生成規則ごとに意味規則を付ける
+///
+/// # members
+/// - grammar: &Grammar => 文法
+/// - attrs: HashMap<(Sym, String), AttrKind> => 文法記号ごとに宣言された属性
+/// - rules: Vec<Vec<Rule<V>>> => 生成規則ごとの意味規則
+/// - lexval: Box<dyn Fn(&Token) -> V> => 終端記号のlexvalを求める関数
+pub struct Sdd<'g, V> {
+    grammar: &'g Grammar,
+    attrs: HashMap<(Sym, String), AttrKind>,
+    rules: Vec<Vec<Rule<V>>>,
+    lexval: Box<dyn Fn(&Token) -> V>
+}
+
+impl<'g, V: Clone> Sdd<'g, V> {
+    /// 意味規則のない構文主導定義を生成して返す
+    ///
+    /// # params
+    /// - grammar: &Grammar => 文法
+    /// - lexval: F => 終端記号のlexvalを求める関数
+    ///
+    /// # returns
+    /// - Sdd
+    pub fn new<F: Fn(&Token) -> V + 'static>(grammar: &'g Grammar, lexval: F) -> Sdd<'g, V> {
+        let mut attrs = HashMap::new();
+        for t in 0..grammar.terminals.len() {
+            attrs.insert((Sym::T(t), LEXVAL.to_string()), AttrKind::Synthesized);
+        }
+        Sdd { grammar, attrs, rules: (0..grammar.productions.len()).map(|_| vec![]).collect(), lexval: Box::new(lexval) }
+    }
+
+    /// 非終端記号に属性を宣言する
+    ///
+    /// # params
+    /// - nonterminal: &str => 非終端記号名
+    /// - attr: &str => 属性名
+    /// - kind: AttrKind => 属性の種類
+    ///
+    /// # returns
+    /// - Result<(), String>
+    pub fn declare(&mut self, nonterminal: &str, attr: &str, kind: AttrKind) -> Result<(), String> {
+        let n = self.grammar.find_nonterminal(nonterminal)
+            .ok_or(format!("unknown nonterminal `{}`", nonterminal))?;
+        match self.attrs.insert((Sym::N(n), attr.to_string()), kind) {
+            Some(old) if old != kind => Err(format!("attribute `{}.{}` declared as both synthesized and inherited", nonterminal, attr)),
+            _ => Ok(())
+        }
+    }
+
+    /// 生成規則に意味規則を付ける
+    /// 左辺(位置0)には合成属性、右辺(位置1以上)には継承属性しか定義できない
+    ///
+    /// # params
+    /// - prod: usize => 生成規則番号
+    /// - target: (usize, &str) => 定義する属性
+    /// - deps: &[(usize, &str)] => 依存する属性
+    /// - f: F => 属性値を求める関数
+    ///
+    /// # returns
+    /// - Result<(), String>
+    pub fn rule<F>(&mut self, prod: usize, target: (usize, &str), deps: &[(usize, &str)], f: F) -> Result<(), String>
+    where
+        F: Fn(&[V]) -> V + 'static
+    {
+        let production = self.grammar.productions.get(prod).ok_or(format!("unknown production {}", prod))?;
+        let expected = if target.0 == 0 { AttrKind::Synthesized } else { AttrKind::Inherited };
+        match self.attr_kind(prod, target.0, target.1)? {
+            kind if kind == expected => {},
+            _ => return Err(format!("rule `{}` cannot define {} attribute `{}`",
+                self.grammar.production_to_string(prod),
+                if expected == AttrKind::Synthesized { "inherited" } else { "synthesized" },
+                self.attr_name(prod, target.0, target.1)))
+        }
+        if target.0 > 0 && matches!(production.rhs[target.0-1], Sym::T(_)) {
+            return Err(format!("terminal `{}` cannot have inherited attributes", self.attr_name(prod, target.0, target.1)));
+        }
+        if self.rules[prod].iter().any(|r| r.target.0 == target.0 && r.target.1 == target.1) {
+            return Err(format!("attribute `{}` defined twice in `{}`", self.attr_name(prod, target.0, target.1),
+                self.grammar.production_to_string(prod)));
+        }
+        for (pos, attr) in deps {
+            self.attr_kind(prod, *pos, attr)?;
+        }
+        self.rules[prod].push(Rule {
+            target: (target.0, target.1.to_string()),
+            deps: deps.iter().map(|(pos, attr)| (*pos, attr.to_string())).collect(),
+            f: Box::new(f)
+        });
+        Ok(())
+    }
+
+    /// 合成属性しか定義していないか(S属性定義)
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_s_attributed(&self) -> bool {
+        self.rules.iter().flatten().all(|r| r.target.0 == 0)
+    }
+
+    /// L属性定義か
+    /// 右辺i番目の継承属性が、左辺の継承属性と1..i-1番目の記号の属性、i番目の記号の継承属性だけに依存していればよい
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_l_attributed(&self) -> bool {
+        self.rules.iter().enumerate().all(|(prod, rules)| {
+            rules.iter().filter(|r| r.target.0 > 0).all(|r| {
+                r.deps.iter().all(|(pos, attr)| match *pos {
+                    0 => self.attr_kind(prod, 0, attr) == Ok(AttrKind::Inherited),
+                    pos if pos < r.target.0 => true,
+                    pos if pos == r.target.0 => self.attr_kind(prod, pos, attr) == Ok(AttrKind::Inherited),
+                    _ => false
+                })
+            })
+        })
+    }
+
+    /// 構文解析木の依存グラフを作る
+    ///
+    /// # params
+    /// - tree: &ParseTree => 構文解析木
+    ///
+    /// # returns
+    /// - Result<DependencyGraph, Diagnostic>
+    pub fn dependency_graph(&self, tree: &ParseTree) -> Result<DependencyGraph, Diagnostic> {
+        let flat = self.flatten(tree)?;
+        Ok(self.graph(&flat).0)
+    }
+
+    /// 構文解析木の属性を評価する
+    /// S属性定義・L属性定義なら1回のなぞりで、それ以外は依存グラフのトポロジカル順で評価する
+    /// なぞりでは、同じ記号の属性を定義する意味規則を互いの依存を満たす順に適用する
+    ///
+    /// # params
+    /// - tree: &ParseTree => 構文解析木
+    /// - inherited: &[(&str, V)] => 根の継承属性の値
+    ///
+    /// # returns
+    /// - Result<Attributes<V>, Diagnostic>
+    pub fn evaluate(&self, tree: &ParseTree, inherited: &[(&str, V)]) -> Result<Attributes<V>, Diagnostic> {
+        let flat = self.flatten(tree)?;
+        let mut values: Vec<HashMap<String, V>> = vec![HashMap::new(); flat.nodes.len()];
+        for (attr, value) in inherited {
+            values[0].insert(attr.to_string(), value.clone());
+        }
+        // 同じ位置の属性を定義する意味規則の間で循環していれば、なぞりでは評価できない
+        let orderable = self.rules.iter().enumerate().all(|(prod, rules)| rules.iter().all(|r| self.ordered(prod, r.target.0).is_some()));
+        let strategy = if !orderable {
+            Strategy::Topological
+        } else if self.is_s_attributed() {
+            Strategy::SAttributed
+        } else if self.is_l_attributed() {
+            Strategy::LAttributed
+        } else {
+            Strategy::Topological
+        };
+        match strategy {
+            Strategy::Topological => self.evaluate_topological(&flat, &mut values)?,
+            _ => self.evaluate_dfs(&flat, 0, &mut values)?
+        }
+        Ok(Attributes { values, strategy })
+    }
+
+    /// 左から右への深さ優先でなぞりながら評価する(S属性・L属性定義用)
+    fn evaluate_dfs(&self, flat: &Flat, node: usize, values: &mut [HashMap<String, V>]) -> Result<(), Diagnostic> {
+        let prod = match flat.prods[node] {
+            Some(prod) => prod,
+            None => {
+                let value = (self.lexval)(flat.nodes[node].token.as_ref().unwrap());
+                values[node].insert(LEXVAL.to_string(), value);
+                return Ok(());
+            }
+        };
+        for (i, child) in flat.children[node].iter().enumerate() {
+            for rule in self.ordered(prod, i+1).unwrap() {
+                self.apply(flat, node, rule, values)?;
+            }
+            self.evaluate_dfs(flat, *child, values)?;
+        }
+        for rule in self.ordered(prod, 0).unwrap() {
+            self.apply(flat, node, rule, values)?;
+        }
+        Ok(())
+    }
+
+    /// 生成規則prodで位置posの属性を定義する意味規則を、互いの依存を満たす順に並べて返す
+    /// 例えば A.y = f(A.x) を A.x = B.v より先に書いても、A.x から計算する
+    /// 循環していて並べられなければNoneを返す
+    fn ordered(&self, prod: usize, pos: usize) -> Option<Vec<&Rule<V>>> {
+        let mut rest: Vec<&Rule<V>> = self.rules[prod].iter().filter(|r| r.target.0 == pos).collect();
+        let mut order = vec![];
+        while !rest.is_empty() {
+            let ready = rest.iter().position(|r| r.deps.iter().all(|dep| rest.iter().all(|other| &other.target != dep)))?;
+            order.push(rest.remove(ready));
+        }
+        Some(order)
+    }
+
+    /// 依存グラフのトポロジカル順に評価する
+    fn evaluate_topological(&self, flat: &Flat, values: &mut [HashMap<String, V>]) -> Result<(), Diagnostic> {
+        let (graph, defs) = self.graph(flat);
+        let order = graph.topological_order().map_err(|cycle| {
+            let mut names: Vec<String> = cycle.iter().map(|v| self.instance_name(flat, &graph.vertices[*v])).collect();
+            names.push(names[0].clone());
+            let (node, _) = &graph.vertices[cycle[0]];
+            Diagnostic::error(flat.nodes[*node].span, format!("circular attribute dependency: {}", names.join(" -> ")))
+        })?;
+        for v in order {
+            let (node, attr) = &graph.vertices[v];
+            if let Some((owner, prod, idx)) = defs[v] {
+                self.apply(flat, owner, &self.rules[prod][idx], values)?;
+            } else if flat.prods[*node].is_none() && attr == LEXVAL {
+                let value = (self.lexval)(flat.nodes[*node].token.as_ref().unwrap());
+                values[*node].insert(LEXVAL.to_string(), value);
+            }
+        }
+        Ok(())
+    }
+
+    /// 節ownerで意味規則ruleを適用する
+    fn apply(&self, flat: &Flat, owner: usize, rule: &Rule<V>, values: &mut [HashMap<String, V>]) -> Result<(), Diagnostic> {
+        let mut args = vec![];
+        for dep in &rule.deps {
+            let node = Self::node_at(flat, owner, dep.0);
+            match values[node].get(&dep.1) {
+                Some(value) => args.push(value.clone()),
+                None => return Err(Diagnostic::error(flat.nodes[node].span,
+                    format!("attribute `{}` has no value", self.instance_name(flat, &(node, dep.1.clone())))))
+            }
+        }
+        let target = Self::node_at(flat, owner, rule.target.0);
+        values[target].insert(rule.target.1.clone(), (rule.f)(&args));
+        Ok(())
+    }
+
+    /// 依存グラフと、各頂点を定義する意味規則(節, 規則番号, 意味規則の添字)を作る
+    #[allow(clippy::type_complexity)]
+    fn graph(&self, flat: &Flat) -> (DependencyGraph, Vec<Option<(usize, usize, usize)>>) {
+        let mut graph = DependencyGraph { vertices: vec![], deps: vec![] };
+        let mut defs = vec![];
+        let mut index: HashMap<(usize, String), usize> = HashMap::new();
+        let mut vertex = |graph: &mut DependencyGraph, defs: &mut Vec<Option<(usize, usize, usize)>>, key: (usize, String)| -> usize {
+            *index.entry(key.clone()).or_insert_with(|| {
+                graph.vertices.push(key);
+                graph.deps.push(vec![]);
+                defs.push(None);
+                graph.vertices.len()-1
+            })
+        };
+        for node in 0..flat.nodes.len() {
+            let prod = match flat.prods[node] {
+                Some(prod) => prod,
+                None => {
+                    vertex(&mut graph, &mut defs, (node, LEXVAL.to_string()));
+                    continue;
+                }
+            };
+            for (idx, rule) in self.rules[prod].iter().enumerate() {
+                let target = vertex(&mut graph, &mut defs, (Self::node_at(flat, node, rule.target.0), rule.target.1.clone()));
+                defs[target] = Some((node, prod, idx));
+                for dep in &rule.deps {
+                    let d = vertex(&mut graph, &mut defs, (Self::node_at(flat, node, dep.0), dep.1.clone()));
+                    graph.deps[target].push(d);
+                }
+            }
+        }
+        (graph, defs)
+    }
+
+    /// 構文解析木を前順に平らにし、各内部節に用いた生成規則を求める
+    fn flatten<'t>(&self, tree: &'t ParseTree) -> Result<Flat<'t>, Diagnostic> {
+        let mut flat = Flat { nodes: vec![], children: vec![], prods: vec![] };
+        self.flatten_node(tree, &mut flat)?;
+        Ok(flat)
+    }
+
+    fn flatten_node<'t>(&self, tree: &'t ParseTree, flat: &mut Flat<'t>) -> Result<usize, Diagnostic> {
+        let node = flat.nodes.len();
+        flat.nodes.push(tree);
+        flat.children.push(vec![]);
+        flat.prods.push(None);
+        if tree.is_leaf() {
+            return Ok(node);
+        }
+        flat.prods[node] = Some(self.production_of(tree)?);
+        for child in &tree.children {
+            let c = self.flatten_node(child, flat)?;
+            flat.children[node].push(c);
+        }
+        Ok(node)
+    }
+
+    /// 内部節とその子の並びに一致する生成規則を探す
+    fn production_of(&self, tree: &ParseTree) -> Result<usize, Diagnostic> {
+        let lhs = self.grammar.find_nonterminal(&tree.label);
+        (0..self.grammar.productions.len()).find(|prod| {
+            let p = &self.grammar.productions[*prod];
+            Some(p.lhs) == lhs && p.rhs.len() == tree.children.len() && p.rhs.iter().zip(&tree.children).all(|(sym, child)| {
+                match (sym, &child.token) {
+                    (Sym::T(t), Some(token)) => self.grammar.classify(token) == Some(*t),
+                    (Sym::N(n), None) => self.grammar.nonterminals[*n] == child.label,
+                    _ => false
+                }
+            })
+        }).ok_or_else(|| {
+            let children: Vec<&str> = tree.children.iter().map(|c| &c.label[..]).collect();
+            Diagnostic::error(tree.span, format!("no production matches `{} -> {}`", tree.label, children.join(" ")))
+        })
+    }
+
+    /// 節ownerの規則における位置posの節を返す
+    fn node_at(flat: &Flat, owner: usize, pos: usize) -> usize {
+        if pos == 0 { owner } else { flat.children[owner][pos-1] }
+    }
+
+    /// 規則prodの位置posの記号の属性attrの種類を返す
+    fn attr_kind(&self, prod: usize, pos: usize, attr: &str) -> Result<AttrKind, String> {
+        let p = &self.grammar.productions[prod];
+        let sym = match pos {
+            0 => Sym::N(p.lhs),
+            pos if pos <= p.rhs.len() => p.rhs[pos-1],
+            _ => return Err(format!("position {} out of range in `{}`", pos, self.grammar.production_to_string(prod)))
+        };
+        self.attrs.get(&(sym, attr.to_string())).cloned()
+            .ok_or(format!("undeclared attribute `{}.{}`", self.grammar.name(sym), attr))
+    }
+
+    fn attr_name(&self, prod: usize, pos: usize, attr: &str) -> String {
+        let p = &self.grammar.productions[prod];
+        let sym = if pos == 0 { Sym::N(p.lhs) } else { p.rhs[pos-1] };
+        format!("{}.{}", self.grammar.name(sym), attr)
+    }
+
+    fn instance_name(&self, flat: &Flat, (node, attr): &(usize, String)) -> String {
+        format!("{}.{}", flat.nodes[*node].label, attr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttrKind, Sdd, Strategy};
+    use super::super::super::chapter2::lexer::Lexer;
+    use super::super::super::chapter2::token::{Span, Token};
+    use super::super::super::chapter4::lr::LrParser;
+    use super::super::super::chapter4::yacc::Yacc;
+
+    fn tokens(program: &str) -> Vec<(Token, Span)> {
+        let mut lexer = Lexer::new(program.to_string());
+        std::iter::from_fn(|| lexer.scan_spanned()).collect()
+    }
+
+    fn num(token: &Token) -> i32 {
+        match token {
+            Token::NumI32 { num } => *num,
+            _ => 0
+        }
+    }
+
+    #[test]
+    fn sdd_s_attributed_test() {
+        // 例5.1 卓上計算機
+        let yacc = Yacc::parse("%token NUM\n%%\ne : e '+' t | t ;\nt : t '*' f | f ;\nf : '(' e ')' | NUM ;\n").unwrap();
+        let g = &yacc.grammar;
+        let mut sdd = Sdd::new(g, num);
+        for n in &["e", "t", "f"] {
+            sdd.declare(n, "val", AttrKind::Synthesized).unwrap();
+        }
+        sdd.rule(1, (0, "val"), &[(1, "val"), (3, "val")], |v| v[0]+v[1]).unwrap();
+        sdd.rule(2, (0, "val"), &[(1, "val")], |v| v[0]).unwrap();
+        sdd.rule(3, (0, "val"), &[(1, "val"), (3, "val")], |v| v[0]*v[1]).unwrap();
+        sdd.rule(4, (0, "val"), &[(1, "val")], |v| v[0]).unwrap();
+        sdd.rule(5, (0, "val"), &[(2, "val")], |v| v[0]).unwrap();
+        sdd.rule(6, (0, "val"), &[(1, "lexval")], |v| v[0]).unwrap();
+        assert!(sdd.is_s_attributed() && sdd.is_l_attributed());
+
+        let lalr = yacc.build();
+        let mut tree = LrParser::new(&lalr.table).parse_tree(g, tokens("3 * 5 + 4")).unwrap();
+        let attrs = sdd.evaluate(&tree, &[]).unwrap();
+        assert_eq!(Strategy::SAttributed, attrs.strategy);
+        assert_eq!(Some(&19), attrs.get(0, "val"));
+
+        // 注釈付き構文解析木
+        attrs.annotate(&mut tree);
+        assert_eq!(Some("19"), tree.attr("val"));
+        assert!(tree.to_dot().contains("[label=\"t\\nval = 15\"]"));
+
+        assert_eq!(Err("undeclared attribute `f.x`".to_string()), sdd.rule(6, (0, "x"), &[], |_| 0));
+        assert!(sdd.rule(6, (0, "val"), &[], |_| 0).is_err());
+    }
+
+    #[test]
+    fn sdd_l_attributed_test() {
+        // 例5.3 T -> F T' ; T' -> * F T1' | ε ; F -> digit
+        let yacc = Yacc::parse("%token NUM\n%%\nt : f tp ;\ntp : '*' f tp | ;\nf : NUM ;\n").unwrap();
+        let g = &yacc.grammar;
+        let mut sdd = Sdd::new(g, num);
+        sdd.declare("t", "val", AttrKind::Synthesized).unwrap();
+        sdd.declare("f", "val", AttrKind::Synthesized).unwrap();
+        sdd.declare("tp", "inh", AttrKind::Inherited).unwrap();
+        sdd.declare("tp", "syn", AttrKind::Synthesized).unwrap();
+        sdd.rule(1, (2, "inh"), &[(1, "val")], |v| v[0]).unwrap();
+        sdd.rule(1, (0, "val"), &[(2, "syn")], |v| v[0]).unwrap();
+        sdd.rule(2, (3, "inh"), &[(0, "inh"), (2, "val")], |v| v[0]*v[1]).unwrap();
+        sdd.rule(2, (0, "syn"), &[(3, "syn")], |v| v[0]).unwrap();
+        sdd.rule(3, (0, "syn"), &[(0, "inh")], |v| v[0]).unwrap();
+        sdd.rule(4, (0, "val"), &[(1, "lexval")], |v| v[0]).unwrap();
+        assert!(!sdd.is_s_attributed() && sdd.is_l_attributed());
+        assert!(sdd.rule(2, (1, "inh"), &[], |_| 0).is_err());
+
+        let lalr = yacc.build();
+        let tree = LrParser::new(&lalr.table).parse_tree(g, tokens("3 * 5 * 2")).unwrap();
+        let attrs = sdd.evaluate(&tree, &[]).unwrap();
+        assert_eq!(Strategy::LAttributed, attrs.strategy);
+        assert_eq!(Some(&30), attrs.get(0, "val"));
+
+        // 依存グラフによる評価でも同じ結果になる
+        let graph = sdd.dependency_graph(&tree).unwrap();
+        assert!(graph.topological_order().is_ok());
+        let mut values = vec![std::collections::HashMap::new(); attrs.values.len()];
+        sdd.evaluate_topological(&sdd.flatten(&tree).unwrap(), &mut values).unwrap();
+        assert_eq!(Some(&30), values[0].get("val"));
+    }
+
+    #[test]
+    fn sdd_rule_order_test() {
+        // 同じ記号の属性に依存する意味規則を、依存先より先に書いてもよい
+        let yacc = Yacc::parse("%token NUM\n%%\ns : a ;\na : NUM ;\n").unwrap();
+        let g = &yacc.grammar;
+        let mut sdd = Sdd::new(g, num);
+        sdd.declare("s", "y", AttrKind::Synthesized).unwrap();
+        sdd.declare("s", "x", AttrKind::Synthesized).unwrap();
+        sdd.declare("a", "j", AttrKind::Inherited).unwrap();
+        sdd.declare("a", "i", AttrKind::Inherited).unwrap();
+        sdd.declare("a", "v", AttrKind::Synthesized).unwrap();
+        sdd.rule(1, (0, "y"), &[(0, "x")], |v| v[0]*10).unwrap();
+        sdd.rule(1, (0, "x"), &[(1, "v")], |v| v[0]).unwrap();
+        sdd.rule(1, (1, "j"), &[(1, "i")], |v| v[0]+1).unwrap();
+        sdd.rule(1, (1, "i"), &[], |_| 1).unwrap();
+        sdd.rule(2, (0, "v"), &[(0, "j"), (1, "lexval")], |v| v[0]+v[1]).unwrap();
+        assert!(sdd.is_l_attributed());
+
+        let lalr = yacc.build();
+        let tree = LrParser::new(&lalr.table).parse_tree(g, tokens("5")).unwrap();
+        let attrs = sdd.evaluate(&tree, &[]).unwrap();
+        assert_eq!(Strategy::LAttributed, attrs.strategy);
+        assert_eq!((Some(&7), Some(&70)), (attrs.get(0, "x"), attrs.get(0, "y")));
+
+        // 同じ記号の属性の間で循環していれば、なぞらずに循環として報告する
+        let mut sdd = Sdd::new(g, num);
+        sdd.declare("s", "y", AttrKind::Synthesized).unwrap();
+        sdd.declare("s", "x", AttrKind::Synthesized).unwrap();
+        sdd.rule(1, (0, "y"), &[(0, "x")], |v| v[0]).unwrap();
+        sdd.rule(1, (0, "x"), &[(0, "y")], |v| v[0]).unwrap();
+        assert!(sdd.is_s_attributed());
+        let err = sdd.evaluate(&tree, &[]).err().unwrap();
+        assert!(err.message.starts_with("circular attribute dependency: "));
+    }
+
+    #[test]
+    fn sdd_topological_test() {
+        // s -> a b ; a.i = b.s は右の兄弟に依存するのでL属性ではない
+        let yacc = Yacc::parse("%token NUM\n%%\ns : a b ;\na : NUM ;\nb : NUM ;\n").unwrap();
+        let g = &yacc.grammar;
+        let mut sdd = Sdd::new(g, num);
+        sdd.declare("s", "val", AttrKind::Synthesized).unwrap();
+        sdd.declare("a", "i", AttrKind::Inherited).unwrap();
+        sdd.declare("a", "s", AttrKind::Synthesized).unwrap();
+        sdd.declare("b", "s", AttrKind::Synthesized).unwrap();
+        sdd.rule(1, (1, "i"), &[(2, "s")], |v| v[0]).unwrap();
+        sdd.rule(1, (0, "val"), &[(1, "s")], |v| v[0]).unwrap();
+        sdd.rule(2, (0, "s"), &[(0, "i"), (1, "lexval")], |v| v[0]-v[1]).unwrap();
+        sdd.rule(3, (0, "s"), &[(1, "lexval")], |v| v[0]).unwrap();
+        assert!(!sdd.is_l_attributed());
+
+        let lalr = yacc.build();
+        let tree = LrParser::new(&lalr.table).parse_tree(g, tokens("3 10")).unwrap();
+        let attrs = sdd.evaluate(&tree, &[]).unwrap();
+        assert_eq!(Strategy::Topological, attrs.strategy);
+        assert_eq!(Some(&7), attrs.get(0, "val"));
+    }
+
+    #[test]
+    fn sdd_cycle_test() {
+        // a.s = b.i, b.i = a.s で循環する
+        let yacc = Yacc::parse("%token NUM\n%%\na : b ;\nb : NUM ;\n").unwrap();
+        let g = &yacc.grammar;
+        let mut sdd = Sdd::new(g, num);
+        sdd.declare("a", "s", AttrKind::Synthesized).unwrap();
+        sdd.declare("b", "i", AttrKind::Inherited).unwrap();
+        sdd.declare("b", "s", AttrKind::Synthesized).unwrap();
+        sdd.rule(1, (0, "s"), &[(1, "s")], |v| v[0]).unwrap();
+        sdd.rule(1, (1, "i"), &[(0, "s")], |v| v[0]).unwrap();
+        sdd.rule(2, (0, "s"), &[(0, "i")], |v| v[0]).unwrap();
+
+        let lalr = yacc.build();
+        let tree = LrParser::new(&lalr.table).parse_tree(g, tokens("1")).unwrap();
+        assert!(sdd.dependency_graph(&tree).unwrap().topological_order().is_err());
+        let err = sdd.evaluate(&tree, &[]).err().unwrap();
+        assert!(err.message.starts_with("circular attribute dependency: "));
+        assert!(err.message.contains("b.i") && err.message.contains("a.s") && err.message.contains("b.s"));
+    }
+}
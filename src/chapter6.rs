@@ -0,0 +1,7 @@
+pub mod typeck;
+pub mod infer;
+pub mod layout;
+pub mod usage;
+pub mod resolve;
+pub mod tac;
+pub mod lower;
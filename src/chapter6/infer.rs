@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::super::chapter2::ast::BinOp;
+use super::super::chapter2::diagnostic::Diagnostic;
+use super::super::chapter2::lexer::Lexer;
+use super::super::chapter2::mtype::Type;
+use super::super::chapter2::token::{Span, Tag, Token};
+
+/// 関数型の部分言語の項
+///
+/// # members
+/// - kind: TermKind => 項の種類
+/// - span: Span => 位置
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Term {
+    pub kind: TermKind,
+    pub span: Span
+}
+
+/// 項の種類
+///
+/// # members
+/// - Int(i32), Float(f32), Bool(bool) => 定数
+/// - Var(String) => 変数
+/// - Fun(String, Box<Term>) => 関数抽象 fun x -> e
+/// - App(Box<Term>, Box<Term>) => 関数適用 f e
+/// - Let(String, Box<Term>, Box<Term>) => let x = e1 in e2
+/// - LetRec(String, Box<Term>, Box<Term>) => let rec f = e1 in e2
+/// - If(Box<Term>, Box<Term>, Box<Term>) => if e1 then e2 else e3
+/// - Binary(BinOp, Box<Term>, Box<Term>) => 二項演算
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub enum TermKind {
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    Var(String),
+    Fun(String, Box<Term>),
+    App(Box<Term>, Box<Term>),
+    Let(String, Box<Term>, Box<Term>),
+    LetRec(String, Box<Term>, Box<Term>),
+    If(Box<Term>, Box<Term>, Box<Term>),
+    Binary(BinOp, Box<Term>, Box<Term>)
+}
+
+/// 型スキーム ∀vars. ty
+///
+/// # members
+/// - vars: Vec<usize> => 全称量化された型変数
+/// - ty: Type => 型
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Scheme {
+    pub vars: Vec<usize>,
+    pub ty: Type
+}
+
+impl Scheme {
+    /// 量化しない(単相の)型スキームを生成して返す
+    ///
+    /// # params
+    /// - ty: Type => 型
+    ///
+    /// # returns
+    /// - Scheme
+    pub fn mono(ty: Type) -> Scheme {
+        Scheme { vars: vec![], ty }
+    }
+}
+
+impl fmt::Display for Scheme {
+    /// 型変数を現れた順に'a, 'b, ..と付け直して forall 'a 'b. ty の形で表示する
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let order = self.ty.vars();
+        let ty = rename(&self.ty, &order);
+        let vars: Vec<String> = order.iter().enumerate()
+            .filter(|(_, v)| self.vars.contains(v))
+            .map(|(i, _)| Type::Var(i).to_string())
+            .collect();
+        if vars.is_empty() {
+            write!(f, "{}", ty)
+        } else {
+            write!(f, "forall {}. {}", vars.join(" "), ty)
+        }
+    }
+}
+
+/// 型変数を order での位置の番号に付け直す
+fn rename(ty: &Type, order: &[usize]) -> Type {
+    map_vars(ty, &|v| order.iter().position(|o| *o == v).map(Type::Var))
+}
+
+/// 型変数を f で置き換えた型を返す(Noneならそのまま)
+fn map_vars(ty: &Type, f: &dyn Fn(usize) -> Option<Type>) -> Type {
+    match ty {
+        Type::Var(v) => f(*v).unwrap_or(Type::Var(*v)),
+        Type::Array(n, elem) => Type::Array(*n, Box::new(map_vars(elem, f))),
+        Type::Pointer(to) => Type::Pointer(Box::new(map_vars(to, f))),
+        Type::Function(params, ret) => Type::Function(params.iter().map(|p| map_vars(p, f)).collect(), Box::new(map_vars(ret, f))),
+        Type::Record(name, fields) => {
+            let mut fields = fields.clone();
+            for field in &mut fields {
+                field.ty = map_vars(&field.ty, f);
+            }
+            Type::Record(name.clone(), fields)
+        },
+        ty => ty.clone()
+    }
+}
+
+/// 単一化の失敗
+enum UnifyError {
+    Mismatch,
+    Occurs(usize, Type)
+}
+
+/// Hindley-Milnerの型推論(6.5.4節、アルゴリズムJ)
+/// 型変数の束縛は置換表に破壊的に書き込み、letで束縛した値の型は汎化する
+///
+/// # members
+/// - subst: Vec<Option<Type>> => 型変数ごとの束縛
+/// - env: Vec<(String, Scheme)> => 型環境(後ろほど内側)
+pub struct Infer {
+    subst: Vec<Option<Type>>,
+    env: Vec<(String, Scheme)>
+}
+
+impl Default for Infer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Infer {
+    /// 空の型環境で Infer を生成して返す
+    ///
+    /// # returns
+    /// - Infer
+    pub fn new() -> Infer {
+        Infer { subst: vec![], env: vec![] }
+    }
+
+    /// 型環境に名前を追加する(組み込み関数の登録などに使う)
+    ///
+    /// # params
+    /// - name: &str => 名前
+    /// - scheme: Scheme => 型スキーム
+    pub fn bind(&mut self, name: &str, scheme: Scheme) {
+        self.env.push((name.to_string(), scheme));
+    }
+
+    /// 新しい型変数を返す
+    ///
+    /// # returns
+    /// - Type
+    pub fn fresh(&mut self) -> Type {
+        self.subst.push(None);
+        Type::Var(self.subst.len()-1)
+    }
+
+    /// 項の主要型を推論し、汎化した型スキームを返す
+    ///
+    /// # params
+    /// - term: &Term => 項
+    ///
+    /// # returns
+    /// - Result<Scheme, Diagnostic>
+    pub fn infer(&mut self, term: &Term) -> Result<Scheme, Diagnostic> {
+        let ty = self.j(term)?;
+        Ok(self.generalize(&ty))
+    }
+
+    fn j(&mut self, term: &Term) -> Result<Type, Diagnostic> {
+        match &term.kind {
+            TermKind::Int(_) => Ok(Type::new_i32()),
+            TermKind::Float(_) => Ok(Type::new_f32()),
+            TermKind::Bool(_) => Ok(Type::new_bool()),
+            TermKind::Var(name) => {
+                let scheme = self.env.iter().rev().find(|(n, _)| n == name).map(|(_, s)| s.clone())
+                    .ok_or_else(|| Diagnostic::error(term.span, format!("unbound variable `{}`", name)))?;
+                Ok(self.instantiate(&scheme))
+            },
+            TermKind::Fun(param, body) => {
+                let a = self.fresh();
+                self.env.push((param.clone(), Scheme::mono(a.clone())));
+                let result = self.j(body);
+                self.env.pop();
+                Ok(Type::new_function(vec![a], result?))
+            },
+            TermKind::App(f, arg) => {
+                let tf = self.j(f)?;
+                let ta = self.j(arg)?;
+                match self.shallow(&tf) {
+                    Type::Function(params, ret) if params.len() == 1 => {
+                        let note = format!("function has type `{}`", self.show(&[&tf])[0]);
+                        self.expect(&params[0], &ta, arg.span, Some((f.span, note)))?;
+                        Ok(*ret)
+                    },
+                    Type::Var(_) => {
+                        let ret = self.fresh();
+                        self.expect(&tf, &Type::new_function(vec![ta], ret.clone()), term.span, None)?;
+                        Ok(ret)
+                    },
+                    other => Err(Diagnostic::error(f.span, format!("expected a function, found `{}`", self.show(&[&other])[0])))
+                }
+            },
+            TermKind::Let(name, value, body) => {
+                let tv = self.j(value)?;
+                let scheme = self.generalize(&tv);
+                self.env.push((name.clone(), scheme));
+                let result = self.j(body);
+                self.env.pop();
+                result
+            },
+            TermKind::LetRec(name, value, body) => {
+                let a = self.fresh();
+                self.env.push((name.clone(), Scheme::mono(a.clone())));
+                let tv = self.j(value);
+                self.env.pop();
+                let tv = tv?;
+                self.expect(&a, &tv, value.span, None)?;
+                let scheme = self.generalize(&tv);
+                self.env.push((name.clone(), scheme));
+                let result = self.j(body);
+                self.env.pop();
+                result
+            },
+            TermKind::If(cond, then, els) => {
+                let tc = self.j(cond)?;
+                self.expect(&Type::new_bool(), &tc, cond.span, None)?;
+                let tt = self.j(then)?;
+                let te = self.j(els)?;
+                self.expect(&tt, &te, els.span, Some((then.span, "expected because of this branch".to_string())))?;
+                Ok(tt)
+            },
+            TermKind::Binary(op, lhs, rhs) => {
+                let tl = self.j(lhs)?;
+                let tr = self.j(rhs)?;
+                match op {
+                    BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => {
+                        self.expect(&Type::new_i32(), &tl, lhs.span, None)?;
+                        self.expect(&Type::new_i32(), &tr, rhs.span, None)?;
+                        Ok(Type::new_i32())
+                    },
+                    BinOp::And | BinOp::Or => {
+                        self.expect(&Type::new_bool(), &tl, lhs.span, None)?;
+                        self.expect(&Type::new_bool(), &tr, rhs.span, None)?;
+                        Ok(Type::new_bool())
+                    },
+                    _ => {
+                        self.expect(&tl, &tr, rhs.span, Some((lhs.span, "expected because of this operand".to_string())))?;
+                        Ok(Type::new_bool())
+                    }
+                }
+            }
+        }
+    }
+
+    /// foundがexpectedと単一化できることを確かめ、できなければ両方の型を示す診断を返す
+    fn expect(&mut self, expected: &Type, found: &Type, span: Span, note: Option<(Span, String)>) -> Result<(), Diagnostic> {
+        match self.unify(expected, found) {
+            Ok(()) => Ok(()),
+            Err(UnifyError::Mismatch) => {
+                let shown = self.show(&[expected, found]);
+                let diag = Diagnostic::error(span, format!("mismatched types: expected `{}`, found `{}`", shown[0], shown[1]));
+                Err(match note {
+                    Some((span, note)) => diag.with_note(span, note),
+                    None => diag
+                })
+            },
+            Err(UnifyError::Occurs(v, ty)) => {
+                let shown = self.show(&[&Type::Var(v), &ty]);
+                Err(Diagnostic::error(span, format!("infinite type: `{}` occurs in `{}`", shown[0], shown[1])))
+            }
+        }
+    }
+
+    /// 2つの型を単一化する(出現検査つき)
+    fn unify(&mut self, a: &Type, b: &Type) -> Result<(), UnifyError> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Type::Var(x), Type::Var(y)) if x == y => Ok(()),
+            (Type::Var(x), t) | (t, Type::Var(x)) => {
+                if self.occurs(x, &t) {
+                    return Err(UnifyError::Occurs(x, self.resolve(&t)));
+                }
+                self.subst[x] = Some(t);
+                Ok(())
+            },
+            (Type::Function(ps, r), Type::Function(qs, s)) if ps.len() == qs.len() => {
+                for (p, q) in ps.iter().zip(&qs) {
+                    self.unify(p, q)?;
+                }
+                self.unify(&r, &s)
+            },
+            (Type::Array(n, a), Type::Array(m, b)) if n == m => self.unify(&a, &b),
+            (Type::Pointer(a), Type::Pointer(b)) => self.unify(&a, &b),
+            (a, b) if a == b => Ok(()),
+            _ => Err(UnifyError::Mismatch)
+        }
+    }
+
+    /// 型変数vがtyに現れるか
+    fn occurs(&self, v: usize, ty: &Type) -> bool {
+        self.resolve(ty).vars().contains(&v)
+    }
+
+    /// 束縛された型変数を1段だけ解決する
+    fn shallow(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(v) => match &self.subst[*v] {
+                Some(t) => self.shallow(t),
+                None => ty.clone()
+            },
+            _ => ty.clone()
+        }
+    }
+
+    /// 束縛された型変数をすべて解決する
+    fn resolve(&self, ty: &Type) -> Type {
+        map_vars(ty, &|v| self.subst[v].as_ref().map(|t| self.resolve(t)))
+    }
+
+    /// 型環境に自由に現れない型変数を量化する
+    fn generalize(&self, ty: &Type) -> Scheme {
+        let ty = self.resolve(ty);
+        let mut free = vec![];
+        for (_, scheme) in &self.env {
+            free.extend(self.resolve(&scheme.ty).vars().into_iter().filter(|v| !scheme.vars.contains(v)));
+        }
+        let vars = ty.vars().into_iter().filter(|v| !free.contains(v)).collect();
+        Scheme { vars, ty }
+    }
+
+    /// 量化された型変数を新しい型変数で置き換える
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        let fresh: HashMap<usize, Type> = scheme.vars.iter().map(|v| (*v, self.fresh())).collect();
+        map_vars(&scheme.ty, &|v| fresh.get(&v).cloned())
+    }
+
+    /// 型を解決し、共通の名前付けで文字列にする
+    fn show(&self, types: &[&Type]) -> Vec<String> {
+        let resolved: Vec<Type> = types.iter().map(|t| self.resolve(t)).collect();
+        let mut order = vec![];
+        for ty in &resolved {
+            order.extend(ty.vars().into_iter().filter(|v| !order.contains(v)).collect::<Vec<usize>>());
+        }
+        resolved.iter().map(|t| rename(t, &order).to_string()).collect()
+    }
+}
+
+/// 関数型の部分言語を構文解析する
+/// term -> let [rec] id = term in term | fun id -> term | if term then term else term | binary
+/// binary -> app (op app)*
+/// app -> atom atom*
+/// atom -> num | true | false | id | ( term )
+///
+/// # params
+/// - program: &str => ソース
+///
+/// # returns
+/// - Result<Term, Diagnostic>
+pub fn parse_term(program: &str) -> Result<Term, Diagnostic> {
+    let mut lexer = Lexer::new(program.to_string());
+    for keyword in &["let", "rec", "in", "fun", "if", "then", "else"] {
+        lexer.reserve(Token::new_word(Tag::Keyword, keyword));
+    }
+    let tokens: Vec<(Token, Span)> = std::iter::from_fn(|| lexer.scan_spanned()).collect();
+    if let Some(diag) = lexer.take_diagnostics().into_iter().next() {
+        return Err(diag);
+    }
+    let mut parser = TermParser { tokens, pos: 0 };
+    let term = parser.term()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(term),
+        Some((token, span)) => Err(Diagnostic::error(*span, format!("syntax error: unexpected `{}`", token)))
+    }
+}
+
+/// 関数型の部分言語の再帰下降構文解析器
+struct TermParser {
+    tokens: Vec<(Token, Span)>,
+    pos: usize
+}
+
+impl TermParser {
+    fn term(&mut self) -> Result<Term, Diagnostic> {
+        let start = self.span();
+        if self.eat("let") {
+            let rec = self.eat("rec");
+            let name = self.ident()?;
+            self.expect("=")?;
+            let value = self.term()?;
+            self.expect("in")?;
+            let body = self.term()?;
+            let span = start.to(body.span);
+            let kind = if rec {
+                TermKind::LetRec(name, Box::new(value), Box::new(body))
+            } else {
+                TermKind::Let(name, Box::new(value), Box::new(body))
+            };
+            Ok(Term { kind, span })
+        } else if self.eat("fun") {
+            let param = self.ident()?;
+            self.expect("->")?;
+            let body = self.term()?;
+            let span = start.to(body.span);
+            Ok(Term { kind: TermKind::Fun(param, Box::new(body)), span })
+        } else if self.eat("if") {
+            let cond = self.term()?;
+            self.expect("then")?;
+            let then = self.term()?;
+            self.expect("else")?;
+            let els = self.term()?;
+            let span = start.to(els.span);
+            Ok(Term { kind: TermKind::If(Box::new(cond), Box::new(then), Box::new(els)), span })
+        } else {
+            self.binary(1)
+        }
+    }
+
+    fn binary(&mut self, min_prec: usize) -> Result<Term, Diagnostic> {
+        let mut lhs = self.app()?;
+        loop {
+            let op = match self.tokens.get(self.pos) {
+                Some((Token::Word { lexeme, .. }, _)) => BinOp::from_lexeme(lexeme),
+                _ => None
+            };
+            match op {
+                Some(op) if op.precedence() >= min_prec => {
+                    self.pos += 1;
+                    let rhs = self.binary(op.precedence()+1)?;
+                    let span = lhs.span.to(rhs.span);
+                    lhs = Term { kind: TermKind::Binary(op, Box::new(lhs), Box::new(rhs)), span };
+                },
+                _ => return Ok(lhs)
+            }
+        }
+    }
+
+    fn app(&mut self) -> Result<Term, Diagnostic> {
+        let mut f = self.atom()?;
+        while self.starts_atom() {
+            let arg = self.atom()?;
+            let span = f.span.to(arg.span);
+            f = Term { kind: TermKind::App(Box::new(f), Box::new(arg)), span };
+        }
+        Ok(f)
+    }
+
+    fn starts_atom(&self) -> bool {
+        match self.tokens.get(self.pos) {
+            Some((Token::NumI32 { .. }, _)) | Some((Token::NumF32 { .. }, _)) => true,
+            Some((Token::Word { tag: Tag::Id, .. }, _)) | Some((Token::Word { tag: Tag::Primary, .. }, _)) => true,
+            Some((Token::Word { lexeme, .. }, _)) => lexeme == "(",
+            None => false
+        }
+    }
+
+    fn atom(&mut self) -> Result<Term, Diagnostic> {
+        let span = self.span();
+        let kind = match self.tokens.get(self.pos).map(|(t, _)| t.clone()) {
+            Some(Token::NumI32 { num }) => TermKind::Int(num),
+            Some(Token::NumF32 { num }) => TermKind::Float(num),
+            Some(Token::Word { tag: Tag::Primary, lexeme }) => TermKind::Bool(lexeme == "true"),
+            Some(Token::Word { tag: Tag::Id, lexeme }) => TermKind::Var(lexeme),
+            Some(Token::Word { lexeme, .. }) if lexeme == "(" => {
+                self.pos += 1;
+                let mut term = self.term()?;
+                let end = self.expect(")")?;
+                term.span = span.to(end);
+                return Ok(term);
+            },
+            _ => return Err(self.error("term"))
+        };
+        self.pos += 1;
+        Ok(Term { kind, span })
+    }
+
+    fn ident(&mut self) -> Result<String, Diagnostic> {
+        match self.tokens.get(self.pos) {
+            Some((Token::Word { tag: Tag::Id, lexeme }, _)) => {
+                self.pos += 1;
+                Ok(lexeme.clone())
+            },
+            _ => Err(self.error("identifier"))
+        }
+    }
+
+    fn eat(&mut self, lexeme: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some((Token::Word { lexeme: l, .. }, _)) if l == lexeme => {
+                self.pos += 1;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn expect(&mut self, lexeme: &str) -> Result<Span, Diagnostic> {
+        let span = self.span();
+        if self.eat(lexeme) {
+            Ok(span)
+        } else {
+            Err(self.error(&format!("`{}`", lexeme)))
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self.tokens.get(self.pos) {
+            Some((_, span)) => *span,
+            None => self.tokens.last().map(|(_, s)| Span::new(s.line, s.col+s.end-s.start, s.end, s.end)).unwrap_or_default()
+        }
+    }
+
+    fn error(&self, expected: &str) -> Diagnostic {
+        let found = match self.tokens.get(self.pos) {
+            Some((token, _)) => format!("`{}`", token),
+            None => "end of input".to_string()
+        };
+        Diagnostic::error(self.span(), format!("syntax error: expected {}, found {}", expected, found))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_term, Infer, Scheme};
+    use super::super::super::chapter2::mtype::Type;
+    use super::super::super::chapter2::token::Span;
+
+    fn infer(program: &str) -> String {
+        match Infer::new().infer(&parse_term(program).unwrap()) {
+            Ok(scheme) => scheme.to_string(),
+            Err(diag) => diag.message
+        }
+    }
+
+    #[test]
+    fn infer_polymorphism_test() {
+        assert_eq!("forall 'a. fn('a) -> 'a", infer("let id = fun x -> x in id"));
+        assert_eq!("i32", infer("let id = fun x -> x in if id true then id 1 else 2"));
+        assert_eq!("forall 'a 'b 'c. fn(fn('a) -> 'b) -> fn(fn('c) -> 'a) -> fn('c) -> 'b",
+            infer("fun f -> fun g -> fun x -> f (g x)"));
+        assert_eq!("forall 'a 'b. fn('a) -> fn('b) -> 'a", infer("fun x -> fun y -> x"));
+        assert_eq!("fn(i32) -> i32", infer("let rec fact = fun n -> if n == 0 then 1 else n * fact (n - 1) in fact"));
+    }
+
+    #[test]
+    fn infer_error_test() {
+        // λで束縛した変数は単相なので、異なる型には適用できない
+        let term = parse_term("fun f -> if f true then f 1 else 0").unwrap();
+        let diag = Infer::new().infer(&term).err().unwrap();
+        assert_eq!("mismatched types: expected `bool`, found `i32`", diag.message);
+        assert_eq!(Span::new(1, 27, 26, 27), diag.span);
+        assert_eq!((Span::new(1, 25, 24, 25), "function has type `fn(bool) -> bool`".to_string()), diag.notes[0]);
+
+        assert_eq!("infinite type: `'a` occurs in `fn('a) -> 'b`", infer("fun x -> x x"));
+        assert_eq!("mismatched types: expected `i32`, found `bool`", infer("if true then 1 else false"));
+        assert_eq!("expected a function, found `i32`", infer("1 2"));
+        assert_eq!("unbound variable `y`", infer("fun x -> y"));
+        assert!(parse_term("let x = in x").is_err());
+    }
+
+    #[test]
+    fn infer_builtin_test() {
+        // 組み込み関数 deref : ∀a. *a -> a のような多相型も mtype::Type で表せる
+        let mut infer = Infer::new();
+        let a = infer.fresh();
+        infer.bind("deref", Scheme { vars: a.vars(), ty: Type::new_function(vec![Type::new_pointer(a.clone())], a) });
+        let b = infer.fresh();
+        infer.bind("null", Scheme { vars: b.vars(), ty: Type::new_pointer(b) });
+        infer.bind("p", Scheme::mono(Type::new_pointer(Type::new_f32())));
+        assert_eq!("f32", infer.infer(&parse_term("deref p").unwrap()).unwrap().to_string());
+        assert_eq!("forall 'a. 'a", infer.infer(&parse_term("deref null").unwrap()).unwrap().to_string());
+        let diag = infer.infer(&parse_term("deref 1").unwrap()).err().unwrap();
+        assert_eq!("mismatched types: expected `*'a`, found `i32`", diag.message);
+    }
+}
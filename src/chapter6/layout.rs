@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use super::super::chapter2::ast::{Block, Decl, NodeId};
+use super::super::chapter2::symbol::{Symbol, SymbolTable};
+use super::super::chapter2::token::Span;
+use super::super::chapter2::visit::{self, Visitor};
+
+/// 1つのブロックが活性レコード内で使う領域
+///
+/// # members
+/// - block: NodeId => ブロックの節番号
+/// - span: Span => ブロックの位置
+/// - depth: usize => 入れ子の深さ(最も外側が0)
+/// - base: usize => 領域の先頭の相対アドレス
+/// - size: usize => 内側のブロックも含めた領域の大きさ
+/// - layout: String => このブロックで宣言された名前の配置(SymbolTable::layout)
+///
+/// # derive
+/// - Clone
+/// - Debug
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub block: NodeId,
+    pub span: Span,
+    pub depth: usize,
+    pub base: usize,
+    pub size: usize,
+    pub layout: String
+}
+
+/// 宣言を処理して名前に相対アドレスを割り付ける(6.3.5節)
+/// 入れ子のブロックは親の変数の後ろに、兄弟のブロックは同じ位置に割り付けるので、
+/// 活性レコードの大きさは同時に生きうる変数の領域の最大になる
+///
+/// # members
+/// - table: SymbolTable => 現在のブロックの記号表
+/// - frames: Vec<Frame> => ブロックごとの領域(前順)
+/// - symbols: HashMap<NodeId, Symbol> => 宣言の節番号ごとの記号
+/// - depth: usize => 現在のブロックの入れ子の深さ
+pub struct StorageLayout {
+    table: SymbolTable,
+    frames: Vec<Frame>,
+    symbols: HashMap<NodeId, Symbol>,
+    depth: usize
+}
+
+impl StorageLayout {
+    /// ブロック中のすべての宣言に相対アドレスを割り付ける
+    ///
+    /// # params
+    /// - block: &Block => 手続き本体のブロック
+    ///
+    /// # returns
+    /// - StorageLayout
+    pub fn of(block: &Block) -> StorageLayout {
+        let mut layout = StorageLayout { table: SymbolTable::new(), frames: vec![], symbols: HashMap::new(), depth: 0 };
+        layout.visit_block(block);
+        layout
+    }
+
+    /// 宣言に割り付けた記号を返す
+    ///
+    /// # params
+    /// - decl: NodeId => 宣言の節番号
+    ///
+    /// # returns
+    /// - Option<&Symbol>
+    pub fn symbol(&self, decl: NodeId) -> Option<&Symbol> {
+        self.symbols.get(&decl)
+    }
+
+    /// ブロックごとの領域を前順で返す
+    ///
+    /// # returns
+    /// - &[Frame]
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// 活性レコードの大きさ(最大の境界に切り上げたもの)を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn frame_size(&self) -> usize {
+        self.table.frame_size()
+    }
+
+    /// 配置を字下げした表にする
+    ///
+    /// # returns
+    /// - String
+    pub fn dump(&self) -> String {
+        let mut out = format!("frame size {}\n", self.frame_size());
+        for frame in &self.frames {
+            let indent = "    ".repeat(frame.depth);
+            out.push_str(&format!("{}block {}:{} [{}, {})\n", indent, frame.span.line, frame.span.col, frame.base, frame.base+frame.size));
+            for line in frame.layout.lines() {
+                out.push_str(&format!("{}{}\n", indent, line));
+            }
+        }
+        out
+    }
+}
+
+impl Visitor for StorageLayout {
+    fn visit_block(&mut self, block: &Block) {
+        self.table.enter(block.span);
+        let index = self.frames.len();
+        self.frames.push(Frame { block: block.id, span: block.span, depth: self.depth, base: self.table.offset(), size: 0, layout: String::new() });
+        self.depth += 1;
+        visit::walk_block(self, block);
+        self.depth -= 1;
+        self.frames[index].size = self.table.size();
+        self.frames[index].layout = self.table.layout();
+        self.table.exit(block.span);
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        if let Ok(symbol) = self.table.insert(Symbol::new(decl.name.clone(), decl.ty.clone()).with_span(decl.span)) {
+            self.symbols.insert(decl.id, symbol);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StorageLayout;
+    use super::super::super::chapter2::ast::StmtKind;
+    use super::super::super::chapter2::parser::astparser::AstParser;
+
+    #[test]
+    fn layout_test() {
+        let src = "{ char c; i32 x; f32[3] a;\n  { char d; i32 y; }\n  { f32 z; }\n  i32 n; }";
+        let block = AstParser::new(src).parse_block().unwrap();
+        let layout = StorageLayout::of(&block);
+        assert_eq!("\
+frame size 28
+block 1:1 [0, 28)
+   0    1 c: char
+   4    4 x: i32
+   8   12 a: [3]f32
+  20    4 n: i32
+    block 2:3 [20, 28)
+      20    1 d: char
+      24    4 y: i32
+    block 3:3 [20, 24)
+      20    4 z: f32
+", layout.dump());
+        match &block.stmts[2].kind {
+            StmtKind::Decl(decl) => assert_eq!(Some(8), layout.symbol(decl.id).map(|s| s.offset)),
+            _ => panic!("test failed at [layout_test]")
+        }
+        assert_eq!(3, layout.frames().len());
+    }
+}
@@ -0,0 +1,813 @@
+use super::super::chapter2::ast::{BinOp, Block, Expr, ExprKind, Program, Stmt, StmtKind, UnOp};
+use super::super::chapter2::diagnostic::{Diagnostic, Level};
+use super::super::chapter2::mtype::Type;
+use super::super::chapter2::symbol::{Symbol, SymbolKind};
+use super::tac::{Constant, Opcode, Operand, Procedure, Quad, Quadruples};
+use super::typeck::TypeChecker;
+
+/// 条件の飛び越しコードで、飛ばずに次の命令へ落ちる側(6.6.5節)
+enum Fall {
+    True,
+    False
+}
+
+/// これ以下の数のcaseは、値を順に比べて振り分ける
+const LINEAR_CASES: usize = 3;
+
+/// 式と文の3番地コードへの翻訳(6.4節, 6.6節, 6.7節, 6.8節)
+/// 型検査で挿入した拡大変換は変換命令に、配列参照 a[i][j] は要素の幅を使った番地の計算にする
+/// 一時変数は式の木の中で後入れ先出しで使われるので、使い終わったものから番号を再利用できる
+/// 条件と制御の流れは、飛び先を空けた飛び越し命令の番号の並び(truelist, falselist, nextlist)を
+/// 後から埋めるバックパッチで1パスで翻訳し、続く命令へ落ちられる側には飛び越し命令を出さない
+/// 関数は手続きごとに別のコードにし、呼出しは実引数をすべて評価してから param を並べて call する(6.9節)
+/// 型の誤りがあるときはコードを生成しない(警告だけなら生成する)
+///
+/// # members
+/// - checker: TypeChecker => 式の型と名前の記号を引く型検査器
+/// - code: Quadruples => 生成したコード
+/// - reuse: bool => 一時変数を再利用するか
+/// - next_temp: usize => 次に割り当てる一時変数の番号
+/// - temps: usize => 使った一時変数の数
+/// - breaks: Vec<Vec<usize>> => 翻訳中のループとswitchごとの、飛び先を後から埋めるbreakの命令の番号(内側が末尾)
+/// - continues: Vec<Vec<usize>> => 翻訳中のループごとの、飛び先を後から埋めるcontinueの命令の番号(内側が末尾)
+/// - procedures: Vec<Procedure> => 翻訳した手続き
+pub struct Lowerer {
+    checker: TypeChecker,
+    code: Quadruples,
+    reuse: bool,
+    next_temp: usize,
+    temps: usize,
+    breaks: Vec<Vec<usize>>,
+    continues: Vec<Vec<usize>>,
+    procedures: Vec<Procedure>
+}
+
+impl Default for Lowerer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lowerer {
+    /// 一時変数を再利用するLowererを生成して返す
+    ///
+    /// # returns
+    /// - Lowerer
+    pub fn new() -> Lowerer {
+        Lowerer { checker: TypeChecker::new(), code: Quadruples::new(), reuse: true, next_temp: 1, temps: 0, breaks: vec![], continues: vec![], procedures: vec![] }
+    }
+
+    /// 一時変数を再利用するかを設定する
+    ///
+    /// # params
+    /// - reuse: bool => 再利用するならtrue
+    ///
+    /// # returns
+    /// - Lowerer
+    pub fn with_reuse(mut self, reuse: bool) -> Lowerer {
+        self.reuse = reuse;
+        self
+    }
+
+    /// ブロックを型検査し、誤りがなければ3番地コードに翻訳する
+    /// ブロックを抜ける飛び越しは、コードの末尾(最後の命令の次の番号)へ飛ぶ
+    ///
+    /// # params
+    /// - block: Block => ブロック
+    pub fn lower_block(&mut self, block: Block) {
+        let block = self.checker.check_block(block);
+        if self.checker.diagnostics().iter().all(|d| d.level != Level::Error) {
+            let next = self.block(&block);
+            self.backpatch(&next, self.code.next());
+        }
+    }
+
+    /// プログラムを型検査し、誤りがなければ本体のある関数をそれぞれ手続きに翻訳する
+    /// 本体の終わりへ落ちる、または飛ぶ場合は、値のない return を補う
+    ///
+    /// # params
+    /// - program: Program => プログラム
+    pub fn lower_program(&mut self, program: Program) {
+        let program = self.checker.check_program(program);
+        if self.checker.diagnostics().iter().any(|d| d.level == Level::Error) {
+            return;
+        }
+        for func in &program.funcs {
+            let body = match &func.body {
+                Some(body) => body,
+                None => continue
+            };
+            self.code = Quadruples::new();
+            self.next_temp = 1;
+            self.temps = 0;
+            let next = self.block(body);
+            let returns = matches!(self.code.quads.last(), Some(quad) if quad.op == Opcode::Return);
+            self.backpatch(&next, self.code.next());
+            // 値を返す関数はどの経路もreturnすることを型検査で確かめたので、末尾へ飛ぶ命令には到達しない
+            if func.ret == Type::Void && (!next.is_empty() || !returns) {
+                self.code.push(Quad::ret(None));
+            }
+            let name = Symbol::new(func.name.clone(), func.ty()).with_kind(SymbolKind::Func).with_span(func.span);
+            let params = func.params.iter()
+                .map(|p| self.checker.symbol(p.id).cloned().expect("parameters are resolved before lowering"))
+                .collect();
+            let code = std::mem::take(&mut self.code);
+            self.procedures.push(Procedure { name, params, code, temps: self.temps });
+        }
+    }
+
+    /// 式を型検査し、誤りがなければ3番地コードに翻訳して値の番地を返す
+    ///
+    /// # params
+    /// - expr: Expr => 式
+    ///
+    /// # returns
+    /// - Option<Operand>
+    pub fn lower_expr(&mut self, expr: Expr) -> Option<Operand> {
+        let expr = self.checker.check_expr(expr);
+        if self.checker.diagnostics().iter().all(|d| d.level != Level::Error) {
+            Some(self.expr(&expr))
+        } else {
+            None
+        }
+    }
+
+    /// 生成したコードを返す
+    ///
+    /// # returns
+    /// - &Quadruples
+    pub fn code(&self) -> &Quadruples {
+        &self.code
+    }
+
+    /// 翻訳した手続きを関数の順に返す
+    ///
+    /// # returns
+    /// - &[Procedure]
+    pub fn procedures(&self) -> &[Procedure] {
+        &self.procedures
+    }
+
+    /// 使った一時変数の数を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn temps(&self) -> usize {
+        self.temps
+    }
+
+    /// 型の誤りを返す
+    ///
+    /// # returns
+    /// - &[Diagnostic]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.checker.diagnostics()
+    }
+
+    fn block(&mut self, block: &Block) -> Vec<usize> {
+        self.stmts(&block.stmts, vec![])
+    }
+
+    /// L → L1 M S: 前の文のnextlistを次の文の先頭で埋める
+    fn stmts(&mut self, stmts: &[Stmt], mut next: Vec<usize>) -> Vec<usize> {
+        for stmt in stmts {
+            self.backpatch(&next, self.code.next());
+            next = self.stmt(stmt);
+        }
+        next
+    }
+
+    /// 文を翻訳し、文の後へ飛ぶ命令の番号(S.nextlist)を返す
+    fn stmt(&mut self, stmt: &Stmt) -> Vec<usize> {
+        match &stmt.kind {
+            StmtKind::Decl(_) => vec![],
+            // 値を返さない関数の呼出しは文としてだけ現れる
+            StmtKind::Expr(expr @ Expr { kind: ExprKind::Call(..), .. }) => {
+                if let Some(addr) = self.call(expr) {
+                    self.release(&addr);
+                }
+                vec![]
+            },
+            StmtKind::Expr(expr) => {
+                let addr = self.expr(expr);
+                self.release(&addr);
+                vec![]
+            },
+            // S → id = E ;
+            StmtKind::Assign(Expr { kind: ExprKind::Var(_), id, .. }, expr) => {
+                let addr = self.expr(expr);
+                self.release(&addr);
+                let name = self.name(*id);
+                self.code.push(Quad::copy(name, addr));
+                vec![]
+            },
+            // S → L = E ;
+            StmtKind::Assign(loc, expr) => {
+                let (array, offset) = self.location(loc);
+                let addr = self.expr(expr);
+                self.release(&addr);
+                self.release(&offset);
+                self.code.push(Quad::index_store(Operand::Name(array), offset, addr));
+                vec![]
+            },
+            // S → if ( B ) M S1
+            StmtKind::If(cond, then, None) => {
+                let (truelist, falselist) = self.cond(cond, Fall::True);
+                self.backpatch(&truelist, self.code.next());
+                let mut next = falselist;
+                next.extend(self.stmt(then));
+                next
+            },
+            // S → if ( B ) M1 S1 N else M2 S2
+            StmtKind::If(cond, then, Some(els)) => {
+                let (truelist, falselist) = self.cond(cond, Fall::True);
+                self.backpatch(&truelist, self.code.next());
+                let mut next = self.stmt(then);
+                next.push(self.code.push(Quad::goto(0)));
+                self.backpatch(&falselist, self.code.next());
+                next.extend(self.stmt(els));
+                next
+            },
+            // S → while M1 ( B ) M2 S1
+            StmtKind::While(cond, body) => {
+                let begin = self.code.next();
+                let (truelist, falselist) = self.cond(cond, Fall::True);
+                self.backpatch(&truelist, self.code.next());
+                self.breaks.push(vec![]);
+                self.continues.push(vec![]);
+                let next = self.stmt(body);
+                self.backpatch(&next, begin);
+                self.code.push(Quad::goto(begin));
+                let continues = self.continues.pop().unwrap();
+                self.backpatch(&continues, begin);
+                let mut next = falselist;
+                next.extend(self.breaks.pop().unwrap());
+                next
+            },
+            // S → do M1 S1 while M2 ( B ) ;
+            StmtKind::DoWhile(body, cond) => {
+                let begin = self.code.next();
+                self.breaks.push(vec![]);
+                self.continues.push(vec![]);
+                let next = self.stmt(body);
+                let test = self.code.next();
+                self.backpatch(&next, test);
+                let (truelist, falselist) = self.cond(cond, Fall::False);
+                self.backpatch(&truelist, begin);
+                let continues = self.continues.pop().unwrap();
+                self.backpatch(&continues, test);
+                let mut next = falselist;
+                next.extend(self.breaks.pop().unwrap());
+                next
+            },
+            // 振り分けのコードの後に各節の文を並べ、節の終わりは次の節へ落ちる
+            StmtKind::Switch(expr, cases) => {
+                let addr = self.expr(expr);
+                let mut labels: Vec<(i32, usize)> = cases.iter().enumerate()
+                    .filter_map(|(i, case)| case.value.map(|value| (value, i)))
+                    .collect();
+                labels.sort_unstable();
+                let mut targets = vec![vec![]; cases.len()];
+                let mut others = vec![];
+                self.dispatch(&addr, &labels, &mut targets, &mut others);
+                self.release(&addr);
+                self.breaks.push(vec![]);
+                let mut next = vec![];
+                for (case, jumps) in cases.iter().zip(targets) {
+                    self.backpatch(&jumps, self.code.next());
+                    if case.value.is_none() {
+                        self.backpatch(&others, self.code.next());
+                        others.clear();
+                    }
+                    next = self.stmts(&case.stmts, next);
+                }
+                next.extend(others);
+                next.extend(self.breaks.pop().unwrap());
+                next
+            },
+            // 構文解析でループの外のbreakとcontinueは誤りにしている
+            StmtKind::Break => {
+                let jump = self.code.push(Quad::goto(0));
+                self.breaks.last_mut().expect("break inside a loop or switch").push(jump);
+                vec![]
+            },
+            StmtKind::Continue => {
+                let jump = self.code.push(Quad::goto(0));
+                self.continues.last_mut().expect("continue inside a loop").push(jump);
+                vec![]
+            },
+            StmtKind::Return(value) => {
+                let addr = value.as_ref().map(|value| self.expr(value));
+                if let Some(addr) = &addr {
+                    self.release(addr);
+                }
+                self.code.push(Quad::ret(addr));
+                vec![]
+            },
+            StmtKind::Block(block) => self.block(block)
+        }
+    }
+
+    /// 条件を飛び越しコードに翻訳し、真のときと偽のときに飛ぶ命令の番号(B.truelist, B.falselist)を返す
+    /// fallの側は飛ばずにコードの直後へ落ちる(&&, || の途中から直後へ飛ぶ命令はリストに入る)
+    fn cond(&mut self, expr: &Expr, fall: Fall) -> (Vec<usize>, Vec<usize>) {
+        match &expr.kind {
+            // B → B1 || M B2
+            ExprKind::Binary(BinOp::Or, lhs, rhs) => {
+                let (mut truelist, falselist) = self.cond(lhs, Fall::False);
+                self.backpatch(&falselist, self.code.next());
+                let (t, falselist) = self.cond(rhs, fall);
+                truelist.extend(t);
+                (truelist, falselist)
+            },
+            // B → B1 && M B2
+            ExprKind::Binary(BinOp::And, lhs, rhs) => {
+                let (truelist, mut falselist) = self.cond(lhs, Fall::True);
+                self.backpatch(&truelist, self.code.next());
+                let (truelist, f) = self.cond(rhs, fall);
+                falselist.extend(f);
+                (truelist, falselist)
+            },
+            // B → ! B1
+            ExprKind::Unary(UnOp::Not, operand) => {
+                let fall = match fall {
+                    Fall::True => Fall::False,
+                    Fall::False => Fall::True
+                };
+                let (truelist, falselist) = self.cond(operand, fall);
+                (falselist, truelist)
+            },
+            ExprKind::Bool(b) => match (b, fall) {
+                (true, Fall::True) | (false, Fall::False) => (vec![], vec![]),
+                (true, Fall::False) => (vec![self.code.push(Quad::goto(0))], vec![]),
+                (false, Fall::True) => (vec![], vec![self.code.push(Quad::goto(0))])
+            },
+            // B → E1 rel E2
+            ExprKind::Binary(op, lhs, rhs) if op.is_relational() => {
+                let l = self.expr(lhs);
+                let r = self.expr(rhs);
+                self.release(&r);
+                self.release(&l);
+                match fall {
+                    Fall::True => (vec![], vec![self.code.push(Quad::if_false_rel(*op, l, r, 0))]),
+                    Fall::False => (vec![self.code.push(Quad::if_rel(*op, l, r, 0))], vec![])
+                }
+            },
+            _ => {
+                let addr = self.expr(expr);
+                self.release(&addr);
+                match fall {
+                    Fall::True => (vec![], vec![self.code.push(Quad::if_false(addr, 0))]),
+                    Fall::False => (vec![self.code.push(Quad::if_true(addr, 0))], vec![])
+                }
+            }
+        }
+    }
+
+    /// switchの値addrを節へ振り分ける
+    /// labelsは(caseの値, 節の番号)を値の順に並べたもので、節へ飛ぶ命令はtargetsに、
+    /// どのcaseにも当たらないときの命令はothersに加える
+    /// - caseが少なければ値を順に比べる
+    /// - 値が密に並んでいれば(範囲がcaseの数の2倍以下)、範囲を確かめてから飛び越し表で飛ぶ
+    /// - それ以外は、値の中央で二分して比べる木にする
+    fn dispatch(&mut self, addr: &Operand, labels: &[(i32, usize)], targets: &mut [Vec<usize>], others: &mut Vec<usize>) {
+        let n = labels.len();
+        if n <= LINEAR_CASES {
+            for (value, i) in labels {
+                let jump = self.code.push(Quad::if_rel(BinOp::Eq, addr.clone(), Operand::Const(Constant::Int(*value)), 0));
+                targets[*i].push(jump);
+            }
+            others.push(self.code.push(Quad::goto(0)));
+            return;
+        }
+        let (min, max) = (labels[0].0, labels[n-1].0);
+        let range = (max as i64 - min as i64 + 1) as usize;
+        if range <= 2*n {
+            others.push(self.code.push(Quad::if_rel(BinOp::Lt, addr.clone(), Operand::Const(Constant::Int(min)), 0)));
+            others.push(self.code.push(Quad::if_rel(BinOp::Gt, addr.clone(), Operand::Const(Constant::Int(max)), 0)));
+            let index = if min == 0 {
+                addr.clone()
+            } else {
+                let temp = self.temp();
+                self.code.push(Quad::binary(BinOp::Sub, temp.clone(), addr.clone(), Operand::Const(Constant::Int(min))));
+                temp
+            };
+            self.release(&index);
+            self.code.push(Quad::jump_table(index, range));
+            let mut labels = labels.iter().peekable();
+            for value in min as i64..=max as i64 {
+                let jump = self.code.push(Quad::goto(0));
+                match labels.next_if(|(v, _)| *v as i64 == value) {
+                    Some((_, i)) => targets[*i].push(jump),
+                    None => others.push(jump)
+                }
+            }
+        } else {
+            let (value, _) = labels[n/2];
+            let jump = self.code.push(Quad::if_rel(BinOp::Ge, addr.clone(), Operand::Const(Constant::Int(value)), 0));
+            self.dispatch(addr, &labels[..n/2], targets, others);
+            self.backpatch(&[jump], self.code.next());
+            self.dispatch(addr, &labels[n/2..], targets, others);
+        }
+    }
+
+    /// リストの飛び越し命令の飛び先をtargetで埋める
+    fn backpatch(&mut self, list: &[usize], target: usize) {
+        for i in list {
+            self.code.quads[*i].set_target(target);
+        }
+    }
+
+    /// 式を翻訳し、値を持つ番地(E.addr)を返す
+    fn expr(&mut self, expr: &Expr) -> Operand {
+        match &expr.kind {
+            ExprKind::Int(num) => Operand::Const(Constant::Int(*num)),
+            ExprKind::Float(num) => Operand::Const(Constant::Float(*num)),
+            ExprKind::Bool(b) => Operand::Const(Constant::Bool(*b)),
+            ExprKind::Var(_) => self.name(expr.id),
+            // E → L
+            ExprKind::Index(..) => {
+                let (array, offset) = self.location(expr);
+                self.release(&offset);
+                let temp = self.temp();
+                self.code.push(Quad::index_load(temp.clone(), Operand::Name(array), offset));
+                temp
+            },
+            ExprKind::Unary(op, operand) => {
+                let addr = self.expr(operand);
+                self.release(&addr);
+                let temp = self.temp();
+                self.code.push(Quad::unary(*op, temp.clone(), addr));
+                temp
+            },
+            // 値として使う && と || は、飛び越しコードで真偽値を一時変数に入れる
+            ExprKind::Binary(BinOp::And, ..) | ExprKind::Binary(BinOp::Or, ..) => {
+                let (truelist, falselist) = self.cond(expr, Fall::True);
+                let temp = self.temp();
+                self.backpatch(&truelist, self.code.next());
+                self.code.push(Quad::copy(temp.clone(), Operand::Const(Constant::Bool(true))));
+                let jump = self.code.push(Quad::goto(0));
+                self.backpatch(&falselist, self.code.next());
+                self.code.push(Quad::copy(temp.clone(), Operand::Const(Constant::Bool(false))));
+                self.backpatch(&[jump], self.code.next());
+                temp
+            },
+            ExprKind::Binary(op, lhs, rhs) => {
+                let l = self.expr(lhs);
+                let r = self.expr(rhs);
+                self.release(&r);
+                self.release(&l);
+                let temp = self.temp();
+                self.code.push(Quad::binary(*op, temp.clone(), l, r));
+                temp
+            },
+            ExprKind::Widen(ty, operand) => {
+                let addr = self.expr(operand);
+                self.release(&addr);
+                let temp = self.temp();
+                self.code.push(Quad::conv(ty.clone(), temp.clone(), addr));
+                temp
+            },
+            ExprKind::Call(..) => self.call(expr).expect("a call used as a value returns a value")
+        }
+    }
+
+    /// 関数呼出しを翻訳し、戻り値があればその番地を返す
+    /// S → call id ( Elist ): 実引数をすべて評価してから param を並べるので、
+    /// 実引数の中の呼出しの param が外側の param の間に混ざらない
+    fn call(&mut self, expr: &Expr) -> Option<Operand> {
+        let args = match &expr.kind {
+            ExprKind::Call(_, args) => args,
+            _ => unreachable!("call must be a function call")
+        };
+        let addrs: Vec<Operand> = args.iter().map(|arg| self.expr(arg)).collect();
+        for addr in addrs.iter().rev() {
+            self.release(addr);
+        }
+        for addr in &addrs {
+            self.code.push(Quad::param(addr.clone()));
+        }
+        let result = match self.checker.type_of(expr.id) {
+            Some(Type::Void) | None => None,
+            Some(_) => Some(self.temp())
+        };
+        self.code.push(Quad::call(result.clone(), self.name(expr.id), args.len()));
+        result
+    }
+
+    /// 配列参照を翻訳し、配列の記号と先頭からのバイト数(L.array, L.addr)を返す
+    /// L → id [ E ] | L1 [ E ]
+    fn location(&mut self, expr: &Expr) -> (Symbol, Operand) {
+        let (array, index) = match &expr.kind {
+            ExprKind::Index(array, index) => (array, index),
+            _ => unreachable!("location must be an array reference")
+        };
+        let width = self.checker.type_of(expr.id).map(|ty| ty.width()).unwrap_or(0) as i32;
+        match &array.kind {
+            ExprKind::Var(_) => {
+                let symbol = self.symbol(array.id);
+                let addr = self.expr(index);
+                self.release(&addr);
+                let offset = self.temp();
+                self.code.push(Quad::binary(BinOp::Mul, offset.clone(), addr, Operand::Const(Constant::Int(width))));
+                (symbol, offset)
+            },
+            _ => {
+                let (symbol, base) = self.location(array);
+                let addr = self.expr(index);
+                self.release(&addr);
+                let t = self.temp();
+                self.code.push(Quad::binary(BinOp::Mul, t.clone(), addr, Operand::Const(Constant::Int(width))));
+                self.release(&t);
+                self.release(&base);
+                let offset = self.temp();
+                self.code.push(Quad::binary(BinOp::Add, offset.clone(), base, t));
+                (symbol, offset)
+            }
+        }
+    }
+
+    fn symbol(&self, id: usize) -> Symbol {
+        self.checker.symbol_of(id).cloned().expect("names are resolved before lowering")
+    }
+
+    fn name(&self, id: usize) -> Operand {
+        Operand::Name(self.symbol(id))
+    }
+
+    /// 新しい一時変数を割り当てる
+    fn temp(&mut self) -> Operand {
+        let temp = self.next_temp;
+        self.next_temp += 1;
+        self.temps = self.temps.max(temp);
+        Operand::Temp(temp)
+    }
+
+    /// 使い終わった一時変数を再利用できるようにする
+    /// 直前に割り当てた一時変数から順に使い終わるので、番号を1つ戻せばよい
+    fn release(&mut self, addr: &Operand) {
+        if let (true, Operand::Temp(temp)) = (self.reuse, addr) {
+            if *temp+1 == self.next_temp {
+                self.next_temp -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lowerer;
+    use super::super::super::chapter2::parser::astparser::AstParser;
+
+    fn lower(program: &str, reuse: bool) -> Lowerer {
+        let block = AstParser::new(program).parse_block().unwrap();
+        let mut lowerer = Lowerer::new().with_reuse(reuse);
+        lowerer.lower_block(block);
+        lowerer
+    }
+
+    #[test]
+    fn lower_array_test() {
+        // 図6.22: c + a[i][j] (aは2×3の整数の配列)
+        let program = "{ i32[2][3] a; i32 c; i32 i; i32 j; i32 x; x = c + a[i][j]; a[i][j] = x; }";
+        let lowerer = lower(program, false);
+        assert_eq!("  0: t1 = i * 12
+  1: t2 = j * 4
+  2: t3 = t1 + t2
+  3: t4 = a[t3]
+  4: t5 = c + t4
+  5: x = t5
+  6: t6 = i * 12
+  7: t7 = j * 4
+  8: t8 = t6 + t7
+  9: a[t8] = x
+", lowerer.code().to_string());
+        assert_eq!(8, lowerer.temps());
+
+        let lowerer = lower(program, true);
+        assert_eq!("  0: t1 = i * 12
+  1: t2 = j * 4
+  2: t1 = t1 + t2
+  3: t1 = a[t1]
+  4: t1 = c + t1
+  5: x = t1
+  6: t1 = i * 12
+  7: t2 = j * 4
+  8: t1 = t1 + t2
+  9: a[t1] = x
+", lowerer.code().to_string());
+        assert_eq!(2, lowerer.temps());
+    }
+
+    #[test]
+    fn lower_coercion_test() {
+        let lowerer = lower("{ i32 i; f32 x; char c; x = i + c * 2.5; i = -c; }", true);
+        assert_eq!("  0: t1 = (f32) i
+  1: t2 = (f32) c
+  2: t2 = t2 * 2.5
+  3: t1 = t1 + t2
+  4: x = t1
+  5: t1 = (i32) c
+  6: t1 = minus t1
+  7: i = t1
+", lowerer.code().to_string());
+    }
+
+    #[test]
+    fn lower_expr_test() {
+        // a = b * - c + b * - c (図6.10)
+        let expr = AstParser::new("b * -c + b * -c").parse_expr().unwrap();
+        let mut lowerer = Lowerer::new().with_reuse(false);
+        assert!(lowerer.lower_expr(expr).is_none());
+        assert_eq!("undeclared identifier `b`", lowerer.diagnostics()[0].message);
+        assert!(lowerer.code().quads.is_empty());
+
+        let lowerer = lower("{ i32 a; i32 b; i32 c; a = b * -c + b * -c; }", false);
+        assert_eq!("  0: t1 = minus c
+  1: t2 = b * t1
+  2: t3 = minus c
+  3: t4 = b * t3
+  4: t5 = t2 + t4
+  5: a = t5
+", lowerer.code().to_string());
+        assert_eq!("(5)  =       a       (4)", lowerer.code().to_triples().dump().lines().last().unwrap());
+    }
+
+    #[test]
+    fn lower_fall_test() {
+        // 図6.40: 続く命令へ落ちられる側には飛び越し命令を出さない
+        let lowerer = lower("{ i32 x; i32 y; if (x < 100 || x > 200 && x != y) x = 0; }", true);
+        assert_eq!("  0: if x < 100 goto 3
+  1: ifFalse x > 200 goto 4
+  2: ifFalse x != y goto 4
+  3: x = 0
+", lowerer.code().to_string());
+        assert_eq!(lowerer.code().to_string(), lowerer.code().to_triples().to_quadruples().to_string());
+
+        let lowerer = lower("{ i32 x; i32 y; bool b; b = x < 1 && y > 2; if (b) x = 1; else { x = 2; y = 3; } }", true);
+        assert_eq!("  0: ifFalse x < 1 goto 4
+  1: ifFalse y > 2 goto 4
+  2: t1 = true
+  3: goto 5
+  4: t1 = false
+  5: b = t1
+  6: ifFalse b goto 9
+  7: x = 1
+  8: goto 11
+  9: x = 2
+ 10: y = 3
+", lowerer.code().to_string());
+    }
+
+    #[test]
+    fn lower_loop_test() {
+        let program = "{ i32 i; bool b;
+            while (i < 10) { if (!b) continue; i = i + 1; if (i == 5) break; }
+            do i = i - 1; while (i > 0 && !b);
+            while (true) { if (false) break; } }";
+        let lowerer = lower(program, true);
+        assert_eq!("  0: ifFalse i < 10 goto 8
+  1: if b goto 3
+  2: goto 0
+  3: t1 = i + 1
+  4: i = t1
+  5: ifFalse i == 5 goto 0
+  6: goto 8
+  7: goto 0
+  8: t1 = i - 1
+  9: i = t1
+ 10: ifFalse i > 0 goto 12
+ 11: ifFalse b goto 8
+ 12: goto 12
+ 13: goto 15
+ 14: goto 12
+", lowerer.code().to_string());
+    }
+
+    #[test]
+    fn lower_switch_test() {
+        // caseが少なければ順に比べる。breakのない節は次の節へ落ちる
+        let lowerer = lower("{ i32 x; i32 y; switch (x) { case 2: y = 20; break; case 1: y = 10; default: y = 0; } }", true);
+        assert_eq!("  0: if x == 1 goto 5
+  1: if x == 2 goto 3
+  2: goto 6
+  3: y = 20
+  4: goto 7
+  5: y = 10
+  6: y = 0
+", lowerer.code().to_string());
+
+        // 密な値は飛び越し表で振り分ける(defaultがなければswitchの後へ抜ける)
+        let program = "{ char c; i32 y; switch (c) { case 1: y = 1; break; case 2: y = 2; break; case 4: y = 4; break; case 5: y = 5; } }";
+        let lowerer = lower(program, true);
+        assert_eq!("switch has no `default` case", lowerer.diagnostics()[0].message);
+        assert_eq!("  0: t1 = (i32) c
+  1: if t1 < 1 goto 17
+  2: if t1 > 5 goto 17
+  3: t2 = t1 - 1
+  4: jumptable t2, 5
+  5: goto 10
+  6: goto 12
+  7: goto 17
+  8: goto 14
+  9: goto 16
+ 10: y = 1
+ 11: goto 17
+ 12: y = 2
+ 13: goto 17
+ 14: y = 4
+ 15: goto 17
+ 16: y = 5
+", lowerer.code().to_string());
+
+        // 疎な値は二分して比べる(breakはswitchを、continueはループの本体を抜ける)
+        let program = "{ i32 x; i32 y; while (true) { switch (x) {
+            case 1: y = 1; case 10: y = 10; case 100: continue; case 1000: y = 1000; case 10000: break; default: y = 0; } } }";
+        let lowerer = lower(program, true);
+        assert_eq!("  0: if x >= 100 goto 4
+  1: if x == 1 goto 8
+  2: if x == 10 goto 9
+  3: goto 13
+  4: if x == 100 goto 10
+  5: if x == 1000 goto 11
+  6: if x == 10000 goto 12
+  7: goto 13
+  8: y = 1
+  9: y = 10
+ 10: goto 0
+ 11: y = 1000
+ 12: goto 0
+ 13: y = 0
+ 14: goto 0
+", lowerer.code().to_string());
+    }
+
+    #[test]
+    fn lower_procedure_test() {
+        let src = "\
+fn even(i32 n) -> bool;
+fn odd(i32 n) -> bool { if (n == 0) return false; return even(n - 1); }
+fn even(i32 n) -> bool { if (n == 0) return true; return odd(n - 1); }
+fn max(f32 a, f32 b) -> f32 { if (a < b) a = b; return a; }
+fn log(i32 k) {}
+fn main() { f32 x; x = max(max(1, x), 2.5 * x); odd(3); log(1); }";
+        let program = AstParser::new(src).parse_program().unwrap();
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_program(program);
+        let text: Vec<String> = lowerer.procedures().iter().map(|p| p.to_string()).collect();
+        assert_eq!(vec!["\
+odd(n):
+  0: ifFalse n == 0 goto 2
+  1: return false
+  2: t1 = n - 1
+  3: param t1
+  4: t1 = call even, 1
+  5: return t1
+", "\
+even(n):
+  0: ifFalse n == 0 goto 2
+  1: return true
+  2: t1 = n - 1
+  3: param t1
+  4: t1 = call odd, 1
+  5: return t1
+", "\
+max(a, b):
+  0: ifFalse a < b goto 2
+  1: a = b
+  2: return a
+", "\
+log(k):
+  0: return
+", "\
+main():
+  0: t1 = (f32) 1
+  1: param t1
+  2: param x
+  3: t1 = call max, 2
+  4: t2 = 2.5 * x
+  5: param t1
+  6: param t2
+  7: t1 = call max, 2
+  8: x = t1
+  9: param 3
+ 10: t1 = call odd, 1
+ 11: param 1
+ 12: call log, 1
+ 13: return
+"], text);
+
+        // 値を返す関数には値のないreturnを足さない
+        let program = AstParser::new("fn sign(i32 n) -> i32 { if (n < 0) return -1; else return 1; }").parse_program().unwrap();
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_program(program);
+        assert_eq!("\
+sign(n):
+  0: ifFalse n < 0 goto 4
+  1: t1 = minus 1
+  2: return t1
+  3: goto 5
+  4: return 1
+", lowerer.procedures()[0].to_string());
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use super::super::chapter2::ast::{Block, Decl, Expr, ExprKind, Func, NodeId, Program};
+use super::super::chapter2::diagnostic::Diagnostic;
+use super::super::chapter2::symbol::{ScopeId, Symbol, SymbolKind, SymbolTable};
+use super::super::chapter2::visit::{self, Visitor};
+
+/// 名前解決
+/// 名前の出現(ExprKind::Var, ExprKind::Callの節)ごとに、それを宣言したDecl(関数ならFunc)の節番号を対応づけた表を作る
+/// 前方宣言された関数は、最初の宣言の節番号に結びつける
+/// 宣言の節番号が宣言を一意に表すので、型検査やコード生成は名前ではなくこの番号で記号を引く
+/// 未宣言の名前はすべて誤りとして集め、最初の1つで止まらない
+///
+/// # members
+/// - table: SymbolTable => 記号表(解決の後もすべてのスコープが残る)
+/// - decls: HashMap<(ScopeId, String), NodeId> => スコープと名前から宣言の節番号への表
+/// - symbols: HashMap<NodeId, Symbol> => 宣言の節番号ごとの記号
+/// - defs: HashMap<NodeId, NodeId> => 名前の出現の節番号から宣言の節番号への表(use → def)
+/// - diagnostics: Vec<Diagnostic> => 誤り
+pub struct Resolver {
+    table: SymbolTable,
+    decls: HashMap<(ScopeId, String), NodeId>,
+    symbols: HashMap<NodeId, Symbol>,
+    defs: HashMap<NodeId, NodeId>,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    /// Resolverを生成して返す
+    ///
+    /// # returns
+    /// - Resolver
+    pub fn new() -> Resolver {
+        Resolver { table: SymbolTable::new(), decls: HashMap::new(), symbols: HashMap::new(), defs: HashMap::new(), diagnostics: vec![] }
+    }
+
+    /// プログラム中の名前を解決する
+    ///
+    /// # params
+    /// - program: &Program => プログラム
+    pub fn resolve_program(&mut self, program: &Program) {
+        self.visit_program(program);
+    }
+
+    /// ブロック中の名前を解決する
+    ///
+    /// # params
+    /// - block: &Block => ブロック
+    pub fn resolve_block(&mut self, block: &Block) {
+        self.visit_block(block);
+    }
+
+    /// 式中の名前を、現在までに宣言された名前で解決する
+    ///
+    /// # params
+    /// - expr: &Expr => 式
+    pub fn resolve_expr(&mut self, expr: &Expr) {
+        self.visit_expr(expr);
+    }
+
+    /// 名前の出現を宣言した節番号を返す
+    ///
+    /// # params
+    /// - id: NodeId => 名前の出現(ExprKind::Var, ExprKind::Call)の節番号
+    ///
+    /// # returns
+    /// - Option<NodeId>
+    pub fn def_of(&self, id: NodeId) -> Option<NodeId> {
+        self.defs.get(&id).copied()
+    }
+
+    /// 宣言の記号を返す
+    ///
+    /// # params
+    /// - decl: NodeId => 宣言の節番号
+    ///
+    /// # returns
+    /// - Option<&Symbol>
+    pub fn symbol(&self, decl: NodeId) -> Option<&Symbol> {
+        self.symbols.get(&decl)
+    }
+
+    /// 名前の出現が指す記号を返す
+    ///
+    /// # params
+    /// - id: NodeId => 名前の出現の節番号
+    ///
+    /// # returns
+    /// - Option<&Symbol>
+    pub fn symbol_of(&self, id: NodeId) -> Option<&Symbol> {
+        self.def_of(id).and_then(|decl| self.symbol(decl))
+    }
+
+    /// 宣言を指す名前の出現を、節番号の順に返す(def → use)
+    ///
+    /// # params
+    /// - decl: NodeId => 宣言の節番号
+    ///
+    /// # returns
+    /// - Vec<NodeId>
+    pub fn uses_of(&self, decl: NodeId) -> Vec<NodeId> {
+        let mut uses: Vec<NodeId> = self.defs.iter().filter(|(_, d)| **d == decl).map(|(u, _)| *u).collect();
+        uses.sort_unstable();
+        uses
+    }
+
+    /// 解決に使った記号表を返す
+    ///
+    /// # returns
+    /// - &SymbolTable
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.table
+    }
+
+    /// 未宣言の名前の誤りを返す
+    ///
+    /// # returns
+    /// - &[Diagnostic]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// 宣言を現在のスコープに入れ、節番号と記号を結びつける
+    /// 再宣言の誤りは構文解析で報告しているので、最初の宣言に結びつける
+    fn declare(&mut self, decl: &Decl, kind: SymbolKind) {
+        let symbol = Symbol::new(decl.name.clone(), decl.ty.clone()).with_kind(kind).with_span(decl.span);
+        if let Ok(symbol) = self.table.insert(symbol) {
+            self.decls.insert((self.table.current(), decl.name.clone()), decl.id);
+            self.symbols.insert(decl.id, symbol);
+        }
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_func(&mut self, func: &Func) {
+        let mut symbol = Symbol::new(func.name.clone(), func.ty()).with_kind(SymbolKind::Func).with_span(func.span);
+        if func.body.is_none() {
+            symbol = symbol.forward();
+        }
+        if let Ok(symbol) = self.table.insert(symbol) {
+            let id = *self.decls.entry((self.table.current(), func.name.clone())).or_insert(func.id);
+            self.symbols.insert(id, symbol);
+        }
+        if let Some(body) = &func.body {
+            self.table.enter_function(func.span);
+            for param in &func.params {
+                self.declare(param, SymbolKind::Param);
+            }
+            self.visit_block(body);
+            self.table.exit(func.span);
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.table.enter(block.span);
+        visit::walk_block(self, block);
+        self.table.exit(block.span);
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        self.declare(decl, SymbolKind::Var);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let ExprKind::Var(name) | ExprKind::Call(name, _) = &expr.kind {
+            match self.table.lookup(name).map(|(scope, _)| scope) {
+                Some(scope) => {
+                    let decl = self.decls[&(scope, name.clone())];
+                    self.defs.insert(expr.id, decl);
+                },
+                None => self.diagnostics.push(Diagnostic::error(expr.span, format!("undeclared identifier `{}`", name)))
+            }
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Resolver;
+    use super::super::super::chapter2::ast::{Expr, ExprKind, StmtKind};
+    use super::super::super::chapter2::parser::astparser::AstParser;
+    use super::super::super::chapter2::visit::{self, Visitor};
+
+    /// 名前の出現を前順に集める
+    struct Vars(Vec<(String, usize)>);
+
+    impl Visitor for Vars {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Var(name) = &expr.kind {
+                self.0.push((name.clone(), expr.id));
+            }
+            visit::walk_expr(self, expr);
+        }
+    }
+
+    #[test]
+    fn resolve_test() {
+        let block = AstParser::new("{ i32 x; f32 y; x = 1; { f32 x; x = y; } y = x; }").parse_block().unwrap();
+        let mut resolver = Resolver::new();
+        resolver.resolve_block(&block);
+        assert!(resolver.diagnostics().is_empty());
+
+        let decl_ids: Vec<usize> = block.stmts.iter().filter_map(|s| match &s.kind {
+            StmtKind::Decl(decl) => Some(decl.id),
+            _ => None
+        }).collect();
+        let mut vars = Vars(vec![]);
+        vars.visit_block(&block);
+        let defs: Vec<(String, Option<usize>)> = vars.0.iter().map(|(name, id)| (name.clone(), resolver.def_of(*id))).collect();
+        let inner_x = resolver.def_of(vars.0[1].1).unwrap();
+        assert_eq!(vec![
+            ("x".to_string(), Some(decl_ids[0])),
+            ("x".to_string(), Some(inner_x)),
+            ("y".to_string(), Some(decl_ids[1])),
+            ("y".to_string(), Some(decl_ids[1])),
+            ("x".to_string(), Some(decl_ids[0]))
+        ], defs);
+        assert_ne!(decl_ids[0], inner_x);
+        assert_eq!("f32", resolver.symbol(inner_x).unwrap().ty.to_string());
+        assert_eq!(vec![vars.0[2].1, vars.0[3].1], resolver.uses_of(decl_ids[1]));
+    }
+
+    #[test]
+    fn resolve_error_test() {
+        // 未宣言の名前はすべて報告する
+        let block = AstParser::new("{ i32 x; x = a + b; { i32 c; } c = x; }").parse_block().unwrap();
+        let mut resolver = Resolver::new();
+        resolver.resolve_block(&block);
+        let messages: Vec<String> = resolver.diagnostics().iter().map(|d| d.message.clone()).collect();
+        assert_eq!(vec!["undeclared identifier `a`", "undeclared identifier `b`", "undeclared identifier `c`"], messages);
+        assert_eq!(17, resolver.diagnostics()[1].span.start);
+
+        // 関数も宣言より前では見えない
+        let program = AstParser::new("fn odd(i32 n) -> bool { return !even(n); } fn even(i32 n) -> bool { return odd(n); }").parse_program().unwrap();
+        let mut resolver = Resolver::new();
+        resolver.resolve_program(&program);
+        let messages: Vec<String> = resolver.diagnostics().iter().map(|d| d.message.clone()).collect();
+        assert_eq!(vec!["undeclared identifier `even`"], messages);
+    }
+}
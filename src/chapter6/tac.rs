@@ -0,0 +1,1085 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::super::chapter2::ast::{BinOp, UnOp};
+use super::super::chapter2::mtype::Type;
+use super::super::chapter2::symbol::Symbol;
+
+/// 定数
+///
+/// # members
+/// - Int(i32) => 整数
+/// - Float(f32) => 小数
+/// - Bool(bool) => 真理値
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Constant {
+    Int(i32),
+    Float(f32),
+    Bool(bool)
+}
+
+impl Constant {
+    /// 定数どうしの二項演算を実行時と同じ意味で計算する
+    /// - i32 => 桁あふれは2の補数で折り返す、0での除算は計算しない
+    /// - f32 => IEEE 754の単精度で計算する(0での除算は無限大やNaNになる)
+    /// - 比較 => 同じ型どうしならboolになる
+    ///
+    /// # params
+    /// - op: BinOp => 演算子
+    /// - lhs: Constant => 左の被演算子
+    /// - rhs: Constant => 右の被演算子
+    ///
+    /// # returns
+    /// - Option<Constant> => 計算できなければNone
+    pub fn binary(op: BinOp, lhs: Constant, rhs: Constant) -> Option<Constant> {
+        match (lhs, rhs) {
+            (Constant::Int(x), Constant::Int(y)) => match op {
+                BinOp::Add => Some(Constant::Int(x.wrapping_add(y))),
+                BinOp::Sub => Some(Constant::Int(x.wrapping_sub(y))),
+                BinOp::Mul => Some(Constant::Int(x.wrapping_mul(y))),
+                BinOp::Div if y == 0 => None,
+                BinOp::Div => Some(Constant::Int(x.wrapping_div(y))),
+                BinOp::And | BinOp::Or => None,
+                op => Some(Constant::Bool(compare(op, x.partial_cmp(&y))))
+            },
+            (Constant::Float(x), Constant::Float(y)) => match op {
+                BinOp::Add => Some(Constant::Float(x+y)),
+                BinOp::Sub => Some(Constant::Float(x-y)),
+                BinOp::Mul => Some(Constant::Float(x*y)),
+                BinOp::Div => Some(Constant::Float(x/y)),
+                BinOp::And | BinOp::Or => None,
+                op => Some(Constant::Bool(compare(op, x.partial_cmp(&y))))
+            },
+            (Constant::Bool(x), Constant::Bool(y)) => match op {
+                BinOp::And => Some(Constant::Bool(x && y)),
+                BinOp::Or => Some(Constant::Bool(x || y)),
+                BinOp::Eq => Some(Constant::Bool(x == y)),
+                BinOp::Ne => Some(Constant::Bool(x != y)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    /// 定数の単項演算を計算する(i32の符号反転は折り返す)
+    ///
+    /// # params
+    /// - op: UnOp => 演算子
+    /// - x: Constant => 被演算子
+    ///
+    /// # returns
+    /// - Option<Constant>
+    pub fn unary(op: UnOp, x: Constant) -> Option<Constant> {
+        match (op, x) {
+            (UnOp::Neg, Constant::Int(x)) => Some(Constant::Int(x.wrapping_neg())),
+            (UnOp::Neg, Constant::Float(x)) => Some(Constant::Float(-x)),
+            (UnOp::Not, Constant::Bool(x)) => Some(Constant::Bool(!x)),
+            _ => None
+        }
+    }
+
+    /// 定数を型の変換(拡大変換)の結果にする
+    ///
+    /// # params
+    /// - ty: &Type => 変換先の型
+    /// - x: Constant => 変換する定数
+    ///
+    /// # returns
+    /// - Option<Constant>
+    pub fn conv(ty: &Type, x: Constant) -> Option<Constant> {
+        match (ty, x) {
+            (Type::F32(_), Constant::Int(x)) => Some(Constant::Float(x as f32)),
+            (Type::F32(_), Constant::Float(_)) | (Type::I32(_), Constant::Int(_)) => Some(x),
+            _ => None
+        }
+    }
+}
+
+/// 比較演算子を比較の結果に当てはめる(NaNとの比較は != だけが真)
+fn compare(op: BinOp, ord: Option<Ordering>) -> bool {
+    match (op, ord) {
+        (BinOp::Ne, None) => true,
+        (_, None) => false,
+        (BinOp::Lt, Some(o)) => o == Ordering::Less,
+        (BinOp::Le, Some(o)) => o != Ordering::Greater,
+        (BinOp::Gt, Some(o)) => o == Ordering::Greater,
+        (BinOp::Ge, Some(o)) => o != Ordering::Less,
+        (BinOp::Eq, Some(o)) => o == Ordering::Equal,
+        (_, Some(o)) => o != Ordering::Equal
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Int(num) => write!(f, "{}", num),
+            Constant::Float(num) if num.fract() == 0.0 && num.is_finite() => write!(f, "{:.1}", num),
+            Constant::Float(num) => write!(f, "{}", num),
+            Constant::Bool(b) => write!(f, "{}", b)
+        }
+    }
+}
+
+/// 3番地コードの番地(6.2.1節)
+///
+/// # members
+/// - Name(Symbol) => 記号表の名前
+/// - Temp(usize) => コンパイラが作る一時変数
+/// - Const(Constant) => 定数
+/// - Label(usize) => 飛び先の命令の番号
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub enum Operand {
+    Name(Symbol),
+    Temp(usize),
+    Const(Constant),
+    Label(usize)
+}
+
+impl Operand {
+    /// 一時変数か
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_temp(&self) -> bool {
+        matches!(self, Operand::Temp(_))
+    }
+
+    /// 名前か一時変数(値を持つ変数)か
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_var(&self) -> bool {
+        matches!(self, Operand::Name(_) | Operand::Temp(_))
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Name(symbol) => write!(f, "{}", symbol.lexeme),
+            Operand::Temp(n) => write!(f, "t{}", n),
+            Operand::Const(c) => write!(f, "{}", c),
+            Operand::Label(n) => write!(f, "{}", n)
+        }
+    }
+}
+
+/// 3番地コードの演算
+///
+/// # members
+/// - Binary(BinOp) => x = y op z
+/// - Unary(UnOp) => x = op y
+/// - Conv(Type) => x = (T) y (型の変換)
+/// - Copy => x = y
+/// - Goto => goto L
+/// - If => if x goto L
+/// - IfFalse => ifFalse x goto L
+/// - IfRel(BinOp) => if x relop y goto L
+/// - IfFalseRel(BinOp) => ifFalse x relop y goto L
+/// - JumpTable => jumptable x, n (直後に並ぶn個の飛び越し命令のうちx番目へ飛ぶ)
+/// - IndexLoad => x = y[i]
+/// - IndexStore => x[i] = y
+/// - Param => param x
+/// - Call => y = call p, n
+/// - Return => return y
+/// - AddrOf => x = &y
+/// - Load => x = *y
+/// - Store => *x = y
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub enum Opcode {
+    Binary(BinOp),
+    Unary(UnOp),
+    Conv(Type),
+    Copy,
+    Goto,
+    If,
+    IfFalse,
+    IfRel(BinOp),
+    IfFalseRel(BinOp),
+    JumpTable,
+    IndexLoad,
+    IndexStore,
+    Param,
+    Call,
+    Return,
+    AddrOf,
+    Load,
+    Store
+}
+
+impl Opcode {
+    /// 結果の値を作る演算か(三つ組では番号で参照できる)
+    ///
+    /// # returns
+    /// - bool
+    pub fn has_value(&self) -> bool {
+        matches!(self, Opcode::Binary(_) | Opcode::Unary(_) | Opcode::Conv(_) | Opcode::IndexLoad | Opcode::Call | Opcode::AddrOf | Opcode::Load)
+    }
+
+    /// 飛び先を持つ飛び越し命令か(jumptableの飛び先は直後の命令なので含めない)
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_jump(&self) -> bool {
+        matches!(self, Opcode::Goto | Opcode::If | Opcode::IfFalse | Opcode::IfRel(_) | Opcode::IfFalseRel(_))
+    }
+}
+
+/// 表に書くときの演算の名前(図6.10, 6.11)
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Opcode::Binary(op) => format!("{}", op),
+            Opcode::Unary(UnOp::Neg) => "minus".to_string(),
+            Opcode::Unary(UnOp::Not) => "not".to_string(),
+            Opcode::Conv(ty) => format!("({})", ty),
+            Opcode::Copy => "=".to_string(),
+            Opcode::Goto => "goto".to_string(),
+            Opcode::If => "if".to_string(),
+            Opcode::IfFalse => "ifFalse".to_string(),
+            Opcode::IfRel(op) => format!("if{}", op),
+            Opcode::IfFalseRel(op) => format!("ifFalse{}", op),
+            Opcode::JumpTable => "jumptable".to_string(),
+            Opcode::IndexLoad => "=[]".to_string(),
+            Opcode::IndexStore => "[]=".to_string(),
+            Opcode::Param => "param".to_string(),
+            Opcode::Call => "call".to_string(),
+            Opcode::Return => "return".to_string(),
+            Opcode::AddrOf => "=&".to_string(),
+            Opcode::Load => "=*".to_string(),
+            Opcode::Store => "*=".to_string()
+        };
+        // 表の桁をそろえられるように幅の指定に従う
+        f.pad(&name)
+    }
+}
+
+/// 四つ組(op, arg1, arg2, result)
+/// 書き込まれる番地(代入先、飛び先、x[i] = y のx、*x = y のx)はresultに置く
+///
+/// # members
+/// - op: Opcode => 演算
+/// - arg1: Option<Operand> => 第1引数
+/// - arg2: Option<Operand> => 第2引数
+/// - result: Option<Operand> => 結果
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Quad {
+    pub op: Opcode,
+    pub arg1: Option<Operand>,
+    pub arg2: Option<Operand>,
+    pub result: Option<Operand>
+}
+
+impl Quad {
+    fn new(op: Opcode, arg1: Option<Operand>, arg2: Option<Operand>, result: Option<Operand>) -> Quad {
+        Quad { op, arg1, arg2, result }
+    }
+
+    /// x = y op z
+    pub fn binary(op: BinOp, x: Operand, y: Operand, z: Operand) -> Quad {
+        Quad::new(Opcode::Binary(op), Some(y), Some(z), Some(x))
+    }
+
+    /// x = op y
+    pub fn unary(op: UnOp, x: Operand, y: Operand) -> Quad {
+        Quad::new(Opcode::Unary(op), Some(y), None, Some(x))
+    }
+
+    /// x = (ty) y
+    pub fn conv(ty: Type, x: Operand, y: Operand) -> Quad {
+        Quad::new(Opcode::Conv(ty), Some(y), None, Some(x))
+    }
+
+    /// x = y
+    pub fn copy(x: Operand, y: Operand) -> Quad {
+        Quad::new(Opcode::Copy, Some(y), None, Some(x))
+    }
+
+    /// goto L
+    pub fn goto(label: usize) -> Quad {
+        Quad::new(Opcode::Goto, None, None, Some(Operand::Label(label)))
+    }
+
+    /// if x goto L
+    pub fn if_true(x: Operand, label: usize) -> Quad {
+        Quad::new(Opcode::If, Some(x), None, Some(Operand::Label(label)))
+    }
+
+    /// ifFalse x goto L
+    pub fn if_false(x: Operand, label: usize) -> Quad {
+        Quad::new(Opcode::IfFalse, Some(x), None, Some(Operand::Label(label)))
+    }
+
+    /// if x relop y goto L
+    pub fn if_rel(op: BinOp, x: Operand, y: Operand, label: usize) -> Quad {
+        Quad::new(Opcode::IfRel(op), Some(x), Some(y), Some(Operand::Label(label)))
+    }
+
+    /// ifFalse x relop y goto L
+    pub fn if_false_rel(op: BinOp, x: Operand, y: Operand, label: usize) -> Quad {
+        Quad::new(Opcode::IfFalseRel(op), Some(x), Some(y), Some(Operand::Label(label)))
+    }
+
+    /// jumptable x, n
+    pub fn jump_table(x: Operand, n: usize) -> Quad {
+        Quad::new(Opcode::JumpTable, Some(x), Some(Operand::Const(Constant::Int(n as i32))), None)
+    }
+
+    /// x = y[i]
+    pub fn index_load(x: Operand, y: Operand, i: Operand) -> Quad {
+        Quad::new(Opcode::IndexLoad, Some(y), Some(i), Some(x))
+    }
+
+    /// x[i] = y
+    pub fn index_store(x: Operand, i: Operand, y: Operand) -> Quad {
+        Quad::new(Opcode::IndexStore, Some(i), Some(y), Some(x))
+    }
+
+    /// param x
+    pub fn param(x: Operand) -> Quad {
+        Quad::new(Opcode::Param, Some(x), None, None)
+    }
+
+    /// y = call p, n (yがNoneなら結果を捨てる)
+    pub fn call(y: Option<Operand>, p: Operand, n: usize) -> Quad {
+        Quad::new(Opcode::Call, Some(p), Some(Operand::Const(Constant::Int(n as i32))), y)
+    }
+
+    /// return y
+    pub fn ret(y: Option<Operand>) -> Quad {
+        Quad::new(Opcode::Return, y, None, None)
+    }
+
+    /// x = &y
+    pub fn addr_of(x: Operand, y: Operand) -> Quad {
+        Quad::new(Opcode::AddrOf, Some(y), None, Some(x))
+    }
+
+    /// x = *y
+    pub fn load(x: Operand, y: Operand) -> Quad {
+        Quad::new(Opcode::Load, Some(y), None, Some(x))
+    }
+
+    /// *x = y
+    pub fn store(x: Operand, y: Operand) -> Quad {
+        Quad::new(Opcode::Store, Some(y), None, Some(x))
+    }
+
+    /// 飛び先の命令の番号を返す
+    ///
+    /// # returns
+    /// - Option<usize>
+    pub fn target(&self) -> Option<usize> {
+        match (&self.op, &self.result) {
+            (op, Some(Operand::Label(label))) if op.is_jump() => Some(*label),
+            _ => None
+        }
+    }
+
+    /// 飛び先を書き換える(バックパッチ)
+    ///
+    /// # params
+    /// - label: usize => 飛び先の命令の番号
+    pub fn set_target(&mut self, label: usize) {
+        if self.op.is_jump() {
+            self.result = Some(Operand::Label(label));
+        }
+    }
+
+    /// 命令が値を読む変数を、読む順に返す
+    /// x[i] = y と *x = y は、xの指す先の一部だけを書き換えるので、xも読むとみなす
+    /// 呼び出す関数の名前と、番地を取る名前は値として読まない
+    ///
+    /// # returns
+    /// - Vec<&Operand>
+    pub fn uses(&self) -> Vec<&Operand> {
+        let operands = match &self.op {
+            Opcode::Binary(_) | Opcode::IfRel(_) | Opcode::IfFalseRel(_) | Opcode::IndexLoad => vec![&self.arg1, &self.arg2],
+            Opcode::Unary(_) | Opcode::Conv(_) | Opcode::Copy | Opcode::If | Opcode::IfFalse | Opcode::JumpTable
+                | Opcode::Param | Opcode::Return | Opcode::Load => vec![&self.arg1],
+            Opcode::IndexStore => vec![&self.result, &self.arg1, &self.arg2],
+            Opcode::Store => vec![&self.result, &self.arg1],
+            Opcode::Goto | Opcode::Call | Opcode::AddrOf => vec![]
+        };
+        operands.into_iter().flatten().filter(|x| x.is_var()).collect()
+    }
+
+    /// 命令が値を書き込む変数を返す(x[i] = y と *x = y はxの値を書き換えないのでNone)
+    ///
+    /// # returns
+    /// - Option<&Operand>
+    pub fn def(&self) -> Option<&Operand> {
+        match &self.result {
+            Some(x) if self.op.has_value() || self.op == Opcode::Copy => Some(x),
+            _ => None
+        }
+    }
+}
+
+/// 3番地命令の形で書く
+impl fmt::Display for Quad {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |o: &Option<Operand>| o.as_ref().map(|o| o.to_string()).unwrap_or_default();
+        let (a, b, r) = (show(&self.arg1), show(&self.arg2), show(&self.result));
+        match &self.op {
+            Opcode::Binary(op) => write!(f, "{} = {} {} {}", r, a, op, b),
+            Opcode::Unary(_) | Opcode::Conv(_) => write!(f, "{} = {} {}", r, self.op, a),
+            Opcode::Copy => write!(f, "{} = {}", r, a),
+            Opcode::Goto => write!(f, "goto {}", r),
+            Opcode::If | Opcode::IfFalse => write!(f, "{} {} goto {}", self.op, a, r),
+            Opcode::IfRel(op) => write!(f, "if {} {} {} goto {}", a, op, b, r),
+            Opcode::IfFalseRel(op) => write!(f, "ifFalse {} {} {} goto {}", a, op, b, r),
+            Opcode::JumpTable => write!(f, "jumptable {}, {}", a, b),
+            Opcode::IndexLoad => write!(f, "{} = {}[{}]", r, a, b),
+            Opcode::IndexStore => write!(f, "{}[{}] = {}", r, a, b),
+            Opcode::Param => write!(f, "param {}", a),
+            Opcode::Call if self.result.is_none() => write!(f, "call {}, {}", a, b),
+            Opcode::Call => write!(f, "{} = call {}, {}", r, a, b),
+            Opcode::Return if self.arg1.is_none() => write!(f, "return"),
+            Opcode::Return => write!(f, "return {}", a),
+            Opcode::AddrOf => write!(f, "{} = &{}", r, a),
+            Opcode::Load => write!(f, "{} = *{}", r, a),
+            Opcode::Store => write!(f, "*{} = {}", r, a)
+        }
+    }
+}
+
+/// 四つ組の列で表した3番地コード(6.2.2節)
+///
+/// # members
+/// - quads: Vec<Quad> => 命令の列(添字が命令の番号)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+/// - Default
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Quadruples {
+    pub quads: Vec<Quad>
+}
+
+impl Quadruples {
+    /// 空の命令列を生成して返す
+    ///
+    /// # returns
+    /// - Quadruples
+    pub fn new() -> Quadruples {
+        Quadruples { quads: vec![] }
+    }
+
+    /// 命令を末尾に加え、その番号を返す
+    ///
+    /// # params
+    /// - quad: Quad => 命令
+    ///
+    /// # returns
+    /// - usize
+    pub fn push(&mut self, quad: Quad) -> usize {
+        self.quads.push(quad);
+        self.quads.len()-1
+    }
+
+    /// 次に加える命令の番号を返す(nextinstr)
+    ///
+    /// # returns
+    /// - usize
+    pub fn next(&self) -> usize {
+        self.quads.len()
+    }
+
+    /// 三つ組に変換する
+    /// 一度だけ代入される一時変数は、それを計算する三つ組の番号で置き換える
+    /// 名前への代入は = の三つ組に、x[i] = y は []= と = の2つに、if x relop y goto L は比較と if (ifFalse) の2つに分ける
+    ///
+    /// # returns
+    /// - Triples
+    pub fn to_triples(&self) -> Triples {
+        let mut defs: HashMap<usize, usize> = HashMap::new();
+        for quad in &self.quads {
+            if let (true, Some(Operand::Temp(t))) = (quad.op.has_value(), &quad.result) {
+                *defs.entry(*t).or_insert(0) += 1;
+            }
+            if let (Opcode::Copy, Some(Operand::Temp(t))) = (&quad.op, &quad.result) {
+                *defs.entry(*t).or_insert(0) += 2;
+            }
+        }
+        let mut triples: Vec<Triple> = vec![];
+        let mut refs: HashMap<usize, usize> = HashMap::new();
+        let mut start = vec![];
+        for quad in &self.quads {
+            start.push(triples.len());
+            let arg = |o: &Option<Operand>| o.as_ref().map(|o| match o {
+                Operand::Temp(t) if refs.contains_key(t) => Arg::Ref(refs[t]),
+                o => Arg::Operand(o.clone())
+            });
+            let (a, b) = (arg(&quad.arg1), arg(&quad.arg2));
+            let result = quad.result.clone().map(Arg::Operand);
+            match &quad.op {
+                Opcode::IfRel(op) => {
+                    triples.push(Triple::new(Opcode::Binary(*op), a, b));
+                    triples.push(Triple::new(Opcode::If, Some(Arg::Ref(triples.len()-1)), result));
+                },
+                Opcode::IfFalseRel(op) => {
+                    triples.push(Triple::new(Opcode::Binary(*op), a, b));
+                    triples.push(Triple::new(Opcode::IfFalse, Some(Arg::Ref(triples.len()-1)), result));
+                },
+                Opcode::IndexStore => {
+                    triples.push(Triple::new(Opcode::IndexStore, result, a));
+                    triples.push(Triple::new(Opcode::Copy, Some(Arg::Ref(triples.len()-1)), b));
+                },
+                op if op.has_value() => {
+                    triples.push(Triple::new(op.clone(), a, b));
+                    let index = triples.len()-1;
+                    match &quad.result {
+                        Some(Operand::Temp(t)) if defs.get(t) == Some(&1) => { refs.insert(*t, index); },
+                        Some(_) => triples.push(Triple::new(Opcode::Copy, result, Some(Arg::Ref(index)))),
+                        None => {}
+                    }
+                },
+                Opcode::Copy | Opcode::Store => triples.push(Triple::new(quad.op.clone(), result, a)),
+                Opcode::Goto => triples.push(Triple::new(Opcode::Goto, result, None)),
+                Opcode::If | Opcode::IfFalse => triples.push(Triple::new(quad.op.clone(), a, result)),
+                _ => triples.push(Triple::new(quad.op.clone(), a, b))
+            }
+        }
+        start.push(triples.len());
+        for triple in &mut triples {
+            for arg in triple.arg1.iter_mut().chain(triple.arg2.iter_mut()) {
+                if let Arg::Operand(Operand::Label(label)) = arg {
+                    *label = start[*label];
+                }
+            }
+        }
+        Triples { triples }
+    }
+
+    /// 間接三つ組に変換する
+    ///
+    /// # returns
+    /// - IndirectTriples
+    pub fn to_indirect(&self) -> IndirectTriples {
+        self.to_triples().to_indirect()
+    }
+
+    /// 図6.10の形の表にする
+    ///
+    /// # returns
+    /// - String
+    pub fn dump(&self) -> String {
+        let show = |o: &Option<Operand>| o.as_ref().map(|o| o.to_string()).unwrap_or_default();
+        let mut out = format!("{:>3}  {:<8}{:<8}{:<8}{}\n", "", "op", "arg1", "arg2", "result");
+        for (i, quad) in self.quads.iter().enumerate() {
+            let line = format!("{:>3}  {:<8}{:<8}{:<8}{}", i, quad.op, show(&quad.arg1), show(&quad.arg2), show(&quad.result));
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// 最適化の前後を比べるために、命令の差分を書く(最長共通部分列)
+    /// 残った命令は空白、消えた命令は -、加わった命令は + で始め、前のコードでの番号と後のコードでの番号を並べる
+    /// 消えた命令には後の番号が、加わった命令には前の番号がないので空ける
+    ///
+    /// # params
+    /// - after: &Quadruples => 変えた後のコード
+    ///
+    /// # returns
+    /// - String
+    pub fn diff(&self, after: &Quadruples) -> String {
+        let a: Vec<String> = self.quads.iter().map(|q| q.to_string()).collect();
+        let b: Vec<String> = after.quads.iter().map(|q| q.to_string()).collect();
+        let (n, m) = (a.len(), b.len());
+        // lcs[i][j] => a[i..] と b[j..] の最長共通部分列の長さ
+        let mut lcs = vec![vec![0; m+1]; n+1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if a[i] == b[j] { lcs[i+1][j+1]+1 } else { lcs[i+1][j].max(lcs[i][j+1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        let mut out = String::new();
+        while i < n || j < m {
+            if i < n && j < m && a[i] == b[j] {
+                out.push_str(&format!("  {:>3} {:>3}: {}\n", i, j, a[i]));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i+1][j] >= lcs[i][j+1]) {
+                out.push_str(&format!("- {:>3} {:>3}: {}\n", i, "", a[i]));
+                i += 1;
+            } else {
+                out.push_str(&format!("+ {:>3} {:>3}: {}\n", "", j, b[j]));
+                j += 1;
+            }
+        }
+        out
+    }
+}
+
+/// 番号をつけた3番地命令の列にする
+impl fmt::Display for Quadruples {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, quad) in self.quads.iter().enumerate() {
+            writeln!(f, "{:>3}: {}", i, quad)?;
+        }
+        Ok(())
+    }
+}
+
+/// 1つの手続き(関数)の3番地コード
+/// 仮引数は呼び出し側が param で渡した順に並ぶ
+///
+/// # members
+/// - name: Symbol => 関数の記号
+/// - params: Vec<Symbol> => 仮引数の記号
+/// - code: Quadruples => 本体のコード(命令の番号は手続きごとに0から)
+/// - temps: usize => 使った一時変数の数
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Procedure {
+    pub name: Symbol,
+    pub params: Vec<Symbol>,
+    pub code: Quadruples,
+    pub temps: usize
+}
+
+/// f(a, b): の見出しに続けて本体のコードを並べる
+impl fmt::Display for Procedure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<&str> = self.params.iter().map(|p| &p.lexeme[..]).collect();
+        writeln!(f, "{}({}):", self.name.lexeme, params.join(", "))?;
+        write!(f, "{}", self.code)
+    }
+}
+
+/// 三つ組の引数
+///
+/// # members
+/// - Operand(Operand) => 番地
+/// - Ref(usize) => 三つ組の番号(その三つ組が計算した値)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub enum Arg {
+    Operand(Operand),
+    Ref(usize)
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::Operand(o) => write!(f, "{}", o),
+            Arg::Ref(n) => write!(f, "({})", n)
+        }
+    }
+}
+
+/// 三つ組(op, arg1, arg2)
+/// 代入 x = y は (=, x, y)、x[i] = y は ([]=, x, i) と (=, (k), y) で表す
+///
+/// # members
+/// - op: Opcode => 演算
+/// - arg1: Option<Arg> => 第1引数
+/// - arg2: Option<Arg> => 第2引数
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Triple {
+    pub op: Opcode,
+    pub arg1: Option<Arg>,
+    pub arg2: Option<Arg>
+}
+
+impl Triple {
+    fn new(op: Opcode, arg1: Option<Arg>, arg2: Option<Arg>) -> Triple {
+        Triple { op, arg1, arg2 }
+    }
+
+    fn refs(&self) -> impl Iterator<Item = usize> + '_ {
+        self.arg1.iter().chain(self.arg2.iter()).filter_map(|arg| match arg {
+            Arg::Ref(k) => Some(*k),
+            _ => None
+        })
+    }
+}
+
+/// 三つ組の列で表した3番地コード
+/// 飛び先は三つ組の番号で表す
+///
+/// # members
+/// - triples: Vec<Triple> => 三つ組の列
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Triples {
+    pub triples: Vec<Triple>
+}
+
+impl Triples {
+    /// 四つ組に変換する
+    /// 値を参照される三つ組には新しい一時変数を割り当てる
+    /// 直後の = や if だけが参照する三つ組は、1つの四つ組にまとめ直す
+    ///
+    /// # returns
+    /// - Quadruples
+    pub fn to_quadruples(&self) -> Quadruples {
+        let mut count = vec![0; self.triples.len()];
+        let mut base = 0;
+        for triple in &self.triples {
+            for k in triple.refs() {
+                count[k] += 1;
+            }
+            for arg in triple.arg1.iter().chain(triple.arg2.iter()) {
+                if let Arg::Operand(Operand::Temp(t)) = arg {
+                    base = base.max(t+1);
+                }
+            }
+        }
+        let temp = |k: usize| Operand::Temp(base+k);
+        let operand = |a: &Option<Arg>| a.as_ref().map(|a| match a {
+            Arg::Operand(o) => o.clone(),
+            Arg::Ref(k) => temp(*k)
+        });
+        let mut quads = Quadruples::new();
+        let mut start = vec![0; self.triples.len()+1];
+        let mut i = 0;
+        while i < self.triples.len() {
+            start[i] = quads.next();
+            let t = &self.triples[i];
+            let next = self.triples.get(i+1);
+            // 直後の三つ組だけが参照しているなら、まとめて1つの四つ組にする
+            let fused = next.filter(|n| count[i] == 1 && n.refs().any(|k| k == i));
+            let (a, b) = (operand(&t.arg1), operand(&t.arg2));
+            match (&t.op, fused.map(|n| (&n.op, &n.arg1, &n.arg2))) {
+                (Opcode::Binary(op), Some((Opcode::If, _, Some(Arg::Operand(Operand::Label(l)))))) if op.is_relational() => {
+                    quads.push(Quad::if_rel(*op, a.unwrap(), b.unwrap(), *l));
+                },
+                (Opcode::Binary(op), Some((Opcode::IfFalse, _, Some(Arg::Operand(Operand::Label(l)))))) if op.is_relational() => {
+                    quads.push(Quad::if_false_rel(*op, a.unwrap(), b.unwrap(), *l));
+                },
+                (op, Some((Opcode::Copy, Some(Arg::Operand(x)), Some(Arg::Ref(_))))) if op.has_value() => {
+                    quads.push(Quad::new(op.clone(), a, b, Some(x.clone())));
+                },
+                (Opcode::IndexStore, Some((Opcode::Copy, Some(Arg::Ref(_)), y))) => {
+                    quads.push(Quad::new(Opcode::IndexStore, b, operand(y), a));
+                },
+                (op, _) if op.has_value() => {
+                    let result = if count[i] > 0 || !matches!(op, Opcode::Call) { Some(temp(i)) } else { None };
+                    quads.push(Quad::new(op.clone(), a, b, result));
+                    i += 1;
+                    continue;
+                },
+                (Opcode::Copy | Opcode::Store, _) => { quads.push(Quad::new(t.op.clone(), b, None, a)); i += 1; continue; },
+                (Opcode::Goto, _) => { quads.push(Quad::new(Opcode::Goto, None, None, a)); i += 1; continue; },
+                (Opcode::If | Opcode::IfFalse, _) => { quads.push(Quad::new(t.op.clone(), a, None, b)); i += 1; continue; },
+                (op, _) => { quads.push(Quad::new(op.clone(), a, b, None)); i += 1; continue; }
+            }
+            // 2つの三つ組をまとめた
+            start[i+1] = start[i];
+            i += 2;
+        }
+        start[self.triples.len()] = quads.next();
+        for quad in &mut quads.quads {
+            if let Some(label) = quad.target() {
+                quad.set_target(start[label]);
+            }
+        }
+        quads
+    }
+
+    /// 三つ組の順のままの間接三つ組に変換する
+    ///
+    /// # returns
+    /// - IndirectTriples
+    pub fn to_indirect(&self) -> IndirectTriples {
+        IndirectTriples { triples: self.triples.clone(), order: (0..self.triples.len()).collect() }
+    }
+
+    /// 図6.11(b)の形の表にする
+    ///
+    /// # returns
+    /// - String
+    pub fn dump(&self) -> String {
+        dump_triples(&self.triples)
+    }
+}
+
+/// 間接三つ組(三つ組の列と、実行順に並べた三つ組への参照の列)
+/// 命令を並べ替えても三つ組の番号は変わらないので、参照を書き換えずに済む
+/// 飛び先はorder上の位置で表す
+///
+/// # members
+/// - triples: Vec<Triple> => 三つ組
+/// - order: Vec<usize> => 実行順に並べた三つ組の番号
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct IndirectTriples {
+    pub triples: Vec<Triple>,
+    pub order: Vec<usize>
+}
+
+impl IndirectTriples {
+    /// orderの順に並べ直した三つ組に変換する
+    ///
+    /// # returns
+    /// - Triples
+    pub fn to_triples(&self) -> Triples {
+        let mut position = vec![0; self.triples.len()];
+        for (pos, k) in self.order.iter().enumerate() {
+            position[*k] = pos;
+        }
+        let triples = self.order.iter().map(|k| {
+            let mut triple = self.triples[*k].clone();
+            for arg in triple.arg1.iter_mut().chain(triple.arg2.iter_mut()) {
+                if let Arg::Ref(r) = arg {
+                    *r = position[*r];
+                }
+            }
+            triple
+        }).collect();
+        Triples { triples }
+    }
+
+    /// 四つ組に変換する
+    ///
+    /// # returns
+    /// - Quadruples
+    pub fn to_quadruples(&self) -> Quadruples {
+        self.to_triples().to_quadruples()
+    }
+
+    /// 図6.12の形(命令の列と三つ組の表)にする
+    ///
+    /// # returns
+    /// - String
+    pub fn dump(&self) -> String {
+        let mut out = String::from("instruction\n");
+        for (pos, k) in self.order.iter().enumerate() {
+            out.push_str(&format!("{:>3}  ({})\n", pos, k));
+        }
+        out.push_str(&dump_triples(&self.triples));
+        out
+    }
+}
+
+fn dump_triples(triples: &[Triple]) -> String {
+    let show = |a: &Option<Arg>| a.as_ref().map(|a| a.to_string()).unwrap_or_default();
+    let mut out = format!("{:<5}{:<8}{:<8}{}\n", "", "op", "arg1", "arg2");
+    for (i, triple) in triples.iter().enumerate() {
+        let line = format!("{:<5}{:<8}{:<8}{}", format!("({})", i), triple.op, show(&triple.arg1), show(&triple.arg2));
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// テストでオペランドを手短に書くための関数
+#[cfg(test)]
+pub mod test_util {
+    use super::{Constant, Operand};
+    use super::super::super::chapter2::mtype::Type;
+    use super::super::super::chapter2::symbol::Symbol;
+
+    /// i32型の名前のオペランドを返す
+    ///
+    /// # params
+    /// - lexeme: &str => 名前
+    ///
+    /// # returns
+    /// - Operand
+    pub fn name(lexeme: &str) -> Operand {
+        Operand::Name(Symbol::new(lexeme.to_string(), Type::new_i32()))
+    }
+
+    /// i32の定数のオペランドを返す
+    ///
+    /// # params
+    /// - num: i32 => 値
+    ///
+    /// # returns
+    /// - Operand
+    pub fn int(num: i32) -> Operand {
+        Operand::Const(Constant::Int(num))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Constant, Operand, Quad, Quadruples};
+    use super::test_util::name;
+    use super::super::super::chapter2::ast::{BinOp, UnOp};
+    use super::super::super::chapter2::mtype::Type;
+
+    /// a = b * - c + b * - c (図6.10)
+    fn example() -> Quadruples {
+        let (t1, t2, t3, t4, t5) = (Operand::Temp(1), Operand::Temp(2), Operand::Temp(3), Operand::Temp(4), Operand::Temp(5));
+        let mut code = Quadruples::new();
+        code.push(Quad::unary(UnOp::Neg, t1.clone(), name("c")));
+        code.push(Quad::binary(BinOp::Mul, t2.clone(), name("b"), t1));
+        code.push(Quad::unary(UnOp::Neg, t3.clone(), name("c")));
+        code.push(Quad::binary(BinOp::Mul, t4.clone(), name("b"), t3));
+        code.push(Quad::binary(BinOp::Add, t5.clone(), t2, t4));
+        code.push(Quad::copy(name("a"), t5));
+        code
+    }
+
+    #[test]
+    fn tac_quadruples_test() {
+        let code = example();
+        assert_eq!("  0: t1 = minus c
+  1: t2 = b * t1
+  2: t3 = minus c
+  3: t4 = b * t3
+  4: t5 = t2 + t4
+  5: a = t5
+", code.to_string());
+        assert_eq!("     op      arg1    arg2    result
+  0  minus   c               t1
+  1  *       b       t1      t2
+  2  minus   c               t3
+  3  *       b       t3      t4
+  4  +       t2      t4      t5
+  5  =       t5              a
+", code.dump());
+    }
+
+    #[test]
+    fn tac_triples_test() {
+        let triples = example().to_triples();
+        assert_eq!("     op      arg1    arg2
+(0)  minus   c
+(1)  *       b       (0)
+(2)  minus   c
+(3)  *       b       (2)
+(4)  +       (1)     (3)
+(5)  =       a       (4)
+", triples.dump());
+        // 三つ組から戻すと、名前への代入は値を計算する命令にまとめられる
+        assert_eq!("  0: t0 = minus c
+  1: t1 = b * t0
+  2: t2 = minus c
+  3: t3 = b * t2
+  4: a = t1 + t3
+", triples.to_quadruples().to_string());
+
+        // 間接三つ組は命令の順だけを入れ替えられる
+        let mut indirect = triples.to_indirect();
+        indirect.order.swap(1, 2);
+        assert!(indirect.dump().starts_with("instruction\n  0  (0)\n  1  (2)\n  2  (1)\n"));
+        assert_eq!("  0: t0 = minus c
+  1: t1 = minus c
+  2: t2 = b * t0
+  3: t3 = b * t1
+  4: a = t2 + t3
+", indirect.to_quadruples().to_string());
+    }
+
+    #[test]
+    fn tac_control_test() {
+        // 飛び先は変換のたびに番号を付け直す
+        let mut code = Quadruples::new();
+        let i = name("i");
+        let x = name("x");
+        code.push(Quad::copy(i.clone(), Operand::Const(Constant::Int(0))));
+        code.push(Quad::if_rel(BinOp::Ge, i.clone(), Operand::Const(Constant::Int(10)), 6));
+        code.push(Quad::binary(BinOp::Mul, Operand::Temp(1), i.clone(), Operand::Const(Constant::Int(4))));
+        code.push(Quad::index_store(x.clone(), Operand::Temp(1), Operand::Const(Constant::Float(0.0))));
+        code.push(Quad::binary(BinOp::Add, i.clone(), i.clone(), Operand::Const(Constant::Int(1))));
+        code.push(Quad::goto(1));
+        code.push(Quad::param(x.clone()));
+        code.push(Quad::call(Some(Operand::Temp(2)), name("f"), 1));
+        code.push(Quad::ret(Some(Operand::Temp(2))));
+        let text = code.to_string();
+        assert!(text.contains("  1: if i >= 10 goto 6\n"));
+        assert!(text.contains("  3: x[t1] = 0.0\n"));
+        assert!(text.contains("  7: t2 = call f, 1\n"));
+        // x[t1] = 0.0 は x を書き換えず、x と t1 を読む
+        assert_eq!(vec![&x, &Operand::Temp(1)], code.quads[3].uses());
+        assert_eq!(None, code.quads[3].def());
+        assert_eq!((vec![&i], Some(&i)), (code.quads[4].uses(), code.quads[4].def()));
+        assert!(code.quads[7].uses().is_empty());
+
+        let triples = code.to_triples();
+        assert_eq!("     op      arg1    arg2
+(0)  =       i       0
+(1)  >=      i       10
+(2)  if      (1)     9
+(3)  *       i       4
+(4)  []=     x       (3)
+(5)  =       (4)     0.0
+(6)  +       i       1
+(7)  =       i       (6)
+(8)  goto    1
+(9)  param   x
+(10) call    f       1
+(11) return  (10)
+", triples.dump());
+        // 四つ組に戻すと一時変数の番号は三つ組の番号になる
+        assert_eq!("  0: i = 0
+  1: if i >= 10 goto 6
+  2: t3 = i * 4
+  3: x[t3] = 0.0
+  4: i = i + 1
+  5: goto 1
+  6: param x
+  7: t10 = call f, 1
+  8: return t10
+", triples.to_quadruples().to_string());
+    }
+
+    #[test]
+    fn tac_constant_test() {
+        let int = Constant::Int;
+        assert_eq!(Some(int(i32::MIN)), Constant::binary(BinOp::Add, int(i32::MAX), int(1)));
+        assert_eq!(Some(int(i32::MIN)), Constant::binary(BinOp::Div, int(i32::MIN), int(-1)));
+        assert_eq!(Some(int(-2)), Constant::binary(BinOp::Div, int(-7), int(3)));
+        assert_eq!(None, Constant::binary(BinOp::Div, int(1), int(0)));
+        assert_eq!(Some(Constant::Bool(true)), Constant::binary(BinOp::Le, int(1), int(1)));
+        assert_eq!(Some(Constant::Float(f32::INFINITY)), Constant::binary(BinOp::Div, Constant::Float(1.0), Constant::Float(0.0)));
+        assert_eq!(Some(Constant::Float(0.3)), Constant::binary(BinOp::Add, Constant::Float(0.1), Constant::Float(0.2)));
+        let nan = Constant::Float(f32::NAN);
+        assert_eq!(Some(Constant::Bool(false)), Constant::binary(BinOp::Eq, nan, nan));
+        assert_eq!(Some(Constant::Bool(true)), Constant::binary(BinOp::Ne, nan, nan));
+        assert_eq!(None, Constant::binary(BinOp::Add, int(1), Constant::Float(1.0)));
+        assert_eq!(Some(int(i32::MIN)), Constant::unary(UnOp::Neg, int(i32::MIN)));
+        assert_eq!(Some(Constant::Float(16777216.0)), Constant::conv(&Type::new_f32(), int(16777217)));
+    }
+}
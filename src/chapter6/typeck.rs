@@ -0,0 +1,642 @@
+use std::collections::HashMap;
+
+use super::super::chapter2::ast::{BinOp, Block, Case, Decl, Expr, ExprKind, Func, NodeId, Program, Stmt, StmtKind, UnOp};
+use super::super::chapter2::diagnostic::Diagnostic;
+use super::super::chapter2::mtype::Type;
+use super::super::chapter2::symbol::Symbol;
+use super::super::chapter2::token::Span;
+use super::super::chapter2::visit::{self, Folder, Visitor};
+use super::resolve::Resolver;
+
+/// 抽象構文木の型検査器(6.5節)
+/// すべての式に型を割り当て、必要な箇所に拡大変換の節(ExprKind::Widen)を挿入する
+/// 型の誤りは診断として記録し、誤りのある式の型は割り当てない(それを含む式では重ねて報告しない)
+/// 名前の型は、名前解決(Resolver)で結びつけた宣言から引く
+///
+/// # members
+/// - resolver: Resolver => 名前の出現から宣言への表
+/// - types: HashMap<NodeId, Type> => 式の節番号ごとの型
+/// - diagnostics: Vec<Diagnostic> => 型の誤り
+/// - next_id: NodeId => 挿入する節に割り当てる番号
+/// - ret: Option<Type> => 検査中の関数の戻り値の型(関数の外ならNone)
+pub struct TypeChecker {
+    resolver: Resolver,
+    types: HashMap<NodeId, Type>,
+    diagnostics: Vec<Diagnostic>,
+    next_id: NodeId,
+    ret: Option<Type>
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeChecker {
+    /// TypeCheckerを生成して返す
+    ///
+    /// # returns
+    /// - TypeChecker
+    pub fn new() -> TypeChecker {
+        TypeChecker { resolver: Resolver::new(), types: HashMap::new(), diagnostics: vec![], next_id: 0, ret: None }
+    }
+
+    /// プログラムを型検査し、拡大変換を挿入したプログラムを返す
+    ///
+    /// # params
+    /// - program: Program => 検査するプログラム
+    ///
+    /// # returns
+    /// - Program
+    pub fn check_program(&mut self, program: Program) -> Program {
+        self.reserve_ids(|max| max.visit_program(&program));
+        self.resolver.resolve_program(&program);
+        self.fold_program(program)
+    }
+
+    /// ブロックを型検査し、拡大変換を挿入したブロックを返す
+    ///
+    /// # params
+    /// - block: Block => 検査するブロック
+    ///
+    /// # returns
+    /// - Block
+    pub fn check_block(&mut self, block: Block) -> Block {
+        self.reserve_ids(|max| max.visit_block(&block));
+        self.resolver.resolve_block(&block);
+        self.fold_block(block)
+    }
+
+    /// 式を型検査し、拡大変換を挿入した式を返す
+    ///
+    /// # params
+    /// - expr: Expr => 検査する式
+    ///
+    /// # returns
+    /// - Expr
+    pub fn check_expr(&mut self, expr: Expr) -> Expr {
+        self.reserve_ids(|max| max.visit_expr(&expr));
+        self.resolver.resolve_expr(&expr);
+        self.fold_expr(expr)
+    }
+
+    /// 式の型を返す(型の誤りがあった式ならNone)
+    ///
+    /// # params
+    /// - id: NodeId => 式の節番号
+    ///
+    /// # returns
+    /// - Option<&Type>
+    pub fn type_of(&self, id: NodeId) -> Option<&Type> {
+        self.types.get(&id)
+    }
+
+    /// 名前の出現が指す記号を返す
+    ///
+    /// # params
+    /// - id: NodeId => 名前の出現の節番号
+    ///
+    /// # returns
+    /// - Option<&Symbol>
+    pub fn symbol_of(&self, id: NodeId) -> Option<&Symbol> {
+        self.resolver.symbol_of(id)
+    }
+
+    /// 宣言の記号を返す
+    ///
+    /// # params
+    /// - decl: NodeId => 宣言(仮引数を含む)の節番号
+    ///
+    /// # returns
+    /// - Option<&Symbol>
+    pub fn symbol(&self, decl: NodeId) -> Option<&Symbol> {
+        self.resolver.symbol(decl)
+    }
+
+    /// 見つかった型の誤りを返す
+    ///
+    /// # returns
+    /// - &[Diagnostic]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// 既存の節番号と重ならないよう、挿入する節の番号の始まりを決める
+    fn reserve_ids<F: FnOnce(&mut MaxId)>(&mut self, visit: F) {
+        let mut max = MaxId(self.next_id);
+        visit(&mut max);
+        self.next_id = max.0;
+    }
+
+    /// 式をtoの型まで拡大変換する(6.5.2節のwiden)
+    fn widen(&mut self, expr: Expr, to: &Type) -> Expr {
+        match self.types.get(&expr.id) {
+            Some(ty) if ty != to => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.types.insert(id, to.clone());
+                let span = expr.span;
+                Expr { id, kind: ExprKind::Widen(to.clone(), Box::new(expr)), span }
+            },
+            _ => expr
+        }
+    }
+
+    /// 整数でなければならない式を検査し、i32に拡大して返す
+    fn integral(&mut self, expr: Expr, what: &str) -> Expr {
+        match self.types.get(&expr.id) {
+            Some(ty) if !ty.is_integral() => {
+                let message = format!("{} must be an integer, found `{}`", what, ty);
+                self.diagnostics.push(Diagnostic::error(expr.span, message));
+                expr
+            },
+            _ => self.widen(expr, &Type::new_i32())
+        }
+    }
+
+    /// boolでなければならない式を検査する
+    fn boolean(&mut self, expr: Expr, what: &str) -> Expr {
+        match self.types.get(&expr.id) {
+            Some(ty) if *ty != Type::new_bool() => {
+                let message = format!("{} must be `bool`, found `{}`", what, ty);
+                self.diagnostics.push(Diagnostic::error(expr.span, message));
+            },
+            _ => {}
+        }
+        expr
+    }
+
+    /// 二項演算を検査する
+    /// - &&, || => 被演算子はbool、結果はbool
+    /// - 算術演算 => 被演算子は数値型で大きい方へ拡大、結果もその型
+    /// - 比較演算 => 被演算子は数値型で大きい方へ拡大(==, != は同じ型のboolとcharも可)、結果はbool
+    fn check_binary(&mut self, op: BinOp, lhs: Expr, rhs: Expr) -> (ExprKind, Option<Type>) {
+        if let BinOp::And | BinOp::Or = op {
+            let what = format!("operand of `{}`", op);
+            let lhs = self.boolean(lhs, &what);
+            let rhs = self.boolean(rhs, &what);
+            let ty = self.both_typed(&lhs, &rhs).map(|_| Type::new_bool());
+            return (ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), ty);
+        }
+        let (l, r) = match self.both_typed(&lhs, &rhs) {
+            Some(types) => types,
+            None => return (ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), None)
+        };
+        let arith = matches!(op, BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div);
+        let operand = match l.max(&r) {
+            Some(max) if max.is_numeric() => Some(max),
+            _ if !arith && matches!(op, BinOp::Eq | BinOp::Ne) && l == r && matches!(l, Type::Bool(_)) => Some(l.clone()),
+            _ => None
+        };
+        match operand {
+            Some(ty) => {
+                let lhs = self.widen(lhs, &ty);
+                let rhs = self.widen(rhs, &ty);
+                let result = if arith { ty } else { Type::new_bool() };
+                (ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), Some(result))
+            },
+            None => {
+                let message = format!("operator `{}` cannot be applied to `{}` and `{}`", op, l, r);
+                self.diagnostics.push(Diagnostic::error(lhs.span.to(rhs.span), message));
+                (ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), None)
+            }
+        }
+    }
+
+    /// 関数呼出しを検査する
+    /// 実引数の個数は仮引数と同じで、各実引数は仮引数の型へ拡大できなければならない
+    /// 結果の型は関数の戻り値の型
+    fn check_call(&mut self, id: NodeId, span: Span, name: String, args: Vec<Expr>) -> (ExprKind, Option<Type>) {
+        let (params, ret) = match self.resolver.symbol_of(id).map(|s| s.ty.clone()) {
+            Some(Type::Function(params, ret)) => (params, *ret),
+            Some(_) => {
+                self.diagnostics.push(Diagnostic::error(span, format!("`{}` is not a function", name)));
+                return (ExprKind::Call(name, args), None);
+            },
+            None => {
+                self.diagnostics.push(Diagnostic::error(span, format!("undeclared identifier `{}`", name)));
+                return (ExprKind::Call(name, args), None);
+            }
+        };
+        if params.len() != args.len() {
+            let message = format!("function `{}` takes {} argument{} but {} {} supplied", name, params.len(),
+                if params.len() == 1 { "" } else { "s" }, args.len(), if args.len() == 1 { "was" } else { "were" });
+            self.diagnostics.push(Diagnostic::error(span, message));
+            return (ExprKind::Call(name, args), Some(ret));
+        }
+        let mut checked = vec![];
+        for (i, (arg, param)) in args.into_iter().zip(params.iter()).enumerate() {
+            match self.types.get(&arg.id).cloned() {
+                Some(ty) if !ty.widens_to(param) => {
+                    let message = format!("mismatched types: argument {} of `{}` expects `{}`, found `{}`", i+1, name, param, ty);
+                    self.diagnostics.push(Diagnostic::error(arg.span, message));
+                    checked.push(arg);
+                },
+                _ => checked.push(self.widen(arg, param))
+            }
+        }
+        (ExprKind::Call(name, checked), Some(ret))
+    }
+
+    /// return文を検査し、値を戻り値の型へ拡大する
+    fn check_return(&mut self, span: Span, value: Option<Expr>) -> StmtKind {
+        let ret = self.ret.clone().unwrap_or(Type::Void);
+        match value {
+            None if ret != Type::Void => {
+                let message = format!("`return` without a value in function returning `{}`", ret);
+                self.diagnostics.push(Diagnostic::error(span, message));
+                StmtKind::Return(None)
+            },
+            None => StmtKind::Return(None),
+            Some(value) if ret == Type::Void => {
+                self.diagnostics.push(Diagnostic::error(value.span, "`return` with a value in function returning `void`".to_string()));
+                StmtKind::Return(Some(value))
+            },
+            Some(value) => match self.types.get(&value.id).cloned() {
+                Some(ty) if !ty.widens_to(&ret) => {
+                    let message = format!("mismatched types: cannot return `{}` from function returning `{}`", ty, ret);
+                    self.diagnostics.push(Diagnostic::error(value.span, message));
+                    StmtKind::Return(Some(value))
+                },
+                _ => StmtKind::Return(Some(self.widen(value, &ret)))
+            }
+        }
+    }
+
+    /// switchの節のラベルを検査する
+    /// 同じ値のcaseと2つ目のdefaultは誤り、defaultがないことは警告にする
+    fn check_cases(&mut self, span: Span, cases: &[Case]) {
+        let mut seen: HashMap<Option<i32>, Span> = HashMap::new();
+        for case in cases {
+            if let Some(previous) = seen.get(&case.value) {
+                let (message, note) = match case.value {
+                    Some(value) => (format!("duplicate case value `{}`", value), "previous case here".to_string()),
+                    None => ("multiple `default` labels in one switch".to_string(), "previous `default` here".to_string())
+                };
+                self.diagnostics.push(Diagnostic::error(case.span, message).with_note(*previous, note));
+            } else {
+                seen.insert(case.value, case.span);
+            }
+        }
+        if !seen.contains_key(&None) {
+            self.diagnostics.push(Diagnostic::warning(span, "switch has no `default` case".to_string()));
+        }
+    }
+
+    fn both_typed(&self, lhs: &Expr, rhs: &Expr) -> Option<(Type, Type)> {
+        match (self.types.get(&lhs.id), self.types.get(&rhs.id)) {
+            (Some(l), Some(r)) => Some((l.clone(), r.clone())),
+            _ => None
+        }
+    }
+}
+
+impl Folder for TypeChecker {
+    fn fold_func(&mut self, func: Func) -> Func {
+        self.ret = Some(func.ret.clone());
+        let func = visit::fold_func(self, func);
+        self.ret = None;
+        if let Some(body) = &func.body {
+            if func.ret != Type::Void && !seq_returns(&body.stmts) {
+                self.diagnostics.push(Diagnostic::error(body.span, format!("missing return in function returning `{}`", func.ret)));
+            }
+        }
+        func
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        let stmt = visit::fold_stmt(self, stmt);
+        let kind = match stmt.kind {
+            StmtKind::Assign(loc, expr) => {
+                match self.both_typed(&loc, &expr) {
+                    // 配列とレコードは1回の写しで代入できない
+                    Some((l, _)) if matches!(l, Type::Array(..) | Type::Record(..)) => {
+                        let message = format!("cannot assign to `{}`: only scalar values can be assigned", l);
+                        self.diagnostics.push(Diagnostic::error(loc.span, message));
+                        StmtKind::Assign(loc, expr)
+                    },
+                    Some((l, r)) if r.widens_to(&l) => {
+                        let expr = self.widen(expr, &l);
+                        StmtKind::Assign(loc, expr)
+                    },
+                    Some((l, r)) => {
+                        let message = format!("mismatched types: cannot assign `{}` to `{}`", r, l);
+                        let note = format!("target has type `{}`", l);
+                        self.diagnostics.push(Diagnostic::error(expr.span, message).with_note(loc.span, note));
+                        StmtKind::Assign(loc, expr)
+                    },
+                    None => StmtKind::Assign(loc, expr)
+                }
+            },
+            StmtKind::If(cond, then, els) => StmtKind::If(self.boolean(cond, "condition"), then, els),
+            StmtKind::While(cond, body) => StmtKind::While(self.boolean(cond, "condition"), body),
+            StmtKind::DoWhile(body, cond) => StmtKind::DoWhile(body, self.boolean(cond, "condition")),
+            StmtKind::Switch(expr, cases) => {
+                self.check_cases(stmt.span, &cases);
+                StmtKind::Switch(self.integral(expr, "switch expression"), cases)
+            },
+            StmtKind::Return(value) => self.check_return(stmt.span, value),
+            kind => kind
+        };
+        Stmt { kind, ..stmt }
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        let expr = visit::fold_expr(self, expr);
+        let (kind, ty) = match expr.kind {
+            ExprKind::Int(num) => (ExprKind::Int(num), Some(Type::new_i32())),
+            ExprKind::Float(num) => (ExprKind::Float(num), Some(Type::new_f32())),
+            ExprKind::Bool(b) => (ExprKind::Bool(b), Some(Type::new_bool())),
+            ExprKind::Var(name) => {
+                let ty = match self.resolver.symbol_of(expr.id) {
+                    Some(symbol) => Some(symbol.ty.clone()),
+                    None => {
+                        self.diagnostics.push(Diagnostic::error(expr.span, format!("undeclared identifier `{}`", name)));
+                        None
+                    }
+                };
+                (ExprKind::Var(name), ty)
+            },
+            ExprKind::Index(array, index) => {
+                let index = self.integral(*index, "array index");
+                let ty = match self.types.get(&array.id) {
+                    Some(Type::Array(_, elem)) => Some(*elem.clone()),
+                    Some(ty) => {
+                        let message = format!("cannot index into a value of type `{}`", ty);
+                        self.diagnostics.push(Diagnostic::error(array.span, message));
+                        None
+                    },
+                    None => None
+                };
+                (ExprKind::Index(array, Box::new(index)), ty)
+            },
+            ExprKind::Unary(UnOp::Neg, operand) => {
+                // charはi32に拡大してから符号を反転する
+                let ty = match self.types.get(&operand.id).cloned() {
+                    Some(ty) if ty.is_numeric() => ty.max(&Type::new_i32()),
+                    Some(ty) => {
+                        let message = format!("operator `-` cannot be applied to `{}`", ty);
+                        self.diagnostics.push(Diagnostic::error(operand.span, message));
+                        None
+                    },
+                    None => None
+                };
+                let operand = match &ty {
+                    Some(ty) => self.widen(*operand, ty),
+                    None => *operand
+                };
+                (ExprKind::Unary(UnOp::Neg, Box::new(operand)), ty)
+            },
+            ExprKind::Unary(UnOp::Not, operand) => {
+                let operand = self.boolean(*operand, "operand of `!`");
+                let ty = self.types.get(&operand.id).filter(|t| **t == Type::new_bool()).cloned();
+                (ExprKind::Unary(UnOp::Not, Box::new(operand)), ty)
+            },
+            ExprKind::Binary(op, lhs, rhs) => self.check_binary(op, *lhs, *rhs),
+            ExprKind::Call(name, args) => self.check_call(expr.id, expr.span, name, args),
+            ExprKind::Widen(ty, operand) => (ExprKind::Widen(ty.clone(), operand), Some(ty))
+        };
+        if let Some(ty) = ty {
+            self.types.insert(expr.id, ty);
+        }
+        Expr { kind, ..expr }
+    }
+}
+
+/// 文の並びが、どの経路でも最後まで進まずにreturnするか
+/// break, continueに出会えば、その先はたどらない
+fn seq_returns(stmts: &[Stmt]) -> bool {
+    for stmt in stmts {
+        match stmt.kind {
+            StmtKind::Break | StmtKind::Continue => return false,
+            _ if returns(stmt) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// 文がどの経路でもreturnするか(後ろの文へ進まないか)
+/// 条件が定数trueのループは、breakで抜けない限り後ろへ進まない
+fn returns(stmt: &Stmt) -> bool {
+    let forever = |cond: &Expr| cond.kind == ExprKind::Bool(true);
+    match &stmt.kind {
+        StmtKind::Return(_) => true,
+        StmtKind::Block(block) => seq_returns(&block.stmts),
+        StmtKind::If(_, then, Some(els)) => returns(then) && returns(els),
+        StmtKind::While(cond, body) => forever(cond) && !escapes(body, true, false),
+        StmtKind::DoWhile(body, cond) => (returns(body) || forever(cond)) && !escapes(body, true, !forever(cond)),
+        // 節の終わりで次の節へ進むので、どの節から入っても最後までにreturnすればよい
+        StmtKind::Switch(_, cases) => cases.iter().any(|case| case.value.is_none())
+            && !cases.iter().flat_map(|case| &case.stmts).any(|stmt| escapes(stmt, true, true))
+            && (0..cases.len()).all(|k| {
+                let rest: Vec<Stmt> = cases[k..].iter().flat_map(|case| case.stmts.iter().cloned()).collect();
+                seq_returns(&rest)
+            }),
+        _ => false
+    }
+}
+
+/// 文の中に、文の外へ抜けるbreak(breakがtrueのとき)かcontinue(continueがtrueのとき)があるか
+/// 内側のループとswitchのbreak、内側のループのcontinueは数えない
+fn escapes(stmt: &Stmt, brk: bool, cont: bool) -> bool {
+    match &stmt.kind {
+        StmtKind::Break => brk,
+        StmtKind::Continue => cont,
+        StmtKind::Block(block) => block.stmts.iter().any(|s| escapes(s, brk, cont)),
+        StmtKind::If(_, then, els) => escapes(then, brk, cont) || els.as_ref().is_some_and(|e| escapes(e, brk, cont)),
+        StmtKind::Switch(_, cases) => cases.iter().flat_map(|case| &case.stmts).any(|s| escapes(s, false, cont)),
+        _ => false
+    }
+}
+
+/// 木の中で最大の節番号の次の番号を求める
+struct MaxId(NodeId);
+
+impl Visitor for MaxId {
+    fn visit_program(&mut self, program: &Program) {
+        self.0 = self.0.max(program.id+1);
+        visit::walk_program(self, program);
+    }
+
+    fn visit_func(&mut self, func: &Func) {
+        self.0 = self.0.max(func.id+1);
+        visit::walk_func(self, func);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.0 = self.0.max(block.id+1);
+        visit::walk_block(self, block);
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.0 = self.0.max(stmt.id+1);
+        visit::walk_stmt(self, stmt);
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        self.0 = self.0.max(decl.id+1);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.0 = self.0.max(expr.id+1);
+        visit::walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypeChecker;
+    use super::super::super::chapter2::ast::StmtKind;
+    use super::super::super::chapter2::diagnostic::Level;
+    use super::super::super::chapter2::mtype::Type;
+    use super::super::super::chapter2::parser::astparser::AstParser;
+    use super::super::super::chapter2::pretty::{pretty_block, sexpr_block, sexpr_expr, sexpr_program};
+    use super::super::super::chapter2::token::Span;
+
+    fn check(program: &str) -> (String, Vec<String>) {
+        let block = AstParser::new(program).parse_block().unwrap();
+        let mut checker = TypeChecker::new();
+        let block = checker.check_block(block);
+        (sexpr_block(&block), checker.diagnostics().iter().map(|d| d.message.clone()).collect())
+    }
+
+    #[test]
+    fn typeck_widen_test() {
+        let (sexpr, diags) = check("{ i32 i; f32 x; char c; x = i + c * 2.5; i = c; x = -c; }");
+        assert!(diags.is_empty());
+        assert_eq!("(block (decl i32 i) (decl f32 x) (decl char c) \
+(= x (+ (widen f32 i) (* (widen f32 c) 2.5))) (= i (widen i32 c)) (= x (widen f32 (- (widen i32 c)))))", sexpr);
+
+        let expr = AstParser::new("1 + 2.0 < 3").parse_expr().unwrap();
+        let mut checker = TypeChecker::new();
+        let expr = checker.check_expr(expr);
+        assert_eq!("(< (+ (widen f32 1) 2.0) (widen f32 3))", sexpr_expr(&expr));
+        assert_eq!(Some(&Type::new_bool()), checker.type_of(expr.id));
+    }
+
+    #[test]
+    fn typeck_error_test() {
+        let program = "{ i32 i; f32 x; i = x; while (x) i = i + 1; if (x && i) y = 1; i[0] = 1; i = i[x]; }";
+        let (_, diags) = check(program);
+        assert_eq!(vec![
+            "mismatched types: cannot assign `f32` to `i32`",
+            "condition must be `bool`, found `f32`",
+            "operand of `&&` must be `bool`, found `f32`",
+            "operand of `&&` must be `bool`, found `i32`",
+            "undeclared identifier `y`",
+            "cannot index into a value of type `i32`",
+            "array index must be an integer, found `f32`",
+            "cannot index into a value of type `i32`"
+        ], diags);
+
+        let (_, diags) = check("{ bool b; i32 i; b = i < 1 && !b; i = b + 1; b = -b; if (b == true) i = 0; if (b < true) i = 0; }");
+        assert_eq!(vec![
+            "operator `+` cannot be applied to `bool` and `i32`",
+            "operator `-` cannot be applied to `bool`",
+            "operator `<` cannot be applied to `bool` and `bool`"
+        ], diags);
+
+        let block = AstParser::new(program).parse_block().unwrap();
+        let mut checker = TypeChecker::new();
+        checker.check_block(block);
+        let diag = &checker.diagnostics()[0];
+        assert_eq!(Span::new(1, 21, 20, 21), diag.span);
+        assert_eq!((Span::new(1, 17, 16, 17), "target has type `i32`".to_string()), diag.notes[0]);
+    }
+
+    #[test]
+    fn typeck_array_test() {
+        let (sexpr, diags) = check("{ i32[10][20] a; f32 x; char c; x = a[c][2] + 1; a[1][1] = x; }");
+        assert_eq!(vec!["mismatched types: cannot assign `f32` to `i32`"], diags);
+        assert!(sexpr.contains("(= x (widen f32 (+ ([] ([] a (widen i32 c)) 2) 1)))"));
+
+        // 配列の行や配列全体は代入できない
+        let (_, diags) = check("{ i32[2][3] a; i32[3] b; i32[3] c; a[1] = a[0]; b = c; a[1] = b; }");
+        assert_eq!(vec!["cannot assign to `[3]i32`: only scalar values can be assigned"; 3], diags);
+    }
+
+    #[test]
+    fn typeck_switch_test() {
+        let (sexpr, diags) = check("{ char c; f32 x; switch (c) { case 1: x = 1; default: case 2: break; } }");
+        assert!(diags.is_empty());
+        assert_eq!("(block (decl char c) (decl f32 x) (switch (widen i32 c) (case 1 (= x (widen f32 1))) (default) (case 2 (break))))", sexpr);
+
+        let (_, diags) = check("{ f32 x; switch (x) { case 1: case 2: case 1: default: default: } }");
+        assert_eq!(vec![
+            "duplicate case value `1`",
+            "multiple `default` labels in one switch",
+            "switch expression must be an integer, found `f32`"
+        ], diags);
+
+        let block = AstParser::new("{ i32 x; switch (x) { case 1: break; } }").parse_block().unwrap();
+        let mut checker = TypeChecker::new();
+        checker.check_block(block);
+        let diag = &checker.diagnostics()[0];
+        assert_eq!(Level::Warning, diag.level);
+        assert_eq!("switch has no `default` case", diag.message);
+        assert_eq!(Span::new(1, 10, 9, 38), diag.span);
+    }
+
+    #[test]
+    fn typeck_scope_test() {
+        let block = AstParser::new("{ f32 x; { i32 x; x = 1; } x = 1; }").parse_block().unwrap();
+        let mut checker = TypeChecker::new();
+        let block = checker.check_block(block);
+        assert!(checker.diagnostics().is_empty());
+        match (&block.stmts[1].kind, &block.stmts[2].kind) {
+            (StmtKind::Block(inner), StmtKind::Assign(_, outer)) => {
+                assert_eq!("(block (decl i32 x) (= x 1))", sexpr_block(inner));
+                assert_eq!("(widen f32 1)", sexpr_expr(outer));
+            },
+            _ => panic!("test failed at [typeck_scope_test]")
+        }
+        // 挿入した変換はソース上は暗黙なので、整形すると元に戻る
+        assert_eq!("{\n    f32 x;\n    {\n        i32 x;\n        x = 1;\n    }\n    x = 1;\n}", pretty_block(&block));
+    }
+
+    #[test]
+    fn typeck_call_test() {
+        let check_program = |src: &str| -> (String, Vec<String>) {
+            let program = AstParser::new(src).parse_program().unwrap();
+            let mut checker = TypeChecker::new();
+            let program = checker.check_program(program);
+            (sexpr_program(&program), checker.diagnostics().iter().map(|d| d.message.clone()).collect())
+        };
+        let (sexpr, diags) = check_program("fn f(f32 x, i32 y) -> f32 { return y; } fn g() { char c; f32 z; z = f(c, c); }");
+        assert!(diags.is_empty());
+        assert_eq!("(program (fn f ((decl f32 x) (decl i32 y)) f32 (block (return (widen f32 y)))) \
+(fn g () void (block (decl char c) (decl f32 z) (= z (call f (widen f32 c) (widen i32 c))))))", sexpr);
+
+        let (_, diags) = check_program("\
+fn f(i32 a, bool b) -> i32 { i32 x; x = f(1); x = f(true, true); x(1); return 1.5; }
+fn g() { i32 y; y = g(); return 1; }
+fn h() -> bool { return; }");
+        assert_eq!(vec![
+            "function `f` takes 2 arguments but 1 was supplied",
+            "mismatched types: argument 1 of `f` expects `i32`, found `bool`",
+            "`x` is not a function",
+            "mismatched types: cannot return `f32` from function returning `i32`",
+            "mismatched types: cannot assign `void` to `i32`",
+            "`return` with a value in function returning `void`",
+            "`return` without a value in function returning `bool`"
+        ], diags);
+
+        // 値を返す関数は、どの経路でもreturnしなければならない
+        let (_, diags) = check_program("\
+fn f() -> i32 { }
+fn g(bool c) -> i32 { if (c) return 1; }
+fn h(i32 n) -> i32 { while (n > 0) return 1; }
+fn k(i32 n) -> i32 { while (true) { if (n > 0) break; return 1; } }
+fn m(i32 n) -> i32 { switch (n) { case 1: return 1; case 2: break; default: return 2; } }");
+        assert_eq!(vec!["missing return in function returning `i32`"; 5], diags);
+        let (_, diags) = check_program("\
+fn f(bool c) -> i32 { if (c) return 1; else { return 2; } }
+fn g(i32 n) -> i32 { while (true) { if (n > 0) return n; n = n + 1; } }
+fn h(i32 n) -> i32 { switch (n) { case 1: n = 2; default: return n; } }
+fn k(i32 n) -> i32 { do { if (n > 0) continue; return n; } while (true); }
+fn m() { }");
+        assert!(diags.is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+use super::super::chapter2::ast::{Block, Decl, Expr, ExprKind, Stmt, StmtKind};
+use super::super::chapter2::diagnostic::Diagnostic;
+use super::super::chapter2::symbol::{Access, ShadowPolicy, Symbol, SymbolTable};
+use super::super::chapter2::visit::{self, Visitor};
+
+/// 名前の使われ方を記号表に記録し、使われ方についての診断を集める
+/// - 一度も使われない変数、代入されるだけで読まれない変数(スコープを抜けるときに検査する)
+/// - 外側の宣言を隠す宣言
+/// - 定数や関数など、代入できない名前への代入
+///
+/// # members
+/// - table: SymbolTable => 現在のブロックの記号表
+/// - diagnostics: Vec<Diagnostic> => 見つかった診断
+pub struct UsageChecker {
+    table: SymbolTable,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl Default for UsageChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UsageChecker {
+    /// UsageCheckerを生成して返す
+    ///
+    /// # returns
+    /// - UsageChecker
+    pub fn new() -> UsageChecker {
+        let mut table = SymbolTable::new();
+        table.set_shadow_policy(ShadowPolicy::Warn);
+        UsageChecker { table, diagnostics: vec![] }
+    }
+
+    /// 検査するブロックの外側で宣言されている名前(組み込みの定数や関数など)を登録する
+    ///
+    /// # params
+    /// - symbol: Symbol => 記号
+    ///
+    /// # returns
+    /// - Result<(), Diagnostic> => 同じ名前を登録済みなら誤り
+    pub fn predeclare(&mut self, symbol: Symbol) -> Result<(), Diagnostic> {
+        self.table.insert(symbol).map(|_| ())
+    }
+
+    /// ブロックを検査する
+    ///
+    /// # params
+    /// - block: &Block => 検査するブロック
+    pub fn check_block(&mut self, block: &Block) {
+        self.visit_block(block);
+    }
+
+    /// 見つかった診断を返す
+    ///
+    /// # returns
+    /// - &[Diagnostic]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// 代入の左辺を記録する(a[i] = e では a への代入と i の読み出し)
+    fn assign(&mut self, loc: &Expr) {
+        match &loc.kind {
+            ExprKind::Var(name) => {
+                if let Some(symbol) = self.table.record_use(name, loc.span, Access::Write) {
+                    if !symbol.mutable {
+                        let message = format!("cannot assign to {} `{}`", symbol.kind, name);
+                        let note = format!("`{}` declared here", name);
+                        self.diagnostics.push(Diagnostic::error(loc.span, message).with_note(symbol.span, note));
+                    }
+                }
+            },
+            ExprKind::Index(array, index) => {
+                self.visit_expr(index);
+                self.assign(array);
+            },
+            _ => self.visit_expr(loc)
+        }
+    }
+}
+
+impl Visitor for UsageChecker {
+    fn visit_block(&mut self, block: &Block) {
+        self.table.enter(block.span);
+        visit::walk_block(self, block);
+        self.diagnostics.extend(self.table.usage_diagnostics());
+        self.table.exit(block.span);
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Assign(loc, expr) => {
+                self.visit_expr(expr);
+                self.assign(loc);
+            },
+            _ => visit::walk_stmt(self, stmt)
+        }
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        // 再宣言の誤りは構文解析で報告しているので、ここでは外側の宣言を隠す警告だけを集める
+        let _ = self.table.insert(Symbol::new(decl.name.clone(), decl.ty.clone()).with_span(decl.span));
+        self.diagnostics.extend(self.table.take_warnings());
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let ExprKind::Var(name) = &expr.kind {
+            self.table.record_use(name, expr.span, Access::Read);
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UsageChecker;
+    use super::super::super::chapter2::diagnostic::Level;
+    use super::super::super::chapter2::mtype::Type;
+    use super::super::super::chapter2::parser::astparser::AstParser;
+    use super::super::super::chapter2::symbol::{Symbol, SymbolKind};
+
+    fn check(src: &str) -> Vec<String> {
+        let block = AstParser::new(src).parse_block().unwrap();
+        let mut checker = UsageChecker::new();
+        checker.check_block(&block);
+        checker.diagnostics().iter().map(|d| d.message.clone()).collect()
+    }
+
+    #[test]
+    fn usage_test() {
+        assert_eq!(vec!["unused variable `y`", "variable `z` is assigned but never read"],
+            check("{ i32 x; i32 y; i32 z; x = 1; z = x; }"));
+        // 配列の添字は読み出し、配列そのものへは代入
+        assert_eq!(vec!["variable `a` is assigned but never read"],
+            check("{ i32[4] a; i32 i; i = 0; a[i] = 1; }"));
+        assert!(check("{ i32 x; x = 1; while (x < 10) x = x + 1; }").is_empty());
+    }
+
+    #[test]
+    fn usage_shadow_test() {
+        let block = AstParser::new("{ i32 x;\n  x = 1;\n  { f32 x; x = 2.0; x; }\n  x; }").parse_block().unwrap();
+        let mut checker = UsageChecker::new();
+        checker.check_block(&block);
+        let diag = &checker.diagnostics()[0];
+        assert_eq!((Level::Warning, "`x` shadows outer declaration at line 1"), (diag.level, diag.message.as_str()));
+        assert_eq!((3, 1), (diag.span.line, diag.notes[0].0.line));
+        assert_eq!(1, checker.diagnostics().len());
+    }
+
+    #[test]
+    fn usage_const_test() {
+        let block = AstParser::new("{ i32 r; r = 2; pi = 3.0; r; }").parse_block().unwrap();
+        let mut checker = UsageChecker::new();
+        checker.predeclare(Symbol::new("pi".to_string(), Type::new_f32()).with_kind(SymbolKind::Const)).unwrap();
+        checker.check_block(&block);
+        let messages: Vec<&str> = checker.diagnostics().iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(vec!["cannot assign to constant `pi`"], messages);
+    }
+}
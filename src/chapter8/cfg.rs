@@ -0,0 +1,477 @@
+use std::fmt;
+
+use super::super::chapter6::tac::{Constant, Opcode, Operand, Quad, Quadruples};
+
+/// 基本ブロック(8.4.1節)
+/// 飛び越し命令の飛び先は、命令の番号ではなく飛び先のブロックの番号で持つ
+///
+/// # members
+/// - quads: Vec<Quad> => ブロックの命令
+/// - start: usize => 先頭の命令の、元のコードでの番号(ENTRYは0, EXITはコードの長さ)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct BasicBlock {
+    pub quads: Vec<Quad>,
+    pub start: usize
+}
+
+/// 流れグラフ(8.4.3節)
+/// 0番のブロックがENTRY、最後のブロックがEXITで、どちらも命令を持たない
+/// 本当の基本ブロックはコードに現れた順にB1, B2, ... と番号をつける
+///
+/// # members
+/// - blocks: Vec<BasicBlock> => ブロック(添字がブロックの番号)
+/// - succs: Vec<Vec<usize>> => ブロックごとの後続
+/// - preds: Vec<Vec<usize>> => ブロックごとの先行
+///
+/// # derive
+/// - Clone
+/// - Debug
+#[derive(Clone, Debug)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    succs: Vec<Vec<usize>>,
+    preds: Vec<Vec<usize>>
+}
+
+impl Cfg {
+    /// 3番地コードを基本ブロックに分けて流れグラフを作る
+    /// 次の命令がリーダー(基本ブロックの先頭)になる
+    /// - 最初の命令
+    /// - 飛び越し命令の飛び先
+    /// - 飛び越し命令、jumptable、returnの直後の命令
+    ///
+    /// # params
+    /// - code: &Quadruples => 3番地コード
+    ///
+    /// # returns
+    /// - Cfg
+    pub fn new(code: &Quadruples) -> Cfg {
+        let n = code.quads.len();
+        let mut leader = vec![false; n+1];
+        leader[0] = true;
+        for (i, quad) in code.quads.iter().enumerate() {
+            if let Some(target) = quad.target() {
+                leader[target.min(n)] = true;
+            }
+            if quad.op.is_jump() || matches!(quad.op, Opcode::JumpTable | Opcode::Return) {
+                leader[i+1] = true;
+            }
+        }
+        // 命令の番号からそれを含むブロックの番号への表(コードの終わりはEXIT)
+        let mut block_of = vec![0; n+1];
+        let mut count = 0;
+        for (i, b) in block_of.iter_mut().enumerate().take(n) {
+            if leader[i] {
+                count += 1;
+            }
+            *b = count;
+        }
+        block_of[n] = count+1;
+
+        let mut blocks = vec![BasicBlock { quads: vec![], start: 0 }];
+        for (i, quad) in code.quads.iter().enumerate() {
+            if leader[i] {
+                blocks.push(BasicBlock { quads: vec![], start: i });
+            }
+            let mut quad = quad.clone();
+            if let Some(target) = quad.target() {
+                quad.set_target(block_of[target.min(n)]);
+            }
+            blocks.last_mut().unwrap().quads.push(quad);
+        }
+        blocks.push(BasicBlock { quads: vec![], start: n });
+        Cfg::link(blocks)
+    }
+
+    /// ブロックの最後の命令から辺を張る
+    /// - goto => 飛び先だけ
+    /// - 条件付きの飛び越し => 次のブロックと飛び先
+    /// - jumptable x, n => 直後のn個のブロック
+    /// - return => EXIT
+    /// - それ以外 => 次のブロック
+    fn link(blocks: Vec<BasicBlock>) -> Cfg {
+        let exit = blocks.len()-1;
+        let mut succs = vec![vec![]; blocks.len()];
+        for (b, block) in blocks.iter().enumerate().take(exit) {
+            let last = block.quads.last();
+            succs[b] = match last.map(|q| (&q.op, q.target())) {
+                Some((Opcode::Goto, Some(target))) => vec![target],
+                Some((_, Some(target))) => vec![b+1, target],
+                Some((Opcode::JumpTable, _)) => match &last.unwrap().arg2 {
+                    Some(Operand::Const(Constant::Int(n))) => (b+1..=b+*n as usize).collect(),
+                    _ => vec![]
+                },
+                Some((Opcode::Return, _)) => vec![exit],
+                _ => vec![b+1]
+            };
+            succs[b].dedup();
+        }
+        let mut preds = vec![vec![]; blocks.len()];
+        for (b, ss) in succs.iter().enumerate() {
+            for s in ss {
+                if !preds[*s].contains(&b) {
+                    preds[*s].push(b);
+                }
+            }
+        }
+        Cfg { blocks, succs, preds }
+    }
+
+    /// ENTRYの番号を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn entry(&self) -> usize {
+        0
+    }
+
+    /// EXITの番号を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn exit(&self) -> usize {
+        self.blocks.len()-1
+    }
+
+    /// ブロックの後続を返す
+    ///
+    /// # params
+    /// - b: usize => ブロックの番号
+    ///
+    /// # returns
+    /// - &[usize]
+    pub fn succs(&self, b: usize) -> &[usize] {
+        &self.succs[b]
+    }
+
+    /// ブロックの先行を返す(番号の小さい順)
+    ///
+    /// # params
+    /// - b: usize => ブロックの番号
+    ///
+    /// # returns
+    /// - &[usize]
+    pub fn preds(&self, b: usize) -> &[usize] {
+        &self.preds[b]
+    }
+
+    /// ENTRYからの深さ優先探索の逆後順(9.6.3節)
+    /// 後退辺を除けば、どのブロックもその先行より後に並ぶ
+    /// ENTRYからたどれないブロックは、番号の順に最後に並べる
+    ///
+    /// # returns
+    /// - Vec<usize>
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let n = self.blocks.len();
+        let mut visited = vec![false; n];
+        let mut postorder = vec![];
+        // (ブロック, 次に調べる後続の位置)
+        let mut stack = vec![(self.entry(), 0)];
+        visited[self.entry()] = true;
+        while let Some((b, i)) = stack.pop() {
+            match self.succs[b].get(i) {
+                Some(s) => {
+                    stack.push((b, i+1));
+                    if !visited[*s] {
+                        visited[*s] = true;
+                        stack.push((*s, 0));
+                    }
+                },
+                None => postorder.push(b)
+            }
+        }
+        postorder.reverse();
+        postorder.extend((0..n).filter(|b| !visited[*b]));
+        postorder
+    }
+
+    /// ENTRYからたどれないブロックを取り除き、残ったブロックに番号をつけ直す
+    /// EXITは、たどれなくても(終わらないループなど)残す
+    ///
+    /// # returns
+    /// - Vec<usize> => 取り除いたブロックの元の番号
+    pub fn remove_unreachable(&mut self) -> Vec<usize> {
+        let exit = self.exit();
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![self.entry()];
+        reachable[self.entry()] = true;
+        while let Some(b) = stack.pop() {
+            for s in &self.succs[b] {
+                if !reachable[*s] {
+                    reachable[*s] = true;
+                    stack.push(*s);
+                }
+            }
+        }
+        reachable[exit] = true;
+        let removed: Vec<usize> = (0..self.blocks.len()).filter(|b| !reachable[*b]).collect();
+        if removed.is_empty() {
+            return removed;
+        }
+        let mut renumber = vec![0; self.blocks.len()];
+        let mut next = 0;
+        for (b, r) in renumber.iter_mut().enumerate() {
+            *r = next;
+            if reachable[b] {
+                next += 1;
+            }
+        }
+        let blocks = std::mem::take(&mut self.blocks).into_iter().enumerate()
+            .filter(|(b, _)| reachable[*b])
+            .map(|(_, mut block)| {
+                for quad in &mut block.quads {
+                    if let Some(target) = quad.target() {
+                        quad.set_target(renumber[target]);
+                    }
+                }
+                block
+            })
+            .collect();
+        *self = Cfg::link(blocks);
+        removed
+    }
+
+    /// ブロックを番号の順に並べて3番地コードに戻す
+    /// 飛び先のブロックの番号は、そのブロックの先頭の命令の番号に戻す
+    ///
+    /// # returns
+    /// - Quadruples
+    pub fn to_quadruples(&self) -> Quadruples {
+        let mut start = vec![0; self.blocks.len()];
+        let mut next = 0;
+        for (b, block) in self.blocks.iter().enumerate() {
+            start[b] = next;
+            next += block.quads.len();
+        }
+        let mut code = Quadruples::new();
+        for block in &self.blocks {
+            for quad in &block.quads {
+                let mut quad = quad.clone();
+                if let Some(target) = quad.target() {
+                    quad.set_target(start[target]);
+                }
+                code.push(quad);
+            }
+        }
+        code
+    }
+
+    /// Graphvizのdot形式にする
+    /// 各ブロックの節には命令を左寄せで並べる
+    ///
+    /// # params
+    /// - name: &str => グラフの名前
+    ///
+    /// # returns
+    /// - String
+    pub fn to_dot(&self, name: &str) -> String {
+        let mut out = format!("digraph \"{}\" {{\n", escape(name));
+        out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for (b, block) in self.blocks.iter().enumerate() {
+            if b == self.entry() || b == self.exit() {
+                out.push_str(&format!("    {} [label=\"{}\", shape=ellipse];\n", self.node(b), self.label(b)));
+                continue;
+            }
+            let mut label = format!("{}\\l", self.label(b));
+            for quad in &block.quads {
+                label.push_str(&escape(&self.show(quad)));
+                label.push_str("\\l");
+            }
+            out.push_str(&format!("    {} [label=\"{}\"];\n", self.node(b), label));
+        }
+        for (b, succs) in self.succs.iter().enumerate() {
+            for s in succs {
+                out.push_str(&format!("    {} -> {};\n", self.node(b), self.node(*s)));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// 図の中でのブロックの名前(ENTRY, B1, ..., EXIT)
+    fn label(&self, b: usize) -> String {
+        if b == self.entry() {
+            "ENTRY".to_string()
+        } else if b == self.exit() {
+            "EXIT".to_string()
+        } else {
+            format!("B{}", b)
+        }
+    }
+
+    /// dotの節の識別子
+    fn node(&self, b: usize) -> String {
+        self.label(b).to_lowercase()
+    }
+
+    /// 命令を、飛び先をブロックの名前にして書く
+    fn show(&self, quad: &Quad) -> String {
+        let text = quad.to_string();
+        match quad.target() {
+            Some(target) => {
+                let cut = text.rfind(' ').unwrap();
+                format!("{} {}", &text[..cut], self.label(target))
+            },
+            None => text
+        }
+    }
+}
+
+/// ブロックごとに先行と後続、命令を並べる
+impl fmt::Display for Cfg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = |bs: &[usize]| bs.iter().map(|b| self.label(*b)).collect::<Vec<String>>().join(", ");
+        for (b, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}: preds [{}] succs [{}]", self.label(b), names(&self.preds[b]), names(&self.succs[b]))?;
+            for quad in &block.quads {
+                writeln!(f, "    {}", self.show(quad))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// dotの文字列の中で特別な意味を持つ文字を逃がす
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cfg;
+    use super::super::super::chapter2::ast::BinOp;
+    use super::super::super::chapter2::parser::astparser::AstParser;
+    use super::super::super::chapter6::lower::Lowerer;
+    use super::super::super::chapter6::tac::{Constant, Operand, Quad, Quadruples};
+    use super::super::super::chapter6::tac::test_util::{int, name};
+
+    /// 図8.7 10×10の行列を単位行列にするコード
+    fn identity() -> Quadruples {
+        let (i, j, a) = (name("i"), name("j"), name("a"));
+        let t = Operand::Temp;
+        let mut code = Quadruples::new();
+        code.push(Quad::copy(i.clone(), int(1)));
+        code.push(Quad::copy(j.clone(), int(1)));
+        code.push(Quad::binary(BinOp::Mul, t(1), int(10), i.clone()));
+        code.push(Quad::binary(BinOp::Add, t(2), t(1), j.clone()));
+        code.push(Quad::binary(BinOp::Mul, t(3), int(8), t(2)));
+        code.push(Quad::binary(BinOp::Sub, t(4), t(3), int(88)));
+        code.push(Quad::index_store(a.clone(), t(4), Operand::Const(Constant::Float(0.0))));
+        code.push(Quad::binary(BinOp::Add, j.clone(), j.clone(), int(1)));
+        code.push(Quad::if_rel(BinOp::Le, j.clone(), int(10), 2));
+        code.push(Quad::binary(BinOp::Add, i.clone(), i.clone(), int(1)));
+        code.push(Quad::if_rel(BinOp::Le, i.clone(), int(10), 1));
+        code.push(Quad::copy(i.clone(), int(1)));
+        code.push(Quad::binary(BinOp::Sub, t(5), i.clone(), int(1)));
+        code.push(Quad::binary(BinOp::Mul, t(6), int(88), t(5)));
+        code.push(Quad::index_store(a, t(6), Operand::Const(Constant::Float(1.0))));
+        code.push(Quad::binary(BinOp::Add, i.clone(), i.clone(), int(1)));
+        code.push(Quad::if_rel(BinOp::Le, i, int(10), 12));
+        code
+    }
+
+    #[test]
+    fn cfg_leader_test() {
+        let code = identity();
+        let cfg = Cfg::new(&code);
+        // 図8.9 リーダーは1, 2, 3, 10, 12, 13番目の命令
+        let starts: Vec<usize> = cfg.blocks.iter().map(|b| b.start).collect();
+        assert_eq!(vec![0, 0, 1, 2, 9, 11, 12, 17], starts);
+        assert_eq!("\
+ENTRY: preds [] succs [B1]
+B1: preds [ENTRY] succs [B2]
+    i = 1
+B2: preds [B1, B4] succs [B3]
+    j = 1
+B3: preds [B2, B3] succs [B4, B3]
+    t1 = 10 * i
+    t2 = t1 + j
+    t3 = 8 * t2
+    t4 = t3 - 88
+    a[t4] = 0.0
+    j = j + 1
+    if j <= 10 goto B3
+B4: preds [B3] succs [B5, B2]
+    i = i + 1
+    if i <= 10 goto B2
+B5: preds [B4] succs [B6]
+    i = 1
+B6: preds [B5, B6] succs [EXIT, B6]
+    t5 = i - 1
+    t6 = 88 * t5
+    a[t6] = 1.0
+    i = i + 1
+    if i <= 10 goto B6
+EXIT: preds [B6] succs []
+", cfg.to_string());
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 6, 7], cfg.reverse_postorder());
+        // ブロックを並べ直すと元のコードに戻る
+        assert_eq!(code, cfg.to_quadruples());
+    }
+
+    #[test]
+    fn cfg_unreachable_test() {
+        let block = AstParser::new("{ i32 x; while (true) { x = 1; break; } x = 2; }").parse_block().unwrap();
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_block(block);
+        let mut cfg = Cfg::new(lowerer.code());
+        assert_eq!("  0: x = 1\n  1: goto 3\n  2: goto 0\n  3: x = 2\n", lowerer.code().to_string());
+        // たどれないブロックは逆後順の最後に並ぶ
+        assert_eq!(vec![0, 1, 3, 4, 2], cfg.reverse_postorder());
+        // break の後の goto には飛んでこない
+        assert_eq!(vec![2], cfg.remove_unreachable());
+        assert_eq!("\
+ENTRY: preds [] succs [B1]
+B1: preds [ENTRY] succs [B2]
+    x = 1
+    goto B2
+B2: preds [B1] succs [EXIT]
+    x = 2
+EXIT: preds [B2] succs []
+", cfg.to_string());
+        assert_eq!("  0: x = 1\n  1: goto 2\n  2: x = 2\n", cfg.to_quadruples().to_string());
+        assert!(cfg.remove_unreachable().is_empty());
+    }
+
+    #[test]
+    fn cfg_jump_table_test() {
+        let src = "{ i32 x; switch (x) { case 1: x = 2; case 2: case 3: x = 3; break; case 4: default: x = 0; } }";
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_block(AstParser::new(src).parse_block().unwrap());
+        let cfg = Cfg::new(lowerer.code());
+        let table = cfg.blocks.iter().position(|b| b.quads.last().map(|q| q.to_string().starts_with("jumptable")).unwrap_or(false)).unwrap();
+        // jumptable x, n の後続は直後のn個のブロック
+        assert_eq!(vec![table+1, table+2, table+3, table+4], cfg.succs(table));
+        assert_eq!(lowerer.code(), &cfg.to_quadruples());
+    }
+
+    #[test]
+    fn cfg_dot_test() {
+        let mut code = Quadruples::new();
+        code.push(Quad::if_rel(BinOp::Lt, name("x"), int(0), 3));
+        code.push(Quad::copy(name("y"), int(1)));
+        code.push(Quad::ret(Some(name("y"))));
+        code.push(Quad::copy(name("y"), name("x")));
+        let cfg = Cfg::new(&code);
+        assert_eq!("\
+digraph \"f\" {
+    node [shape=box, fontname=\"monospace\"];
+    entry [label=\"ENTRY\", shape=ellipse];
+    b1 [label=\"B1\\lif x < 0 goto B3\\l\"];
+    b2 [label=\"B2\\ly = 1\\lreturn y\\l\"];
+    b3 [label=\"B3\\ly = x\\l\"];
+    exit [label=\"EXIT\", shape=ellipse];
+    entry -> b1;
+    b1 -> b2;
+    b1 -> b3;
+    b2 -> exit;
+    b3 -> exit;
+}
+", cfg.to_dot("f"));
+    }
+}
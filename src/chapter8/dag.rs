@@ -0,0 +1,692 @@
+use std::fmt;
+
+use super::super::chapter2::ast::BinOp;
+use super::super::chapter6::tac::{Constant, Opcode, Operand, Quad};
+use super::cfg::Cfg;
+
+/// 命令の中で番地を置く場所
+#[derive(Clone, Copy)]
+enum Field {
+    Arg1,
+    Arg2,
+    Result
+}
+
+/// DAGの節の種類
+///
+/// # members
+/// - Leaf(Operand) => 名前や一時変数の、ブロックに入るときの値(図8.12のb0, c0)
+/// - Const(Constant) => 定数
+/// - Op(Box<Quad>) => 演算(命令の雛形で、読む番地は子の節の値で置き換える)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub enum NodeKind {
+    Leaf(Operand),
+    Const(Constant),
+    Op(Box<Quad>)
+}
+
+/// DAGの節
+///
+/// # members
+/// - kind: NodeKind => 種類
+/// - children: Vec<usize> => 読む値の節(命令の読む番地の順)
+/// - labels: Vec<Operand> => ブロックの終わりでこの節の値を持つ名前(付けられた順)
+/// - killed: bool => 配列への代入で値が変わりうるので、共通部分式として使えない
+/// - effect: bool => 副作用があるので取り除けない(代入 x[i] = y, param, call, 飛び越しなど)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub children: Vec<usize>,
+    pub labels: Vec<Operand>,
+    pub killed: bool,
+    pub effect: bool
+}
+
+/// 基本ブロックのDAG(8.5節)
+/// 同じ演算と子を持つ節は作らずに使い回す(局所的な共通部分式の除去、値番号法)
+/// 配列への代入 a[j] = y は、aの要素を読む節をすべて殺す(添字が同じかはわからないので、8.5.5節)
+/// 節を作るときに定数の畳み込みと代数的な恒等式の適用をする
+/// - i32 => 桁あふれは折り返し、0での除算は畳み込まない(Constant::binary)
+/// - f32 => 符号付きの0とNaNがあるので、x * 1.0, x / 1.0, x - 0.0 だけを x にする
+/// - x * 2 => x + x(強さの軽減)
+///
+/// # members
+/// - nodes: Vec<Node> => 節(作られた順なので、子は親より前にある)
+/// - current: Vec<(Operand, usize)> => 名前や一時変数と、いまその値を持つ節
+#[derive(Clone, Debug)]
+pub struct Dag {
+    pub nodes: Vec<Node>,
+    current: Vec<(Operand, usize)>
+}
+
+impl Dag {
+    /// 命令の列からDAGを作る
+    ///
+    /// # params
+    /// - quads: &[Quad] => 基本ブロック(の一部)の命令
+    ///
+    /// # returns
+    /// - Dag
+    pub fn new(quads: &[Quad]) -> Dag {
+        let mut dag = Dag { nodes: vec![], current: vec![] };
+        for quad in quads {
+            dag.add(quad);
+        }
+        dag
+    }
+
+    /// 番地に値を付けた節を返す
+    ///
+    /// # params
+    /// - x: &Operand => 名前や一時変数
+    ///
+    /// # returns
+    /// - Option<usize> => ブロックの終わりでxの値を持つ節(ブロックで値を使わず代入もしなければNone)
+    pub fn node_of(&self, x: &Operand) -> Option<usize> {
+        self.current.iter().find(|(y, _)| y == x).map(|(_, n)| *n)
+    }
+
+    /// DAGから命令の列を作り直す
+    /// 節は作られた順に並べるので、配列の読み書きや副作用の順は元のままになる
+    /// 値は、ブロックの出口で生きている名前のうち最初に付けられたものに計算し、残りの生きている名前へ写す
+    /// 生きている名前も、値を使う節も、副作用もない節は取り除く(死んだコードの除去)
+    /// 値を使う前にその値を持つ名前を書き換えてしまうときは、先に一時変数へ退避する
+    ///
+    /// # params
+    /// - live: F => ブロックの出口で生きている名前か
+    /// - next_temp: &mut usize => 退避などに使う新しい一時変数の番号
+    ///
+    /// # returns
+    /// - Vec<Quad>
+    pub fn generate<F: Fn(&Operand) -> bool>(&self, live: F, next_temp: &mut usize) -> Vec<Quad> {
+        let n = self.nodes.len();
+        let mut gen = Gen {
+            dag: self,
+            out: vec![],
+            holder: self.nodes.iter().map(|node| match &node.kind {
+                NodeKind::Leaf(x) => Some(x.clone()),
+                NodeKind::Const(c) => Some(Operand::Const(*c)),
+                NodeKind::Op(_) => None
+            }).collect(),
+            remaining: vec![0; n],
+            pending: self.nodes.iter().map(|node| match node.kind {
+                NodeKind::Op(_) => vec![],
+                _ => node.labels.iter().filter(|l| live(l)).cloned().collect()
+            }).collect(),
+            next_temp
+        };
+
+        // 生きている節: 副作用があるか、生きている名前が付いているか、生きている節の子
+        let mut alive = vec![false; n];
+        for i in (0..n).rev() {
+            let node = &self.nodes[i];
+            alive[i] = alive[i] || node.effect || node.labels.iter().any(&live);
+            if alive[i] {
+                for c in &node.children {
+                    alive[*c] = true;
+                    gen.remaining[*c] += 1;
+                }
+            }
+        }
+
+        // ブロックの最後の飛び越しは、名前への写しをすべて済ませてから置く
+        let last = match self.nodes.last() {
+            Some(Node { kind: NodeKind::Op(quad), .. }) if quad.op.is_jump() || matches!(quad.op, Opcode::JumpTable | Opcode::Return) => Some(n-1),
+            _ => None
+        };
+        for (i, alive) in alive.into_iter().enumerate() {
+            if alive && Some(i) != last {
+                if let NodeKind::Op(quad) = &self.nodes[i].kind {
+                    gen.emit(i, quad, &live);
+                }
+            }
+        }
+        for i in 0..n {
+            // 写し終えるまで名前を残しておき、退避の判断で葉の値がまだ必要とわかるようにする
+            while let Some(label) = gen.pending[i].last().cloned() {
+                let value = gen.holder[i].clone().unwrap();
+                if label != value {
+                    gen.protect(&label, &[]);
+                    gen.out.push(Quad::copy(label, value));
+                }
+                gen.pending[i].pop();
+            }
+        }
+        if let Some(i) = last {
+            if let NodeKind::Op(quad) = &self.nodes[i].kind {
+                gen.emit(i, quad, &live);
+            }
+        }
+        gen.out
+    }
+
+    fn add(&mut self, quad: &Quad) {
+        let children: Vec<usize> = reads(quad).into_iter().map(|f| self.operand_node(get(quad, f).unwrap())).collect();
+        let node = match &quad.op {
+            Opcode::Copy => children[0],
+            Opcode::Binary(op) => self.binary(*op, quad, children[0], children[1]),
+            Opcode::Unary(op) => match self.constant(children[0]).and_then(|c| Constant::unary(*op, c)) {
+                Some(c) => self.constant_node(c),
+                None => self.find_or_push(quad, children)
+            },
+            Opcode::Conv(ty) => match self.constant(children[0]).and_then(|c| Constant::conv(ty, c)) {
+                Some(c) => self.constant_node(c),
+                None => self.find_or_push(quad, children)
+            },
+            Opcode::IndexLoad => self.find_or_push(quad, children),
+            Opcode::IndexStore => {
+                let array = children[0];
+                for node in &mut self.nodes {
+                    if matches!(&node.kind, NodeKind::Op(q) if q.op == Opcode::IndexLoad) && node.children[0] == array {
+                        node.killed = true;
+                    }
+                }
+                self.push(NodeKind::Op(Box::new(quad.clone())), children, true)
+            },
+            _ => self.push(NodeKind::Op(Box::new(quad.clone())), children, true)
+        };
+        if let (true, Some(x)) = (quad.op.has_value() || quad.op == Opcode::Copy, &quad.result) {
+            for node in &mut self.nodes {
+                node.labels.retain(|l| l != x);
+            }
+            self.nodes[node].labels.push(x.clone());
+            match self.current.iter_mut().find(|(y, _)| y == x) {
+                Some(entry) => entry.1 = node,
+                None => self.current.push((x.clone(), node))
+            }
+        }
+    }
+
+    /// x = y op z の節を作る(畳み込み、恒等式、強さの軽減、共通部分式の順に試す)
+    fn binary(&mut self, op: BinOp, quad: &Quad, y: usize, z: usize) -> usize {
+        let (cy, cz) = (self.constant(y), self.constant(z));
+        if let Some(c) = cy.zip(cz).and_then(|(cy, cz)| Constant::binary(op, cy, cz)) {
+            return self.constant_node(c);
+        }
+        let one = |c: Option<Constant>| matches!(c, Some(Constant::Int(1))) || matches!(c, Some(Constant::Float(f)) if f == 1.0);
+        let zero = |c: Option<Constant>| matches!(c, Some(Constant::Int(0)));
+        let positive_zero = |c: Option<Constant>| zero(c) || matches!(c, Some(Constant::Float(f)) if f.to_bits() == 0);
+        let two = |c: Option<Constant>| matches!(c, Some(Constant::Int(2))) || matches!(c, Some(Constant::Float(f)) if f == 2.0);
+        match op {
+            BinOp::Add if zero(cz) => return y,
+            BinOp::Add if zero(cy) => return z,
+            BinOp::Sub if positive_zero(cz) => return y,
+            BinOp::Mul | BinOp::Div if one(cz) => return y,
+            BinOp::Mul if one(cy) => return z,
+            BinOp::Mul if zero(cy) || zero(cz) => return self.constant_node(Constant::Int(0)),
+            _ => {}
+        }
+        let (op, y, z) = match op {
+            BinOp::Mul if two(cz) => (BinOp::Add, y, y),
+            BinOp::Mul if two(cy) => (BinOp::Add, z, z),
+            op => (op, y, z)
+        };
+        let quad = Quad { op: Opcode::Binary(op), ..quad.clone() };
+        self.find_or_push(&quad, vec![y, z])
+    }
+
+    /// 同じ演算で同じ子を持つ、殺されていない節を探し、なければ作る
+    /// 可換な演算は子の順を問わないので、b + c と c + b は同じ節になる
+    fn find_or_push(&mut self, quad: &Quad, children: Vec<usize>) -> usize {
+        let commutative = matches!(quad.op, Opcode::Binary(BinOp::Add) | Opcode::Binary(BinOp::Mul) | Opcode::Binary(BinOp::Eq) | Opcode::Binary(BinOp::Ne));
+        let found = self.nodes.iter().position(|node| match &node.kind {
+            NodeKind::Op(q) if q.op == quad.op && !node.killed && !node.effect => {
+                node.children == children || (commutative && node.children.iter().rev().eq(children.iter()))
+            },
+            _ => false
+        });
+        match found {
+            Some(n) => n,
+            None => self.push(NodeKind::Op(Box::new(quad.clone())), children, false)
+        }
+    }
+
+    fn push(&mut self, kind: NodeKind, children: Vec<usize>, effect: bool) -> usize {
+        self.nodes.push(Node { kind, children, labels: vec![], killed: false, effect });
+        self.nodes.len()-1
+    }
+
+    /// 読む番地の値を持つ節を返す(初めて読む名前には葉を作る)
+    fn operand_node(&mut self, x: &Operand) -> usize {
+        if let Operand::Const(c) = x {
+            return self.constant_node(*c);
+        }
+        match self.node_of(x) {
+            Some(n) => n,
+            None => {
+                let n = self.push(NodeKind::Leaf(x.clone()), vec![], false);
+                self.current.push((x.clone(), n));
+                n
+            }
+        }
+    }
+
+    /// 定数の節を返す(0.0と-0.0は別の定数)
+    fn constant_node(&mut self, c: Constant) -> usize {
+        let found = self.nodes.iter().position(|node| match (&node.kind, c) {
+            (NodeKind::Const(Constant::Float(x)), Constant::Float(y)) => x.to_bits() == y.to_bits(),
+            (NodeKind::Const(d), c) => *d == c,
+            _ => false
+        });
+        match found {
+            Some(n) => n,
+            None => self.push(NodeKind::Const(c), vec![], false)
+        }
+    }
+
+    fn constant(&self, n: usize) -> Option<Constant> {
+        match self.nodes[n].kind {
+            NodeKind::Const(c) => Some(c),
+            _ => None
+        }
+    }
+}
+
+/// 節を1行ずつ書く(n2: + n0 n1 [a])
+impl fmt::Display for Dag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, node) in self.nodes.iter().enumerate() {
+            let mut line = match &node.kind {
+                NodeKind::Leaf(x) => format!("n{}: leaf {}", i, x),
+                NodeKind::Const(c) => format!("n{}: const {}", i, c),
+                NodeKind::Op(quad) => {
+                    let mut line = format!("n{}: {}", i, quad.op);
+                    for c in &node.children {
+                        line.push_str(&format!(" n{}", c));
+                    }
+                    if let Some(target) = quad.target() {
+                        line.push_str(&format!(" -> {}", target));
+                    }
+                    line
+                }
+            };
+            if !node.labels.is_empty() {
+                let labels: Vec<String> = node.labels.iter().map(|l| l.to_string()).collect();
+                line.push_str(&format!(" [{}]", labels.join(", ")));
+            }
+            if node.killed {
+                line.push_str(" killed");
+            }
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// 命令の列を作り直すときの状態
+///
+/// # members
+/// - dag: &Dag => DAG
+/// - out: Vec<Quad> => 作った命令
+/// - holder: Vec<Option<Operand>> => 節の値をいま持っている番地
+/// - remaining: Vec<usize> => 節の値をまだ読んでいない、生きている親の数
+/// - pending: Vec<Vec<Operand>> => 葉と定数の値をまだ写していない、生きている名前
+/// - next_temp: &mut usize => 次の新しい一時変数の番号
+struct Gen<'a> {
+    dag: &'a Dag,
+    out: Vec<Quad>,
+    holder: Vec<Option<Operand>>,
+    remaining: Vec<usize>,
+    pending: Vec<Vec<Operand>>,
+    next_temp: &'a mut usize
+}
+
+impl<'a> Gen<'a> {
+    /// 演算の節の命令を出す
+    fn emit<F: Fn(&Operand) -> bool>(&mut self, i: usize, quad: &Quad, live: &F) {
+        let node = &self.dag.nodes[i];
+        let mut quad = quad.clone();
+        let labels: Vec<&Operand> = node.labels.iter().filter(|l| live(l)).collect();
+        let dest = match (quad.op.has_value(), labels.first(), node.labels.first()) {
+            (false, _, _) => None,
+            (true, Some(l), _) => Some((*l).clone()),
+            (true, None, _) if self.remaining[i] == 0 => None,
+            (true, None, Some(l)) => Some(l.clone()),
+            (true, None, None) => Some(self.temp())
+        };
+        // 退避の写しは引数のある番地に書かないので、引数は退避の前の番地から読んでよい
+        for (field, c) in reads(&quad).into_iter().zip(&node.children) {
+            set(&mut quad, field, self.holder[*c].clone());
+        }
+        // この命令の引数もまだ必要な値として数えたまま退避する
+        if let Some(dest) = &dest {
+            self.protect(dest, &node.children);
+        }
+        for c in &node.children {
+            self.remaining[*c] -= 1;
+        }
+        let dest = match dest {
+            Some(dest) => dest,
+            None if !quad.op.has_value() => {
+                self.out.push(quad);
+                return;
+            },
+            // 値を使わない呼出しは結果を捨てる
+            None if quad.op == Opcode::Call => {
+                quad.result = None;
+                self.out.push(quad);
+                return;
+            },
+            None => return
+        };
+        quad.result = Some(dest.clone());
+        self.out.push(quad);
+        self.holder[i] = Some(dest.clone());
+        for label in labels.into_iter().skip(1) {
+            self.protect(label, &[]);
+            self.out.push(Quad::copy(label.clone(), dest.clone()));
+        }
+    }
+
+    /// xに書き込む前に、xが持っていてまだ必要な値を退避する
+    /// 葉の値をまだ写していない名前があり、その名前に書いてよければそこへ写し、だめなら新しい一時変数に写す
+    /// # params
+    /// - x: &Operand => これから書き込む番地
+    /// - reading: &[usize] => xに書き込む命令自身が読む節。書き込みより先に読むので、xにある値のこの読み出しは退避しなくてよい
+    fn protect(&mut self, x: &Operand, reading: &[usize]) {
+        for m in 0..self.holder.len() {
+            if self.holder[m].as_ref() != Some(x) || !self.needed(m, x, reading) {
+                continue;
+            }
+            let free = self.pending[m].iter().position(|l| l != x && (0..self.holder.len()).all(|k| self.holder[k].as_ref() != Some(l) || !self.needed(k, l, &[])));
+            let to = match free {
+                Some(p) => self.pending[m].remove(p),
+                None => self.temp()
+            };
+            self.out.push(Quad::copy(to.clone(), x.clone()));
+            self.holder[m] = Some(to);
+        }
+    }
+
+    /// 節mの値(いまxにある)がこの後も必要か。readingに含まれる読み出しは数えない
+    fn needed(&self, m: usize, x: &Operand, reading: &[usize]) -> bool {
+        let read = reading.iter().filter(|c| **c == m).count();
+        self.remaining[m] > read || self.pending[m].iter().any(|l| l != x)
+    }
+
+    fn temp(&mut self) -> Operand {
+        let temp = *self.next_temp;
+        *self.next_temp += 1;
+        Operand::Temp(temp)
+    }
+}
+
+/// 流れグラフの各基本ブロックをDAGで最適化する
+/// 呼出し、ポインタを通した読み書き、番地を取る命令は何を読み書きするかわからないので、
+/// その前後でブロックを区切り、区切りではすべての名前が生きているとみなす
+///
+/// # params
+/// - cfg: &mut Cfg => 流れグラフ
+/// - live_out: F => ブロックの番号と名前から、ブロックの出口で生きているか(わからなければすべて真)
+pub fn optimize<F: Fn(usize, &Operand) -> bool>(cfg: &mut Cfg, live_out: F) {
+    let mut next_temp = cfg.blocks.iter().flat_map(|b| &b.quads)
+        .flat_map(|q| q.arg1.iter().chain(q.arg2.iter()).chain(q.result.iter()))
+        .filter_map(|o| match o {
+            Operand::Temp(t) => Some(*t+1),
+            _ => None
+        })
+        .max()
+        .unwrap_or(1);
+    for b in 0..cfg.blocks.len() {
+        let quads = optimize_block(&cfg.blocks[b].quads, |x| live_out(b, x), &mut next_temp);
+        cfg.blocks[b].quads = quads;
+    }
+}
+
+/// 基本ブロックの命令をDAGで最適化する(optimizeの1ブロック分)
+///
+/// # params
+/// - quads: &[Quad] => 基本ブロックの命令
+/// - live: F => ブロックの出口で生きている名前か
+/// - next_temp: &mut usize => 新しい一時変数の番号
+///
+/// # returns
+/// - Vec<Quad>
+pub fn optimize_block<F: Fn(&Operand) -> bool>(quads: &[Quad], live: F, next_temp: &mut usize) -> Vec<Quad> {
+    let mut segments: Vec<&[Quad]> = vec![];
+    let mut start = 0;
+    for (i, quad) in quads.iter().enumerate() {
+        if matches!(quad.op, Opcode::Call | Opcode::Load | Opcode::Store | Opcode::AddrOf) {
+            segments.push(&quads[start..i]);
+            segments.push(&quads[i..i+1]);
+            start = i+1;
+        }
+    }
+    segments.push(&quads[start..]);
+    let last = segments.len()-1;
+    let mut out = vec![];
+    for (i, segment) in segments.into_iter().enumerate() {
+        let dag = Dag::new(segment);
+        if i == last {
+            out.extend(dag.generate(&live, next_temp));
+        } else {
+            out.extend(dag.generate(|_| true, next_temp));
+        }
+    }
+    out
+}
+
+/// 命令が値を読む番地の場所(読む順)
+fn reads(quad: &Quad) -> Vec<Field> {
+    match &quad.op {
+        Opcode::Binary(_) | Opcode::IfRel(_) | Opcode::IfFalseRel(_) | Opcode::IndexLoad => vec![Field::Arg1, Field::Arg2],
+        Opcode::Unary(_) | Opcode::Conv(_) | Opcode::Copy | Opcode::If | Opcode::IfFalse | Opcode::JumpTable | Opcode::Param | Opcode::Load => vec![Field::Arg1],
+        Opcode::Return if quad.arg1.is_some() => vec![Field::Arg1],
+        Opcode::IndexStore => vec![Field::Result, Field::Arg1, Field::Arg2],
+        Opcode::Store => vec![Field::Result, Field::Arg1],
+        // 呼び出す関数の名前と、番地を取る名前は値として読まない
+        _ => vec![]
+    }
+}
+
+fn get(quad: &Quad, field: Field) -> Option<&Operand> {
+    match field {
+        Field::Arg1 => quad.arg1.as_ref(),
+        Field::Arg2 => quad.arg2.as_ref(),
+        Field::Result => quad.result.as_ref()
+    }
+}
+
+fn set(quad: &mut Quad, field: Field, x: Option<Operand>) {
+    match field {
+        Field::Arg1 => quad.arg1 = x,
+        Field::Arg2 => quad.arg2 = x,
+        Field::Result => quad.result = x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{optimize, optimize_block, Dag};
+    use super::super::cfg::Cfg;
+    use super::super::super::chapter2::ast::BinOp;
+    use super::super::super::chapter2::mtype::Type;
+    use super::super::super::chapter2::parser::astparser::AstParser;
+    use super::super::super::chapter2::symbol::Symbol;
+    use super::super::super::chapter6::lower::Lowerer;
+    use super::super::super::chapter6::tac::{Constant, Operand, Quad};
+    use super::super::super::chapter6::tac::test_util::{int, name};
+
+    fn float(lexeme: &str) -> Operand {
+        Operand::Name(Symbol::new(lexeme.to_string(), Type::new_f32()))
+    }
+
+    fn show(quads: &[Quad]) -> String {
+        quads.iter().map(|q| format!("{}\n", q)).collect()
+    }
+
+    fn is_name(x: &Operand) -> bool {
+        !x.is_temp()
+    }
+
+    #[test]
+    fn dag_cse_test() {
+        // 例8.10 b - d は1つの節になり、b が死んでいれば d にだけ計算する
+        let (a, b, c, d) = (name("a"), name("b"), name("c"), name("d"));
+        let quads = vec![
+            Quad::binary(BinOp::Add, a.clone(), b.clone(), c.clone()),
+            Quad::binary(BinOp::Sub, b.clone(), a.clone(), d.clone()),
+            Quad::binary(BinOp::Add, c.clone(), b.clone(), c.clone()),
+            Quad::binary(BinOp::Sub, d.clone(), a.clone(), d.clone())
+        ];
+        let dag = Dag::new(&quads);
+        assert_eq!("\
+n0: leaf b
+n1: leaf c
+n2: + n0 n1 [a]
+n3: leaf d
+n4: - n2 n3 [b, d]
+n5: + n4 n1 [c]
+", dag.to_string());
+        let mut next = 1;
+        assert_eq!("a = b + c\nb = a - d\nd = b\nc = b + c\n", show(&dag.generate(|_| true, &mut next)));
+        assert_eq!("a = b + c\nd = a - d\nc = d + c\n", show(&dag.generate(|x| *x != b, &mut next)));
+
+        // 例8.12 b, c を書き換えた後の b + c は a と同じ値ではない
+        let e = name("e");
+        let quads = vec![
+            Quad::binary(BinOp::Add, a.clone(), b.clone(), c.clone()),
+            Quad::binary(BinOp::Sub, b.clone(), b.clone(), d.clone()),
+            Quad::binary(BinOp::Add, c.clone(), c.clone(), d.clone()),
+            Quad::binary(BinOp::Add, e.clone(), b.clone(), c.clone())
+        ];
+        assert_eq!(show(&quads), show(&optimize_block(&quads, |_| true, &mut next)));
+        // 可換な演算は被演算子の順によらず同じ節にする
+        let quads = vec![
+            Quad::binary(BinOp::Mul, a.clone(), b.clone(), c.clone()),
+            Quad::binary(BinOp::Mul, d.clone(), c.clone(), b.clone()),
+            Quad::binary(BinOp::Sub, e.clone(), c.clone(), b.clone())
+        ];
+        assert_eq!("a = b * c\nd = a\ne = c - b\n", show(&optimize_block(&quads, |_| true, &mut next)));
+    }
+
+    #[test]
+    fn dag_array_test() {
+        // 例8.13 a[j] = y の後の a[i] は x と同じとは限らない
+        let (a, i, j, x, y, z) = (name("a"), name("i"), name("j"), name("x"), name("y"), name("z"));
+        let quads = vec![
+            Quad::index_load(x.clone(), a.clone(), i.clone()),
+            Quad::index_store(a.clone(), j.clone(), y.clone()),
+            Quad::index_load(z.clone(), a.clone(), i.clone())
+        ];
+        let dag = Dag::new(&quads);
+        assert!(dag.to_string().contains("n2: =[] n0 n1 [x] killed\n"));
+        let mut next = 1;
+        assert_eq!(show(&quads), show(&dag.generate(|_| true, &mut next)));
+
+        // 書き込みのない読み出しは共通部分式になる
+        let src = "{ i32[10] a; i32 i; i32 x; x = a[i] + a[i]; a[i] = x; }";
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_block(AstParser::new(src).parse_block().unwrap());
+        let mut next = 10;
+        let quads = optimize_block(&lowerer.code().quads, is_name, &mut next);
+        assert_eq!("t1 = i * 4\nt2 = a[t1]\nx = t2 + t2\na[t1] = x\n", show(&quads));
+    }
+
+    #[test]
+    fn dag_fold_test() {
+        let (x, y, z) = (name("x"), name("y"), name("z"));
+        let t = Operand::Temp;
+        let quads = vec![
+            Quad::binary(BinOp::Mul, t(1), int(2), int(3)),
+            Quad::binary(BinOp::Add, t(2), x.clone(), int(0)),
+            Quad::binary(BinOp::Mul, t(3), t(2), int(1)),
+            Quad::binary(BinOp::Mul, t(4), t(3), int(2)),
+            Quad::binary(BinOp::Add, y.clone(), t(4), t(1)),
+            Quad::binary(BinOp::Add, t(5), int(i32::MAX), int(1)),
+            Quad::copy(z.clone(), t(5)),
+            Quad::binary(BinOp::Div, t(6), int(1), int(0)),
+            Quad::binary(BinOp::Mul, x.clone(), t(6), int(0))
+        ];
+        let mut next = 7;
+        // i32 は折り返す。0での除算は畳み込まないが、x * 0 は 0 なので使われずに取り除かれる
+        assert_eq!("t4 = x + x\ny = t4 + 6\nx = 0\nz = -2147483648\n", show(&optimize_block(&quads, is_name, &mut next)));
+
+        // f32 の x + 0.0 と x * 0.0 はそのまま(-0.0 と NaN)
+        let (f, g, h) = (float("f"), float("g"), float("h"));
+        let fc = |num: f32| Operand::Const(Constant::Float(num));
+        let quads = vec![
+            Quad::binary(BinOp::Add, f.clone(), h.clone(), fc(0.0)),
+            Quad::binary(BinOp::Mul, g.clone(), h.clone(), fc(0.0)),
+            Quad::binary(BinOp::Sub, t(1), h.clone(), fc(0.0)),
+            Quad::binary(BinOp::Mul, t(2), t(1), fc(1.0)),
+            Quad::binary(BinOp::Sub, h.clone(), t(2), fc(-0.0)),
+            Quad::binary(BinOp::Add, t(3), fc(0.1), fc(0.2)),
+            Quad::copy(float("k"), t(3))
+        ];
+        assert_eq!("f = h + 0.0\ng = h * 0.0\nh = h - -0.0\nk = 0.3\n", show(&optimize_block(&quads, is_name, &mut next)));
+    }
+
+    #[test]
+    fn dag_dead_code_test() {
+        let (a, b, c, x, y) = (name("a"), name("b"), name("c"), name("x"), name("y"));
+        let t = Operand::Temp;
+        let quads = vec![
+            Quad::binary(BinOp::Add, t(1), a.clone(), b.clone()),
+            Quad::copy(x.clone(), t(1)),
+            Quad::copy(x.clone(), c.clone())
+        ];
+        let mut next = 2;
+        assert_eq!("x = c\n", show(&optimize_block(&quads, is_name, &mut next)));
+
+        // 入れ替えでは、上書きされる値を一時変数に退避する
+        let quads = vec![Quad::copy(t(1), x.clone()), Quad::copy(x.clone(), y.clone()), Quad::copy(y.clone(), t(1))];
+        assert_eq!("t2 = y\ny = x\nx = t2\n", show(&optimize_block(&quads, is_name, &mut next)));
+        // 名前への写しで済むときは一時変数を使わない
+        let quads = vec![Quad::copy(y.clone(), x.clone()), Quad::binary(BinOp::Add, x.clone(), x.clone(), int(1))];
+        assert_eq!("y = x\nx = x + 1\n", show(&optimize_block(&quads, is_name, &mut next)));
+        // 行き先を退避するとき、この命令の引数を上書きしない
+        let (d, t2) = (name("d"), t(2));
+        let quads = vec![
+            Quad::copy(t2.clone(), a.clone()),
+            Quad::copy(a.clone(), b.clone()),
+            Quad::binary(BinOp::Sub, b.clone(), b.clone(), t2.clone()),
+            Quad::copy(d.clone(), c.clone())
+        ];
+        assert_eq!("t3 = b\nb = b - a\na = t3\nd = c\n", show(&optimize_block(&quads, is_name, &mut next)));
+        let quads = vec![
+            Quad::binary(BinOp::Mul, t(1), a.clone(), c.clone()),
+            Quad::binary(BinOp::Mul, c.clone(), a.clone(), int(1)),
+            Quad::copy(a.clone(), t(1))
+        ];
+        assert_eq!("t4 = a\na = a * c\nc = t4\n", show(&optimize_block(&quads, is_name, &mut next)));
+        // 飛び越しは写しの後に置き、呼出しの前後は区切る
+        let quads = vec![
+            Quad::binary(BinOp::Add, t(1), a.clone(), b.clone()),
+            Quad::param(t(1)),
+            Quad::call(Some(t(2)), name("f"), 1),
+            Quad::binary(BinOp::Add, t(3), a.clone(), b.clone()),
+            Quad::copy(y.clone(), a.clone()),
+            Quad::if_rel(BinOp::Lt, t(3), t(2), 0)
+        ];
+        assert_eq!("t1 = a + b\nparam t1\nt2 = call f, 1\nt3 = a + b\ny = a\nif t3 < t2 goto 0\n", show(&optimize_block(&quads, is_name, &mut next)));
+    }
+
+    #[test]
+    fn dag_cfg_test() {
+        let src = "{ i32 i; i32 x; i32[10] a; i = 0; while (i < 10) { x = 2 * 4 + i * 1; a[i] = x; i = i + 1; } }";
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_block(AstParser::new(src).parse_block().unwrap());
+        let mut cfg = Cfg::new(lowerer.code());
+        // 一時変数 t1 を使い回していても、ブロックの中で値を区別できる
+        optimize(&mut cfg, |_, x| !x.is_temp());
+        assert_eq!("  0: i = 0
+  1: ifFalse i < 10 goto 7
+  2: x = 8 + i
+  3: t3 = i * 4
+  4: a[t3] = x
+  5: i = i + 1
+  6: goto 1
+", cfg.to_quadruples().to_string());
+    }
+}
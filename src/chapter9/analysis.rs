@@ -0,0 +1,701 @@
+use std::fmt;
+
+use super::super::chapter2::diagnostic::Diagnostic;
+use super::super::chapter2::mtype::Type;
+use super::super::chapter2::symbol::SymbolKind;
+use super::super::chapter6::tac::{Opcode, Operand, Quad, Quadruples};
+use super::super::chapter8::cfg::{BasicBlock, Cfg};
+use super::dataflow::{solve, Analysis, BitSet, Direction, Solution};
+
+/// 流れグラフの中の命令の位置
+///
+/// # members
+/// - block: usize => ブロックの番号
+/// - index: usize => ブロックの中での命令の位置
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq, Eq
+/// - PartialOrd, Ord
+/// - Hash
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Point {
+    pub block: usize,
+    pub index: usize
+}
+
+/// B2:1 のように書く
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&format!("B{}:{}", self.block, self.index))
+    }
+}
+
+/// 変数の定義
+///
+/// # members
+/// - var: Operand => 定義する変数
+/// - point: Option<Point> => 定義する命令の位置(ENTRYに置いた、未初期化の値の定義ならNone)
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Definition {
+    pub var: Operand,
+    pub point: Option<Point>
+}
+
+/// 到達定義(9.2.4節)
+/// 前向きで、交わりは和集合、OUT[B] = gen_B ∪ (IN[B] - kill_B)
+/// 定義には、ENTRYに置いた未初期化の値の定義、命令の定義の順に番号をつける
+///
+/// # members
+/// - defs: Vec<Definition> => 定義(添字が定義の番号)
+/// - entries: usize => ENTRYに置いた定義の数
+/// - at: Vec<Vec<Option<usize>>> => ブロックと命令の位置から、その命令の定義の番号への表
+/// - gens: Vec<BitSet> => ブロックごとのgen
+/// - kills: Vec<BitSet> => ブロックごとのkill
+#[derive(Clone, Debug)]
+pub struct ReachingDefinitions {
+    pub defs: Vec<Definition>,
+    entries: usize,
+    at: Vec<Vec<Option<usize>>>,
+    gens: Vec<BitSet>,
+    kills: Vec<BitSet>
+}
+
+impl ReachingDefinitions {
+    /// 流れグラフの定義を集める
+    ///
+    /// # params
+    /// - cfg: &Cfg => 流れグラフ
+    ///
+    /// # returns
+    /// - ReachingDefinitions
+    pub fn new(cfg: &Cfg) -> ReachingDefinitions {
+        ReachingDefinitions::with_entry(cfg, &[])
+    }
+
+    /// 流れグラフの定義を集め、ENTRYでvarsを未初期化の値に定義したとみなす
+    /// ある使用にENTRYの定義が届けば、初期化されずに使われる経路がある
+    ///
+    /// # params
+    /// - cfg: &Cfg => 流れグラフ
+    /// - vars: &[Operand] => ENTRYで定義する変数
+    ///
+    /// # returns
+    /// - ReachingDefinitions
+    pub fn with_entry(cfg: &Cfg, vars: &[Operand]) -> ReachingDefinitions {
+        let mut defs: Vec<Definition> = vars.iter().map(|x| Definition { var: x.clone(), point: None }).collect();
+        let mut at = vec![];
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            at.push(block.quads.iter().enumerate().map(|(i, quad)| quad.def().map(|x| {
+                defs.push(Definition { var: x.clone(), point: Some(Point { block: b, index: i }) });
+                defs.len()-1
+            })).collect());
+        }
+        let mut rd = ReachingDefinitions { defs, entries: vars.len(), at, gens: vec![], kills: vec![] };
+        for b in 0..cfg.blocks.len() {
+            let n = rd.defs.len();
+            let (mut gen, mut kill) = (BitSet::new(n), BitSet::new(n));
+            for d in rd.at[b].iter().flatten() {
+                let all = rd.defs_of(&rd.defs[*d].var);
+                gen.subtract(&all);
+                gen.insert(*d);
+                kill.union_with(&all);
+            }
+            rd.gens.push(gen);
+            rd.kills.push(kill);
+        }
+        rd
+    }
+
+    /// 変数のすべての定義を返す
+    ///
+    /// # params
+    /// - x: &Operand => 変数
+    ///
+    /// # returns
+    /// - BitSet
+    pub fn defs_of(&self, x: &Operand) -> BitSet {
+        let mut set = BitSet::new(self.defs.len());
+        for (d, def) in self.defs.iter().enumerate() {
+            if def.var == *x {
+                set.insert(d);
+            }
+        }
+        set
+    }
+
+    /// 命令の定義の番号を返す
+    ///
+    /// # params
+    /// - point: Point => 命令の位置
+    ///
+    /// # returns
+    /// - Option<usize>
+    pub fn def_at(&self, point: Point) -> Option<usize> {
+        self.at[point.block][point.index]
+    }
+
+    /// 定義の名前を返す(命令の定義は本と同じくd1から数え、ENTRYの定義は x? と書く)
+    ///
+    /// # params
+    /// - d: usize => 定義の番号
+    ///
+    /// # returns
+    /// - String
+    pub fn name(&self, d: usize) -> String {
+        match self.defs[d].point {
+            Some(_) => format!("d{}", d-self.entries+1),
+            None => format!("{}?", self.defs[d].var)
+        }
+    }
+
+    /// 定義の集合を {d1, d2} のように書く
+    ///
+    /// # params
+    /// - set: &BitSet => 定義の集合
+    ///
+    /// # returns
+    /// - String
+    pub fn show(&self, set: &BitSet) -> String {
+        show(set.iter().map(|d| self.name(d)))
+    }
+
+    /// ud連鎖とdu連鎖を作る(9.2.5節)
+    ///
+    /// # params
+    /// - cfg: &Cfg => 流れグラフ
+    /// - solution: &Solution<BitSet> => この解析の解
+    ///
+    /// # returns
+    /// - Chains
+    pub fn chains(&self, cfg: &Cfg, solution: &Solution<BitSet>) -> Chains {
+        let mut chains = Chains { uses: vec![], du: vec![vec![]; self.defs.len()] };
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            let points = solution.points(self, cfg, b);
+            for (i, quad) in block.quads.iter().enumerate() {
+                let point = Point { block: b, index: i };
+                let mut vars: Vec<&Operand> = vec![];
+                for x in quad.uses() {
+                    if !vars.contains(&x) {
+                        vars.push(x);
+                    }
+                }
+                for x in vars {
+                    let mut defs = self.defs_of(x);
+                    defs.intersect_with(&points[i]);
+                    let defs: Vec<usize> = defs.iter().collect();
+                    for d in &defs {
+                        chains.du[*d].push(point);
+                    }
+                    chains.uses.push(Use { point, var: x.clone(), defs });
+                }
+            }
+        }
+        chains
+    }
+}
+
+impl Analysis for ReachingDefinitions {
+    type Value = BitSet;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn top(&self) -> BitSet {
+        BitSet::new(self.defs.len())
+    }
+
+    fn bottom(&self) -> BitSet {
+        BitSet::full(self.defs.len())
+    }
+
+    fn meet(&self, x: &mut BitSet, y: &BitSet) {
+        x.union_with(y);
+    }
+
+    fn boundary(&self) -> BitSet {
+        let mut set = BitSet::new(self.defs.len());
+        for d in 0..self.entries {
+            set.insert(d);
+        }
+        set
+    }
+
+    fn transfer(&self, b: usize, i: usize, _: &Quad, x: &mut BitSet) {
+        if let Some(d) = self.at[b][i] {
+            x.subtract(&self.defs_of(&self.defs[d].var));
+            x.insert(d);
+        }
+    }
+
+    fn transfer_block(&self, b: usize, _: &BasicBlock, x: &mut BitSet) {
+        x.subtract(&self.kills[b]);
+        x.union_with(&self.gens[b]);
+    }
+}
+
+/// 変数の使用と、そこに届く定義(ud連鎖の1つ)
+///
+/// # members
+/// - point: Point => 使用する命令の位置
+/// - var: Operand => 使用する変数
+/// - defs: Vec<usize> => 届く定義の番号
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Use {
+    pub point: Point,
+    pub var: Operand,
+    pub defs: Vec<usize>
+}
+
+/// ud連鎖とdu連鎖
+///
+/// # members
+/// - uses: Vec<Use> => 使用ごとのud連鎖(ブロックと命令の順)
+/// - du: Vec<Vec<Point>> => 定義ごとの、その定義が届く使用の位置
+///
+/// # derive
+/// - Clone
+/// - Debug
+#[derive(Clone, Debug)]
+pub struct Chains {
+    pub uses: Vec<Use>,
+    pub du: Vec<Vec<Point>>
+}
+
+impl Chains {
+    /// 使用に届く定義を返す
+    ///
+    /// # params
+    /// - point: Point => 使用する命令の位置
+    /// - x: &Operand => 使用する変数
+    ///
+    /// # returns
+    /// - &[usize] => 定義の番号(その命令がxを使わなければ空)
+    pub fn ud(&self, point: Point, x: &Operand) -> &[usize] {
+        self.uses.iter().find(|u| u.point == point && u.var == *x).map(|u| &u.defs[..]).unwrap_or(&[])
+    }
+
+    /// 定義が届く使用の位置を返す
+    ///
+    /// # params
+    /// - d: usize => 定義の番号
+    ///
+    /// # returns
+    /// - &[Point]
+    pub fn du(&self, d: usize) -> &[Point] {
+        &self.du[d]
+    }
+}
+
+/// 生きている変数(9.2.5節)
+/// 後向きで、交わりは和集合、IN[B] = use_B ∪ (OUT[B] - def_B)
+///
+/// # members
+/// - vars: Vec<Operand> => 変数(名前と一時変数、添字が集合の要素)
+#[derive(Clone, Debug)]
+pub struct LiveVariables {
+    pub vars: Vec<Operand>
+}
+
+impl LiveVariables {
+    /// 流れグラフの変数を集める
+    ///
+    /// # params
+    /// - cfg: &Cfg => 流れグラフ
+    ///
+    /// # returns
+    /// - LiveVariables
+    pub fn new(cfg: &Cfg) -> LiveVariables {
+        LiveVariables { vars: variables(cfg) }
+    }
+
+    /// 変数が集合に含まれるか
+    ///
+    /// # params
+    /// - set: &BitSet => 生きている変数の集合
+    /// - x: &Operand => 変数
+    ///
+    /// # returns
+    /// - bool
+    pub fn contains(&self, set: &BitSet, x: &Operand) -> bool {
+        self.vars.iter().position(|y| y == x).map(|i| set.contains(i)).unwrap_or(false)
+    }
+
+    /// 変数の集合を {a, b} のように書く
+    ///
+    /// # params
+    /// - set: &BitSet => 変数の集合
+    ///
+    /// # returns
+    /// - String
+    pub fn show(&self, set: &BitSet) -> String {
+        show(set.iter().map(|i| self.vars[i].to_string()))
+    }
+}
+
+impl Analysis for LiveVariables {
+    type Value = BitSet;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn top(&self) -> BitSet {
+        BitSet::new(self.vars.len())
+    }
+
+    fn bottom(&self) -> BitSet {
+        BitSet::full(self.vars.len())
+    }
+
+    fn meet(&self, x: &mut BitSet, y: &BitSet) {
+        x.union_with(y);
+    }
+
+    fn boundary(&self) -> BitSet {
+        BitSet::new(self.vars.len())
+    }
+
+    fn transfer(&self, _: usize, _: usize, quad: &Quad, x: &mut BitSet) {
+        if let Some(i) = quad.def().and_then(|d| self.vars.iter().position(|y| y == d)) {
+            x.remove(i);
+        }
+        for u in quad.uses() {
+            if let Some(i) = self.vars.iter().position(|y| y == u) {
+                x.insert(i);
+            }
+        }
+    }
+}
+
+/// 式(x = y op z の右辺)
+///
+/// # members
+/// - op: Opcode => 演算(二項演算、単項演算、型の変換、配列の読み出し)
+/// - args: Vec<Operand> => 被演算子
+///
+/// # derive
+/// - Clone
+/// - PartialEq
+/// - Debug
+#[derive(Clone, PartialEq, Debug)]
+pub struct Expression {
+    pub op: Opcode,
+    pub args: Vec<Operand>
+}
+
+impl Expression {
+    /// 命令が計算する式を返す
+    ///
+    /// # params
+    /// - quad: &Quad => 命令
+    ///
+    /// # returns
+    /// - Option<Expression>
+    pub fn of(quad: &Quad) -> Option<Expression> {
+        match quad.op {
+            Opcode::Binary(_) | Opcode::Unary(_) | Opcode::Conv(_) | Opcode::IndexLoad => {
+                let args = quad.arg1.iter().chain(quad.arg2.iter()).cloned().collect();
+                Some(Expression { op: quad.op.clone(), args })
+            },
+            _ => None
+        }
+    }
+}
+
+/// 3番地命令の右辺と同じ形で書く
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match (&self.op, &self.args[..]) {
+            (Opcode::Binary(op), [y, z]) => format!("{} {} {}", y, op, z),
+            (Opcode::IndexLoad, [y, i]) => format!("{}[{}]", y, i),
+            (op, args) => format!("{} {}", op, args.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" "))
+        };
+        f.pad(&text)
+    }
+}
+
+/// 利用可能な式(9.2.6節)
+/// 前向きで、交わりは積集合、ENTRY以外のブロックの初期値は全体集合
+/// x = y + z は y + z を生成してから、xを被演算子に持つ式を消去する
+/// x[i] = y は xの要素の読み出しを、*x = y はすべての式を消去する
+///
+/// # members
+/// - exprs: Vec<Expression> => 式(添字が集合の要素)
+#[derive(Clone, Debug)]
+pub struct AvailableExpressions {
+    pub exprs: Vec<Expression>
+}
+
+impl AvailableExpressions {
+    /// 流れグラフの式を集める
+    ///
+    /// # params
+    /// - cfg: &Cfg => 流れグラフ
+    ///
+    /// # returns
+    /// - AvailableExpressions
+    pub fn new(cfg: &Cfg) -> AvailableExpressions {
+        let mut exprs = vec![];
+        for quad in cfg.blocks.iter().flat_map(|b| &b.quads) {
+            if let Some(e) = Expression::of(quad) {
+                if !exprs.contains(&e) {
+                    exprs.push(e);
+                }
+            }
+        }
+        AvailableExpressions { exprs }
+    }
+
+    /// 式の番号を返す
+    ///
+    /// # params
+    /// - e: &Expression => 式
+    ///
+    /// # returns
+    /// - Option<usize>
+    pub fn index(&self, e: &Expression) -> Option<usize> {
+        self.exprs.iter().position(|f| f == e)
+    }
+
+    /// 式の集合を {b + c, 4 * i} のように書く
+    ///
+    /// # params
+    /// - set: &BitSet => 式の集合
+    ///
+    /// # returns
+    /// - String
+    pub fn show(&self, set: &BitSet) -> String {
+        show(set.iter().map(|i| self.exprs[i].to_string()))
+    }
+}
+
+impl Analysis for AvailableExpressions {
+    type Value = BitSet;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn top(&self) -> BitSet {
+        BitSet::full(self.exprs.len())
+    }
+
+    fn bottom(&self) -> BitSet {
+        BitSet::new(self.exprs.len())
+    }
+
+    fn meet(&self, x: &mut BitSet, y: &BitSet) {
+        x.intersect_with(y);
+    }
+
+    fn boundary(&self) -> BitSet {
+        BitSet::new(self.exprs.len())
+    }
+
+    fn transfer(&self, _: usize, _: usize, quad: &Quad, x: &mut BitSet) {
+        if let Some(i) = Expression::of(quad).and_then(|e| self.index(&e)) {
+            x.insert(i);
+        }
+        for (i, e) in self.exprs.iter().enumerate() {
+            let killed = match (&quad.op, quad.def(), &quad.result) {
+                (_, Some(d), _) => e.args.contains(d),
+                (Opcode::IndexStore, _, Some(array)) => e.op == Opcode::IndexLoad && e.args[0] == *array,
+                (Opcode::Store, _, _) => true,
+                _ => false
+            };
+            if killed {
+                x.remove(i);
+            }
+        }
+    }
+}
+
+/// 初期化される前に使われるかもしれない変数を警告する
+/// ENTRYに未初期化の値の定義を置いた到達定義で、その定義が届く使用を探す
+/// 警告は変数ごとに1つで、位置は変数の宣言(3番地コードは使用の位置を持たない)
+/// 仮引数と、要素ごとに書き込む配列とレコードは調べない
+///
+/// # params
+/// - code: &Quadruples => 手続きやブロックの3番地コード
+///
+/// # returns
+/// - Vec<Diagnostic> => 使われた順の警告
+pub fn uninitialized(code: &Quadruples) -> Vec<Diagnostic> {
+    let mut cfg = Cfg::new(code);
+    cfg.remove_unreachable();
+    let vars: Vec<Operand> = variables(&cfg).into_iter().filter(|x| match x {
+        Operand::Name(symbol) => symbol.kind == SymbolKind::Var && !matches!(symbol.ty, Type::Array(..) | Type::Record(..)),
+        _ => false
+    }).collect();
+    let rd = ReachingDefinitions::with_entry(&cfg, &vars);
+    let solution = solve(&rd, &cfg);
+    let mut warned = vec![];
+    let mut diagnostics = vec![];
+    for u in rd.chains(&cfg, &solution).uses {
+        if let (true, Operand::Name(symbol)) = (u.defs.iter().any(|d| *d < vars.len()), &u.var) {
+            if !warned.contains(&u.var) {
+                diagnostics.push(Diagnostic::warning(symbol.span, format!("variable `{}` may be used before initialization", symbol.lexeme)));
+                warned.push(u.var.clone());
+            }
+        }
+    }
+    diagnostics
+}
+
+/// 流れグラフに現れる名前と一時変数を、現れた順に集める
+fn variables(cfg: &Cfg) -> Vec<Operand> {
+    let mut vars: Vec<Operand> = vec![];
+    for quad in cfg.blocks.iter().flat_map(|b| &b.quads) {
+        for x in quad.uses().into_iter().chain(quad.def()) {
+            if !vars.contains(x) {
+                vars.push(x.clone());
+            }
+        }
+    }
+    vars
+}
+
+fn show<I: Iterator<Item=String>>(items: I) -> String {
+    format!("{{{}}}", items.collect::<Vec<String>>().join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{uninitialized, AvailableExpressions, LiveVariables, Point, ReachingDefinitions};
+    use super::super::dataflow::solve;
+    use super::super::super::chapter2::ast::BinOp;
+    use super::super::super::chapter2::parser::astparser::AstParser;
+    use super::super::super::chapter6::lower::Lowerer;
+    use super::super::super::chapter6::tac::{Operand, Quad, Quadruples};
+    use super::super::super::chapter6::tac::test_util::{int, name};
+    use super::super::super::chapter8::cfg::Cfg;
+
+    /// 図9.13 到達定義の例の流れグラフ
+    fn example() -> Cfg {
+        let (i, j, a) = (name("i"), name("j"), name("a"));
+        let mut code = Quadruples::new();
+        code.push(Quad::binary(BinOp::Sub, i.clone(), name("m"), int(1)));
+        code.push(Quad::copy(j.clone(), name("n")));
+        code.push(Quad::copy(a.clone(), name("u1")));
+        code.push(Quad::binary(BinOp::Add, i.clone(), i.clone(), int(1)));
+        code.push(Quad::binary(BinOp::Sub, j.clone(), j.clone(), int(1)));
+        code.push(Quad::if_true(name("c"), 7));
+        code.push(Quad::copy(a, name("u2")));
+        code.push(Quad::copy(i, name("u3")));
+        code.push(Quad::if_true(name("y"), 3));
+        Cfg::new(&code)
+    }
+
+    #[test]
+    fn reaching_definitions_test() {
+        let cfg = example();
+        let rd = ReachingDefinitions::new(&cfg);
+        let solution = solve(&rd, &cfg);
+        // 図9.14 の最後の列
+        let table: Vec<String> = (1..cfg.blocks.len()).map(|b| format!("{} {}", rd.show(&solution.ins[b]), rd.show(&solution.outs[b]))).collect();
+        assert_eq!(vec![
+            "{} {d1, d2, d3}",
+            "{d1, d2, d3, d5, d6, d7} {d3, d4, d5, d6}",
+            "{d3, d4, d5, d6} {d4, d5, d6}",
+            "{d3, d4, d5, d6} {d3, d5, d6, d7}",
+            "{d3, d5, d6, d7} {d3, d5, d6, d7}"
+        ], table);
+
+        // i = i + 1 の i には d1 と d7 が届き、d4 は i = u3 で消される
+        let chains = rd.chains(&cfg, &solution);
+        let use_i = Point { block: 2, index: 0 };
+        assert_eq!(vec!["d1", "d7"], chains.ud(use_i, &name("i")).iter().map(|d| rd.name(*d)).collect::<Vec<String>>());
+        assert!(chains.ud(use_i, &name("j")).is_empty());
+        let d5 = rd.def_at(Point { block: 2, index: 1 }).unwrap();
+        assert_eq!(vec![use_i], chains.du(rd.def_at(Point { block: 1, index: 0 }).unwrap()));
+        assert_eq!(vec![Point { block: 2, index: 1 }], chains.du(d5));
+        // a はどこでも使われない
+        assert!(chains.du(rd.def_at(Point { block: 3, index: 0 }).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn live_variables_test() {
+        let cfg = example();
+        let live = LiveVariables::new(&cfg);
+        let solution = solve(&live, &cfg);
+        assert_eq!("{m, n, u1, c, u2, u3, y}", live.show(&solution.ins[1]));
+        assert_eq!("{i, j, c, u2, u3, y}", live.show(&solution.ins[2]));
+        assert_eq!("{j, c, u2, u3, y}", live.show(&solution.outs[2]));
+        assert_eq!("{}", live.show(&solution.ins[cfg.exit()]));
+        // a は代入されるが使われないので、どこでも生きていない
+        assert!(!live.contains(&solution.outs[1], &name("a")));
+        // B2 の各点(i = i + 1 の前、j = j - 1 の前、if c の前、終わり)
+        let points: Vec<String> = solution.points(&live, &cfg, 2).iter().map(|p| live.show(p)).collect();
+        assert_eq!(vec![
+            "{i, j, c, u2, u3, y}",
+            "{j, c, u2, u3, y}",
+            "{j, c, u2, u3, y}",
+            "{j, c, u2, u3, y}"
+        ], points);
+    }
+
+    #[test]
+    fn available_expressions_test() {
+        let (a, b, c, i) = (name("a"), name("b"), name("c"), name("i"));
+        let mut code = Quadruples::new();
+        code.push(Quad::binary(BinOp::Add, a.clone(), b.clone(), c.clone()));
+        code.push(Quad::binary(BinOp::Mul, Operand::Temp(1), int(4), i.clone()));
+        code.push(Quad::if_true(name("x"), 5));
+        code.push(Quad::binary(BinOp::Add, name("d"), b.clone(), c.clone()));
+        code.push(Quad::copy(b.clone(), name("e")));
+        code.push(Quad::binary(BinOp::Add, name("f"), b, c));
+        code.push(Quad::binary(BinOp::Mul, Operand::Temp(2), int(4), i.clone()));
+        code.push(Quad::index_load(Operand::Temp(3), a.clone(), i.clone()));
+        code.push(Quad::index_store(a, Operand::Temp(2), int(0)));
+        code.push(Quad::copy(i, int(0)));
+        let cfg = Cfg::new(&code);
+        let avail = AvailableExpressions::new(&cfg);
+        let solution = solve(&avail, &cfg);
+        assert_eq!("{b + c, 4 * i}", avail.show(&solution.outs[1]));
+        assert_eq!("{4 * i}", avail.show(&solution.outs[2]));
+        // B3 では 4 * i だけが両方の経路で計算済み
+        assert_eq!("{4 * i}", avail.show(&solution.ins[3]));
+        let points: Vec<String> = solution.points(&avail, &cfg, 3).iter().map(|p| avail.show(p)).collect();
+        assert_eq!(vec![
+            "{4 * i}",
+            "{b + c, 4 * i}",
+            "{b + c, 4 * i}",
+            "{b + c, 4 * i, a[i]}",
+            "{b + c, 4 * i}",
+            "{b + c}"
+        ], points);
+    }
+
+    #[test]
+    fn uninitialized_test() {
+        let src = "\
+fn f(i32 n) -> i32 {
+    i32 x; i32 y; i32 z; i32[4] a;
+    if (n > 0) { x = 1; }
+    y = x + n;
+    while (y < 10) { z = y; y = y + a[0]; }
+    return z + x;
+}
+fn g() -> i32 { i32 k; k = 0; while (k < 10) k = k + 1; return k; }";
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_program(AstParser::new(src).parse_program().unwrap());
+        let diagnostics = uninitialized(&lowerer.procedures()[0].code);
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.message.clone()).collect();
+        assert_eq!(vec!["variable `x` may be used before initialization", "variable `z` may be used before initialization"], messages);
+        assert_eq!(src.find("i32 x").unwrap(), diagnostics[0].span.start);
+        assert!(uninitialized(&lowerer.procedures()[1].code).is_empty());
+    }
+}
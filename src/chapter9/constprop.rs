@@ -0,0 +1,430 @@
+use std::fmt;
+
+use super::super::chapter2::symbol::SymbolKind;
+use super::super::chapter6::tac::{Constant, Opcode, Operand, Quad, Quadruples};
+use super::super::chapter8::cfg::Cfg;
+use super::dataflow::{solve, Analysis, Direction};
+
+/// 定数伝播の半束の値(9.4.1節)
+///
+/// # members
+/// - Undef => まだ値がわからない(⊤)
+/// - Const(Constant) => 定数
+/// - Nac => 定数ではない(⊥)
+///
+/// # derive
+/// - Clone, Copy
+/// - Debug
+#[derive(Clone, Copy, Debug)]
+pub enum ConstValue {
+    Undef,
+    Const(Constant),
+    Nac
+}
+
+impl ConstValue {
+    /// 交わり(異なる定数の交わりはNAC)
+    ///
+    /// # params
+    /// - other: ConstValue => 値
+    ///
+    /// # returns
+    /// - ConstValue
+    pub fn meet(self, other: ConstValue) -> ConstValue {
+        match (self, other) {
+            (ConstValue::Undef, v) | (v, ConstValue::Undef) => v,
+            (x, y) if x == y => x,
+            _ => ConstValue::Nac
+        }
+    }
+
+    /// 定数を返す
+    ///
+    /// # returns
+    /// - Option<Constant>
+    pub fn constant(self) -> Option<Constant> {
+        match self {
+            ConstValue::Const(c) => Some(c),
+            _ => None
+        }
+    }
+}
+
+/// f32の定数はビットで比べる(NaNどうしは等しく、0.0と-0.0は異なる)
+impl PartialEq for ConstValue {
+    fn eq(&self, other: &ConstValue) -> bool {
+        match (self, other) {
+            (ConstValue::Const(Constant::Float(x)), ConstValue::Const(Constant::Float(y))) => x.to_bits() == y.to_bits(),
+            (ConstValue::Const(x), ConstValue::Const(y)) => x == y,
+            (ConstValue::Undef, ConstValue::Undef) | (ConstValue::Nac, ConstValue::Nac) => true,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstValue::Undef => f.pad("undef"),
+            ConstValue::Const(c) => f.pad(&c.to_string()),
+            ConstValue::Nac => f.pad("nac")
+        }
+    }
+}
+
+/// 条件付きの定数伝播
+/// 9.4節の前向きの解析で、値は変数ごとのConstValueの組
+/// 条件が定数の分岐は、通る辺にだけ値を流すので、通らない枝の代入は合流点の値を壊さない
+/// どの辺からも値が流れてこないブロックの値はNone(実行されない、半束の⊤)
+/// 演算は Constant::binary, unary, conv と同じ意味で計算し、計算できない0での除算はNACにする
+/// 仮引数はENTRYでNAC、ほかの変数はUNDEF
+///
+/// # members
+/// - vars: Vec<Operand> => 変数(名前と一時変数、添字が組の位置)
+#[derive(Clone, Debug)]
+pub struct ConstantPropagation {
+    pub vars: Vec<Operand>
+}
+
+impl ConstantPropagation {
+    /// 流れグラフの変数を集める
+    ///
+    /// # params
+    /// - cfg: &Cfg => 流れグラフ
+    ///
+    /// # returns
+    /// - ConstantPropagation
+    pub fn new(cfg: &Cfg) -> ConstantPropagation {
+        let mut vars: Vec<Operand> = vec![];
+        for quad in cfg.blocks.iter().flat_map(|b| &b.quads) {
+            for x in quad.uses().into_iter().chain(quad.def()) {
+                if !vars.contains(x) {
+                    vars.push(x.clone());
+                }
+            }
+        }
+        ConstantPropagation { vars }
+    }
+
+    /// 番地の値を返す
+    ///
+    /// # params
+    /// - m: &[ConstValue] => 変数の値
+    /// - x: &Operand => 番地
+    ///
+    /// # returns
+    /// - ConstValue
+    pub fn value(&self, m: &[ConstValue], x: &Operand) -> ConstValue {
+        match x {
+            Operand::Const(c) => ConstValue::Const(*c),
+            x => self.vars.iter().position(|y| y == x).map(|i| m[i]).unwrap_or(ConstValue::Nac)
+        }
+    }
+
+    /// UNDEFでない変数の値を {x = 6, y = nac} のように書く(実行されなければ unreachable)
+    ///
+    /// # params
+    /// - m: &Option<Vec<ConstValue>> => 変数の値
+    ///
+    /// # returns
+    /// - String
+    pub fn show(&self, m: &Option<Vec<ConstValue>>) -> String {
+        match m {
+            Some(m) => {
+                let items: Vec<String> = self.vars.iter().zip(m)
+                    .filter(|(_, v)| **v != ConstValue::Undef)
+                    .map(|(x, v)| format!("{} = {}", x, v))
+                    .collect();
+                format!("{{{}}}", items.join(", "))
+            },
+            None => "unreachable".to_string()
+        }
+    }
+
+    /// 命令を実行した後の変数の値を求める
+    fn eval(&self, quad: &Quad, m: &mut [ConstValue]) {
+        if quad.op == Opcode::Store {
+            // ポインタの指す先はわからない
+            for v in m.iter_mut() {
+                *v = ConstValue::Nac;
+            }
+            return;
+        }
+        let x = match quad.def().and_then(|d| self.vars.iter().position(|y| y == d)) {
+            Some(x) => x,
+            None => return
+        };
+        let arg = |a: &Option<Operand>| a.as_ref().map(|a| self.value(m, a)).unwrap_or(ConstValue::Nac);
+        let (y, z) = (arg(&quad.arg1), arg(&quad.arg2));
+        let fold = |c: Option<Constant>| c.map(ConstValue::Const).unwrap_or(ConstValue::Nac);
+        m[x] = match (&quad.op, y, z) {
+            (Opcode::Copy, y, _) => y,
+            (Opcode::Binary(op), ConstValue::Const(y), ConstValue::Const(z)) => fold(Constant::binary(*op, y, z)),
+            (Opcode::Binary(_), ConstValue::Nac, _) | (Opcode::Binary(_), _, ConstValue::Nac) => ConstValue::Nac,
+            (Opcode::Binary(_), _, _) => ConstValue::Undef,
+            (Opcode::Unary(op), ConstValue::Const(y), _) => fold(Constant::unary(*op, y)),
+            (Opcode::Conv(ty), ConstValue::Const(y), _) => fold(Constant::conv(ty, y)),
+            (Opcode::Unary(_), y, _) | (Opcode::Conv(_), y, _) => y,
+            // 配列の要素、呼出しの結果、番地、ポインタの指す先は定数とみなさない
+            _ => ConstValue::Nac
+        };
+    }
+
+    /// ブロックの最後の分岐の条件が定数なら、実際に飛ぶ先のブロックを返す
+    fn taken(&self, cfg: &Cfg, b: usize, m: &[ConstValue]) -> Option<usize> {
+        let quad = cfg.blocks[b].quads.last()?;
+        let target = quad.target()?;
+        branch(quad, |x| self.value(m, x).constant()).map(|jump| if jump { target } else { b+1 })
+    }
+}
+
+impl Analysis for ConstantPropagation {
+    type Value = Option<Vec<ConstValue>>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn top(&self) -> Self::Value {
+        None
+    }
+
+    fn bottom(&self) -> Self::Value {
+        Some(vec![ConstValue::Nac; self.vars.len()])
+    }
+
+    fn meet(&self, x: &mut Self::Value, y: &Self::Value) {
+        match (x.as_mut(), y) {
+            (Some(x), Some(y)) => for (v, w) in x.iter_mut().zip(y) {
+                *v = v.meet(*w);
+            },
+            (None, Some(_)) => *x = y.clone(),
+            (_, None) => {}
+        }
+    }
+
+    fn boundary(&self) -> Self::Value {
+        Some(self.vars.iter().map(|x| match x {
+            Operand::Name(symbol) if symbol.kind == SymbolKind::Param => ConstValue::Nac,
+            _ => ConstValue::Undef
+        }).collect())
+    }
+
+    fn transfer(&self, _: usize, _: usize, quad: &Quad, x: &mut Self::Value) {
+        if let Some(m) = x {
+            self.eval(quad, m);
+        }
+    }
+
+    fn flows(&self, cfg: &Cfg, from: usize, to: usize, x: &Self::Value) -> bool {
+        match x {
+            Some(m) => self.taken(cfg, from, m).map(|b| b == to).unwrap_or(true),
+            None => false
+        }
+    }
+}
+
+/// 3番地コードに定数伝播と畳み込みをする
+/// - 値が定数の変数の使用を定数に置き換える
+/// - 結果が定数になる演算を x = c にする
+/// - 条件が定数の分岐を goto にするか取り除き、たどれなくなったブロックを取り除く
+/// - 次の命令へのgotoを取り除く
+///
+/// ブロックを取り除くと定数が増えることがあるので、コードが変わらなくなるまで繰り返す
+///
+/// # params
+/// - code: &Quadruples => 3番地コード
+///
+/// # returns
+/// - Quadruples
+pub fn propagate(code: &Quadruples) -> Quadruples {
+    let mut code = code.clone();
+    loop {
+        let next = propagate_once(&code);
+        if next == code {
+            return code;
+        }
+        code = next;
+    }
+}
+
+fn propagate_once(code: &Quadruples) -> Quadruples {
+    let mut cfg = Cfg::new(code);
+    let analysis = ConstantPropagation::new(&cfg);
+    let solution = solve(&analysis, &cfg);
+    for b in 0..cfg.blocks.len() {
+        let points = solution.points(&analysis, &cfg, b);
+        let block = &mut cfg.blocks[b];
+        let mut quads = vec![];
+        for (i, quad) in block.quads.iter().enumerate() {
+            let (before, after) = match (&points[i], &points[i+1]) {
+                (Some(before), Some(after)) => (before, after),
+                // 実行されないブロックは、分岐を畳み込んだ後で取り除く
+                _ => {
+                    quads.push(quad.clone());
+                    continue;
+                }
+            };
+            let mut quad = quad.clone();
+            let substitute = |field: &mut Option<Operand>| {
+                if let Some(c) = field.as_ref().and_then(|x| analysis.value(before, x).constant()) {
+                    *field = Some(Operand::Const(c));
+                }
+            };
+            let (arg1, arg2) = value_fields(&quad.op);
+            if arg1 {
+                substitute(&mut quad.arg1);
+            }
+            if arg2 {
+                substitute(&mut quad.arg2);
+            }
+            if let (Opcode::Binary(_) | Opcode::Unary(_) | Opcode::Conv(_), Some(x)) = (&quad.op, quad.def()) {
+                if let Some(c) = analysis.value(after, x).constant() {
+                    quad = Quad::copy(x.clone(), Operand::Const(c));
+                }
+            }
+            let constant = |x: &Operand| match x {
+                Operand::Const(c) => Some(*c),
+                _ => None
+            };
+            match (quad.target(), branch(&quad, constant)) {
+                (Some(target), Some(true)) => quads.push(Quad::goto(target)),
+                (Some(_), Some(false)) => {},
+                _ => quads.push(quad)
+            }
+        }
+        if matches!(quads.last(), Some(q) if q.op == Opcode::Goto && q.target() == Some(b+1)) {
+            quads.pop();
+        }
+        block.quads = quads;
+    }
+    let mut cfg = Cfg::new(&cfg.to_quadruples());
+    cfg.remove_unreachable();
+    cfg.to_quadruples()
+}
+
+/// 条件付きの飛び越しが飛ぶか(条件が定数でなければNone)
+fn branch<F: Fn(&Operand) -> Option<Constant>>(quad: &Quad, value: F) -> Option<bool> {
+    let arg = |a: &Option<Operand>| a.as_ref().and_then(&value);
+    let cond = match &quad.op {
+        Opcode::If | Opcode::IfFalse => arg(&quad.arg1),
+        Opcode::IfRel(op) | Opcode::IfFalseRel(op) => Constant::binary(*op, arg(&quad.arg1)?, arg(&quad.arg2)?),
+        _ => None
+    };
+    match (cond, &quad.op) {
+        (Some(Constant::Bool(c)), Opcode::If | Opcode::IfRel(_)) => Some(c),
+        (Some(Constant::Bool(c)), Opcode::IfFalse | Opcode::IfFalseRel(_)) => Some(!c),
+        _ => None
+    }
+}
+
+/// 命令のarg1, arg2が値として読まれるか(配列の名前やポインタ、呼び出す関数の名前は置き換えない)
+fn value_fields(op: &Opcode) -> (bool, bool) {
+    match op {
+        Opcode::Binary(_) | Opcode::IfRel(_) | Opcode::IfFalseRel(_) | Opcode::IndexStore => (true, true),
+        Opcode::Unary(_) | Opcode::Conv(_) | Opcode::Copy | Opcode::If | Opcode::IfFalse
+            | Opcode::JumpTable | Opcode::Param | Opcode::Return | Opcode::Store => (true, false),
+        Opcode::IndexLoad => (false, true),
+        Opcode::Goto | Opcode::Call | Opcode::AddrOf | Opcode::Load => (false, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{propagate, ConstantPropagation};
+    use super::super::dataflow::solve;
+    use super::super::super::chapter2::parser::astparser::AstParser;
+    use super::super::super::chapter6::lower::Lowerer;
+    use super::super::super::chapter6::tac::Quadruples;
+    use super::super::super::chapter8::cfg::Cfg;
+
+    fn lower(src: &str) -> Quadruples {
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_block(AstParser::new(src).parse_block().unwrap());
+        lowerer.code().clone()
+    }
+
+    #[test]
+    fn constprop_branch_test() {
+        let code = lower("{ i32 x; i32 y; i32 z; x = 2 * 3; y = x + 1; if (y > 5) z = y * 2; else z = 0; while (x < 0) x = x - 1; z = z + x; }");
+        let cfg = Cfg::new(&code);
+        let analysis = ConstantPropagation::new(&cfg);
+        let solution = solve(&analysis, &cfg);
+        // else の枝とループの本体には値が流れない
+        let values: Vec<String> = (1..cfg.exit()).map(|b| analysis.show(&solution.ins[b])).collect();
+        assert_eq!(vec![
+            "{}",
+            "{t1 = 7, x = 6, y = 7}",
+            "unreachable",
+            "{t1 = 14, x = 6, y = 7, z = 14}",
+            "unreachable",
+            "{t1 = 14, x = 6, y = 7, z = 14}"
+        ], values);
+
+        let after = propagate(&code);
+        assert_eq!("  0: t1 = 6
+  1: x = 6
+  2: t1 = 7
+  3: y = 7
+  4: t1 = 14
+  5: z = 14
+  6: t1 = 20
+  7: z = 20
+", after.to_string());
+        assert!(code.diff(&after).starts_with("-   0    : t1 = 2 * 3\n-   1    : x = t1\n"));
+        assert_eq!(after, propagate(&after));
+    }
+
+    #[test]
+    fn constprop_semantics_test() {
+        // i32 は折り返し、0での除算は残し、f32 は単精度で計算する
+        let code = lower("{ i32 m; i32 k; f32 f; f32 g; bool b; m = 2147483647 + 1; k = m / 0; f = 0.1 + 0.2; g = 1 + f; b = f == 0.3; if (b) k = 1; }");
+        let after = propagate(&code);
+        assert_eq!("\
+-   0    : t1 = 2147483647 + 1
+-   1    : m = t1
+-   2    : t1 = m / 0
++       0: t1 = -2147483648
++       1: m = -2147483648
++       2: t1 = -2147483648 / 0
+    3   3: k = t1
+-   4    : t1 = 0.1 + 0.2
+-   5    : f = t1
+-   6    : t1 = (f32) 1
+-   7    : t1 = t1 + f
+-   8    : g = t1
+-   9    : t1 = f == 0.3
+-  10    : b = t1
+-  11    : ifFalse b goto 13
++       4: t1 = 0.3
++       5: f = 0.3
++       6: t1 = 1.0
++       7: t1 = 1.3
++       8: g = 1.3
++       9: t1 = true
++      10: b = true
+   12  11: k = 1
+", code.diff(&after));
+
+        // ループで値が変わる変数はNACになり、変わらない変数は定数のまま
+        let code = lower("{ i32 i; i32 c; i = 0; c = 5; while (i < 10) { i = i + c; } c = c * i; }");
+        assert_eq!("    0   0: i = 0
+    1   1: c = 5
+    2   2: ifFalse i < 10 goto 6
+-   3    : t1 = i + c
++       3: t1 = i + 5
+    4   4: i = t1
+    5   5: goto 2
+-   6    : t1 = c * i
++       6: t1 = 5 * i
+    7   7: c = t1
+", code.diff(&propagate(&code)));
+
+        // 仮引数は定数ではない
+        let program = AstParser::new("fn f(i32 n) -> i32 { i32 x; x = 1; if (n > 0) x = 2; return x; }").parse_program().unwrap();
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_program(program);
+        let code = &lowerer.procedures()[0].code;
+        assert_eq!(code, &propagate(code));
+    }
+}
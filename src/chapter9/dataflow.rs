@@ -0,0 +1,569 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::super::chapter6::tac::Quad;
+use super::super::chapter8::cfg::{BasicBlock, Cfg};
+
+/// ビットベクトルで表した集合(9.2.4節)
+/// 要素は0からsize-1までの番号で、定義や式や変数に番号をつけて使う
+///
+/// # members
+/// - words: Vec<u64> => ビットの列(要素iはwords[i / 64]のi % 64ビット目)
+/// - size: usize => 全体集合の大きさ
+///
+/// # derive
+/// - Clone
+/// - PartialEq, Eq
+/// - Hash
+/// - Debug
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BitSet {
+    words: Vec<u64>,
+    size: usize
+}
+
+impl BitSet {
+    /// 空集合を返す
+    ///
+    /// # params
+    /// - size: usize => 全体集合の大きさ
+    ///
+    /// # returns
+    /// - BitSet
+    pub fn new(size: usize) -> BitSet {
+        BitSet { words: vec![0; size.div_ceil(64)], size }
+    }
+
+    /// 全体集合を返す
+    ///
+    /// # params
+    /// - size: usize => 全体集合の大きさ
+    ///
+    /// # returns
+    /// - BitSet
+    pub fn full(size: usize) -> BitSet {
+        let mut set = BitSet { words: vec![!0; size.div_ceil(64)], size };
+        if !size.is_multiple_of(64) {
+            *set.words.last_mut().unwrap() = (1 << (size % 64)) - 1;
+        }
+        set
+    }
+
+    /// 全体集合の大きさを返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// 要素を加える
+    ///
+    /// # params
+    /// - i: usize => 要素
+    ///
+    /// # returns
+    /// - bool => 新しく加わったか
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (w, bit) = (i/64, 1 << (i%64));
+        let inserted = self.words[w] & bit == 0;
+        self.words[w] |= bit;
+        inserted
+    }
+
+    /// 要素を取り除く
+    ///
+    /// # params
+    /// - i: usize => 要素
+    ///
+    /// # returns
+    /// - bool => 要素があったか
+    pub fn remove(&mut self, i: usize) -> bool {
+        let (w, bit) = (i/64, 1 << (i%64));
+        let removed = self.words[w] & bit != 0;
+        self.words[w] &= !bit;
+        removed
+    }
+
+    /// 要素を含むか
+    ///
+    /// # params
+    /// - i: usize => 要素
+    ///
+    /// # returns
+    /// - bool
+    pub fn contains(&self, i: usize) -> bool {
+        i < self.size && self.words[i/64] & (1 << (i%64)) != 0
+    }
+
+    /// 和集合にする
+    ///
+    /// # params
+    /// - other: &BitSet => 同じ大きさの集合
+    ///
+    /// # returns
+    /// - bool => 変わったか
+    pub fn union_with(&mut self, other: &BitSet) -> bool {
+        self.combine(other, |x, y| x | y)
+    }
+
+    /// 積集合にする
+    ///
+    /// # params
+    /// - other: &BitSet => 同じ大きさの集合
+    ///
+    /// # returns
+    /// - bool => 変わったか
+    pub fn intersect_with(&mut self, other: &BitSet) -> bool {
+        self.combine(other, |x, y| x & y)
+    }
+
+    /// 差集合にする
+    ///
+    /// # params
+    /// - other: &BitSet => 同じ大きさの集合
+    ///
+    /// # returns
+    /// - bool => 変わったか
+    pub fn subtract(&mut self, other: &BitSet) -> bool {
+        self.combine(other, |x, y| x & !y)
+    }
+
+    /// 空集合か
+    ///
+    /// # returns
+    /// - bool
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    /// 要素の数を返す
+    ///
+    /// # returns
+    /// - usize
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// 要素を小さい順に返す
+    ///
+    /// # returns
+    /// - impl Iterator<Item=usize>
+    pub fn iter(&self) -> impl Iterator<Item=usize> + '_ {
+        (0..self.size).filter(move |i| self.contains(*i))
+    }
+
+    fn combine<F: Fn(u64, u64) -> u64>(&mut self, other: &BitSet, op: F) -> bool {
+        assert_eq!(self.size, other.size, "bit sets of different sizes");
+        let mut changed = false;
+        for (x, y) in self.words.iter_mut().zip(&other.words) {
+            let z = op(*x, *y);
+            changed |= z != *x;
+            *x = z;
+        }
+        changed
+    }
+}
+
+/// 要素を波括弧で囲んで並べる({0, 3, 5})
+impl fmt::Display for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let elems: Vec<String> = self.iter().map(|i| i.to_string()).collect();
+        f.pad(&format!("{{{}}}", elems.join(", ")))
+    }
+}
+
+/// データフロー解析の向き
+///
+/// # members
+/// - Forward => 前向き(IN[B]は先行のOUTの交わり、OUT[B] = f(IN[B]))
+/// - Backward => 後向き(OUT[B]は後続のINの交わり、IN[B] = f(OUT[B]))
+///
+/// # derive
+/// - Clone, Copy
+/// - PartialEq
+/// - Debug
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Forward,
+    Backward
+}
+
+/// データフロー解析の枠組み(9.3節)
+/// 値の半束(交わり、上限、下限)、向き、境界条件、伝達関数を与えると、solveが解を求める
+/// 伝達関数は命令ごとに与え、ブロックの伝達関数はそれを向きの順に合成したものになる
+/// 単調な伝達関数と有限の高さの半束なら、解は最大不動点になる
+pub trait Analysis {
+    /// 半束の値
+    type Value: Clone + PartialEq;
+
+    /// 解析の向きを返す
+    ///
+    /// # returns
+    /// - Direction
+    fn direction(&self) -> Direction;
+
+    /// 半束の上限(⊤)を返す(ENTRY, EXIT以外のブロックの初期値で、交わりの単位元)
+    ///
+    /// # returns
+    /// - Self::Value
+    fn top(&self) -> Self::Value;
+
+    /// 半束の下限(⊥)を返す
+    ///
+    /// # returns
+    /// - Self::Value
+    fn bottom(&self) -> Self::Value;
+
+    /// 交わり x ∧ y をxに求める
+    ///
+    /// # params
+    /// - x: &mut Self::Value => 値
+    /// - y: &Self::Value => 値
+    fn meet(&self, x: &mut Self::Value, y: &Self::Value);
+
+    /// 境界条件(前向きならOUT[ENTRY]、後向きならIN[EXIT])を返す
+    ///
+    /// # returns
+    /// - Self::Value
+    fn boundary(&self) -> Self::Value;
+
+    /// 命令の伝達関数をxに施す
+    ///
+    /// # params
+    /// - b: usize => ブロックの番号
+    /// - i: usize => ブロックの中での命令の位置
+    /// - quad: &Quad => 命令
+    /// - x: &mut Self::Value => 前向きなら命令の直前、後向きなら直後の値
+    fn transfer(&self, b: usize, i: usize, quad: &Quad, x: &mut Self::Value);
+
+    /// ブロックの伝達関数をxに施す(命令の伝達関数を向きの順に合成する)
+    /// 生成と消去の集合を前もって求めておける解析は、これを書き換えて速くできる
+    ///
+    /// # params
+    /// - b: usize => ブロックの番号
+    /// - block: &BasicBlock => ブロック
+    /// - x: &mut Self::Value => 前向きならIN[B]、後向きならOUT[B]
+    fn transfer_block(&self, b: usize, block: &BasicBlock, x: &mut Self::Value) {
+        match self.direction() {
+            Direction::Forward => for (i, quad) in block.quads.iter().enumerate() {
+                self.transfer(b, i, quad, x);
+            },
+            Direction::Backward => for (i, quad) in block.quads.iter().enumerate().rev() {
+                self.transfer(b, i, quad, x);
+            }
+        }
+    }
+
+    /// 流れの向きの辺 from → to に値が流れるか
+    /// 条件が定数の分岐のように、値によっては通らない辺を交わりから外すときに書き換える
+    ///
+    /// # params
+    /// - cfg: &Cfg => 流れグラフ
+    /// - from: usize => 値を出すブロック(前向きなら先行)
+    /// - to: usize => 値を受け取るブロック
+    /// - x: &Self::Value => fromの出る側の値
+    ///
+    /// # returns
+    /// - bool
+    fn flows(&self, _cfg: &Cfg, _from: usize, _to: usize, _x: &Self::Value) -> bool {
+        true
+    }
+}
+
+/// データフロー解析の解
+///
+/// # members
+/// - ins: Vec<V> => ブロックごとのIN
+/// - outs: Vec<V> => ブロックごとのOUT
+/// - visits: usize => ブロックの伝達関数を施した回数
+///
+/// # derive
+/// - Clone
+/// - Debug
+#[derive(Clone, Debug)]
+pub struct Solution<V> {
+    pub ins: Vec<V>,
+    pub outs: Vec<V>,
+    pub visits: usize
+}
+
+impl<V: Clone> Solution<V> {
+    /// ブロックの中の各点の値を返す
+    /// i番目の値はi番目の命令の直前の値で、最後の値はブロックの終わりの値
+    ///
+    /// # params
+    /// - analysis: &A => 解を求めた解析
+    /// - cfg: &Cfg => 流れグラフ
+    /// - b: usize => ブロックの番号
+    ///
+    /// # returns
+    /// - Vec<V> => 命令の数+1個の値
+    pub fn points<A: Analysis<Value=V>>(&self, analysis: &A, cfg: &Cfg, b: usize) -> Vec<V> {
+        let quads = &cfg.blocks[b].quads;
+        match analysis.direction() {
+            Direction::Forward => {
+                let mut x = self.ins[b].clone();
+                let mut points = vec![x.clone()];
+                for (i, quad) in quads.iter().enumerate() {
+                    analysis.transfer(b, i, quad, &mut x);
+                    points.push(x.clone());
+                }
+                points
+            },
+            Direction::Backward => {
+                let mut x = self.outs[b].clone();
+                let mut points = vec![x.clone()];
+                for (i, quad) in quads.iter().enumerate().rev() {
+                    analysis.transfer(b, i, quad, &mut x);
+                    points.push(x.clone());
+                }
+                points.reverse();
+                points
+            }
+        }
+    }
+
+    /// 収束までにかかった手間を返す
+    ///
+    /// # returns
+    /// - String
+    pub fn report(&self) -> String {
+        format!("converged after {} visits of {} blocks", self.visits, self.ins.len())
+    }
+}
+
+/// ブロックごとのINとOUTを並べる
+impl<V: fmt::Display> fmt::Display for Solution<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let exit = self.ins.len()-1;
+        for (b, (x, y)) in self.ins.iter().zip(&self.outs).enumerate() {
+            let name = match b {
+                0 => "ENTRY".to_string(),
+                b if b == exit => "EXIT".to_string(),
+                b => format!("B{}", b)
+            };
+            writeln!(f, "{}: in {} out {}", name, x, y)?;
+        }
+        Ok(())
+    }
+}
+
+/// データフロー解析を作業リストで解く(図9.23の反復アルゴリズム)
+/// 作業リストは、前向きなら逆後順、後向きならその逆順で小さいものから取り出すので、
+/// 多くの場合、先行(後向きなら後続)の値が決まってからブロックを調べることになる
+/// ブロックの値が変わったときだけ、後続(後向きなら先行)を作業リストに戻す
+///
+/// # params
+/// - analysis: &A => 解析
+/// - cfg: &Cfg => 流れグラフ
+///
+/// # returns
+/// - Solution<A::Value>
+pub fn solve<A: Analysis>(analysis: &A, cfg: &Cfg) -> Solution<A::Value> {
+    let n = cfg.blocks.len();
+    let forward = analysis.direction() == Direction::Forward;
+    let mut order = cfg.reverse_postorder();
+    if !forward {
+        order.reverse();
+    }
+    let mut position = vec![0; n];
+    for (p, b) in order.iter().enumerate() {
+        position[*b] = p;
+    }
+    let start = if forward { cfg.entry() } else { cfg.exit() };
+
+    // before は流れが入る側(前向きならIN)、after は出る側の値
+    let mut before = vec![analysis.top(); n];
+    let mut after = vec![analysis.top(); n];
+    before[start] = analysis.boundary();
+    after[start] = analysis.boundary();
+    let mut worklist: BTreeSet<usize> = (0..n).filter(|b| *b != start).map(|b| position[b]).collect();
+    let mut visits = 0;
+    while let Some(p) = worklist.pop_first() {
+        let b = order[p];
+        let (sources, targets) = if forward { (cfg.preds(b), cfg.succs(b)) } else { (cfg.succs(b), cfg.preds(b)) };
+        let mut x = analysis.top();
+        for s in sources.iter().filter(|s| analysis.flows(cfg, **s, b, &after[**s])) {
+            analysis.meet(&mut x, &after[*s]);
+        }
+        let mut y = x.clone();
+        analysis.transfer_block(b, &cfg.blocks[b], &mut y);
+        visits += 1;
+        before[b] = x;
+        if y != after[b] {
+            after[b] = y;
+            worklist.extend(targets.iter().filter(|t| **t != start).map(|t| position[*t]));
+        }
+    }
+    let (ins, outs) = if forward { (before, after) } else { (after, before) };
+    Solution { ins, outs, visits }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{solve, Analysis, BitSet, Direction};
+    use super::super::super::chapter2::parser::astparser::AstParser;
+    use super::super::super::chapter6::lower::Lowerer;
+    use super::super::super::chapter6::tac::{Operand, Quad};
+    use super::super::super::chapter8::cfg::Cfg;
+
+    /// どの経路(must なら すべての経路)でも代入された名前
+    struct Assigned {
+        names: Vec<Operand>,
+        must: bool
+    }
+
+    impl Analysis for Assigned {
+        type Value = BitSet;
+
+        fn direction(&self) -> Direction {
+            Direction::Forward
+        }
+
+        fn top(&self) -> BitSet {
+            if self.must { BitSet::full(self.names.len()) } else { BitSet::new(self.names.len()) }
+        }
+
+        fn bottom(&self) -> BitSet {
+            if self.must { BitSet::new(self.names.len()) } else { BitSet::full(self.names.len()) }
+        }
+
+        fn meet(&self, x: &mut BitSet, y: &BitSet) {
+            if self.must {
+                x.intersect_with(y);
+            } else {
+                x.union_with(y);
+            }
+        }
+
+        fn boundary(&self) -> BitSet {
+            BitSet::new(self.names.len())
+        }
+
+        fn transfer(&self, _: usize, _: usize, quad: &Quad, x: &mut BitSet) {
+            if let Some(i) = self.names.iter().position(|n| Some(n) == quad.result.as_ref()) {
+                x.insert(i);
+            }
+        }
+    }
+
+    /// この後で読まれうる名前(後向き)
+    struct Read(Vec<Operand>);
+
+    impl Analysis for Read {
+        type Value = BitSet;
+
+        fn direction(&self) -> Direction {
+            Direction::Backward
+        }
+
+        fn top(&self) -> BitSet {
+            BitSet::new(self.0.len())
+        }
+
+        fn bottom(&self) -> BitSet {
+            BitSet::full(self.0.len())
+        }
+
+        fn meet(&self, x: &mut BitSet, y: &BitSet) {
+            x.union_with(y);
+        }
+
+        fn boundary(&self) -> BitSet {
+            BitSet::new(self.0.len())
+        }
+
+        fn transfer(&self, _: usize, _: usize, quad: &Quad, x: &mut BitSet) {
+            for arg in quad.arg1.iter().chain(quad.arg2.iter()) {
+                if let Some(i) = self.0.iter().position(|n| n == arg) {
+                    x.insert(i);
+                }
+            }
+        }
+    }
+
+    fn names(cfg: &Cfg) -> Vec<Operand> {
+        let mut names = vec![];
+        for quad in cfg.blocks.iter().flat_map(|b| &b.quads) {
+            for x in quad.arg1.iter().chain(quad.arg2.iter()).chain(quad.result.iter()) {
+                if let Operand::Name(_) = x {
+                    if !names.contains(x) {
+                        names.push(x.clone());
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    #[test]
+    fn bitset_test() {
+        let mut x = BitSet::new(70);
+        assert!(x.is_empty());
+        assert!(x.insert(3));
+        assert!(!x.insert(3));
+        x.insert(64);
+        x.insert(69);
+        assert_eq!("{3, 64, 69}", x.to_string());
+        let full = BitSet::full(70);
+        assert_eq!(70, full.count());
+        assert!(!full.contains(70));
+        let mut y = full.clone();
+        assert!(y.subtract(&x));
+        assert_eq!(67, y.count());
+        assert!(!y.intersect_with(&full));
+        assert!(y.union_with(&x));
+        assert_eq!(full, y);
+        assert!(x.remove(64));
+        assert!(!x.remove(64));
+        assert_eq!(vec![3, 69], x.iter().collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn dataflow_forward_test() {
+        let src = "{ i32 x; i32 y; i32 z; if (x < 0) { y = 1; z = 1; } else { y = 2; } while (y < 10) { y = y + 1; } }";
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_block(AstParser::new(src).parse_block().unwrap());
+        let cfg = Cfg::new(lowerer.code());
+        let names = names(&cfg);
+        let show = |set: &BitSet| set.iter().map(|i| names[i].to_string()).collect::<Vec<String>>().join(" ");
+
+        let may = Assigned { names: names.clone(), must: false };
+        let solution = solve(&may, &cfg);
+        assert_eq!("y z", show(&solution.ins[cfg.exit()]));
+        let must = Assigned { names: names.clone(), must: true };
+        let solution = solve(&must, &cfg);
+        assert_eq!("y", show(&solution.ins[cfg.exit()]));
+        // ループの先頭は、ループに入る前の値と後退辺の値の交わり
+        let head = (1..cfg.exit()).find(|b| cfg.preds(*b).iter().any(|p| p > b)).unwrap();
+        assert_eq!("y", show(&solution.ins[head]));
+        assert_eq!(format!("converged after {} visits of {} blocks", solution.visits, cfg.blocks.len()), solution.report());
+        // 逆後順なら、ループの先頭をもう一度調べるだけで収束する
+        assert_eq!(cfg.blocks.len(), solution.visits);
+        let points = solution.points(&must, &cfg, 2);
+        assert_eq!(cfg.blocks[2].quads.len()+1, points.len());
+        assert_eq!(&solution.ins[2], &points[0]);
+        assert_eq!(&solution.outs[2], points.last().unwrap());
+    }
+
+    #[test]
+    fn dataflow_backward_test() {
+        let src = "{ i32 a; i32 b; i32 c; a = 0; while (a < 10) { b = a + 1; a = b; } c = a; }";
+        let mut lowerer = Lowerer::new();
+        lowerer.lower_block(AstParser::new(src).parse_block().unwrap());
+        let cfg = Cfg::new(lowerer.code());
+        let read = Read(names(&cfg));
+        let solution = solve(&read, &cfg);
+        assert_eq!("\
+ENTRY: in {0, 1} out {0, 1}
+B1: in {0, 1} out {0, 1}
+B2: in {0, 1} out {0, 1}
+B3: in {0, 1} out {0, 1}
+B4: in {0} out {}
+EXIT: in {} out {}
+", solution.to_string());
+        let points = solution.points(&read, &cfg, 4);
+        assert_eq!(vec!["{0}", "{}"], points.iter().map(|p| p.to_string()).collect::<Vec<String>>());
+    }
+}
@@ -1 +1,6 @@
 pub mod chapter2;
+pub mod chapter4;
+pub mod chapter5;
+pub mod chapter6;
+pub mod chapter8;
+pub mod chapter9;